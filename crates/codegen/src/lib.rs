@@ -7,6 +7,8 @@
 //! * The `http` and verb (e.g. `get`, `post`, `delete`, etc.) macros that define a user's HTTP-triggered function.
 //! * The `env` macro that declares a required environment variable.
 //!
+//! The `url_for` macro builds the path of another function's route from the route it declares.
+//!
 //! Each macro expands to include a "descriptor" comprising a static array of bytes that is appended to a custom section
 //! in the resulting WebAssembly module.
 //!
@@ -14,11 +16,48 @@
 //!
 //! * The `__functions` section that defines the metadata about user functions and how they can be triggered.
 //! * The `__vars` section that defines the metadata about the required environment variables for the application.
+//! * The `__interface` section that records the version of the host interface the application was built for.
 //!
 //! The `__functions` section is required to run a Wasmtime Functions application, as without it there is nothing for the runtime to do.
 //!
 //! The `__vars` sections is optional.  It is primarily used by the host to source the required
 //! environment variable values when running an application.
+//!
+//! The `__interface` section is emitted by each function and lets the runtime reject applications built
+//! for an incompatible host interface before linking them.
+//!
+//! HTTP functions and middleware may be declared with `async fn`, but there are no awaitable host APIs:
+//! the future is run to completion within the instance, and calls to the host, such as outbound HTTP
+//! requests or cache reads, block the instance rather than returning futures. A future that waits on an
+//! event from outside the function (such as a timer or channel) can never complete, so the function
+//! responds with `500 Internal Server Error` instead.
+//!
+//! The HTTP macros accept the following options after the path, which the runtime uses to reject
+//! invalid requests before invoking the function:
+//!
+//! * `consumes = "application/json"` - the comma-separated content types accepted by the function.
+//! * `params(id = "integer")` - the types of path parameters; supported types are `string`, `integer`,
+//!   `unsigned`, `number`, and `boolean`.
+//! * `concurrency = 8` - the maximum number of concurrent invocations of the function.
+//! * `vars = "DATABASE_URL, API_KEY"` - the comma-separated environment variables available to the function;
+//!   each must be declared with the `var` macro. Without this option, the function has every declared variable.
+//! * `cache(ttl = 60, vary = "accept")` - caches successful `GET` responses in the host for the given number
+//!   of seconds, keyed on the request path, query, and the optional comma-separated list of request headers.
+//! * `headers(cache_control = "public, max-age=60")` - static headers the host adds to the function's responses;
+//!   underscores in a name are replaced with hyphens. A header the function sets itself is not replaced.
+//! * `signed` - requests must use a URL signed with `sign_url` that has not expired; the host rejects other
+//!   requests with `403 Forbidden` before invoking the function.
+//! * `etag` - the host tags successful `GET` and `HEAD` responses with a hash of their body and answers
+//!   requests with a matching `If-None-Match` header with `304 Not Modified`.
+//! * `trace_sample = 0.01` - the ratio of requests whose host calls are traced when the host samples traces,
+//!   replacing the host's ratio; e.g. `1.0` fully traces a rarely used route.
+//!
+//! For a route with named parameters, the HTTP macros also generate a struct named after the function
+//! (e.g. `HelloParams` for `hello`) with a field for each parameter, typed by the `params` option. A function
+//! receives its route's parameters by taking the struct as a second parameter after the `Request`.
+//!
+//! When not targeting WebAssembly, the descriptors are still emitted (outside of any custom section) so that
+//! the `function_metadata` macro can read them in native tests of an application.
 
 #![deny(missing_docs)]
 
@@ -26,53 +65,18 @@ extern crate proc_macro;
 
 use proc_macro::{Span, TokenStream};
 use quote::quote;
-use serde::Serialize;
+use std::collections::BTreeMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use syn::{
+    ext::IdentExt,
     parse::{Parse, ParseStream},
     parse_macro_input,
     spanned::Spanned,
-    Error, FnArg, Ident, ItemFn, LitByteStr, LitStr, Result, Token, Type,
+    Error, FnArg, Ident, ItemFn, Lit, LitByteStr, LitInt, LitStr, Result, Token, Type,
+};
+use wasmtime_functions_types::{
+    Cache, Function, FunctionOutput, FunctionTrigger, Method, Parameter, ParameterType,
 };
-
-#[derive(Clone, Copy, Eq, PartialEq, Serialize)]
-#[serde(rename_all = "UPPERCASE")]
-enum Method {
-    Get,
-    Head,
-    Post,
-    Put,
-    Delete,
-    Connect,
-    Options,
-    Trace,
-    Patch,
-}
-
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase", tag = "type")]
-enum FunctionTrigger {
-    Http { path: String, methods: Vec<Method> },
-}
-
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase", tag = "type")]
-enum FunctionInput {}
-
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase", tag = "type")]
-enum FunctionOutput {
-    Http,
-}
-
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct Function {
-    name: String,
-    trigger: FunctionTrigger,
-    inputs: Vec<FunctionInput>,
-    outputs: Vec<FunctionOutput>,
-}
 
 fn parse_methods(s: &LitStr) -> Result<Vec<Method>> {
     let mut methods = Vec::new();
@@ -86,7 +90,7 @@ fn parse_methods(s: &LitStr) -> Result<Vec<Method>> {
             "connect" => Method::Connect,
             "options" => Method::Options,
             "trace" => Method::Trace,
-            "path" => Method::Patch,
+            "patch" => Method::Patch,
             _ => {
                 return Err(Error::new(
                     s.span(),
@@ -103,15 +107,275 @@ fn parse_methods(s: &LitStr) -> Result<Vec<Method>> {
     Ok(methods)
 }
 
+struct RouteArgs {
+    path: LitStr,
+    consumes: Vec<String>,
+    params: Vec<Parameter>,
+    concurrency: Option<u32>,
+    vars: Option<Vec<String>>,
+    cache: Option<Cache>,
+    headers: BTreeMap<String, String>,
+    signed: bool,
+    etag: bool,
+    trace_sample: Option<f64>,
+}
+
+impl Parse for RouteArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let path: LitStr = input.parse()?;
+        let mut consumes = Vec::new();
+        let mut params = Vec::new();
+        let mut concurrency = None;
+        let mut vars = None;
+        let mut cache = None;
+        let mut headers = BTreeMap::new();
+        let mut signed = false;
+        let mut etag = false;
+        let mut trace_sample = None;
+
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+
+            let option: Ident = input.parse()?;
+            match option.to_string().as_ref() {
+                "consumes" => {
+                    input.parse::<Token![=]>()?;
+                    let s: LitStr = input.parse()?;
+                    for ty in s.value().split(',') {
+                        let ty = ty.trim();
+                        if !ty.contains('/') {
+                            return Err(Error::new(
+                                s.span(),
+                                format!("invalid content type '{}'", ty),
+                            ));
+                        }
+                        consumes.push(ty.to_lowercase());
+                    }
+                }
+                "params" => {
+                    let content;
+                    syn::parenthesized!(content in input);
+
+                    for param in content.parse_terminated::<_, Token![,]>(parse_param)? {
+                        if params.iter().any(|p: &Parameter| p.name == param.name) {
+                            return Err(Error::new(
+                                option.span(),
+                                format!("duplicate parameter '{}'", param.name),
+                            ));
+                        }
+
+                        let name = param.name.as_str();
+                        if !path
+                            .value()
+                            .split('/')
+                            .any(|s| s.strip_prefix(&[':', '*'][..]) == Some(name))
+                        {
+                            return Err(Error::new(
+                                path.span(),
+                                format!("path has no parameter named '{}'", name),
+                            ));
+                        }
+
+                        params.push(param);
+                    }
+                }
+                "concurrency" => {
+                    input.parse::<Token![=]>()?;
+                    let limit: LitInt = input.parse()?;
+                    let value = limit.base10_parse::<u32>()?;
+                    if value == 0 {
+                        return Err(Error::new(
+                            limit.span(),
+                            "concurrency limit must be greater than zero",
+                        ));
+                    }
+                    concurrency = Some(value);
+                }
+                "vars" => {
+                    input.parse::<Token![=]>()?;
+                    let s: LitStr = input.parse()?;
+                    let mut names = Vec::new();
+                    for name in s
+                        .value()
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|n| !n.is_empty())
+                    {
+                        if names.iter().any(|n| n == name) {
+                            return Err(Error::new(
+                                s.span(),
+                                format!("duplicate variable '{}'", name),
+                            ));
+                        }
+                        names.push(name.to_string());
+                    }
+                    vars = Some(names);
+                }
+                "cache" => {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    cache = Some(parse_cache(option.span(), &content)?);
+                }
+                "headers" => {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    parse_headers(option.span(), &content, &mut headers)?;
+                }
+                "signed" => signed = true,
+                "etag" => etag = true,
+                "trace_sample" => {
+                    input.parse::<Token![=]>()?;
+                    let (value, span) = match input.parse::<Lit>()? {
+                        Lit::Float(ratio) => (ratio.base10_parse::<f64>()?, ratio.span()),
+                        Lit::Int(ratio) => (ratio.base10_parse::<f64>()?, ratio.span()),
+                        lit => return Err(Error::new(lit.span(), "expected a number")),
+                    };
+                    if !(0.0..=1.0).contains(&value) {
+                        return Err(Error::new(
+                            span,
+                            "trace sample ratio must be between 0.0 and 1.0",
+                        ));
+                    }
+                    trace_sample = Some(value);
+                }
+                _ => {
+                    return Err(Error::new(
+                        option.span(),
+                        format!("unsupported option '{}'", option),
+                    ))
+                }
+            }
+        }
+
+        Ok(Self {
+            path,
+            consumes,
+            params,
+            concurrency,
+            vars,
+            cache,
+            headers,
+            signed,
+            etag,
+            trace_sample,
+        })
+    }
+}
+
+fn parse_cache(span: proc_macro2::Span, input: ParseStream) -> Result<Cache> {
+    let mut ttl = None;
+    let mut vary = Vec::new();
+
+    while !input.is_empty() {
+        let option: Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+
+        match option.to_string().as_ref() {
+            "ttl" => {
+                let value: LitInt = input.parse()?;
+                ttl = Some(value.base10_parse::<u32>()?);
+            }
+            "vary" => {
+                let value: LitStr = input.parse()?;
+                vary.extend(
+                    value
+                        .value()
+                        .split(',')
+                        .map(|h| h.trim().to_lowercase())
+                        .filter(|h| !h.is_empty()),
+                );
+            }
+            _ => {
+                return Err(Error::new(
+                    option.span(),
+                    format!("unsupported cache option '{}'", option),
+                ))
+            }
+        }
+
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+    }
+
+    match ttl {
+        Some(ttl) if ttl > 0 => Ok(Cache { ttl, vary }),
+        _ => Err(Error::new(span, "cache requires a `ttl` greater than zero")),
+    }
+}
+
+fn parse_headers(
+    span: proc_macro2::Span,
+    input: ParseStream,
+    headers: &mut BTreeMap<String, String>,
+) -> Result<()> {
+    if input.is_empty() {
+        return Err(Error::new(span, "at least one header is required"));
+    }
+
+    while !input.is_empty() {
+        let name = input.call(Ident::parse_any)?;
+        input.parse::<Token![=]>()?;
+        let value: LitStr = input.parse()?;
+
+        let header = name.unraw().to_string().replace('_', "-").to_lowercase();
+        if headers.contains_key(&header) {
+            return Err(Error::new(
+                name.span(),
+                format!("duplicate header '{}'", header),
+            ));
+        }
+
+        // Values are sent verbatim, so they can't contain line breaks or other control characters
+        let v = value.value();
+        if v.trim().is_empty() || v.chars().any(|c| c.is_control() && c != '\t') {
+            return Err(Error::new(
+                value.span(),
+                format!("invalid value for header '{}'", header),
+            ));
+        }
+
+        headers.insert(header, v.trim().to_string());
+
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_param(input: ParseStream) -> Result<Parameter> {
+    let name: Ident = input.parse()?;
+    input.parse::<Token![=]>()?;
+    let ty: LitStr = input.parse()?;
+
+    Ok(Parameter {
+        name: name.to_string(),
+        ty: match ty.value().as_ref() {
+            "string" => ParameterType::String,
+            "integer" => ParameterType::Integer,
+            "unsigned" => ParameterType::Unsigned,
+            "number" => ParameterType::Number,
+            "boolean" => ParameterType::Boolean,
+            _ => {
+                return Err(Error::new(
+                    ty.span(),
+                    format!("unsupported parameter type '{}'", ty.value()),
+                ))
+            }
+        },
+    })
+}
+
 fn check_function_validity(func: &ItemFn) -> Result<()> {
     if let Some(constness) = func.sig.constness {
         return Err(Error::new(constness.span, "function cannot be const"));
     }
 
-    if let Some(asyncness) = func.sig.asyncness {
-        return Err(Error::new(asyncness.span, "function cannot be async"));
-    }
-
     if let Some(abi) = &func.sig.abi {
         return Err(Error::new(
             abi.extern_token.span,
@@ -130,41 +394,127 @@ fn check_function_validity(func: &ItemFn) -> Result<()> {
         ));
     }
 
+    // The function is exported under its own name, so it can't collide with the exports of the
+    // WASI runtime or those reserved for the generated code
+    let name = func.sig.ident.to_string();
+    if name.starts_with("__") || matches!(name.as_str(), "_start" | "_initialize" | "memory") {
+        return Err(Error::new(
+            func.sig.ident.span(),
+            format!("function name '{}' is reserved", name),
+        ));
+    }
+
     Ok(())
 }
 
-fn check_http_validity(func: &ItemFn) -> Result<()> {
+/// Checks the signature of an HTTP function, returning whether it takes its route's parameter struct.
+fn check_http_validity(func: &ItemFn, params: Option<&Ident>) -> Result<bool> {
     let inputs = &func.sig.inputs;
     if inputs.is_empty() {
         return Err(Error::new(
             func.sig.ident.span(),
-            "function must have a single parameter of type 'Request'",
+            "function must have a first parameter of type 'Request'",
+        ));
+    }
+
+    if inputs.len() > 2 {
+        return Err(Error::new(
+            inputs[2].span(),
+            "function cannot have more than two parameters",
         ));
     }
 
-    if inputs.len() > 1 {
+    if !is_type(&inputs[0], "Request") {
         return Err(Error::new(
-            inputs[1].span(),
-            "function cannot have more than one parameter",
+            inputs[0].span(),
+            "parameter must be type 'Request'",
         ));
     }
 
-    if let FnArg::Typed(arg) = &inputs[0] {
+    match (inputs.iter().nth(1), params) {
+        (None, _) => Ok(false),
+        (Some(arg), Some(params)) if is_type(arg, &params.to_string()) => Ok(true),
+        (Some(arg), Some(params)) => Err(Error::new(
+            arg.span(),
+            format!("parameter must be type '{}'", params),
+        )),
+        (Some(arg), None) => Err(Error::new(
+            arg.span(),
+            "route has no named parameters to extract",
+        )),
+    }
+}
+
+fn check_middleware_validity(func: &ItemFn) -> Result<()> {
+    let inputs = &func.sig.inputs;
+    if inputs.len() != 2 {
+        return Err(Error::new(
+            func.sig.ident.span(),
+            "middleware must have two parameters of type 'Request' and 'Response'",
+        ));
+    }
+
+    for (arg, name) in inputs.iter().zip(["Request", "Response"].iter()) {
+        if !is_type(arg, name) {
+            return Err(Error::new(
+                arg.span(),
+                format!("parameter must be type '{}'", name),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// Converts the result of calling a function or middleware into a `Response`
+fn emit_response(func: &ItemFn, call: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    // An async function's future is run to completion in the instance, as calls to the host block
+    if func.sig.asyncness.is_some() {
+        quote!(
+            match wasmtime_functions::__block_on(#call) {
+                Some(response) => wasmtime_functions::Response::from(response),
+                None => wasmtime_functions::Response::from(
+                    wasmtime_functions::Problem::new(
+                        wasmtime_functions::StatusCode::INTERNAL_SERVER_ERROR,
+                    )
+                    .detail("the function is waiting for an event that will never occur"),
+                ),
+            }
+        )
+    } else {
+        quote!(wasmtime_functions::Response::from(#call))
+    }
+}
+
+fn is_type(arg: &FnArg, name: &str) -> bool {
+    if let FnArg::Typed(arg) = arg {
         if let Type::Path(ty) = &*arg.ty {
             if ty.qself.is_none() {
                 if let Some(segment) = ty.path.segments.last() {
-                    if segment.ident == "Request" {
-                        return Ok(());
-                    }
+                    return segment.ident == name;
                 }
             }
         }
     }
 
-    Err(Error::new(
-        inputs[0].span(),
-        "parameter must be type 'Request'",
-    ))
+    false
+}
+
+/// The version of the host interface implemented by the `wasmtime-functions` crate.
+///
+/// This must be kept in sync with the runtime's `HOST_INTERFACE_VERSION` and bumped whenever a
+/// witx interface changes incompatibly.
+const INTERFACE_VERSION: u32 = 17;
+
+fn emit_interface_version(name: &Ident) -> proc_macro2::TokenStream {
+    let bytes = LitByteStr::new(&INTERFACE_VERSION.to_le_bytes(), Span::call_site().into());
+
+    quote!(
+        #[allow(dead_code)]
+        #[link_section = "__interface"]
+        #[cfg(target_arch = "wasm32")]
+        pub static #name: [u8; 4] = *#bytes;
+    )
 }
 
 fn emit_descriptor(section: &str, name: &Ident, descriptor: &[u8]) -> proc_macro2::TokenStream {
@@ -181,37 +531,288 @@ fn emit_descriptor(section: &str, name: &Ident, descriptor: &[u8]) -> proc_macro
     bytes.extend_from_slice(descriptor);
     let descriptor_bytes = LitByteStr::new(&bytes, Span::call_site().into());
 
+    // Native builds keep the descriptor out of the object file's sections so that tests can read it
     quote!(
         #[allow(dead_code)]
         #[link_section = #section]
         #[cfg(target_arch = "wasm32")]
         pub static #name: [u8; #descriptor_length] = *#descriptor_bytes;
+
+        #[allow(dead_code)]
+        #[doc(hidden)]
+        #[cfg(not(target_arch = "wasm32"))]
+        pub static #name: [u8; #descriptor_length] = *#descriptor_bytes;
+    )
+}
+
+/// Emits a hidden struct with a field for each parameter of the route that formats as its path.
+///
+/// This is what `url_for!` constructs; the struct is not emitted if a parameter's name is not an identifier.
+fn emit_url_builder(name: &Ident, path: &str, params: &[Parameter]) -> proc_macro2::TokenStream {
+    let mut fields = Vec::new();
+    let mut writes = Vec::new();
+    let mut literal = String::new();
+
+    for segment in path.split('/').filter(|s| !s.is_empty()) {
+        literal.push('/');
+
+        let (param, encode) = match segment.chars().next() {
+            Some(':') => (&segment[1..], true),
+            Some('*') => (&segment[1..], false),
+            _ => {
+                literal.push_str(segment);
+                continue;
+            }
+        };
+
+        let param = if param.is_empty() { "rest" } else { param };
+        let field = match syn::parse_str::<Ident>(param) {
+            Ok(field) => field,
+            Err(_) if syn::parse_str::<Ident>(&format!("r#{}", param)).is_ok() => {
+                Ident::new_raw(param, name.span())
+            }
+            Err(_) => return quote!(),
+        };
+
+        if !literal.is_empty() {
+            writes.push(quote!(f.write_str(#literal)?;));
+            literal.clear();
+        }
+
+        match params.iter().find(|p| p.name == param).map(|p| p.ty) {
+            Some(ParameterType::Integer) => {
+                fields.push(quote!(pub #field: i64));
+                writes.push(quote!(write!(f, "{}", self.#field)?;));
+            }
+            Some(ParameterType::Unsigned) => {
+                fields.push(quote!(pub #field: u64));
+                writes.push(quote!(write!(f, "{}", self.#field)?;));
+            }
+            Some(ParameterType::Number) => {
+                fields.push(quote!(pub #field: f64));
+                writes.push(quote!(write!(f, "{}", self.#field)?;));
+            }
+            Some(ParameterType::Boolean) => {
+                fields.push(quote!(pub #field: bool));
+                writes.push(quote!(write!(f, "{}", self.#field)?;));
+            }
+            Some(ParameterType::String) | None => {
+                fields.push(quote!(pub #field: &'a str));
+                if encode {
+                    writes.push(quote!(
+                        f.write_str(&wasmtime_functions::__encode_path_segment(self.#field))?;
+                    ));
+                } else {
+                    writes.push(quote!(f.write_str(self.#field)?;));
+                }
+            }
+        }
+    }
+
+    if path.ends_with('/') || (fields.is_empty() && literal.is_empty()) {
+        literal.push('/');
+    }
+
+    if !literal.is_empty() {
+        writes.push(quote!(f.write_str(#literal)?;));
+    }
+
+    quote!(
+        #[doc(hidden)]
+        #[allow(non_camel_case_types, dead_code)]
+        pub struct #name<'a> {
+            #(#fields,)*
+            pub __marker: ::std::marker::PhantomData<&'a ()>,
+        }
+
+        impl ::std::fmt::Display for #name<'_> {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                #(#writes)*
+                Ok(())
+            }
+        }
     )
 }
 
-fn emit_http_function(mut func: ItemFn, path: LitStr, methods: Vec<Method>) -> Result<TokenStream> {
+/// Emits a struct with a typed field for each named parameter of the route and its `RouteParams` implementation.
+///
+/// The struct is not emitted if the route has no named parameters or a parameter's name is not an identifier.
+fn emit_params_struct(
+    name: &Ident,
+    function: &str,
+    path: &str,
+    params: &[Parameter],
+) -> Option<proc_macro2::TokenStream> {
+    let mut fields = Vec::new();
+    let mut extracts = Vec::new();
+
+    for segment in path.split('/') {
+        let param = match segment.strip_prefix(&[':', '*'][..]) {
+            Some(param) if !param.is_empty() => param,
+            _ => continue,
+        };
+
+        let field = match syn::parse_str::<Ident>(param) {
+            Ok(field) => field,
+            Err(_) if syn::parse_str::<Ident>(&format!("r#{}", param)).is_ok() => {
+                Ident::new_raw(param, name.span())
+            }
+            Err(_) => return None,
+        };
+
+        let doc = format!("The `{}` parameter of the route.", param);
+        let missing = format!("missing route parameter '{}'", param);
+        let value = quote!(req
+            .param(#param)
+            .ok_or_else(|| ::std::string::String::from(#missing))?);
+
+        let (ty, expected) = match params.iter().find(|p| p.name == param).map(|p| p.ty) {
+            Some(ParameterType::Integer) => (quote!(i64), "an integer"),
+            Some(ParameterType::Unsigned) => (quote!(u64), "an unsigned integer"),
+            Some(ParameterType::Number) => (quote!(f64), "a number"),
+            Some(ParameterType::Boolean) => (quote!(bool), "`true` or `false`"),
+            Some(ParameterType::String) | None => {
+                fields.push(quote!(#[doc = #doc] pub #field: ::std::string::String));
+                extracts.push(quote!(#field: #value));
+                continue;
+            }
+        };
+
+        let invalid = format!("route parameter '{}' must be {}", param, expected);
+        fields.push(quote!(#[doc = #doc] pub #field: #ty));
+        extracts.push(quote!(#field: #value
+            .parse::<#ty>()
+            .map_err(|_| ::std::string::String::from(#invalid))?));
+    }
+
+    if fields.is_empty() {
+        return None;
+    }
+
+    let doc = format!(
+        "The parameters of the route of the `{}` function.",
+        function
+    );
+
+    Some(quote!(
+        #[doc = #doc]
+        #[derive(Debug, Clone)]
+        #[allow(dead_code)]
+        pub struct #name {
+            #(#fields,)*
+        }
+
+        impl wasmtime_functions::RouteParams for #name {
+            fn from_request(
+                req: &wasmtime_functions::Request,
+            ) -> ::std::result::Result<Self, ::std::string::String> {
+                Ok(Self {
+                    #(#extracts,)*
+                })
+            }
+        }
+    ))
+}
+
+fn emit_http_function(
+    mut func: ItemFn,
+    route: RouteArgs,
+    methods: Vec<Method>,
+) -> Result<TokenStream> {
     check_function_validity(&func)?;
-    check_http_validity(&func)?;
 
     let function = Function {
         name: func.sig.ident.to_string(),
         trigger: FunctionTrigger::Http {
-            path: path.value(),
+            path: route.path.value(),
             methods,
+            consumes: route.consumes,
+            params: route.params,
+            cache: route.cache,
+            headers: route.headers,
+            signed: route.signed,
+            etag: route.etag,
         },
         inputs: Vec::new(),
         outputs: vec![FunctionOutput::Http],
+        concurrency: route.concurrency,
+        vars: route.vars,
+        trace_sample: route.trace_sample,
     };
 
-    let ident = func.sig.ident;
+    let ident = func.sig.ident.clone();
     let inner = Ident::new(&format!("__{}", ident), ident.span());
     let name = Ident::new(
         &format!("__FUNCTION_{}", function.name.to_uppercase()),
         ident.span(),
     );
 
+    let version = Ident::new(
+        &format!("__INTERFACE_VERSION_{}", function.name.to_uppercase()),
+        ident.span(),
+    );
+
+    let url = Ident::new(
+        &format!("__URL_{}", function.name.to_uppercase()),
+        ident.span(),
+    );
+
+    // Route parameter structs are named after the function, e.g. `HelloParams` for `hello`
+    let params = Ident::new(
+        &format!(
+            "{}Params",
+            function
+                .name
+                .trim_start_matches("r#")
+                .split('_')
+                .map(|word| {
+                    let mut chars = word.chars();
+                    chars
+                        .next()
+                        .map(|c| c.to_uppercase().chain(chars).collect::<String>())
+                        .unwrap_or_default()
+                })
+                .collect::<String>()
+        ),
+        ident.span(),
+    );
+
+    let params_struct = match &function.trigger {
+        FunctionTrigger::Http {
+            path, params: p, ..
+        } => emit_params_struct(&params, &function.name, path, p),
+    };
+
+    let takes_params = check_http_validity(&func, params_struct.as_ref().map(|_| &params))?;
+
     func.sig.ident = inner.clone();
 
+    let run = |call: proc_macro2::TokenStream| emit_response(&func, call);
+
+    let url_builder = match &function.trigger {
+        FunctionTrigger::Http { path, params, .. } => emit_url_builder(&url, path, params),
+    };
+
+    let call = if takes_params {
+        let response = run(quote!(#inner(req, params)));
+        quote!(
+            let req = wasmtime_functions::Request::from_raw(req);
+            match <#params as wasmtime_functions::RouteParams>::from_request(&req) {
+                Ok(params) => #response,
+                Err(e) => wasmtime_functions::Response::from(
+                    wasmtime_functions::Problem::new(wasmtime_functions::StatusCode::BAD_REQUEST)
+                        .detail(e),
+                ),
+            }
+            .into_raw()
+        )
+    } else {
+        let response = run(quote!(#inner(wasmtime_functions::Request::from_raw(req))));
+        quote!(
+            (#response).into_raw()
+        )
+    };
+    let interface_version = emit_interface_version(&version);
     let descriptor = emit_descriptor(
         "__functions",
         &name,
@@ -223,15 +824,19 @@ fn emit_http_function(mut func: ItemFn, path: LitStr, methods: Vec<Method>) -> R
         pub extern "C" fn #ident(req: u32) -> u32 {
             #func
 
-            unsafe {
-                wasmtime_functions::Response::from(
-                    #inner(wasmtime_functions::Request::from_raw(req))
-                )
-                .into_raw()
-            }
+            wasmtime_functions::__invoke(|| unsafe { #call })
         }
 
+        // The runtime requires this signature for HTTP-triggered functions
+        const _: extern "C" fn(u32) -> u32 = #ident;
+
         #descriptor
+
+        #interface_version
+
+        #url_builder
+
+        #params_struct
     )
     .into())
 }
@@ -241,7 +846,7 @@ fn emit_http_function(mut func: ItemFn, path: LitStr, methods: Vec<Method>) -> R
 pub fn get(attr: TokenStream, item: TokenStream) -> TokenStream {
     match emit_http_function(
         parse_macro_input!(item as ItemFn),
-        parse_macro_input!(attr as LitStr),
+        parse_macro_input!(attr as RouteArgs),
         vec![Method::Get],
     ) {
         Ok(s) => s,
@@ -254,7 +859,7 @@ pub fn get(attr: TokenStream, item: TokenStream) -> TokenStream {
 pub fn head(attr: TokenStream, item: TokenStream) -> TokenStream {
     match emit_http_function(
         parse_macro_input!(item as ItemFn),
-        parse_macro_input!(attr as LitStr),
+        parse_macro_input!(attr as RouteArgs),
         vec![Method::Head],
     ) {
         Ok(s) => s,
@@ -267,7 +872,7 @@ pub fn head(attr: TokenStream, item: TokenStream) -> TokenStream {
 pub fn post(attr: TokenStream, item: TokenStream) -> TokenStream {
     match emit_http_function(
         parse_macro_input!(item as ItemFn),
-        parse_macro_input!(attr as LitStr),
+        parse_macro_input!(attr as RouteArgs),
         vec![Method::Post],
     ) {
         Ok(s) => s,
@@ -280,7 +885,7 @@ pub fn post(attr: TokenStream, item: TokenStream) -> TokenStream {
 pub fn put(attr: TokenStream, item: TokenStream) -> TokenStream {
     match emit_http_function(
         parse_macro_input!(item as ItemFn),
-        parse_macro_input!(attr as LitStr),
+        parse_macro_input!(attr as RouteArgs),
         vec![Method::Put],
     ) {
         Ok(s) => s,
@@ -293,7 +898,7 @@ pub fn put(attr: TokenStream, item: TokenStream) -> TokenStream {
 pub fn delete(attr: TokenStream, item: TokenStream) -> TokenStream {
     match emit_http_function(
         parse_macro_input!(item as ItemFn),
-        parse_macro_input!(attr as LitStr),
+        parse_macro_input!(attr as RouteArgs),
         vec![Method::Delete],
     ) {
         Ok(s) => s,
@@ -306,7 +911,7 @@ pub fn delete(attr: TokenStream, item: TokenStream) -> TokenStream {
 pub fn connect(attr: TokenStream, item: TokenStream) -> TokenStream {
     match emit_http_function(
         parse_macro_input!(item as ItemFn),
-        parse_macro_input!(attr as LitStr),
+        parse_macro_input!(attr as RouteArgs),
         vec![Method::Connect],
     ) {
         Ok(s) => s,
@@ -319,7 +924,7 @@ pub fn connect(attr: TokenStream, item: TokenStream) -> TokenStream {
 pub fn options(attr: TokenStream, item: TokenStream) -> TokenStream {
     match emit_http_function(
         parse_macro_input!(item as ItemFn),
-        parse_macro_input!(attr as LitStr),
+        parse_macro_input!(attr as RouteArgs),
         vec![Method::Options],
     ) {
         Ok(s) => s,
@@ -332,7 +937,7 @@ pub fn options(attr: TokenStream, item: TokenStream) -> TokenStream {
 pub fn trace(attr: TokenStream, item: TokenStream) -> TokenStream {
     match emit_http_function(
         parse_macro_input!(item as ItemFn),
-        parse_macro_input!(attr as LitStr),
+        parse_macro_input!(attr as RouteArgs),
         vec![Method::Trace],
     ) {
         Ok(s) => s,
@@ -345,7 +950,7 @@ pub fn trace(attr: TokenStream, item: TokenStream) -> TokenStream {
 pub fn patch(attr: TokenStream, item: TokenStream) -> TokenStream {
     match emit_http_function(
         parse_macro_input!(item as ItemFn),
-        parse_macro_input!(attr as LitStr),
+        parse_macro_input!(attr as RouteArgs),
         vec![Method::Patch],
     ) {
         Ok(s) => s,
@@ -358,16 +963,16 @@ pub fn patch(attr: TokenStream, item: TokenStream) -> TokenStream {
 pub fn http(attr: TokenStream, item: TokenStream) -> TokenStream {
     struct Args {
         methods: LitStr,
-        path: LitStr,
+        route: RouteArgs,
     }
 
     impl Parse for Args {
         fn parse(input: ParseStream) -> Result<Self> {
             let methods = input.parse()?;
             input.parse::<Token![,]>()?;
-            let path = input.parse()?;
+            let route = input.parse()?;
 
-            Ok(Self { methods, path })
+            Ok(Self { methods, route })
         }
     }
 
@@ -378,7 +983,7 @@ pub fn http(attr: TokenStream, item: TokenStream) -> TokenStream {
         Err(e) => return e.to_compile_error().into(),
     };
 
-    match emit_http_function(parse_macro_input!(item as ItemFn), args.path, methods) {
+    match emit_http_function(parse_macro_input!(item as ItemFn), args.route, methods) {
         Ok(s) => s,
         Err(e) => e.to_compile_error().into(),
     }
@@ -419,3 +1024,136 @@ pub fn var(item: TokenStream) -> TokenStream {
     )
     .into()
 }
+
+/// A macro for declaring the middleware of a Wasmtime Functions application.
+///
+/// The middleware is passed the request and the response of every HTTP-triggered function in the
+/// application, and returns the response to send, e.g. to add security headers to every response.
+/// An application may only declare one middleware.
+#[proc_macro_attribute]
+pub fn middleware(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return Error::new(
+            proc_macro2::TokenStream::from(attr).span(),
+            "middleware does not accept options",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let mut func = parse_macro_input!(item as ItemFn);
+
+    if let Err(e) = check_function_validity(&func).and_then(|_| check_middleware_validity(&func)) {
+        return e.to_compile_error().into();
+    }
+
+    let ident = func.sig.ident.clone();
+    let inner = Ident::new(&format!("__{}", ident), ident.span());
+    func.sig.ident = inner.clone();
+
+    let response = emit_response(
+        &func,
+        quote!(#inner(
+            wasmtime_functions::Request::from_raw(req),
+            wasmtime_functions::Response::from_raw(res),
+        )),
+    );
+
+    quote!(
+        // The runtime calls the middleware through this export after every function
+        #[export_name = "__wasmtime_functions_middleware"]
+        pub extern "C" fn #ident(req: u32, res: u32) -> u32 {
+            #func
+
+            wasmtime_functions::__respond(|| unsafe {
+                let response = #response;
+                response.into_raw()
+            })
+        }
+
+        // The runtime requires this signature for the middleware
+        const _: extern "C" fn(u32, u32) -> u32 = #ident;
+    )
+    .into()
+}
+
+/// A macro for building the path of a function's route.
+///
+/// The first argument is the name of the function, or a path to it (e.g. `"api::get_user"`), followed by
+/// a value for each parameter of the route, e.g. `url_for!("get_user", id = 42)`. The parameters are
+/// checked against the route at compile time: a missing or unknown parameter, or a value of the wrong type
+/// for a parameter declared with `params`, is an error. Unnamed wildcards are set with `rest`.
+///
+/// The macro evaluates to a `String` with the path, with string parameters percent-encoded.
+#[proc_macro]
+pub fn url_for(item: TokenStream) -> TokenStream {
+    struct Args {
+        function: syn::Path,
+        params: Vec<(Ident, syn::Expr)>,
+    }
+
+    impl Parse for Args {
+        fn parse(input: ParseStream) -> Result<Self> {
+            let name: LitStr = input.parse()?;
+            let mut function: syn::Path = name
+                .parse()
+                .map_err(|_| Error::new(name.span(), "expected the name of a function"))?;
+
+            let last = function.segments.last_mut().unwrap();
+            last.ident = Ident::new(
+                &format!("__URL_{}", last.ident.to_string().to_uppercase()),
+                name.span(),
+            );
+
+            let mut params = Vec::new();
+            while !input.is_empty() {
+                input.parse::<Token![,]>()?;
+                if input.is_empty() {
+                    break;
+                }
+
+                // Parameters named after keywords are fields with raw identifiers
+                let mut name = input.call(Ident::parse_any)?;
+                if syn::parse2::<Ident>(quote!(#name)).is_err() {
+                    name = Ident::new_raw(&name.to_string(), name.span());
+                }
+
+                input.parse::<Token![=]>()?;
+                params.push((name, input.parse()?));
+            }
+
+            Ok(Self { function, params })
+        }
+    }
+
+    let Args { function, params } = parse_macro_input!(item as Args);
+    let (names, values): (Vec<_>, Vec<_>) = params.into_iter().unzip();
+
+    quote!(
+        ::std::string::ToString::to_string(&#function {
+            #(#names: #values,)*
+            __marker: ::std::marker::PhantomData,
+        })
+    )
+    .into()
+}
+
+/// A macro for reading the metadata of a function declared with a HTTP macro in native tests.
+///
+/// The argument is the path to the function, e.g. `function_metadata!(hello)` or
+/// `function_metadata!(crate::api::hello)`.
+#[proc_macro]
+pub fn function_metadata(item: TokenStream) -> TokenStream {
+    let mut path = parse_macro_input!(item as syn::Path);
+
+    let last = path.segments.last_mut().unwrap();
+    last.ident = Ident::new(
+        &format!("__FUNCTION_{}", last.ident.to_string().to_uppercase()),
+        last.ident.span(),
+    );
+
+    quote!(
+        wasmtime_functions::mock::read_function_descriptor(&#path)
+    )
+    .into()
+}
@@ -0,0 +1,133 @@
+//! Caches for memoizing values in functions.
+//!
+//! The cache has two tiers:
+//!
+//! * The [`request`] tier holds values of any type for the current invocation, including its
+//!   middleware and tasks, so a value looked up in several places is only computed once per request.
+//! * The [`app`] tier holds bytes in the host, shared by every instance of the application, so a
+//!   value computed by one request is reused by later requests until it expires.
+//!
+//! ```ignore
+//! use wasmtime_functions::cache;
+//! use std::time::Duration;
+//!
+//! let user = cache::request::get_or_insert_with("user", || load_user(&req));
+//! let rates = cache::app::get_or_insert_with("rates", Duration::from_secs(300), fetch_rates);
+//! ```
+
+#[cfg(target_arch = "wasm32")]
+witx_bindgen_rust::import!("../../crates/runtime/witx/cache.witx");
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mock::cache;
+
+/// The cache of the current invocation.
+///
+/// Values are kept in the instance and cleared when the next function is invoked, so they are
+/// never seen by another request.
+pub mod request {
+    use std::any::Any;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    thread_local! {
+        static VALUES: RefCell<HashMap<String, Box<dyn Any>>> = RefCell::new(HashMap::new());
+    }
+
+    /// Gets the value of a key, or `None` if the key isn't set or its value is of another type.
+    ///
+    /// Values are cloned out of the cache; wrap values that are expensive to clone in an `Rc`.
+    pub fn get<T: Clone + 'static>(key: &str) -> Option<T> {
+        VALUES.with(|values| values.borrow().get(key)?.downcast_ref::<T>().cloned())
+    }
+
+    /// Sets the value of a key, replacing its value of any type.
+    pub fn insert<T: 'static>(key: &str, value: T) {
+        VALUES.with(|values| values.borrow_mut().insert(key.to_string(), Box::new(value)));
+    }
+
+    /// Removes a key, if it is set.
+    pub fn remove(key: &str) {
+        VALUES.with(|values| values.borrow_mut().remove(key));
+    }
+
+    /// Gets the value of a key, computing and caching it with the given function if the key isn't
+    /// set or its value is of another type.
+    ///
+    /// The function may use the cache itself.
+    pub fn get_or_insert_with<T: Clone + 'static, F: FnOnce() -> T>(key: &str, f: F) -> T {
+        if let Some(value) = get(key) {
+            return value;
+        }
+
+        let value = f();
+        insert(key, value.clone());
+        value
+    }
+
+    pub(crate) fn clear() {
+        VALUES.with(|values| values.borrow_mut().clear());
+    }
+}
+
+/// The cache of the application, which is stored by the host.
+///
+/// Values are bytes; encode structured values with a format such as JSON. Keys are shared by
+/// every function of the application. The host may evict a value before it expires, so a cached
+/// value should always be possible to recompute.
+pub mod app {
+    use super::cache;
+    use std::convert::Infallible;
+    use std::time::Duration;
+
+    /// Gets the value of a key, or `None` if the key isn't set or has expired.
+    pub fn get(key: &str) -> Result<Option<Vec<u8>>, String> {
+        cache::app_get(key)
+    }
+
+    /// Sets the value of a key, which expires after the given time-to-live.
+    ///
+    /// The time-to-live is measured in whole seconds. Setting a value fails if it is larger than
+    /// 1 MiB.
+    pub fn set<T: AsRef<[u8]>>(key: &str, value: T, ttl: Duration) -> Result<(), String> {
+        cache::app_set(key, value.as_ref(), ttl.as_secs())
+    }
+
+    /// Removes a key, if it is set.
+    pub fn remove(key: &str) -> Result<(), String> {
+        cache::app_remove(key)
+    }
+
+    /// Gets the value of a key, computing and caching it with the given function if the key isn't
+    /// set or has expired.
+    ///
+    /// Errors from the cache are ignored as the value can always be computed. Concurrent requests
+    /// that miss the cache each compute the value, with the last one cached.
+    pub fn get_or_insert_with<T, F>(key: &str, ttl: Duration, f: F) -> Vec<u8>
+    where
+        T: Into<Vec<u8>>,
+        F: FnOnce() -> T,
+    {
+        match get_or_try_insert_with(key, ttl, || Ok::<_, Infallible>(f())) {
+            Ok(value) => value,
+            Err(e) => match e {},
+        }
+    }
+
+    /// Like [`get_or_insert_with`], but for a function that may fail.
+    ///
+    /// Nothing is cached if the function fails.
+    pub fn get_or_try_insert_with<T, E, F>(key: &str, ttl: Duration, f: F) -> Result<Vec<u8>, E>
+    where
+        T: Into<Vec<u8>>,
+        F: FnOnce() -> Result<T, E>,
+    {
+        if let Ok(Some(value)) = get(key) {
+            return Ok(value);
+        }
+
+        let value = f()?.into();
+        let _ = set(key, &value, ttl);
+        Ok(value)
+    }
+}
@@ -0,0 +1,73 @@
+//! The configuration of the application as resolved by the host.
+//!
+//! This is intended for functions that log their effective configuration; use `std::env` to read
+//! the value of a particular environment variable.
+
+#[cfg(target_arch = "wasm32")]
+witx_bindgen_rust::import!("../../crates/runtime/witx/config.witx");
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mock::config;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Represents the value of a setting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// A text value.
+    Text(String),
+    /// An integer value.
+    Integer(i64),
+    /// A boolean value.
+    Boolean(bool),
+    /// A duration value.
+    Duration(Duration),
+}
+
+/// Represents the configuration of the application as resolved by the host.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// The environment variables declared by the application that the host exposes to functions.
+    ///
+    /// Variables are hidden unless the host explicitly exposes them, such as with `--config-var`.
+    pub vars: BTreeMap<String, String>,
+    /// The host's settings for the function being invoked.
+    ///
+    /// The settings include `function`, `path`, `timeout`, `outbound_requests`, and `grpc_calls`, and when
+    /// configured, `concurrency`, `cache_ttl`, `sql_statement_timeout`, and `fuel_limit`.
+    pub settings: BTreeMap<String, Value>,
+}
+
+impl Config {
+    /// Gets the value of an environment variable.
+    pub fn var<T: AsRef<str>>(&self, name: T) -> Option<&str> {
+        self.vars.get(name.as_ref()).map(String::as_str)
+    }
+
+    /// Gets the value of a setting.
+    pub fn setting<T: AsRef<str>>(&self, name: T) -> Option<&Value> {
+        self.settings.get(name.as_ref())
+    }
+}
+
+/// Gets the configuration of the application as resolved by the host.
+pub fn all() -> Config {
+    Config {
+        vars: config::vars().into_iter().collect(),
+        settings: config::settings()
+            .into_iter()
+            .map(|(name, setting)| {
+                (
+                    name,
+                    match setting {
+                        config::Setting::Text(s) => Value::Text(s),
+                        config::Setting::Integer(i) => Value::Integer(i),
+                        config::Setting::Boolean(b) => Value::Boolean(b),
+                        config::Setting::Duration(ms) => Value::Duration(Duration::from_millis(ms)),
+                    },
+                )
+            })
+            .collect(),
+    }
+}
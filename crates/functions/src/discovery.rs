@@ -0,0 +1,49 @@
+//! Service discovery provided by the host.
+//!
+//! Functions resolve services by name rather than embedding host names, so the host decides which
+//! endpoints a function connects to. Resolving a service the host doesn't know fails.
+
+#[cfg(target_arch = "wasm32")]
+witx_bindgen_rust::import!("../../crates/runtime/witx/discovery.witx");
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mock::discovery;
+
+/// Represents an endpoint of a service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    /// The host name or address of the endpoint.
+    pub host: String,
+    /// The port of the endpoint.
+    pub port: u16,
+    /// The priority of the endpoint; endpoints with a lower value should be preferred.
+    pub priority: u16,
+    /// The relative weight of the endpoint among endpoints with the same priority.
+    pub weight: u16,
+}
+
+impl Endpoint {
+    /// Gets the authority of the endpoint, of the form `HOST:PORT`.
+    ///
+    /// Use the authority to build the URI of an outbound request to the endpoint.
+    pub fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// Resolves a service to its endpoints, ordered by priority.
+pub fn resolve<T: AsRef<str>>(service: T) -> Result<Vec<Endpoint>, String> {
+    Ok(discovery::resolve(service.as_ref())?
+        .into_iter()
+        .map(|e| Endpoint {
+            host: e.host,
+            port: e.port,
+            priority: e.priority,
+            weight: e.weight,
+        })
+        .collect())
+}
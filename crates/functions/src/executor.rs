@@ -0,0 +1,44 @@
+//! The executor of `async` functions.
+//!
+//! `async fn` is accepted, but there are no awaitable host APIs: every call to the host blocks the
+//! instance until it completes, so the futures of a function never wait on I/O. They may only
+//! return pending to yield, and so are polled until they complete.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+// Records whether a future was woken since it was last polled
+#[derive(Default)]
+struct Woken(AtomicBool);
+
+impl Wake for Woken {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+/// Runs a future to completion.
+///
+/// Returns `None` if the future is pending without having been woken, as nothing else can wake it.
+pub fn block_on<F: Future>(future: F) -> Option<F::Output> {
+    let mut future = Box::pin(future);
+    let woken = Arc::new(Woken::default());
+    let waker = Waker::from(woken.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return Some(output);
+        }
+
+        if !woken.0.swap(false, Ordering::AcqRel) {
+            return None;
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Outbound HTTP requests sent by the host.
+//!
+//! The host only sends requests to the hosts it allows; requests to any other host fail.
+
+#[cfg(target_arch = "wasm32")]
+witx_bindgen_rust::import!("../../crates/runtime/witx/fetch.witx");
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mock::fetch;
+
+use crate::StatusCode;
+
+/// Represents the response to an outbound HTTP request.
+#[derive(Debug)]
+pub struct Response {
+    /// The status code of the response.
+    pub status: StatusCode,
+    /// The headers of the response.
+    pub headers: Vec<(String, String)>,
+    /// The body of the response.
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Gets the first value of a header of the response.
+    pub fn header<T: AsRef<str>>(&self, name: T) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name.as_ref()))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl From<Response> for crate::Response {
+    /// Converts the response to an outbound request into a response for the function to return.
+    ///
+    /// The framing headers of the response are not copied, as the host frames the body itself.
+    /// Use [`into_builder`](crate::Response::into_builder) on the result to change it before returning it.
+    fn from(res: Response) -> Self {
+        res.headers
+            .iter()
+            .filter(|(name, _)| {
+                !name.eq_ignore_ascii_case("content-length")
+                    && !name.eq_ignore_ascii_case("transfer-encoding")
+            })
+            .fold(
+                crate::Response::build(res.status),
+                |builder, (name, value)| builder.header(name, value),
+            )
+            .body(res.body)
+    }
+}
+
+/// Sends an outbound HTTP request.
+pub fn send<T: AsRef<str>, U: AsRef<str>>(
+    method: T,
+    uri: U,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> Result<Response, String> {
+    let res = fetch::send(method.as_ref(), uri.as_ref(), headers, body)?;
+
+    Ok(Response {
+        status: StatusCode::from_u16(res.status).map_err(|e| e.to_string())?,
+        headers: res.headers,
+        body: res.body,
+    })
+}
+
+/// Sends an outbound HTTP `GET` request.
+pub fn get<T: AsRef<str>>(uri: T) -> Result<Response, String> {
+    send("GET", uri, &[], &[])
+}
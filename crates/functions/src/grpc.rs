@@ -0,0 +1,97 @@
+//! Outbound gRPC calls made by the host.
+//!
+//! Calls name a protobuf service rather than a server; the host decides which server receives the
+//! calls for each service and fails calls to any other service. Only unary calls are supported,
+//! and messages are passed as serialized protobuf, so use a crate such as `prost` to encode
+//! requests and decode responses.
+
+#[cfg(target_arch = "wasm32")]
+witx_bindgen_rust::import!("../../crates/runtime/witx/grpc.witx");
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mock::grpc;
+
+use std::fmt;
+use std::time::Duration;
+
+/// Represents the response to a successful gRPC call.
+#[derive(Debug)]
+pub struct Response {
+    /// The serialized protobuf message of the response.
+    pub payload: Vec<u8>,
+    /// The metadata of the response, including its trailers.
+    ///
+    /// Binary metadata is not included.
+    pub metadata: Vec<(String, String)>,
+}
+
+impl Response {
+    /// Gets the first value of a metadata entry of the response.
+    pub fn metadata<T: AsRef<str>>(&self, name: T) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name.as_ref()))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Represents the status of a failed gRPC call.
+///
+/// Failures detected by the host, such as calls to a service the host doesn't allow or calls that
+/// exceed their deadline, are reported with the corresponding gRPC status code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    /// The gRPC status code, such as `5` for `NOT_FOUND`.
+    pub code: u32,
+    /// The message describing the failure.
+    pub message: String,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "gRPC status {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for Status {}
+
+/// Makes a unary gRPC call to a method of a service.
+///
+/// The service is the fully-qualified name of the protobuf service, such as `orders.v1.Orders`,
+/// and the method is the name of the method, such as `GetOrder`.
+/// Without a deadline, the host's default deadline applies.
+pub fn call<T: AsRef<str>, U: AsRef<str>>(
+    service: T,
+    method: U,
+    metadata: &[(&str, &str)],
+    payload: &[u8],
+    deadline: Option<Duration>,
+) -> Result<Response, Status> {
+    let deadline = deadline.map(|d| d.as_millis().min(u64::MAX as u128) as u64);
+
+    match grpc::call(
+        service.as_ref(),
+        method.as_ref(),
+        metadata,
+        payload,
+        deadline,
+    ) {
+        Ok(res) => Ok(Response {
+            payload: res.payload,
+            metadata: res.metadata,
+        }),
+        Err(status) => Err(Status {
+            code: status.code,
+            message: status.message,
+        }),
+    }
+}
+
+/// Makes a unary gRPC call without metadata, using the host's default deadline.
+pub fn unary<T: AsRef<str>, U: AsRef<str>>(
+    service: T,
+    method: U,
+    payload: &[u8],
+) -> Result<Response, Status> {
+    call(service, method, &[], payload, None)
+}
@@ -0,0 +1,299 @@
+//! Well-known HTTP header names and builders for their values.
+//!
+//! Header names and values are validated in the function before they are passed to the host, so an
+//! illegal header fails with an error naming the header rather than failing the invocation.
+//!
+//! ```ignore
+//! use wasmtime_functions::headers::{CacheControl, ContentType};
+//! use std::time::Duration;
+//!
+//! Response::build(StatusCode::OK)
+//!     .typed_header(ContentType::new("text/html").charset("utf-8"))
+//!     .typed_header(CacheControl::new().public().max_age(Duration::from_secs(60)))
+//!     .body(html)
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+pub use http::header::{HeaderName, HeaderValue};
+pub use http::header::{
+    ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE, ALLOW, AUTHORIZATION, CACHE_CONTROL,
+    CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LANGUAGE, CONTENT_LENGTH, CONTENT_TYPE, COOKIE,
+    ETAG, EXPIRES, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, LOCATION, ORIGIN,
+    RETRY_AFTER, SET_COOKIE, USER_AGENT, VARY, WWW_AUTHENTICATE,
+};
+
+/// An error for a header name or value that isn't legal in HTTP.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidHeader {
+    name: String,
+    reason: &'static str,
+}
+
+impl fmt::Display for InvalidHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid header `{}`: {}", self.name, self.reason)
+    }
+}
+
+impl std::error::Error for InvalidHeader {}
+
+/// Validates a header name and value.
+///
+/// Names must be tokens, such as `X-Request-Id`. Values may only contain visible ASCII
+/// characters, spaces, and tabs; in particular, line breaks and non-ASCII text are illegal.
+pub fn validate(name: &str, value: &str) -> Result<(), InvalidHeader> {
+    let invalid = |reason| InvalidHeader {
+        name: name.to_string(),
+        reason,
+    };
+
+    HeaderName::from_bytes(name.as_bytes())
+        .map_err(|_| invalid("the name is not a valid token"))?;
+
+    // Stricter than `HeaderValue`, which allows non-ASCII bytes that the host rejects
+    if !value
+        .bytes()
+        .all(|b| b == b'\t' || (b' '..=b'~').contains(&b))
+    {
+        return Err(invalid(
+            "the value may only contain visible ASCII characters, spaces, and tabs",
+        ));
+    }
+
+    Ok(())
+}
+
+/// A header value that knows the name of its header.
+///
+/// Typed headers are set with [`ResponseBuilder::typed_header`](crate::ResponseBuilder::typed_header).
+pub trait TypedHeader: fmt::Display {
+    /// Gets the name of the header.
+    fn name() -> HeaderName;
+}
+
+/// The value of a `Content-Type` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentType {
+    mime: String,
+    params: Vec<(String, String)>,
+}
+
+impl ContentType {
+    /// Creates a content type of the given media type, such as `text/html`.
+    pub fn new<T: Into<String>>(mime: T) -> Self {
+        Self {
+            mime: mime.into(),
+            params: Vec::new(),
+        }
+    }
+
+    /// Creates the `application/json` content type.
+    pub fn json() -> Self {
+        Self::new("application/json")
+    }
+
+    /// Creates the `text/plain; charset=utf-8` content type.
+    pub fn text() -> Self {
+        Self::new("text/plain").charset("utf-8")
+    }
+
+    /// Creates the `text/html; charset=utf-8` content type.
+    pub fn html() -> Self {
+        Self::new("text/html").charset("utf-8")
+    }
+
+    /// Sets the `charset` parameter.
+    pub fn charset<T: Into<String>>(self, charset: T) -> Self {
+        self.param("charset", charset)
+    }
+
+    /// Adds a parameter, such as `boundary` for a multipart media type.
+    ///
+    /// The value is quoted if it isn't a token.
+    pub fn param<T: Into<String>, U: Into<String>>(mut self, name: T, value: U) -> Self {
+        self.params.push((name.into(), value.into()));
+        self
+    }
+}
+
+impl fmt::Display for ContentType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mime)?;
+
+        for (name, value) in &self.params {
+            if !value.is_empty() && value.bytes().all(is_token) {
+                write!(f, "; {}={}", name, value)?;
+            } else {
+                write!(
+                    f,
+                    "; {}=\"{}\"",
+                    name,
+                    value.replace('\\', "\\\\").replace('"', "\\\"")
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl TypedHeader for ContentType {
+    fn name() -> HeaderName {
+        CONTENT_TYPE
+    }
+}
+
+/// The value of an `Authorization` header.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Authorization {
+    /// A bearer token, as used by OAuth 2.0.
+    Bearer(String),
+    /// A username and password, which are encoded with Base64 but not encrypted.
+    Basic {
+        /// The username.
+        username: String,
+        /// The password.
+        password: String,
+    },
+}
+
+impl Authorization {
+    /// Creates a bearer token authorization.
+    pub fn bearer<T: Into<String>>(token: T) -> Self {
+        Self::Bearer(token.into())
+    }
+
+    /// Creates a basic authorization with the given credentials.
+    pub fn basic<T: Into<String>, U: Into<String>>(username: T, password: U) -> Self {
+        Self::Basic {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+}
+
+// The credentials are redacted so that they aren't logged by accident
+impl fmt::Debug for Authorization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bearer(_) => write!(f, "Bearer(..)"),
+            Self::Basic { username, .. } => write!(f, "Basic({}, ..)", username),
+        }
+    }
+}
+
+impl fmt::Display for Authorization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bearer(token) => write!(f, "Bearer {}", token),
+            Self::Basic { username, password } => write!(
+                f,
+                "Basic {}",
+                encode_base64(format!("{}:{}", username, password).as_bytes())
+            ),
+        }
+    }
+}
+
+impl TypedHeader for Authorization {
+    fn name() -> HeaderName {
+        AUTHORIZATION
+    }
+}
+
+/// The value of a `Cache-Control` header.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheControl(Vec<String>);
+
+impl CacheControl {
+    /// Creates an empty cache control.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn directive<T: Into<String>>(mut self, directive: T) -> Self {
+        self.0.push(directive.into());
+        self
+    }
+
+    /// Allows shared caches, such as CDNs, to store the response.
+    pub fn public(self) -> Self {
+        self.directive("public")
+    }
+
+    /// Only allows the client's own cache to store the response.
+    pub fn private(self) -> Self {
+        self.directive("private")
+    }
+
+    /// Requires caches to revalidate the response before each use.
+    pub fn no_cache(self) -> Self {
+        self.directive("no-cache")
+    }
+
+    /// Prevents caches from storing the response.
+    pub fn no_store(self) -> Self {
+        self.directive("no-store")
+    }
+
+    /// Requires caches to revalidate the response once it is stale.
+    pub fn must_revalidate(self) -> Self {
+        self.directive("must-revalidate")
+    }
+
+    /// Indicates that the response won't change while it is fresh.
+    pub fn immutable(self) -> Self {
+        self.directive("immutable")
+    }
+
+    /// Sets how long the response is fresh, in whole seconds.
+    pub fn max_age(self, age: Duration) -> Self {
+        self.directive(format!("max-age={}", age.as_secs()))
+    }
+
+    /// Sets how long the response is fresh in shared caches, in whole seconds.
+    pub fn s_maxage(self, age: Duration) -> Self {
+        self.directive(format!("s-maxage={}", age.as_secs()))
+    }
+}
+
+impl fmt::Display for CacheControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.join(", "))
+    }
+}
+
+impl TypedHeader for CacheControl {
+    fn name() -> HeaderName {
+        CACHE_CONTROL
+    }
+}
+
+fn is_token(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len() * 4 / 3 + 4);
+
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
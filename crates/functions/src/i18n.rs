@@ -0,0 +1,24 @@
+//! Messages translated by the host.
+//!
+//! The host loads the application's translation bundles once at startup, so multi-language
+//! applications don't need to embed every catalog in the module. The locale of a request is
+//! negotiated by the host with [`Request::locale`](crate::Request::locale).
+
+#[cfg(target_arch = "wasm32")]
+witx_bindgen_rust::import!("../../crates/runtime/witx/i18n.witx");
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mock::i18n;
+
+/// Translates the message with the given key into a locale.
+///
+/// Messages missing from the locale's bundle fall back to less specific locales (e.g. `fr-CA` to
+/// `fr`) and then the host's default locale. Placeholders such as `{name}` in the message are
+/// replaced with the argument of the same name; translation fails if an argument is missing.
+pub fn translate<T: AsRef<str>, U: AsRef<str>>(
+    key: T,
+    locale: U,
+    args: &[(&str, &str)],
+) -> Result<String, String> {
+    i18n::translate(key.as_ref(), locale.as_ref(), args)
+}
@@ -4,11 +4,35 @@
 
 #![deny(missing_docs)]
 
+#[cfg(target_arch = "wasm32")]
 witx_bindgen_rust::import!("../../crates/runtime/witx/functions.witx");
 
+pub mod cache;
+pub mod config;
+pub mod discovery;
+mod executor;
+pub mod fetch;
+pub mod grpc;
+pub mod headers;
+pub mod i18n;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod mock;
+mod problem;
+mod signing;
+pub mod sql;
+mod tasks;
+pub mod templates;
+
+#[cfg(not(target_arch = "wasm32"))]
+use mock::functions;
+
 use http::Uri;
 use std::fmt;
-use time::Duration;
+use time::{Duration, OffsetDateTime};
+
+pub use problem::Problem;
+pub use signing::sign_url;
+pub use tasks::spawn_after_response;
 
 /// Represents a HTTP status code.
 pub type StatusCode = http::StatusCode;
@@ -24,8 +48,46 @@ impl Request {
     }
 
     /// Gets the URI of the HTTP request.
+    ///
+    /// Characters that are legal in a URL but not accepted by [`Uri`], such as `|` or `{`, are
+    /// percent-encoded; use [`Request::path`] and [`Request::query`] for the parts as received.
     pub fn uri(&self) -> Uri {
-        self.0.uri().parse().expect("URI is invalid")
+        let parts = self.0.uri_parts();
+
+        let mut path_and_query = encode_uri_part(&parts.path);
+        if let Some(query) = &parts.query {
+            path_and_query.push('?');
+            path_and_query.push_str(&encode_uri_part(query));
+        }
+
+        let mut builder = Uri::builder();
+        if let Some(authority) = &parts.authority {
+            builder = builder
+                .scheme(parts.scheme.as_str())
+                .authority(authority.as_str());
+        }
+
+        builder
+            .path_and_query(path_and_query)
+            .build()
+            .unwrap_or_default()
+    }
+
+    /// Gets the path of the HTTP request's URI.
+    ///
+    /// The path is percent-encoded as it was received.
+    pub fn path(&self) -> String {
+        self.0.uri_parts().path
+    }
+
+    /// Gets the authority of the HTTP request's URI, which is the host and any explicit port.
+    pub fn authority(&self) -> Option<String> {
+        self.0.uri_parts().authority
+    }
+
+    /// Gets the query string of the HTTP request's URI, without the leading `?`.
+    pub fn query(&self) -> Option<String> {
+        self.0.uri_parts().query
     }
 
     /// Gets the method of the HTTP request.
@@ -38,6 +100,22 @@ impl Request {
         self.0.header(name.as_ref())
     }
 
+    /// Gets the idempotency key of the HTTP request from its `Idempotency-Key` header.
+    ///
+    /// When the server replays responses for idempotency keys, a function is only invoked for the
+    /// first request with a key; retries receive the stored response. Functions can also use the
+    /// key to deduplicate side effects in the services they call.
+    pub fn idempotency_key(&self) -> Option<String> {
+        self.header("Idempotency-Key")
+    }
+
+    /// Gets the headers of the HTTP request as name-value pairs.
+    ///
+    /// The casing of the names and whether repeated headers are combined depends on the server.
+    pub fn headers(&self) -> Vec<(String, String)> {
+        self.0.headers()
+    }
+
     /// Gets a cookie of the HTTP request.
     pub fn cookie<T: AsRef<str>>(&self, name: T) -> Option<String> {
         self.0.cookie(name.as_ref())
@@ -48,10 +126,95 @@ impl Request {
         self.0.param(name.as_ref())
     }
 
+    /// Gets an extension of the HTTP request.
+    ///
+    /// Extensions are values attached to the request by the host, such as the identity of an
+    /// authenticated user, rather than sent by the client.
+    pub fn extension<T: AsRef<str>>(&self, name: T) -> Option<String> {
+        self.0.extension(name.as_ref())
+    }
+
     /// Gets the body of the HTTP request.
     pub fn body(&self) -> Result<Vec<u8>, String> {
         self.0.body()
     }
+
+    /// Deserializes the JSON body of the HTTP request.
+    ///
+    /// The `Content-Type` header of the request is not checked.
+    ///
+    /// Requires the `json` feature.
+    #[cfg(feature = "json")]
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, String> {
+        serde_json::from_slice(&self.body()?).map_err(|e| format!("invalid JSON body: {}", e))
+    }
+
+    /// Gets the locale negotiated for the HTTP request from its `Accept-Language` header.
+    ///
+    /// When the host has translation bundles, this is the locale of the bundle that best matches the
+    /// client's preferred languages, or the host's default locale if none match. Otherwise, it is the
+    /// client's most preferred language, or `None` if the request has no `Accept-Language` header.
+    pub fn locale(&self) -> Option<String> {
+        self.0.locale()
+    }
+
+    /// Gets the nonce of the HTTP request, a random Base64 value that is unique to the request.
+    ///
+    /// The host generates the nonce when it is first asked for, and it stays the same for the rest
+    /// of the request, including in middleware. Mark inline scripts and styles with it, as in
+    /// `<script nonce="...">`; if the host has a Content-Security-Policy configured, the nonce is
+    /// appended to the policy of the response so that only the marked elements are allowed.
+    pub fn nonce(&self) -> String {
+        self.0.nonce()
+    }
+
+    /// Uploads a part of a `multipart/form-data` request to a bucket of the host's blob store.
+    ///
+    /// The part is streamed from the request to the store by the host, so it is never held in the
+    /// function's memory. The host reads the body as parts are uploaded, so parts must be uploaded in
+    /// the order they were sent and the body is no longer available from [`Request::body`].
+    pub fn upload_part<T: AsRef<str>, U: AsRef<str>, V: AsRef<str>>(
+        &self,
+        part: T,
+        bucket: U,
+        key: V,
+    ) -> Result<UploadedObject, String> {
+        self.0
+            .upload_part(part.as_ref(), bucket.as_ref(), key.as_ref())
+            .map(|object| UploadedObject {
+                bucket: object.bucket,
+                key: object.key,
+                size: object.size,
+                content_type: object.content_type,
+                filename: object.filename,
+            })
+    }
+}
+
+/// Extracts the typed parameters of a route from a request.
+///
+/// The HTTP macros implement this for the parameter struct they generate for each route with
+/// parameters, such as `HelloParams` for a function named `hello`. A function receives the
+/// parameters of its route by taking the struct as a second parameter; requests whose parameters
+/// can't be extracted receive a `400 Bad Request` problem response without invoking the function.
+pub trait RouteParams: Sized {
+    /// Extracts the parameters from a request, failing if one is missing or has the wrong type.
+    fn from_request(req: &Request) -> Result<Self, String>;
+}
+
+/// Represents an object uploaded from a part of a request with [`Request::upload_part`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadedObject {
+    /// The bucket the object was uploaded to.
+    pub bucket: String,
+    /// The key of the object within its bucket.
+    pub key: String,
+    /// The size of the object in bytes.
+    pub size: u64,
+    /// The content type of the part, if it had one.
+    pub content_type: Option<String>,
+    /// The file name of the part, if it had one.
+    pub filename: Option<String>,
 }
 
 /// Used for building HTTP responses.
@@ -63,9 +226,42 @@ impl ResponseBuilder {
         Self(functions::Response::new(status.as_u16()).expect("status code is invalid"))
     }
 
+    /// Sets the status code of the HTTP response.
+    pub fn status(self, status: StatusCode) -> Self {
+        self.0
+            .set_status(status.as_u16())
+            .expect("status code is invalid");
+        self
+    }
+
     /// Sets a header of the HTTP response.
+    ///
+    /// Panics if the name or value isn't legal in HTTP; use [`try_header`](Self::try_header) for
+    /// values that may not be, such as those from user input.
     pub fn header<T: AsRef<str>, U: AsRef<str>>(self, name: T, value: U) -> Self {
+        self.try_header(name, value)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Sets a header of the HTTP response, failing if the name or value isn't legal in HTTP.
+    pub fn try_header<T: AsRef<str>, U: AsRef<str>>(
+        self,
+        name: T,
+        value: U,
+    ) -> Result<Self, headers::InvalidHeader> {
+        headers::validate(name.as_ref(), value.as_ref())?;
         self.0.set_header(name.as_ref(), value.as_ref());
+        Ok(self)
+    }
+
+    /// Sets a header of the HTTP response from a typed value, such as a [`headers::CacheControl`].
+    pub fn typed_header<H: headers::TypedHeader>(self, value: H) -> Self {
+        self.header(H::name(), value.to_string())
+    }
+
+    /// Removes a header of the HTTP response.
+    pub fn remove_header<T: AsRef<str>>(self, name: T) -> Self {
+        self.0.remove_header(name.as_ref());
         self
     }
 
@@ -88,6 +284,48 @@ impl ResponseBuilder {
         self.0.set_body(body.as_ref());
         Response(self.0)
     }
+
+    /// Sets the body of the HTTP response to a value serialized as JSON.
+    ///
+    /// The `Content-Type` header is set to `application/json` unless it was already set.
+    /// This completes the builder and returns the response.
+    ///
+    /// Panics if the value can't be serialized, such as a map with keys that aren't strings;
+    /// use [`try_json`](Self::try_json) to handle the error instead.
+    ///
+    /// Requires the `json` feature.
+    #[cfg(feature = "json")]
+    pub fn json<T: serde::Serialize + ?Sized>(self, value: &T) -> Response {
+        self.try_json(value).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Sets the body of the HTTP response to a value serialized as JSON, failing if the value
+    /// can't be serialized.
+    ///
+    /// Requires the `json` feature.
+    #[cfg(feature = "json")]
+    pub fn try_json<T: serde::Serialize + ?Sized>(
+        self,
+        value: &T,
+    ) -> Result<Response, serde_json::Error> {
+        let body = serde_json::to_vec(value)?;
+
+        let builder = if self.0.header("Content-Type").is_none() {
+            self.typed_header(headers::ContentType::json())
+        } else {
+            self
+        };
+
+        Ok(builder.body(body))
+    }
+
+    /// Completes the builder, keeping the existing body of the HTTP response.
+    ///
+    /// Use this with a builder from [`Response::into_builder`] to change a response without
+    /// copying its body.
+    pub fn finish(self) -> Response {
+        Response(self.0)
+    }
 }
 
 /// Represents a HTTP response.
@@ -100,6 +338,14 @@ impl Response {
         ResponseBuilder::new(status)
     }
 
+    /// Converts the HTTP response back into a builder.
+    ///
+    /// The status, headers, cookies, and body of the response are kept; use
+    /// [`ResponseBuilder::finish`] to complete the builder without replacing the body.
+    pub fn into_builder(self) -> ResponseBuilder {
+        ResponseBuilder(self.0)
+    }
+
     /// Gets the status code of the HTTP response.
     pub fn status(&self) -> StatusCode {
         StatusCode::from_u16(self.0.status()).unwrap()
@@ -110,11 +356,21 @@ impl Response {
         self.0.header(name.as_ref())
     }
 
+    /// Gets the headers of the HTTP response as name-value pairs.
+    pub fn headers(&self) -> Vec<(String, String)> {
+        self.0.headers()
+    }
+
     /// Gets the body of the HTTP response.
     pub fn body(&self) -> Vec<u8> {
         self.0.body()
     }
 
+    #[doc(hidden)]
+    pub unsafe fn from_raw(handle: u32) -> Self {
+        Self(functions::Response::from_raw(handle as i32))
+    }
+
     #[doc(hidden)]
     pub unsafe fn into_raw(self) -> u32 {
         self.0.into_raw() as u32
@@ -145,6 +401,13 @@ impl From<String> for Response {
     }
 }
 
+#[cfg(feature = "json")]
+impl From<serde_json::Value> for Response {
+    fn from(value: serde_json::Value) -> Self {
+        Self::build(StatusCode::OK).json(&value)
+    }
+}
+
 /// The `SameSite` cookie attribute.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SameSite {
@@ -156,6 +419,17 @@ pub enum SameSite {
     None,
 }
 
+/// The `Priority` cookie attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Priority {
+    /// Cookies are the first to be evicted when the browser's cookie limit is reached.
+    Low,
+    /// Cookies are evicted after low priority cookies; this is the browser's default.
+    Medium,
+    /// Cookies are the last to be evicted when the browser's cookie limit is reached.
+    High,
+}
+
 /// Used for building HTTP response cookies.
 pub struct CookieBuilder(functions::Cookie);
 
@@ -183,6 +457,14 @@ impl CookieBuilder {
         self
     }
 
+    /// Sets the Expires attribute on the cookie.
+    ///
+    /// Times before the Unix epoch are treated as the epoch.
+    pub fn expires(self, value: OffsetDateTime) -> Self {
+        self.0.set_expires(value.unix_timestamp());
+        self
+    }
+
     /// Sets the SameSite attribute on the cookie.
     pub fn same_site(self, value: SameSite) -> Self {
         self.0.set_same_site(match value {
@@ -205,6 +487,24 @@ impl CookieBuilder {
         self
     }
 
+    /// Sets the Priority attribute on the cookie.
+    pub fn priority(self, value: Priority) -> Self {
+        self.0.set_priority(match value {
+            Priority::Low => functions::CookiePriority::Low,
+            Priority::Medium => functions::CookiePriority::Medium,
+            Priority::High => functions::CookiePriority::High,
+        });
+        self
+    }
+
+    /// Sets the Partitioned attribute on the cookie.
+    ///
+    /// Browsers only accept partitioned cookies that are also secure.
+    pub fn partitioned(self) -> Self {
+        self.0.set_partitioned(true);
+        self
+    }
+
     /// Finishes building the cookie.
     pub fn finish(self) -> Cookie {
         Cookie(self.0)
@@ -221,6 +521,89 @@ impl Cookie {
     }
 }
 
+// Percent-encodes the characters of a path or query that `Uri` does not accept.
+fn encode_uri_part(part: &str) -> String {
+    let mut encoded = String::with_capacity(part.len());
+    for b in part.bytes() {
+        match b {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'.'
+            | b'_'
+            | b'~'
+            | b'!'
+            | b'$'
+            | b'&'
+            | b'\''
+            | b'('
+            | b')'
+            | b'*'
+            | b'+'
+            | b','
+            | b';'
+            | b'='
+            | b':'
+            | b'@'
+            | b'/'
+            | b'?'
+            | b'%' => encoded.push(b as char),
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+/// Aborts the function, immediately responding with the given status and a plain text message.
+///
+/// This is a way to bail out of a guard clause in a helper without returning a response through
+/// every caller. The function stops executing: no code after the call runs, including destructors,
+/// the application's middleware, and tasks spawned with [`spawn_after_response`].
+pub fn abort<T: AsRef<str>>(status: StatusCode, message: T) -> ! {
+    functions::abort(status.as_u16(), message.as_ref());
+
+    // The host sends the response once the instance exits
+    std::process::exit(0)
+}
+
+#[doc(hidden)]
+#[cfg(target_arch = "wasm32")]
+pub fn __respond<F: FnOnce() -> u32>(entry: F) -> u32 {
+    entry()
+}
+
+#[doc(hidden)]
+#[cfg(not(target_arch = "wasm32"))]
+pub fn __respond<F: FnOnce() -> u32>(entry: F) -> u32 {
+    mock::catch_abort(entry)
+}
+
+// The request cache is cleared when a function is invoked rather than when the middleware or
+// tasks of its invocation are run
+#[doc(hidden)]
+pub fn __invoke<F: FnOnce() -> u32>(entry: F) -> u32 {
+    cache::request::clear();
+    __respond(entry)
+}
+
+#[doc(hidden)]
+pub fn __encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for b in segment.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+#[doc(hidden)]
+pub use executor::block_on as __block_on;
+
 pub use wasmtime_functions_codegen::{
-    connect, delete, get, head, http, options, patch, post, put, trace, var,
+    connect, delete, get, head, http, middleware, options, patch, post, put, trace, url_for, var,
 };
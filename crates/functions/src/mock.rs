@@ -0,0 +1,1051 @@
+//! An in-memory mock of the host for testing functions natively.
+//!
+//! When the crate is compiled for a target other than `wasm32`, the host interface is
+//! implemented by this module so that the logic of functions can be unit tested with
+//! `cargo test` without building a WebAssembly module.
+//!
+//! Functions are invoked through their exported entry points with a [`MockRequest`] fixture:
+//!
+//! ```ignore
+//! use wasmtime_functions::mock::{self, MockRequest};
+//!
+//! #[test]
+//! fn hello() {
+//!     let res = mock::invoke(super::hello, MockRequest::get("/hello/world").param("name", "world"));
+//!
+//!     assert_eq!(res.status, 200);
+//!     assert_eq!(res.text(), "Hello, world!");
+//! }
+//! ```
+//!
+//! Route parameters are not parsed from the request path; set them with [`MockRequest::param`].
+//!
+//! The metadata produced by a function's attribute macro can be checked with [`function_metadata!`],
+//! which catches route typos and options that differ from what the application expects:
+//!
+//! ```ignore
+//! use wasmtime_functions::mock::{function_metadata, metadata::FunctionTrigger};
+//!
+//! #[test]
+//! fn hello_route() {
+//!     let FunctionTrigger::Http { path, .. } = function_metadata!(super::hello).trigger;
+//!     assert_eq!(path, "/hello/:name");
+//! }
+//! ```
+//!
+//! Outbound HTTP requests are answered by the handler set with [`set_fetch_handler`], gRPC calls
+//! are answered by the handler set with [`set_grpc_handler`], services are resolved to the
+//! endpoints set with [`set_endpoints`], templates are rendered by the handler set with
+//! [`set_template_handler`], request parts are uploaded by the handler set with [`set_upload_handler`],
+//! messages are translated by the handler set with [`set_translation_handler`], URLs are signed by
+//! the handler set with [`set_url_signing_handler`],
+//! and the configuration returned by [`config::all`](crate::config::all) is set with [`set_config`].
+//! SQL statements always fail as there is no database in the mock host.
+//!
+//! Values cached with [`cache::app`](crate::cache::app) are kept on the current thread until they
+//! expire or [`clear_app_cache`] is called, so tests can observe values cached by earlier requests.
+//!
+//! Tasks spawned with [`spawn_after_response`](crate::spawn_after_response) are run by [`invoke`]
+//! after the function returns; [`MockResponse::tasks`] is the number of tasks that ran.
+//!
+//! A function that calls [`abort`](crate::abort) unwinds to its entry point, which responds with
+//! the given status as the host would; [`MockResponse::aborted`] is set and no tasks are run.
+//!
+//! The application's middleware is not found automatically; use [`invoke_with_middleware`] to pass
+//! a function's response to it.
+
+use crate::{Priority, SameSite};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+pub use wasmtime_functions_codegen::function_metadata;
+pub use wasmtime_functions_metadata as metadata;
+
+thread_local! {
+    static REQUESTS: RefCell<HashMap<i32, MockRequest>> = RefCell::new(HashMap::new());
+    static RESPONSES: RefCell<HashMap<i32, functions::Response>> = RefCell::new(HashMap::new());
+    static NEXT_HANDLE: Cell<i32> = Cell::new(1);
+    static ABORTED: Cell<bool> = Cell::new(false);
+    static FETCH_HANDLER: RefCell<Option<Box<FetchHandler>>> = RefCell::new(None);
+    static GRPC_HANDLER: RefCell<Option<Box<GrpcHandler>>> = RefCell::new(None);
+    static TEMPLATE_HANDLER: RefCell<Option<Box<TemplateHandler>>> = RefCell::new(None);
+    static UPLOAD_HANDLER: RefCell<Option<Box<UploadHandler>>> = RefCell::new(None);
+    static TRANSLATION_HANDLER: RefCell<Option<Box<TranslationHandler>>> = RefCell::new(None);
+    static URL_SIGNING_HANDLER: RefCell<Option<Box<UrlSigningHandler>>> = RefCell::new(None);
+    static ENDPOINTS: RefCell<HashMap<String, Vec<crate::discovery::Endpoint>>> = RefCell::new(HashMap::new());
+    static CONFIG: RefCell<crate::config::Config> = RefCell::new(Default::default());
+    static APP_CACHE: RefCell<AppCache> = RefCell::new(HashMap::new());
+}
+
+fn next_handle() -> i32 {
+    NEXT_HANDLE.with(|next| {
+        let handle = next.get();
+        next.set(handle + 1);
+        handle
+    })
+}
+
+type FetchHandler =
+    dyn Fn(&str, &str, &[(String, String)], &[u8]) -> Result<crate::fetch::Response, String>;
+
+type TemplateHandler = dyn Fn(&str, &str) -> Result<String, String>;
+
+type UploadHandler = dyn Fn(&str, &str, &str) -> Result<crate::UploadedObject, String>;
+
+type TranslationHandler = dyn Fn(&str, &str, &[(&str, &str)]) -> Result<String, String>;
+
+type UrlSigningHandler = dyn Fn(&str, std::time::Duration) -> Result<String, String>;
+
+// Cached values and the time they expire, if it is representable
+type AppCache = HashMap<String, (Vec<u8>, Option<std::time::Instant>)>;
+
+// The nonce of requests that don't set one: 16 zero bytes encoded with Base64
+const MOCK_NONCE: &str = "AAAAAAAAAAAAAAAAAAAAAA==";
+
+type GrpcHandler = dyn Fn(
+    &str,
+    &str,
+    &[(String, String)],
+    &[u8],
+) -> Result<crate::grpc::Response, crate::grpc::Status>;
+
+/// Represents a request fixture used to invoke a function.
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    method: String,
+    uri: String,
+    headers: Vec<(String, String)>,
+    cookies: HashMap<String, String>,
+    params: HashMap<String, String>,
+    extensions: HashMap<String, String>,
+    locale: Option<String>,
+    nonce: String,
+    body: Vec<u8>,
+}
+
+impl MockRequest {
+    /// Creates a request fixture with the given method and URI.
+    ///
+    /// A URI without a scheme and authority is relative to `http://localhost`.
+    pub fn new<T: Into<String>, U: AsRef<str>>(method: T, uri: U) -> Self {
+        let uri = uri.as_ref();
+
+        Self {
+            method: method.into(),
+            uri: if uri.starts_with('/') {
+                format!("http://localhost{}", uri)
+            } else {
+                uri.to_string()
+            },
+            headers: Vec::new(),
+            cookies: HashMap::new(),
+            params: HashMap::new(),
+            extensions: HashMap::new(),
+            locale: None,
+            nonce: MOCK_NONCE.to_string(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Creates a `GET` request fixture with the given URI.
+    pub fn get<T: AsRef<str>>(uri: T) -> Self {
+        Self::new("GET", uri)
+    }
+
+    /// Creates a `POST` request fixture with the given URI.
+    pub fn post<T: AsRef<str>>(uri: T) -> Self {
+        Self::new("POST", uri)
+    }
+
+    /// Adds a header to the request.
+    pub fn header<T: Into<String>, U: Into<String>>(mut self, name: T, value: U) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Adds a cookie to the request.
+    pub fn cookie<T: Into<String>, U: Into<String>>(mut self, name: T, value: U) -> Self {
+        self.cookies.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sets a route parameter of the request.
+    pub fn param<T: Into<String>, U: Into<String>>(mut self, name: T, value: U) -> Self {
+        self.params.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sets an extension of the request, as attached by a host request hook.
+    pub fn extension<T: Into<String>, U: Into<String>>(mut self, name: T, value: U) -> Self {
+        self.extensions.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sets the locale negotiated for the request.
+    ///
+    /// The mock host doesn't negotiate a locale from the `Accept-Language` header, so
+    /// [`Request::locale`](crate::Request::locale) returns `None` unless one is set.
+    pub fn locale<T: Into<String>>(mut self, locale: T) -> Self {
+        self.locale = Some(locale.into());
+        self
+    }
+
+    /// Sets the nonce of the request.
+    ///
+    /// The mock host doesn't generate nonces, so [`Request::nonce`](crate::Request::nonce) returns
+    /// 16 zero bytes encoded with Base64 unless one is set. No Content-Security-Policy header is
+    /// added to the response.
+    pub fn nonce<T: Into<String>>(mut self, nonce: T) -> Self {
+        self.nonce = nonce.into();
+        self
+    }
+
+    /// Sets the body of the request.
+    pub fn body<T: Into<Vec<u8>>>(mut self, body: T) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+/// Represents a cookie added to a response.
+#[derive(Debug, Clone, Default)]
+pub struct MockCookie {
+    /// The name of the cookie.
+    pub name: String,
+    /// The value of the cookie.
+    pub value: String,
+    /// Whether or not the HttpOnly attribute is set.
+    pub http_only: bool,
+    /// Whether or not the Secure attribute is set.
+    pub secure: bool,
+    /// The MaxAge attribute, in seconds.
+    pub max_age: Option<i64>,
+    /// The Expires attribute, as a Unix timestamp.
+    pub expires: Option<i64>,
+    /// The SameSite attribute.
+    pub same_site: Option<SameSite>,
+    /// The Domain attribute.
+    pub domain: Option<String>,
+    /// The Path attribute.
+    pub path: Option<String>,
+    /// The Priority attribute.
+    pub priority: Option<Priority>,
+    /// Whether or not the Partitioned attribute is set.
+    pub partitioned: bool,
+}
+
+/// Represents the response captured from invoking a function.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    /// The status code of the response.
+    pub status: u16,
+    /// The headers of the response.
+    pub headers: Vec<(String, String)>,
+    /// The cookies added to the response.
+    pub cookies: Vec<MockCookie>,
+    /// The names of the cookies removed by the response.
+    pub removed_cookies: Vec<String>,
+    /// The body of the response.
+    pub body: Vec<u8>,
+    /// The number of tasks spawned with [`spawn_after_response`](crate::spawn_after_response) that ran.
+    pub tasks: u32,
+    /// Whether the response was sent by [`abort`](crate::abort).
+    pub aborted: bool,
+}
+
+impl MockResponse {
+    /// Gets the value of a header of the response.
+    pub fn header<T: AsRef<str>>(&self, name: T) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name.as_ref()))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Gets the body of the response as text.
+    ///
+    /// Invalid UTF-8 sequences are replaced with the replacement character.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// Invokes a function's exported entry point with the given request fixture.
+///
+/// Pass the function declared with one of the HTTP attribute macros, e.g. `mock::invoke(hello, req)`.
+pub fn invoke(function: extern "C" fn(u32) -> u32, request: MockRequest) -> MockResponse {
+    let handle = next_handle();
+    REQUESTS.with(|requests| requests.borrow_mut().insert(handle, request));
+
+    complete(function(handle as u32) as i32)
+}
+
+/// Invokes a function's exported entry point and passes its response to the given middleware.
+///
+/// Pass the middleware declared with the `middleware` attribute macro, as the host would call it
+/// after the function returns.
+pub fn invoke_with_middleware(
+    function: extern "C" fn(u32) -> u32,
+    middleware: extern "C" fn(u32, u32) -> u32,
+    request: MockRequest,
+) -> MockResponse {
+    let (handle, copy) = (next_handle(), next_handle());
+    REQUESTS.with(|requests| {
+        let mut requests = requests.borrow_mut();
+        requests.insert(copy, request.clone());
+        requests.insert(handle, request);
+    });
+
+    let response = function(handle as u32);
+
+    // As in the host, the response of a function that aborted is sent without the middleware
+    if ABORTED.with(Cell::get) {
+        REQUESTS.with(|requests| requests.borrow_mut().remove(&copy));
+        return complete(response as i32);
+    }
+
+    // As in the host, the middleware is passed the request again
+    complete(middleware(copy as u32, response) as i32)
+}
+
+// The payload of the unwind that aborts a function in the mock host.
+struct Aborted {
+    status: u16,
+    message: String,
+}
+
+// Runs a function's entry point, responding with the status and message it aborted with, if any.
+//
+// The host exits the instance when a function aborts; natively, the function unwinds to here instead.
+pub(crate) fn catch_abort<F: FnOnce() -> u32>(entry: F) -> u32 {
+    let payload = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(entry)) {
+        Ok(handle) => return handle,
+        Err(payload) => payload,
+    };
+
+    let aborted = match payload.downcast::<Aborted>() {
+        Ok(aborted) => aborted,
+        Err(payload) => std::panic::resume_unwind(payload),
+    };
+
+    ABORTED.with(|flag| flag.set(true));
+
+    let status = crate::StatusCode::from_u16(aborted.status)
+        .unwrap_or(crate::StatusCode::INTERNAL_SERVER_ERROR);
+
+    unsafe {
+        crate::ResponseBuilder::new(status)
+            .header("content-type", "text/plain")
+            .body(aborted.message)
+            .into_raw()
+    }
+}
+
+// Completes an invocation by running the pending tasks and capturing the response of the given handle.
+fn complete(handle: i32) -> MockResponse {
+    let response = RESPONSES
+        .with(|responses| responses.borrow_mut().remove(&handle))
+        .unwrap_or_else(|| {
+            panic!(
+                "function returned {} which is not a valid response handle",
+                handle
+            )
+        });
+
+    // As in the host, tasks run after the response is complete unless the function aborted
+    let aborted = ABORTED.with(|flag| flag.replace(false));
+    let tasks = if aborted {
+        crate::tasks::discard();
+        0
+    } else {
+        crate::tasks::run()
+    };
+
+    let data = response.0.borrow();
+    MockResponse {
+        status: data.status,
+        headers: data.headers.clone(),
+        cookies: data.cookies.clone(),
+        removed_cookies: data.removed_cookies.clone(),
+        body: data.body.clone(),
+        tasks,
+        aborted,
+    }
+}
+
+#[doc(hidden)]
+pub fn read_function_descriptor(descriptor: &[u8]) -> metadata::Function {
+    metadata::Metadata::function_from_descriptor(descriptor)
+        .expect("function descriptor is invalid")
+}
+
+/// Sets the handler that answers outbound HTTP requests sent by functions on this thread.
+///
+/// The handler receives the method, URI, headers, and body of the request.
+/// Without a handler, outbound requests fail as if the host was not allowed to send them.
+pub fn set_fetch_handler<F>(handler: F)
+where
+    F: Fn(&str, &str, &[(String, String)], &[u8]) -> Result<crate::fetch::Response, String>
+        + 'static,
+{
+    FETCH_HANDLER.with(|h| *h.borrow_mut() = Some(Box::new(handler)));
+}
+
+/// Sets the handler that answers gRPC calls made by functions on this thread.
+///
+/// The handler receives the service, method, metadata, and payload of the call.
+/// Without a handler, calls fail as if the host did not allow the service.
+pub fn set_grpc_handler<F>(handler: F)
+where
+    F: Fn(
+            &str,
+            &str,
+            &[(String, String)],
+            &[u8],
+        ) -> Result<crate::grpc::Response, crate::grpc::Status>
+        + 'static,
+{
+    GRPC_HANDLER.with(|h| *h.borrow_mut() = Some(Box::new(handler)));
+}
+
+/// Sets the handler that renders templates for functions on this thread.
+///
+/// The handler receives the name of the template and the JSON context.
+/// Without a handler, rendering fails as if the host had no templates.
+pub fn set_template_handler<F>(handler: F)
+where
+    F: Fn(&str, &str) -> Result<String, String> + 'static,
+{
+    TEMPLATE_HANDLER.with(|h| *h.borrow_mut() = Some(Box::new(handler)));
+}
+
+/// Sets the handler that uploads request parts for functions on this thread.
+///
+/// The handler receives the name of the part, the bucket, and the key; the request body is not
+/// parsed, so the handler decides what was uploaded.
+/// Without a handler, uploads fail as if the host had no blob store.
+pub fn set_upload_handler<F>(handler: F)
+where
+    F: Fn(&str, &str, &str) -> Result<crate::UploadedObject, String> + 'static,
+{
+    UPLOAD_HANDLER.with(|h| *h.borrow_mut() = Some(Box::new(handler)));
+}
+
+/// Sets the handler that translates messages for functions on this thread.
+///
+/// The handler receives the key of the message, the locale, and the arguments.
+/// Without a handler, translation fails as if the host had no translations.
+pub fn set_translation_handler<F>(handler: F)
+where
+    F: Fn(&str, &str, &[(&str, &str)]) -> Result<String, String> + 'static,
+{
+    TRANSLATION_HANDLER.with(|h| *h.borrow_mut() = Some(Box::new(handler)));
+}
+
+/// Sets the handler that signs URLs for functions on this thread.
+///
+/// The handler receives the path and the time until the URL expires, in whole seconds.
+/// Without a handler, signing fails as if the host had no URL signing key.
+pub fn set_url_signing_handler<F>(handler: F)
+where
+    F: Fn(&str, std::time::Duration) -> Result<String, String> + 'static,
+{
+    URL_SIGNING_HANDLER.with(|h| *h.borrow_mut() = Some(Box::new(handler)));
+}
+
+/// Sets the endpoints a service resolves to on this thread.
+///
+/// Resolving a service without endpoints fails as if the host did not know the service.
+pub fn set_endpoints<T: Into<String>>(service: T, endpoints: Vec<crate::discovery::Endpoint>) {
+    ENDPOINTS.with(|e| e.borrow_mut().insert(service.into(), endpoints));
+}
+
+/// Sets the configuration returned by [`config::all`](crate::config::all) on the current thread.
+pub fn set_config(config: crate::config::Config) {
+    CONFIG.with(|c| *c.borrow_mut() = config);
+}
+
+/// Removes the values cached with [`cache::app`](crate::cache::app) on the current thread.
+pub fn clear_app_cache() {
+    APP_CACHE.with(|cache| cache.borrow_mut().clear());
+}
+
+/// Mirrors the bindings generated for `functions.witx`.
+pub(crate) mod functions {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy)]
+    pub enum SameSitePolicy {
+        Strict,
+        Lax,
+        None,
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub enum CookiePriority {
+        Low,
+        Medium,
+        High,
+    }
+
+    #[derive(Debug)]
+    pub struct Request(MockRequest);
+
+    impl Request {
+        pub unsafe fn from_raw(handle: i32) -> Self {
+            Self(
+                REQUESTS
+                    .with(|requests| requests.borrow_mut().remove(&handle))
+                    .expect("invalid request handle"),
+            )
+        }
+
+        pub fn method(&self) -> String {
+            self.0.method.clone()
+        }
+
+        pub fn uri_parts(&self) -> UriParts {
+            let uri = self.0.uri.split('#').next().unwrap_or_default();
+
+            let (scheme, rest) = match uri.split_once("://") {
+                Some((scheme, rest)) => (scheme.to_string(), rest),
+                None => (String::new(), uri),
+            };
+
+            let (authority, rest) = if scheme.is_empty() {
+                (None, rest)
+            } else {
+                let end = rest.find(&['/', '?'][..]).unwrap_or(rest.len());
+                (Some(rest[..end].to_string()), &rest[end..])
+            };
+
+            let (path, query) = match rest.split_once('?') {
+                Some((path, query)) => (path, Some(query.to_string())),
+                None => (rest, None),
+            };
+
+            UriParts {
+                scheme,
+                authority,
+                path: if path.is_empty() { "/" } else { path }.to_string(),
+                query,
+            }
+        }
+
+        pub fn header(&self, name: &str) -> Option<String> {
+            self.0
+                .headers
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.clone())
+        }
+
+        pub fn headers(&self) -> Vec<(String, String)> {
+            self.0.headers.clone()
+        }
+
+        pub fn cookie(&self, name: &str) -> Option<String> {
+            self.0.cookies.get(name).cloned()
+        }
+
+        pub fn param(&self, name: &str) -> Option<String> {
+            self.0.params.get(name).cloned()
+        }
+
+        pub fn extension(&self, name: &str) -> Option<String> {
+            self.0.extensions.get(name).cloned()
+        }
+
+        pub fn locale(&self) -> Option<String> {
+            self.0.locale.clone()
+        }
+
+        pub fn nonce(&self) -> String {
+            self.0.nonce.clone()
+        }
+
+        pub fn body(&self) -> Result<Vec<u8>, String> {
+            Ok(self.0.body.clone())
+        }
+
+        pub fn upload_part(
+            &self,
+            part: &str,
+            bucket: &str,
+            key: &str,
+        ) -> Result<UploadedObject, String> {
+            UPLOAD_HANDLER.with(|h| match h.borrow().as_ref() {
+                Some(handler) => handler(part, bucket, key).map(|object| UploadedObject {
+                    bucket: object.bucket,
+                    key: object.key,
+                    size: object.size,
+                    content_type: object.content_type,
+                    filename: object.filename,
+                }),
+                None => Err("uploads are not allowed".to_string()),
+            })
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct UriParts {
+        pub scheme: String,
+        pub authority: Option<String>,
+        pub path: String,
+        pub query: Option<String>,
+    }
+
+    #[derive(Debug)]
+    pub struct UploadedObject {
+        pub bucket: String,
+        pub key: String,
+        pub size: u64,
+        pub content_type: Option<String>,
+        pub filename: Option<String>,
+    }
+
+    #[derive(Debug, Default)]
+    pub struct ResponseData {
+        pub status: u16,
+        pub headers: Vec<(String, String)>,
+        pub cookies: Vec<MockCookie>,
+        pub removed_cookies: Vec<String>,
+        pub body: Vec<u8>,
+    }
+
+    #[derive(Debug)]
+    pub struct Response(pub RefCell<ResponseData>);
+
+    impl Response {
+        pub fn new(status: u16) -> Result<Self, String> {
+            crate::StatusCode::from_u16(status).map_err(|e| e.to_string())?;
+
+            Ok(Self(RefCell::new(ResponseData {
+                status,
+                ..Default::default()
+            })))
+        }
+
+        pub unsafe fn from_raw(handle: i32) -> Self {
+            RESPONSES
+                .with(|responses| responses.borrow_mut().remove(&handle))
+                .expect("invalid response handle")
+        }
+
+        pub unsafe fn into_raw(self) -> i32 {
+            let handle = next_handle();
+            RESPONSES.with(|responses| responses.borrow_mut().insert(handle, self));
+            handle
+        }
+
+        pub fn status(&self) -> u16 {
+            self.0.borrow().status
+        }
+
+        pub fn set_status(&self, status: u16) -> Result<u16, String> {
+            crate::StatusCode::from_u16(status).map_err(|e| e.to_string())?;
+            Ok(std::mem::replace(&mut self.0.borrow_mut().status, status))
+        }
+
+        pub fn header(&self, name: &str) -> Option<String> {
+            self.0
+                .borrow()
+                .headers
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.clone())
+        }
+
+        pub fn set_header(&self, name: &str, value: &str) {
+            let mut data = self.0.borrow_mut();
+            data.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+            data.headers.push((name.to_string(), value.to_string()));
+        }
+
+        pub fn headers(&self) -> Vec<(String, String)> {
+            self.0.borrow().headers.clone()
+        }
+
+        pub fn remove_header(&self, name: &str) -> Option<String> {
+            let mut data = self.0.borrow_mut();
+            let index = data
+                .headers
+                .iter()
+                .position(|(n, _)| n.eq_ignore_ascii_case(name))?;
+            let (_, value) = data.headers.remove(index);
+            data.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+            Some(value)
+        }
+
+        pub fn add_cookie(&self, cookie: &Cookie) {
+            self.0.borrow_mut().cookies.push(cookie.0.borrow().clone());
+        }
+
+        pub fn remove_cookie(&self, cookie: &Cookie) {
+            self.0
+                .borrow_mut()
+                .removed_cookies
+                .push(cookie.0.borrow().name.clone());
+        }
+
+        pub fn body(&self) -> Vec<u8> {
+            self.0.borrow().body.clone()
+        }
+
+        pub fn set_body(&self, body: &[u8]) {
+            self.0.borrow_mut().body = body.to_vec();
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Cookie(RefCell<MockCookie>);
+
+    impl Cookie {
+        pub fn new(name: &str, value: &str) -> Self {
+            Self(RefCell::new(MockCookie {
+                name: name.to_string(),
+                value: value.to_string(),
+                ..Default::default()
+            }))
+        }
+
+        pub fn set_http_only(&self, enabled: bool) {
+            self.0.borrow_mut().http_only = enabled;
+        }
+
+        pub fn set_secure(&self, enabled: bool) {
+            self.0.borrow_mut().secure = enabled;
+        }
+
+        pub fn set_max_age(&self, age: i64) {
+            self.0.borrow_mut().max_age = Some(age);
+        }
+
+        pub fn set_expires(&self, timestamp: i64) {
+            self.0.borrow_mut().expires = Some(timestamp.max(0));
+        }
+
+        pub fn set_same_site(&self, policy: SameSitePolicy) {
+            self.0.borrow_mut().same_site = Some(match policy {
+                SameSitePolicy::Strict => SameSite::Strict,
+                SameSitePolicy::Lax => SameSite::Lax,
+                SameSitePolicy::None => SameSite::None,
+            });
+        }
+
+        pub fn set_domain(&self, domain: &str) {
+            self.0.borrow_mut().domain = Some(domain.to_string());
+        }
+
+        pub fn set_path(&self, path: &str) {
+            self.0.borrow_mut().path = Some(path.to_string());
+        }
+
+        pub fn set_priority(&self, priority: CookiePriority) {
+            self.0.borrow_mut().priority = Some(match priority {
+                CookiePriority::Low => Priority::Low,
+                CookiePriority::Medium => Priority::Medium,
+                CookiePriority::High => Priority::High,
+            });
+        }
+
+        pub fn set_partitioned(&self, enabled: bool) {
+            self.0.borrow_mut().partitioned = enabled;
+        }
+    }
+
+    pub fn abort(status: u16, message: &str) {
+        // Unwinding with `resume_unwind` skips the panic hook, so nothing is printed
+        std::panic::resume_unwind(Box::new(super::Aborted {
+            status,
+            message: message.to_string(),
+        }))
+    }
+}
+
+/// Mirrors the bindings generated for `fetch.witx`.
+pub(crate) mod fetch {
+    use super::*;
+
+    pub struct FetchResponse {
+        pub status: u16,
+        pub headers: Vec<(String, String)>,
+        pub body: Vec<u8>,
+    }
+
+    pub fn send(
+        method: &str,
+        uri: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<FetchResponse, String> {
+        let headers: Vec<_> = headers
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect();
+
+        FETCH_HANDLER.with(|handler| match &*handler.borrow() {
+            Some(handler) => handler(method, uri, &headers, body).map(|res| FetchResponse {
+                status: res.status.as_u16(),
+                headers: res.headers,
+                body: res.body,
+            }),
+            None => Err(format!(
+                "requests to '{}' are not allowed by the mock host; use `set_fetch_handler` to answer them",
+                uri
+            )),
+        })
+    }
+}
+
+/// Mirrors the bindings generated for `grpc.witx`.
+pub(crate) mod grpc {
+    use super::*;
+
+    // The status code of calls to a service the host doesn't allow
+    const PERMISSION_DENIED: u32 = 7;
+
+    pub struct GrpcResponse {
+        pub payload: Vec<u8>,
+        pub metadata: Vec<(String, String)>,
+    }
+
+    pub struct GrpcStatus {
+        pub code: u32,
+        pub message: String,
+    }
+
+    pub fn call(
+        service: &str,
+        method: &str,
+        metadata: &[(&str, &str)],
+        payload: &[u8],
+        _deadline_ms: Option<u64>,
+    ) -> Result<GrpcResponse, GrpcStatus> {
+        let metadata: Vec<_> = metadata
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect();
+
+        GRPC_HANDLER.with(|handler| match &*handler.borrow() {
+            Some(handler) => handler(service, method, &metadata, payload)
+                .map(|res| GrpcResponse {
+                    payload: res.payload,
+                    metadata: res.metadata,
+                })
+                .map_err(|status| GrpcStatus {
+                    code: status.code,
+                    message: status.message,
+                }),
+            None => Err(GrpcStatus {
+                code: PERMISSION_DENIED,
+                message: format!(
+                    "calls to `{}` are not allowed by the mock host; use `set_grpc_handler` to answer them",
+                    service
+                ),
+            }),
+        })
+    }
+}
+
+/// Mirrors the bindings generated for `discovery.witx`.
+pub(crate) mod discovery {
+    use super::ENDPOINTS;
+
+    pub struct Endpoint {
+        pub host: String,
+        pub port: u16,
+        pub priority: u16,
+        pub weight: u16,
+    }
+
+    pub fn resolve(service: &str) -> Result<Vec<Endpoint>, String> {
+        ENDPOINTS.with(|endpoints| match endpoints.borrow().get(service) {
+            Some(endpoints) => {
+                let mut endpoints: Vec<_> = endpoints
+                    .iter()
+                    .map(|e| Endpoint {
+                        host: e.host.clone(),
+                        port: e.port,
+                        priority: e.priority,
+                        weight: e.weight,
+                    })
+                    .collect();
+                endpoints.sort_by_key(|e| e.priority);
+                Ok(endpoints)
+            }
+            None => Err(format!(
+                "service `{}` is not known to the mock host; use `set_endpoints` to resolve it",
+                service
+            )),
+        })
+    }
+}
+
+/// Mirrors the bindings generated for `templates.witx`.
+pub(crate) mod templates {
+    use super::TEMPLATE_HANDLER;
+
+    pub fn render(name: &str, context: &str) -> Result<String, String> {
+        TEMPLATE_HANDLER.with(|handler| match &*handler.borrow() {
+            Some(handler) => handler(name, context),
+            None => Err(format!(
+                "template `{}` does not exist in the mock host; use `set_template_handler` to render it",
+                name
+            )),
+        })
+    }
+}
+
+/// Mirrors the bindings generated for `i18n.witx`.
+pub(crate) mod i18n {
+    use super::TRANSLATION_HANDLER;
+
+    pub fn translate(key: &str, locale: &str, args: &[(&str, &str)]) -> Result<String, String> {
+        TRANSLATION_HANDLER.with(|handler| match &*handler.borrow() {
+            Some(handler) => handler(key, locale, args),
+            None => Err(format!(
+                "message `{}` does not exist in the mock host; use `set_translation_handler` to translate it",
+                key
+            )),
+        })
+    }
+}
+
+/// Mirrors the bindings generated for `signing.witx`.
+pub(crate) mod signing {
+    use super::URL_SIGNING_HANDLER;
+
+    pub fn sign_url(path: &str, expires_in: u64) -> Result<String, String> {
+        URL_SIGNING_HANDLER.with(|handler| match &*handler.borrow() {
+            Some(handler) => handler(path, std::time::Duration::from_secs(expires_in)),
+            None => Err(
+                "no URL signing key is configured in the mock host; use `set_url_signing_handler` to sign URLs"
+                    .to_string(),
+            ),
+        })
+    }
+}
+
+/// Mirrors the bindings generated for `cache.witx`.
+pub(crate) mod cache {
+    use super::APP_CACHE;
+    use std::time::{Duration, Instant};
+
+    // The host's limit on the size of values
+    const MAX_VALUE_BYTES: usize = 1024 * 1024;
+
+    pub fn app_get(key: &str) -> Result<Option<Vec<u8>>, String> {
+        APP_CACHE.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            match cache.get(key) {
+                Some((_, Some(expires))) if *expires <= Instant::now() => {
+                    cache.remove(key);
+                    Ok(None)
+                }
+                Some((value, _)) => Ok(Some(value.clone())),
+                None => Ok(None),
+            }
+        })
+    }
+
+    pub fn app_set(key: &str, value: &[u8], ttl: u64) -> Result<(), String> {
+        if value.len() > MAX_VALUE_BYTES {
+            return Err(format!(
+                "the value of {} bytes exceeds the maximum of {} bytes",
+                value.len(),
+                MAX_VALUE_BYTES
+            ));
+        }
+
+        let expires = Instant::now().checked_add(Duration::from_secs(ttl));
+        APP_CACHE.with(|cache| {
+            cache
+                .borrow_mut()
+                .insert(key.to_string(), (value.to_vec(), expires))
+        });
+        Ok(())
+    }
+
+    pub fn app_remove(key: &str) -> Result<(), String> {
+        APP_CACHE.with(|cache| cache.borrow_mut().remove(key));
+        Ok(())
+    }
+}
+
+/// Mirrors the bindings generated for `sql.witx`.
+pub(crate) mod sql {
+    const NO_DATABASE: &str = "the mock host does not provide a database";
+
+    #[allow(dead_code)]
+    pub enum ValueParam<'a> {
+        Null,
+        Integer(i64),
+        Real(f64),
+        Text(&'a str),
+        Blob(&'a [u8]),
+    }
+
+    #[allow(dead_code)]
+    pub enum ValueResult {
+        Null,
+        Integer(i64),
+        Real(f64),
+        Text(String),
+        Blob(Vec<u8>),
+    }
+
+    pub fn execute(_: &str, _: &[ValueParam]) -> Result<u64, String> {
+        Err(NO_DATABASE.to_string())
+    }
+
+    pub fn query(_: &str, _: &[ValueParam]) -> Result<Vec<Vec<ValueResult>>, String> {
+        Err(NO_DATABASE.to_string())
+    }
+
+    pub fn query_idempotent(_: &str, _: &[ValueParam]) -> Result<Vec<Vec<ValueResult>>, String> {
+        Err(NO_DATABASE.to_string())
+    }
+}
+
+/// Mirrors the bindings generated for `config.witx`.
+pub(crate) mod config {
+    use super::CONFIG;
+    use crate::config::Value;
+
+    pub enum Setting {
+        Text(String),
+        Integer(i64),
+        Boolean(bool),
+        Duration(u64),
+    }
+
+    pub fn vars() -> Vec<(String, String)> {
+        CONFIG.with(|c| {
+            c.borrow()
+                .vars
+                .iter()
+                .map(|(n, v)| (n.clone(), v.clone()))
+                .collect()
+        })
+    }
+
+    pub fn settings() -> Vec<(String, Setting)> {
+        CONFIG.with(|c| {
+            c.borrow()
+                .settings
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.clone(),
+                        match value {
+                            Value::Text(s) => Setting::Text(s.clone()),
+                            Value::Integer(i) => Setting::Integer(*i),
+                            Value::Boolean(b) => Setting::Boolean(*b),
+                            Value::Duration(d) => Setting::Duration(d.as_millis() as u64),
+                        },
+                    )
+                })
+                .collect()
+        })
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{Response, StatusCode};
+use std::fmt::Write;
+
+/// Represents a problem details error response, as defined by RFC 7807.
+///
+/// Problems are returned as `application/problem+json` responses, which is also the format the host
+/// renders its own errors in when configured to.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    status: StatusCode,
+    type_uri: String,
+    title: String,
+    detail: Option<String>,
+    instance: Option<String>,
+    members: Vec<(String, String)>,
+}
+
+impl Problem {
+    /// Creates a new problem with the given status code.
+    ///
+    /// The problem type is `about:blank` and its title is the reason phrase of the status code.
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            status,
+            type_uri: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            detail: None,
+            instance: None,
+            members: Vec::new(),
+        }
+    }
+
+    /// Sets the URI that identifies the type of the problem.
+    pub fn type_uri<T: Into<String>>(mut self, uri: T) -> Self {
+        self.type_uri = uri.into();
+        self
+    }
+
+    /// Sets the short, human-readable summary of the type of the problem.
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Sets the human-readable explanation of this occurrence of the problem.
+    pub fn detail<T: Into<String>>(mut self, detail: T) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Sets the URI that identifies this occurrence of the problem.
+    pub fn instance<T: Into<String>>(mut self, instance: T) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Adds an extension member with a string value to the problem.
+    ///
+    /// Members with the names of the standard members are ignored.
+    pub fn member<T: Into<String>, U: Into<String>>(mut self, name: T, value: U) -> Self {
+        let name = name.into();
+        if !matches!(
+            name.as_str(),
+            "type" | "title" | "status" | "detail" | "instance"
+        ) {
+            self.members.retain(|(n, _)| n != &name);
+            self.members.push((name, value.into()));
+        }
+        self
+    }
+
+    /// Gets the status code of the problem.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Serializes the problem as JSON.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{");
+        write!(
+            json,
+            "\"type\":{},\"title\":{},\"status\":{}",
+            quote(&self.type_uri),
+            quote(&self.title),
+            self.status.as_u16()
+        )
+        .unwrap();
+
+        if let Some(detail) = &self.detail {
+            write!(json, ",\"detail\":{}", quote(detail)).unwrap();
+        }
+
+        if let Some(instance) = &self.instance {
+            write!(json, ",\"instance\":{}", quote(instance)).unwrap();
+        }
+
+        for (name, value) in &self.members {
+            write!(json, ",{}:{}", quote(name), quote(value)).unwrap();
+        }
+
+        json.push('}');
+        json
+    }
+}
+
+impl From<Problem> for Response {
+    fn from(problem: Problem) -> Self {
+        Response::build(problem.status)
+            .header("Content-Type", "application/problem+json")
+            .body(problem.to_json())
+    }
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,25 @@
+//! URLs signed by the host.
+//!
+//! Functions hand out temporary links to routes declared with the `signed` option, such as
+//! downloads or webhooks, by signing a path with the host's key. The host verifies the signature
+//! and expiry of requests to those routes before invoking the function, so functions need no
+//! HMAC implementation or clock handling of their own.
+
+#[cfg(target_arch = "wasm32")]
+witx_bindgen_rust::import!("../../crates/runtime/witx/signing.witx");
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mock::signing;
+
+use std::time::Duration;
+
+/// Signs a path, which may include a query, so that requests for it are accepted until it expires.
+///
+/// The signed path has `expires` and `signature` query parameters appended; the path must start
+/// with `/` and must not already have either parameter. Signing fails if the host has no URL
+/// signing key.
+///
+/// Expiry is measured in whole seconds.
+pub fn sign_url<T: AsRef<str>>(path: T, expires_in: Duration) -> Result<String, String> {
+    signing::sign_url(path.as_ref(), expires_in.as_secs())
+}
@@ -0,0 +1,118 @@
+//! Access to the SQL database provided by the host.
+
+#[cfg(target_arch = "wasm32")]
+witx_bindgen_rust::import!("../../crates/runtime/witx/sql.witx");
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mock::sql;
+
+/// Represents a value bound to or returned from a SQL statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// The SQL `NULL` value.
+    Null,
+    /// A signed integer value.
+    Integer(i64),
+    /// A floating point value.
+    Real(f64),
+    /// A text value.
+    Text(String),
+    /// A binary value.
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    fn as_param(&self) -> sql::ValueParam<'_> {
+        match self {
+            Self::Null => sql::ValueParam::Null,
+            Self::Integer(i) => sql::ValueParam::Integer(*i),
+            Self::Real(r) => sql::ValueParam::Real(*r),
+            Self::Text(s) => sql::ValueParam::Text(s),
+            Self::Blob(b) => sql::ValueParam::Blob(b),
+        }
+    }
+}
+
+impl From<sql::ValueResult> for Value {
+    fn from(value: sql::ValueResult) -> Self {
+        match value {
+            sql::ValueResult::Null => Self::Null,
+            sql::ValueResult::Integer(i) => Self::Integer(i),
+            sql::ValueResult::Real(r) => Self::Real(r),
+            sql::ValueResult::Text(s) => Self::Text(s),
+            sql::ValueResult::Blob(b) => Self::Blob(b),
+        }
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Self::Integer(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Real(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::Text(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Blob(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Self::Null)
+    }
+}
+
+/// Represents a row returned from a SQL query.
+pub type Row = Vec<Value>;
+
+/// Executes a SQL statement, returning the number of rows affected.
+pub fn execute<T: AsRef<str>>(statement: T, params: &[Value]) -> Result<u64, String> {
+    let params: Vec<_> = params.iter().map(Value::as_param).collect();
+    sql::execute(statement.as_ref(), &params)
+}
+
+/// Executes a SQL query, returning the resulting rows.
+///
+/// The query is never retried by the host, as it may modify the database; use [`query_idempotent`]
+/// for queries that are safe to repeat.
+pub fn query<T: AsRef<str>>(statement: T, params: &[Value]) -> Result<Vec<Row>, String> {
+    let params: Vec<_> = params.iter().map(Value::as_param).collect();
+    Ok(into_rows(sql::query(statement.as_ref(), &params)?))
+}
+
+/// Executes a SQL query that is safe to repeat, returning the resulting rows.
+///
+/// The host may retry the query if it fails or times out, according to its retry policy. Only use
+/// this for queries without side effects, such as a `SELECT` that doesn't call volatile functions.
+pub fn query_idempotent<T: AsRef<str>>(statement: T, params: &[Value]) -> Result<Vec<Row>, String> {
+    let params: Vec<_> = params.iter().map(Value::as_param).collect();
+    Ok(into_rows(sql::query_idempotent(
+        statement.as_ref(),
+        &params,
+    )?))
+}
+
+fn into_rows(rows: Vec<Vec<sql::ValueResult>>) -> Vec<Row> {
+    rows.into_iter()
+        .map(|row| row.into_iter().map(Into::into).collect())
+        .collect()
+}
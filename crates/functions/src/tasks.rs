@@ -0,0 +1,60 @@
+//! Tasks that run after a function's response is sent.
+//!
+//! Tasks are queued in the instance and run by the host through the exports below once the
+//! response has been sent; the host only calls them when tasks are pending.
+
+use std::cell::RefCell;
+
+thread_local! {
+    static TASKS: RefCell<Vec<Box<dyn FnOnce()>>> = RefCell::new(Vec::new());
+}
+
+/// Spawns a task that runs after the function's response is sent.
+///
+/// Use this for work the client shouldn't wait on, such as cleanup or notifications. Tasks run
+/// in the order they were spawned with a separate timeout that is shorter than the function's;
+/// a task that traps or times out stops the remaining tasks, and the client is never told.
+pub fn spawn_after_response<F: FnOnce() + 'static>(task: F) {
+    TASKS.with(|tasks| tasks.borrow_mut().push(Box::new(task)));
+}
+
+/// Gets the number of tasks waiting to run.
+#[cfg(target_arch = "wasm32")]
+fn pending() -> u32 {
+    TASKS.with(|tasks| tasks.borrow().len() as u32)
+}
+
+/// Runs the pending tasks, including any spawned by the tasks themselves.
+pub(crate) fn run() -> u32 {
+    let mut count = 0;
+
+    loop {
+        let tasks = TASKS.with(|tasks| std::mem::take(&mut *tasks.borrow_mut()));
+        if tasks.is_empty() {
+            return count;
+        }
+
+        for task in tasks {
+            task();
+            count += 1;
+        }
+    }
+}
+
+/// Discards the pending tasks, as the host does when a function aborts.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn discard() {
+    TASKS.with(|tasks| tasks.borrow_mut().clear());
+}
+
+#[cfg(target_arch = "wasm32")]
+#[no_mangle]
+extern "C" fn __wasmtime_functions_pending_tasks() -> u32 {
+    pending()
+}
+
+#[cfg(target_arch = "wasm32")]
+#[no_mangle]
+extern "C" fn __wasmtime_functions_run_tasks() -> u32 {
+    run()
+}
@@ -0,0 +1,19 @@
+//! Templates rendered by the host.
+//!
+//! The host parses the application's Handlebars templates once at startup, so functions that
+//! produce HTML don't need to embed a template engine or parse templates on every request.
+//! Rendering fails if the host has no template with the given name.
+
+#[cfg(target_arch = "wasm32")]
+witx_bindgen_rust::import!("../../crates/runtime/witx/templates.witx");
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mock::templates;
+
+/// Renders a template with the given context, which must be a JSON value.
+///
+/// Templates are named by their path in the host's templates directory without the extension,
+/// such as `pages/index`. Values substituted with `{{...}}` are HTML-escaped.
+pub fn render<T: AsRef<str>, U: AsRef<str>>(name: T, context: U) -> Result<String, String> {
+    templates::render(name.as_ref(), context.as_ref())
+}
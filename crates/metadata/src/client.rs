@@ -0,0 +1,266 @@
+use crate::{FunctionTrigger, Metadata, Method, ParameterType};
+use std::fmt::Write;
+
+/// Represents a file of a generated client crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientFile {
+    /// The path of the file, relative to the root of the crate.
+    pub path: String,
+    /// The contents of the file.
+    pub contents: String,
+}
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while",
+];
+
+const PRELUDE: &str = r#"
+pub use surf;
+
+use surf::http::Method;
+use surf::{Client as HttpClient, RequestBuilder, Url};
+
+/// A client for the functions of the application.
+///
+/// Each method creates a request for a function; add a body or headers to it as needed and
+/// `await` it to send the request.
+#[derive(Clone)]
+pub struct Client {
+    base: Url,
+    client: HttpClient,
+}
+
+impl Client {
+    /// Creates a client for the application served at the given base URL.
+    pub fn new(base: &str) -> surf::Result<Self> {
+        Ok(Self {
+            base: Url::parse(base)?,
+            client: HttpClient::new(),
+        })
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let mut url = self.base.clone();
+        let base = url.path().trim_end_matches('/').to_string();
+        url.set_path(&format!("{}{}", base, path));
+        self.client.request(method, url)
+    }
+"#;
+
+const ENCODE: &str = r#"
+fn encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for b in segment.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+"#;
+
+struct Argument {
+    name: String,
+    ty: &'static str,
+    encode: bool,
+}
+
+fn argument_name(name: &str) -> String {
+    let name = if name.is_empty() { "rest" } else { name };
+
+    if KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn method_variant(method: Method) -> &'static str {
+    match method {
+        Method::Get => "Get",
+        Method::Head => "Head",
+        Method::Post => "Post",
+        Method::Put => "Put",
+        Method::Delete => "Delete",
+        Method::Connect => "Connect",
+        Method::Options => "Options",
+        Method::Trace => "Trace",
+        Method::Patch => "Patch",
+    }
+}
+
+impl Metadata {
+    /// Generates a Rust client crate for the HTTP functions of the module.
+    ///
+    /// The client has a method for each function that takes the function's path parameters, typed
+    /// according to the function's metadata, and returns a `surf` request. Functions that are
+    /// triggered by more than one method also take the method to use.
+    pub fn generate_client(&self, name: &str) -> Vec<ClientFile> {
+        let manifest = format!(
+            r#"[package]
+name = "{}"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+surf = {{ version = "2.3.1", default-features = false, features = ["h1-client-rustls"] }}
+"#,
+            name
+        );
+
+        let mut lib = String::from(
+            "//! A client for the functions of a Wasmtime Functions application.\n//!\n//! This crate was generated from the metadata of the application's WebAssembly module; generate\n//! it again rather than editing it when the application's functions change.\n",
+        );
+        lib.push_str(PRELUDE);
+
+        let mut encodes = false;
+
+        let mut functions: Vec<_> = self.functions.iter().collect();
+        functions.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for function in functions {
+            let (path, methods, consumes, params) = match &function.trigger {
+                FunctionTrigger::Http {
+                    path,
+                    methods,
+                    consumes,
+                    params,
+                    ..
+                } => (path, methods, consumes, params),
+            };
+
+            let mut arguments = Vec::new();
+            let mut format = String::new();
+
+            for segment in path.split('/').filter(|s| !s.is_empty()) {
+                format.push('/');
+
+                let (name, encode) = match segment.chars().next() {
+                    Some(':') => (&segment[1..], true),
+                    Some('*') => (&segment[1..], false),
+                    _ => {
+                        format.push_str(&segment.replace('{', "{{").replace('}', "}}"));
+                        continue;
+                    }
+                };
+
+                let ty = match params.iter().find(|p| p.name == name).map(|p| p.ty) {
+                    Some(ParameterType::Integer) => "i64",
+                    Some(ParameterType::Unsigned) => "u64",
+                    Some(ParameterType::Number) => "f64",
+                    Some(ParameterType::Boolean) => "bool",
+                    Some(ParameterType::String) | None => "&str",
+                };
+
+                format.push_str("{}");
+                arguments.push(Argument {
+                    name: argument_name(name),
+                    ty,
+                    encode: encode && ty == "&str",
+                });
+            }
+
+            if format.is_empty() || path.ends_with('/') {
+                format.push('/');
+            }
+
+            let methods_doc = if methods.is_empty() {
+                "ANY".to_string()
+            } else {
+                methods
+                    .iter()
+                    .map(|m| m.as_ref())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+
+            writeln!(lib).unwrap();
+            writeln!(
+                lib,
+                "    /// Creates a request for the `{}` function (`{} {}`).",
+                function.name, methods_doc, path
+            )
+            .unwrap();
+
+            if !consumes.is_empty() {
+                writeln!(lib, "    ///").unwrap();
+                writeln!(
+                    lib,
+                    "    /// The function accepts request bodies of type `{}`.",
+                    consumes.join("`, `")
+                )
+                .unwrap();
+            }
+
+            let method = match methods.as_slice() {
+                [method] => format!("Method::{}", method_variant(*method)),
+                _ => "method".to_string(),
+            };
+
+            let mut signature = String::from("&self");
+            if method == "method" {
+                signature.push_str(", method: Method");
+            }
+            for arg in &arguments {
+                write!(signature, ", {}: {}", arg.name, arg.ty).unwrap();
+            }
+
+            writeln!(
+                lib,
+                "    pub fn {}({}) -> RequestBuilder {{",
+                argument_name(&function.name),
+                signature
+            )
+            .unwrap();
+
+            if arguments.is_empty() {
+                writeln!(lib, "        self.request({}, {:?})", method, format).unwrap();
+            } else {
+                let values = arguments
+                    .iter()
+                    .map(|arg| {
+                        if arg.encode {
+                            encodes = true;
+                            format!("encode({})", arg.name)
+                        } else {
+                            arg.name.clone()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                writeln!(
+                    lib,
+                    "        self.request({}, &format!({:?}, {}))",
+                    method, format, values
+                )
+                .unwrap();
+            }
+
+            writeln!(lib, "    }}").unwrap();
+        }
+
+        lib.push_str("}\n");
+
+        if encodes {
+            lib.push_str(ENCODE);
+        }
+
+        vec![
+            ClientFile {
+                path: "Cargo.toml".to_string(),
+                contents: manifest,
+            },
+            ClientFile {
+                path: "src/lib.rs".to_string(),
+                contents: lib,
+            },
+        ]
+    }
+}
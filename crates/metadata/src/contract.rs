@@ -0,0 +1,161 @@
+use crate::{FunctionTrigger, Metadata};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
+use wasmparser::{ExternalKind, FuncType, ImportSectionEntryType, Parser, Payload, Type, TypeDef};
+
+/// Represents a mismatch between a module's exports and the functions declared in its metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContractViolation {
+    /// A declared function is not exported by the module.
+    MissingExport {
+        /// The name of the function.
+        function: String,
+    },
+    /// The export of a declared function is not a function.
+    NotAFunction {
+        /// The name of the function.
+        function: String,
+    },
+    /// The export of a declared function does not have the signature its trigger requires.
+    Signature {
+        /// The name of the function.
+        function: String,
+        /// The signature required by the function's trigger.
+        expected: String,
+        /// The signature of the exported function.
+        actual: String,
+    },
+}
+
+impl fmt::Display for ContractViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingExport { function } => {
+                write!(f, "function '{}' is not exported by the module", function)
+            }
+            Self::NotAFunction { function } => write!(
+                f,
+                "the export for function '{}' is not a function",
+                function
+            ),
+            Self::Signature {
+                function,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "function '{}' has signature {} but its trigger requires {}",
+                function, actual, expected
+            ),
+        }
+    }
+}
+
+/// Gets the signature the exported function must have for its trigger.
+fn signature(trigger: &FunctionTrigger) -> (&'static [Type], &'static [Type]) {
+    match trigger {
+        FunctionTrigger::Http { .. } => (&[Type::I32], &[Type::I32]),
+    }
+}
+
+fn format_signature(params: &[Type], returns: &[Type]) -> String {
+    fn format_types(types: &[Type]) -> String {
+        types
+            .iter()
+            .map(|ty| match ty {
+                Type::I32 => "i32",
+                Type::I64 => "i64",
+                Type::F32 => "f32",
+                Type::F64 => "f64",
+                Type::V128 => "v128",
+                Type::FuncRef => "funcref",
+                Type::ExternRef => "externref",
+                _ => "?",
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    format!("({}) -> ({})", format_types(params), format_types(returns))
+}
+
+impl Metadata {
+    /// Validates that the module exports each declared function with the signature its trigger
+    /// requires.
+    ///
+    /// This catches modules whose metadata is out of sync with their code, such as a stale
+    /// `__functions` section, before any function is invoked. Returns the violations found, which
+    /// are empty if the module satisfies its metadata.
+    pub fn validate_exports<T: AsRef<[u8]>>(&self, bytes: &T) -> Result<Vec<ContractViolation>> {
+        let mut types = Vec::new();
+        let mut functions = Vec::new();
+        let mut exports = HashMap::new();
+
+        for payload in Parser::new(0).parse_all(bytes.as_ref()) {
+            match payload? {
+                Payload::TypeSection(reader) => {
+                    for ty in reader {
+                        types.push(match ty? {
+                            TypeDef::Func(ty) => Some(ty),
+                            _ => None,
+                        });
+                    }
+                }
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        if let ImportSectionEntryType::Function(index) = import?.ty {
+                            functions.push(index);
+                        }
+                    }
+                }
+                Payload::FunctionSection(reader) => {
+                    for index in reader {
+                        functions.push(index?);
+                    }
+                }
+                Payload::ExportSection(reader) => {
+                    for export in reader {
+                        let export = export?;
+                        exports.insert(export.field.to_string(), (export.kind, export.index));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let func_type = |index: u32| -> Option<&FuncType> {
+            functions
+                .get(index as usize)
+                .and_then(|ty| types.get(*ty as usize))
+                .and_then(Option::as_ref)
+        };
+
+        let mut violations = Vec::new();
+
+        for function in &self.functions {
+            let (params, returns) = signature(&function.trigger);
+
+            match exports.get(&function.name) {
+                None => violations.push(ContractViolation::MissingExport {
+                    function: function.name.clone(),
+                }),
+                Some((ExternalKind::Function, index)) => match func_type(*index) {
+                    Some(ty) if &*ty.params == params && &*ty.returns == returns => {}
+                    ty => violations.push(ContractViolation::Signature {
+                        function: function.name.clone(),
+                        expected: format_signature(params, returns),
+                        actual: ty
+                            .map(|ty| format_signature(&ty.params, &ty.returns))
+                            .unwrap_or_else(|| "unknown".to_string()),
+                    }),
+                },
+                Some(_) => violations.push(ContractViolation::NotAFunction {
+                    function: function.name.clone(),
+                }),
+            }
+        }
+
+        Ok(violations)
+    }
+}
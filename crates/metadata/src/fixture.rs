@@ -0,0 +1,218 @@
+//! Assembles WebAssembly modules with arbitrary metadata for testing.
+//!
+//! The modules are generated from a WebAssembly text template rather than compiled from Rust,
+//! so metadata and runtime behavior can be tested without building the example crates.
+
+use crate::{Function, FunctionOutput, FunctionTrigger, Method};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fmt::Write;
+
+/// The body of a function export that traps when invoked.
+pub const TRAP_BODY: &str = "unreachable";
+
+/// The host interface version recorded in built modules unless overridden.
+pub const INTERFACE_VERSION: u32 = 17;
+
+/// Builds a WebAssembly module containing Wasmtime Functions metadata.
+///
+/// Each function added to the builder is described in the `__functions` section and exported
+/// with the signature the runtime invokes (`(param i32) (result i32)`).
+pub struct ModuleBuilder {
+    functions: Vec<Function>,
+    vars: Vec<String>,
+    interface_version: Option<u32>,
+    exports: Vec<(String, String)>,
+    sections: Vec<(String, Vec<u8>)>,
+    fragments: Vec<String>,
+}
+
+impl Default for ModuleBuilder {
+    fn default() -> Self {
+        Self {
+            functions: Vec::new(),
+            vars: Vec::new(),
+            interface_version: Some(INTERFACE_VERSION),
+            exports: Vec::new(),
+            sections: Vec::new(),
+            fragments: Vec::new(),
+        }
+    }
+}
+
+impl ModuleBuilder {
+    /// Creates a new module builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a function described by the given metadata.
+    ///
+    /// The function is exported with a body that traps; use [`export`](Self::export) to replace it.
+    pub fn function(mut self, function: Function) -> Self {
+        if !self.exports.iter().any(|(n, _)| n == &function.name) {
+            self.exports
+                .push((function.name.clone(), TRAP_BODY.to_string()));
+        }
+        self.functions.push(function);
+        self
+    }
+
+    /// Adds a HTTP-triggered function with the given name, route, and methods.
+    pub fn http_function<T: Into<String>, U: Into<String>>(
+        self,
+        name: T,
+        path: U,
+        methods: &[Method],
+    ) -> Self {
+        self.function(Function {
+            name: name.into(),
+            trigger: FunctionTrigger::Http {
+                path: path.into(),
+                methods: methods.to_vec(),
+                consumes: Vec::new(),
+                params: Vec::new(),
+                cache: None,
+                headers: Default::default(),
+                signed: false,
+                etag: false,
+            },
+            inputs: Vec::new(),
+            outputs: vec![FunctionOutput::Http],
+            concurrency: None,
+            vars: None,
+            trace_sample: None,
+        })
+    }
+
+    /// Adds a required environment variable.
+    pub fn var<T: Into<String>>(mut self, name: T) -> Self {
+        self.vars.push(name.into());
+        self
+    }
+
+    /// Sets the host interface version recorded for each function.
+    ///
+    /// Use `None` to build a module that predates host interface versioning.
+    pub fn interface_version(mut self, version: Option<u32>) -> Self {
+        self.interface_version = version;
+        self
+    }
+
+    /// Exports a function with the given body.
+    ///
+    /// The body is WebAssembly text for a function of type `(param i32) (result i32)`.
+    /// Exporting a name again replaces the body of the previous export.
+    pub fn export<T: Into<String>, U: Into<String>>(mut self, name: T, body: U) -> Self {
+        let (name, body) = (name.into(), body.into());
+        match self.exports.iter_mut().find(|(n, _)| n == &name) {
+            Some(export) => export.1 = body,
+            None => self.exports.push((name, body)),
+        }
+        self
+    }
+
+    /// Adds a custom section with the given name and contents.
+    ///
+    /// This can be used to add malformed `__functions` or `__vars` sections.
+    pub fn custom_section<T: Into<String>, U: Into<Vec<u8>>>(mut self, name: T, data: U) -> Self {
+        self.sections.push((name.into(), data.into()));
+        self
+    }
+
+    /// Adds a fragment of WebAssembly text to the module, such as imports or globals.
+    pub fn wat<T: Into<String>>(mut self, fragment: T) -> Self {
+        self.fragments.push(fragment.into());
+        self
+    }
+
+    /// Gets the WebAssembly text of the module.
+    pub fn to_wat(&self) -> Result<String> {
+        let mut wat = String::from("(module\n  (memory (export \"memory\") 1)\n");
+
+        for fragment in &self.fragments {
+            writeln!(wat, "  {}", fragment)?;
+        }
+
+        for (name, body) in &self.exports {
+            writeln!(
+                wat,
+                "  (func (export {}) (param i32) (result i32)\n    {})",
+                quote(name.as_bytes()),
+                body
+            )?;
+        }
+
+        // Each descriptor is emitted separately, as the codegen crate does for each macro invocation
+        for function in &self.functions {
+            writeln!(
+                wat,
+                "  (@custom \"__functions\" {})",
+                quote(&descriptor(&[function])?)
+            )?;
+
+            if let Some(version) = self.interface_version {
+                writeln!(
+                    wat,
+                    "  (@custom \"__interface\" {})",
+                    quote(&version.to_le_bytes())
+                )?;
+            }
+        }
+
+        if !self.vars.is_empty() {
+            writeln!(
+                wat,
+                "  (@custom \"__vars\" {})",
+                quote(&descriptor(&self.vars)?)
+            )?;
+        }
+
+        for (name, data) in &self.sections {
+            writeln!(
+                wat,
+                "  (@custom {} {})",
+                quote(name.as_bytes()),
+                quote(data)
+            )?;
+        }
+
+        wat.push(')');
+
+        Ok(wat)
+    }
+
+    /// Builds the WebAssembly module.
+    pub fn build(&self) -> Result<Vec<u8>> {
+        let wat = self.to_wat()?;
+        wat::parse_str(&wat).with_context(|| format!("failed to assemble fixture module:\n{}", wat))
+    }
+}
+
+/// Encodes a descriptor in the format emitted by the codegen crate: a little-endian length followed by JSON.
+fn descriptor<T: Serialize>(items: &[T]) -> Result<Vec<u8>> {
+    let json = serde_json::to_vec(items)?;
+    let mut bytes = (json.len() as u32).to_le_bytes().to_vec();
+    bytes.extend(json);
+    Ok(bytes)
+}
+
+/// Quotes bytes as a WebAssembly text string.
+fn quote(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 3 + 2);
+    s.push('"');
+    for b in bytes {
+        match b {
+            b'"' | b'\\' => {
+                s.push('\\');
+                s.push(*b as char);
+            }
+            0x20..=0x7e => s.push(*b as char),
+            _ => {
+                let _ = write!(s, "\\{:02x}", b);
+            }
+        }
+    }
+    s.push('"');
+    s
+}
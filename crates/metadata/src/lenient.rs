@@ -0,0 +1,624 @@
+use crate::{Function, Metadata};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use wasmparser::{Chunk, Parser, Payload};
+
+/// Limits applied when parsing metadata from untrusted modules.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// The maximum size of the module, in bytes.
+    pub max_module_size: usize,
+    /// The maximum combined size of the `__functions` and `__vars` sections, in bytes.
+    pub max_metadata_size: usize,
+    /// The maximum size of a single descriptor in a metadata section, in bytes.
+    pub max_descriptor_size: usize,
+    /// The maximum number of functions.
+    pub max_functions: usize,
+    /// The maximum number of environment variables.
+    pub max_vars: usize,
+    /// The maximum number of errors reported; parsing stops once it is reached.
+    pub max_errors: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_module_size: 256 * 1024 * 1024,
+            max_metadata_size: 1024 * 1024,
+            max_descriptor_size: 64 * 1024,
+            max_functions: 1024,
+            max_vars: 256,
+            max_errors: 100,
+        }
+    }
+}
+
+/// The kinds of errors found while leniently parsing metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// The module exceeds the maximum module size.
+    ModuleTooLarge,
+    /// The module is not a valid WebAssembly module.
+    InvalidModule,
+    /// The metadata sections exceed the maximum metadata size.
+    MetadataTooLarge,
+    /// A descriptor exceeds the maximum descriptor size.
+    DescriptorTooLarge,
+    /// A descriptor's length extends past the end of its section.
+    TruncatedDescriptor,
+    /// A descriptor could not be deserialized.
+    InvalidDescriptor,
+    /// The module declares more than the maximum number of functions.
+    TooManyFunctions,
+    /// The module declares more than the maximum number of environment variables.
+    TooManyVars,
+    /// A function with the same name was already declared.
+    DuplicateFunction,
+    /// An environment variable with the same name was already declared.
+    DuplicateVar,
+    /// A function uses an environment variable the module does not declare.
+    UndeclaredVar,
+    /// The `__interface` section is invalid.
+    InvalidInterfaceVersion,
+}
+
+impl ErrorCode {
+    /// Gets the stable string representation of the error code.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ModuleTooLarge => "module_too_large",
+            Self::InvalidModule => "invalid_module",
+            Self::MetadataTooLarge => "metadata_too_large",
+            Self::DescriptorTooLarge => "descriptor_too_large",
+            Self::TruncatedDescriptor => "truncated_descriptor",
+            Self::InvalidDescriptor => "invalid_descriptor",
+            Self::TooManyFunctions => "too_many_functions",
+            Self::TooManyVars => "too_many_vars",
+            Self::DuplicateFunction => "duplicate_function",
+            Self::DuplicateVar => "duplicate_var",
+            Self::UndeclaredVar => "undeclared_var",
+            Self::InvalidInterfaceVersion => "invalid_interface_version",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Represents an error found while leniently parsing metadata.
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    /// The kind of error.
+    pub code: ErrorCode,
+    /// The offset in the module where the error was found, if known.
+    pub offset: Option<usize>,
+    /// A message describing the error.
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "{} (at offset {}): {}", self.code, offset, self.message),
+            None => write!(f, "{}: {}", self.code, self.message),
+        }
+    }
+}
+
+/// Represents the result of leniently parsing metadata.
+///
+/// The metadata contains everything that could be read; the errors describe what could not.
+pub struct LenientMetadata {
+    /// The metadata that was successfully read.
+    pub metadata: Metadata,
+    /// The errors found while parsing.
+    pub errors: Vec<ParseError>,
+}
+
+impl LenientMetadata {
+    /// Determines if the metadata was read without errors.
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+struct State {
+    limits: Limits,
+    result: LenientMetadata,
+    metadata_size: usize,
+    function_names: HashSet<String>,
+    var_names: HashSet<String>,
+}
+
+impl State {
+    /// Records an error, returning `false` if parsing should stop.
+    fn error(&mut self, code: ErrorCode, offset: Option<usize>, message: String) -> bool {
+        if self.result.errors.len() < self.limits.max_errors {
+            self.result.errors.push(ParseError {
+                code,
+                offset,
+                message,
+            });
+        }
+
+        self.result.errors.len() < self.limits.max_errors
+    }
+
+    /// Reads the length-prefixed descriptors of a metadata section.
+    fn read_section<T, F>(&mut self, data: &[u8], data_offset: usize, mut add: F) -> bool
+    where
+        T: for<'de> Deserialize<'de>,
+        F: FnMut(&mut Self, T, usize) -> bool,
+    {
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let descriptor_offset = data_offset + offset;
+
+            if data.len() - offset < 4 {
+                return self.error(
+                    ErrorCode::TruncatedDescriptor,
+                    Some(descriptor_offset),
+                    "not enough data for the descriptor length".to_string(),
+                );
+            }
+
+            let len = u32::from_le_bytes([
+                data[offset],
+                data[offset + 1],
+                data[offset + 2],
+                data[offset + 3],
+            ]) as usize;
+
+            let begin = offset + 4;
+
+            // Without a valid length the start of the next descriptor is unknown
+            if len > data.len() - begin {
+                return self.error(
+                    ErrorCode::TruncatedDescriptor,
+                    Some(descriptor_offset),
+                    format!(
+                        "descriptor length {} exceeds the remaining {} bytes of the section",
+                        len,
+                        data.len() - begin
+                    ),
+                );
+            }
+
+            offset = begin + len;
+
+            if len > self.limits.max_descriptor_size {
+                if !self.error(
+                    ErrorCode::DescriptorTooLarge,
+                    Some(descriptor_offset),
+                    format!(
+                        "descriptor of {} bytes exceeds the limit of {} bytes",
+                        len, self.limits.max_descriptor_size
+                    ),
+                ) {
+                    return false;
+                }
+                continue;
+            }
+
+            match serde_json::from_slice::<Vec<T>>(&data[begin..offset]) {
+                Ok(items) => {
+                    for item in items {
+                        if !add(self, item, descriptor_offset) {
+                            return false;
+                        }
+                    }
+                }
+                Err(e) => {
+                    if !self.error(
+                        ErrorCode::InvalidDescriptor,
+                        Some(descriptor_offset),
+                        e.to_string(),
+                    ) {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    fn add_function(&mut self, function: Function, offset: usize) -> bool {
+        if self.result.metadata.functions.len() >= self.limits.max_functions {
+            return self.error(
+                ErrorCode::TooManyFunctions,
+                Some(offset),
+                format!(
+                    "function '{}' exceeds the limit of {} functions",
+                    function.name, self.limits.max_functions
+                ),
+            );
+        }
+
+        if !self.function_names.insert(function.name.clone()) {
+            return self.error(
+                ErrorCode::DuplicateFunction,
+                Some(offset),
+                format!("duplicate function named '{}'", function.name),
+            );
+        }
+
+        self.result.metadata.functions.push(function);
+        true
+    }
+
+    fn add_var(&mut self, var: String, offset: usize) -> bool {
+        if self.result.metadata.vars.len() >= self.limits.max_vars {
+            return self.error(
+                ErrorCode::TooManyVars,
+                Some(offset),
+                format!(
+                    "variable '{}' exceeds the limit of {} variables",
+                    var, self.limits.max_vars
+                ),
+            );
+        }
+
+        if !self.var_names.insert(var.clone()) {
+            return self.error(
+                ErrorCode::DuplicateVar,
+                Some(offset),
+                format!("duplicate variable named '{}'", var),
+            );
+        }
+
+        self.result.metadata.vars.push(var);
+        true
+    }
+}
+
+impl Metadata {
+    /// Leniently parses the metadata of a WebAssembly module from untrusted input.
+    ///
+    /// Unlike [`from_module_bytes`](Self::from_module_bytes), this never fails: invalid descriptors,
+    /// duplicates, and anything exceeding the given limits are skipped and reported as errors
+    /// alongside the metadata that could be read. Allocations are bounded by the limits.
+    pub fn parse_lenient<T: AsRef<[u8]>>(bytes: &T, limits: Limits) -> LenientMetadata {
+        let bytes = bytes.as_ref();
+
+        let mut state = State {
+            limits,
+            result: LenientMetadata {
+                metadata: Metadata {
+                    functions: Vec::new(),
+                    vars: Vec::new(),
+                    interface_version: None,
+                },
+                errors: Vec::new(),
+            },
+            metadata_size: 0,
+            function_names: HashSet::new(),
+            var_names: HashSet::new(),
+        };
+
+        if bytes.len() > limits.max_module_size {
+            state.error(
+                ErrorCode::ModuleTooLarge,
+                None,
+                format!(
+                    "module of {} bytes exceeds the limit of {} bytes",
+                    bytes.len(),
+                    limits.max_module_size
+                ),
+            );
+            return state.result;
+        }
+
+        let mut parser = Parser::new(0);
+        let mut offset = 0;
+
+        while offset < bytes.len() {
+            let (consumed, payload) = match parser.parse(&bytes[offset..], true) {
+                Ok(Chunk::Parsed { consumed, payload }) => (consumed, payload),
+                Ok(Chunk::NeedMoreData(_)) => {
+                    state.error(
+                        ErrorCode::InvalidModule,
+                        Some(offset),
+                        "the module is truncated".to_string(),
+                    );
+                    break;
+                }
+                Err(e) => {
+                    state.error(ErrorCode::InvalidModule, Some(offset), e.to_string());
+                    break;
+                }
+            };
+
+            offset += consumed;
+
+            // The interface version is small and fixed-size, so it isn't subject to the limits
+            if let Payload::CustomSection {
+                name: "__interface",
+                data,
+                data_offset,
+                ..
+            } = payload
+            {
+                match Metadata::read_interface_version(data) {
+                    Ok(version) => state.result.metadata.interface_version = Some(version),
+                    Err(e) => {
+                        if !state.error(
+                            ErrorCode::InvalidInterfaceVersion,
+                            Some(data_offset),
+                            e.to_string(),
+                        ) {
+                            break;
+                        }
+                    }
+                }
+                continue;
+            }
+
+            let (name, data, data_offset) = match payload {
+                Payload::CustomSection {
+                    name,
+                    data,
+                    data_offset,
+                    ..
+                } if name == "__functions" || name == "__vars" => (name, data, data_offset),
+                _ => continue,
+            };
+
+            state.metadata_size += data.len();
+            if state.metadata_size > limits.max_metadata_size {
+                state.error(
+                    ErrorCode::MetadataTooLarge,
+                    Some(data_offset),
+                    format!(
+                        "metadata sections exceed the limit of {} bytes",
+                        limits.max_metadata_size
+                    ),
+                );
+                break;
+            }
+
+            let proceed = if name == "__functions" {
+                state.read_section(data, data_offset, State::add_function)
+            } else {
+                state.read_section(data, data_offset, State::add_var)
+            };
+
+            if !proceed {
+                break;
+            }
+        }
+
+        // Variables may be declared after the functions that use them, so check once everything is read
+        let undeclared: Vec<_> = state
+            .result
+            .metadata
+            .functions
+            .iter()
+            .filter_map(|f| {
+                crate::undeclared_var(f, &state.result.metadata.vars)
+                    .map(|v| format!("function '{}' uses undeclared variable '{}'", f.name, v))
+            })
+            .collect();
+
+        for message in undeclared {
+            if !state.error(ErrorCode::UndeclaredVar, None, message) {
+                break;
+            }
+        }
+
+        state.result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::ModuleBuilder;
+    use crate::Method;
+
+    // Encodes a descriptor as the codegen crate does: a little-endian length followed by the data
+    fn descriptor(data: &[u8]) -> Vec<u8> {
+        let mut bytes = (data.len() as u32).to_le_bytes().to_vec();
+        bytes.extend(data);
+        bytes
+    }
+
+    fn parse(builder: ModuleBuilder, limits: Limits) -> LenientMetadata {
+        Metadata::parse_lenient(&builder.build().unwrap(), limits)
+    }
+
+    fn codes(result: &LenientMetadata) -> Vec<ErrorCode> {
+        result.errors.iter().map(|e| e.code).collect()
+    }
+
+    fn names(result: &LenientMetadata) -> Vec<&str> {
+        result
+            .metadata
+            .functions
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn valid_module_is_complete() {
+        let result = parse(
+            ModuleBuilder::new()
+                .http_function("a", "/a", &[Method::Get])
+                .http_function("b", "/b", &[Method::Post])
+                .var("DATABASE_URL"),
+            Limits::default(),
+        );
+
+        assert!(result.is_complete(), "{:?}", result.errors);
+        assert_eq!(names(&result), ["a", "b"]);
+        assert_eq!(result.metadata.vars, ["DATABASE_URL"]);
+        assert_eq!(
+            result.metadata.interface_version,
+            Some(crate::fixture::INTERFACE_VERSION)
+        );
+    }
+
+    #[test]
+    fn truncated_length_is_reported() {
+        let result = parse(
+            ModuleBuilder::new()
+                .http_function("a", "/a", &[Method::Get])
+                .custom_section("__functions", vec![1, 0]),
+            Limits::default(),
+        );
+
+        assert_eq!(codes(&result), [ErrorCode::TruncatedDescriptor]);
+        assert_eq!(names(&result), ["a"]);
+    }
+
+    #[test]
+    fn truncated_descriptor_is_reported() {
+        let mut data = descriptor(b"[\"A\", \"B\"]");
+        data.truncate(data.len() - 2);
+
+        let result = parse(
+            ModuleBuilder::new().custom_section("__vars", data),
+            Limits::default(),
+        );
+
+        assert_eq!(codes(&result), [ErrorCode::TruncatedDescriptor]);
+        assert!(result.metadata.vars.is_empty());
+    }
+
+    #[test]
+    fn oversized_descriptor_is_skipped() {
+        let mut data = descriptor(format!("[\"{}\"]", "A".repeat(64)).as_bytes());
+        data.extend(descriptor(b"[\"B\"]"));
+
+        let result = parse(
+            ModuleBuilder::new().custom_section("__vars", data),
+            Limits {
+                max_descriptor_size: 32,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(codes(&result), [ErrorCode::DescriptorTooLarge]);
+        assert_eq!(result.metadata.vars, ["B"]);
+    }
+
+    #[test]
+    fn oversized_metadata_stops_parsing() {
+        let result = parse(
+            ModuleBuilder::new()
+                .http_function("a", "/a", &[Method::Get])
+                .http_function("b", "/b", &[Method::Get]),
+            Limits {
+                max_metadata_size: 16,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(codes(&result), [ErrorCode::MetadataTooLarge]);
+        assert!(result.metadata.functions.is_empty());
+    }
+
+    #[test]
+    fn oversized_module_is_rejected() {
+        let result = parse(
+            ModuleBuilder::new().http_function("a", "/a", &[Method::Get]),
+            Limits {
+                max_module_size: 8,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(codes(&result), [ErrorCode::ModuleTooLarge]);
+        assert!(result.metadata.functions.is_empty());
+    }
+
+    #[test]
+    fn duplicates_are_skipped() {
+        let result = parse(
+            ModuleBuilder::new()
+                .http_function("a", "/a", &[Method::Get])
+                .http_function("a", "/other", &[Method::Post])
+                .custom_section("__vars", descriptor(b"[\"A\", \"A\"]")),
+            Limits::default(),
+        );
+
+        assert_eq!(
+            codes(&result),
+            [ErrorCode::DuplicateFunction, ErrorCode::DuplicateVar]
+        );
+        assert_eq!(names(&result), ["a"]);
+        assert_eq!(result.metadata.vars, ["A"]);
+    }
+
+    #[test]
+    fn malformed_descriptor_is_skipped() {
+        let mut data = descriptor(b"[{\"name\": 1}]");
+        data.extend(descriptor(b"not json"));
+
+        let result = parse(
+            ModuleBuilder::new()
+                .http_function("a", "/a", &[Method::Get])
+                .custom_section("__functions", data),
+            Limits::default(),
+        );
+
+        assert_eq!(
+            codes(&result),
+            [ErrorCode::InvalidDescriptor, ErrorCode::InvalidDescriptor]
+        );
+        assert_eq!(names(&result), ["a"]);
+    }
+
+    #[test]
+    fn truncated_module_keeps_earlier_metadata() {
+        let module = ModuleBuilder::new()
+            .http_function("a", "/a", &[Method::Get])
+            .custom_section("trailer", vec![0; 32])
+            .build()
+            .unwrap();
+
+        let result = Metadata::parse_lenient(&&module[..module.len() - 8], Limits::default());
+
+        assert_eq!(codes(&result), [ErrorCode::InvalidModule]);
+        assert_eq!(names(&result), ["a"]);
+    }
+
+    #[test]
+    fn invalid_interface_version_is_reported() {
+        let result = parse(
+            ModuleBuilder::new()
+                .interface_version(None)
+                .custom_section("__interface", vec![1, 2]),
+            Limits::default(),
+        );
+
+        assert_eq!(codes(&result), [ErrorCode::InvalidInterfaceVersion]);
+        assert!(result.metadata.interface_version.is_none());
+    }
+
+    #[test]
+    fn limits_bound_counts_and_errors() {
+        let mut builder = ModuleBuilder::new();
+        for i in 0..4 {
+            builder = builder.http_function(format!("f{}", i), format!("/{}", i), &[Method::Get]);
+        }
+
+        let result = parse(
+            builder,
+            Limits {
+                max_functions: 2,
+                max_errors: 1,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(codes(&result), [ErrorCode::TooManyFunctions]);
+        assert_eq!(names(&result), ["f0", "f1"]);
+    }
+}
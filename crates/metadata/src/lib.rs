@@ -3,118 +3,69 @@
 //! This crate is responsible for reading the metadata present in a WebAssembly module created by the
 //! Wasmtime Functions procedural macros.
 //!
-//! The data structures defined here should correspond to those in the `wasmtime-functions-codegen` crate.
+//! The function descriptors are defined in the `wasmtime-functions-types` crate, which is shared with
+//! the `wasmtime-functions-codegen` crate, and are re-exported here.
 //!
 //! See the documentation of the `wasmtime-functions-codegen` crate for more information.
 
 #![deny(missing_docs)]
 
 use anyhow::{anyhow, bail, Result};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use wasmparser::{Chunk, Parser, Payload};
 
-/// Represents a HTTP method.
-#[derive(Clone, Copy, Eq, PartialEq, Deserialize)]
-#[serde(rename_all = "UPPERCASE")]
-pub enum Method {
-    /// The `GET` HTTP method.
-    Get,
-    /// The `HEAD` HTTP method.
-    Head,
-    /// The `POST` HTTP method.
-    Post,
-    /// The `PUT` HTTP method.
-    Put,
-    /// The `DELETE` HTTP method.
-    Delete,
-    /// The `CONNECT` HTTP method.
-    Connect,
-    /// The `OPTIONS` HTTP method.
-    Options,
-    /// The `TRACE` HTTP method.
-    Trace,
-    /// The `PATCH` HTTP method.
-    Patch,
-}
-
-impl std::fmt::Display for Method {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.as_ref())
-    }
-}
-
-impl AsRef<str> for Method {
-    fn as_ref(&self) -> &str {
-        match self {
-            Self::Get => "GET",
-            Self::Head => "HEAD",
-            Self::Post => "POST",
-            Self::Put => "PUT",
-            Self::Delete => "DELETE",
-            Self::Connect => "CONNECT",
-            Self::Options => "OPTIONS",
-            Self::Trace => "TRACE",
-            Self::Patch => "PATCH",
-        }
-    }
-}
-
-impl std::borrow::Borrow<str> for Method {
-    fn borrow(&self) -> &str {
-        self.as_ref()
-    }
-}
-
-/// Represents the ways a Wasmtime Function can be triggered.
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase", tag = "type")]
-pub enum FunctionTrigger {
-    /// The function is triggered by a HTTP request.
-    Http {
-        /// The request path that triggers the function.
-        path: String,
-        /// The request methods that trigger the function.
-        methods: Vec<Method>,
-    },
-}
-
-/// Represents an input to a Wasmtime Function.
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase", tag = "type")]
-pub enum FunctionInput {}
-
-/// Represents an output of a Wasmtime Function.
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase", tag = "type")]
-pub enum FunctionOutput {
-    /// The Wasmtime Function returns a HTTP response.
-    Http,
-}
+mod client;
+mod contract;
+#[cfg(any(test, feature = "fixture"))]
+pub mod fixture;
+mod lenient;
 
-/// Represents the metadata of a Wasmtime Function.
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Function {
-    /// The name of the function.
-    pub name: String,
-    /// The trigger of the function.
-    pub trigger: FunctionTrigger,
-    /// The inputs of the function.
-    pub inputs: Vec<FunctionInput>,
-    /// The outputs of the function.
-    pub outputs: Vec<FunctionOutput>,
-}
+pub use client::ClientFile;
+pub use contract::ContractViolation;
+pub use lenient::{ErrorCode, LenientMetadata, Limits, ParseError};
+pub use wasmtime_functions_types::{
+    Cache, Function, FunctionInput, FunctionOutput, FunctionTrigger, Method, Parameter,
+    ParameterType,
+};
 
 /// Represents the Wasmtime Functions metadata for a WebAssembly module.
+#[derive(Serialize)]
 pub struct Metadata {
     /// The set of functions exposed in the WebAssembly module.
     pub functions: Vec<Function>,
     /// The set of required environment variables exposed in the WebAssembly module.
     pub vars: Vec<String>,
+    /// The version of the host interface the module was built for.
+    ///
+    /// This is `None` for modules built with an SDK that predates host interface versioning.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface_version: Option<u32>,
+}
+
+/// Gets the first variable of the function that is not in the given declared variables.
+pub(crate) fn undeclared_var<'a>(function: &'a Function, declared: &[String]) -> Option<&'a str> {
+    function
+        .vars
+        .iter()
+        .flatten()
+        .find(|v| !declared.contains(v))
+        .map(String::as_str)
 }
 
 impl Metadata {
+    /// Reads a function from the descriptor emitted for it by the procedural macros.
+    pub fn function_from_descriptor(descriptor: &[u8]) -> Result<Function> {
+        let mut functions = Vec::new();
+        Self::read_section_data(descriptor, &mut functions)?;
+
+        if functions.len() != 1 {
+            bail!("the descriptor does not describe exactly one function");
+        }
+
+        Ok(functions.remove(0))
+    }
+
     /// Creates a `Metadata` from the bytes of a WebAssembly module.
     pub fn from_module_bytes<T: AsRef<[u8]>>(bytes: &T) -> Result<Self> {
         let mut parser = Parser::new(0);
@@ -123,6 +74,7 @@ impl Metadata {
 
         let mut functions: Vec<Function> = Vec::new();
         let mut vars: Vec<String> = Vec::new();
+        let mut interface_version = None;
 
         loop {
             if offset >= bytes.len() {
@@ -146,6 +98,14 @@ impl Metadata {
                             Self::read_section_data(data, &mut vars).map_err(|e| {
                                 anyhow!("WebAssembly module has an invalid '__vars' section: {}", e)
                             })?;
+                        } else if name == "__interface" {
+                            interface_version =
+                                Some(Self::read_interface_version(data).map_err(|e| {
+                                    anyhow!(
+                                        "WebAssembly module has an invalid '__interface' section: {}",
+                                        e
+                                    )
+                                })?);
                         }
                     }
                 }
@@ -169,7 +129,104 @@ impl Metadata {
             }
         }
 
-        Ok(Self { functions, vars })
+        for f in functions.iter() {
+            if let Some(v) = undeclared_var(f, &vars) {
+                bail!(
+                    "WebAssembly function '{}' uses undeclared variable '{}'.",
+                    f.name,
+                    v
+                );
+            }
+        }
+
+        Ok(Self {
+            functions,
+            vars,
+            interface_version,
+        })
+    }
+
+    /// Gets a normalized, stable JSON representation of the metadata.
+    ///
+    /// Functions and variables are sorted by name, and the methods, content types, parameters,
+    /// varying headers, and variables of each function are sorted, so the representation only
+    /// changes when the metadata does. This is suitable for snapshot tests and for diffing the
+    /// metadata of builds.
+    pub fn to_canonical_json(&self) -> String {
+        fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+            match value {
+                serde_json::Value::Object(map) => {
+                    let mut entries: Vec<_> = map.into_iter().collect();
+                    entries.sort_by(|a, b| a.0.cmp(&b.0));
+                    serde_json::Value::Object(
+                        entries
+                            .into_iter()
+                            .map(|(k, v)| (k, sort_keys(v)))
+                            .collect(),
+                    )
+                }
+                serde_json::Value::Array(items) => {
+                    serde_json::Value::Array(items.into_iter().map(sort_keys).collect())
+                }
+                value => value,
+            }
+        }
+
+        fn sort_strings(value: Option<&mut serde_json::Value>) {
+            if let Some(serde_json::Value::Array(items)) = value {
+                items.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+            }
+        }
+
+        let mut value = serde_json::to_value(self).expect("metadata should serialize");
+
+        if let Some(serde_json::Value::Array(functions)) = value.get_mut("functions") {
+            functions.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+
+            for function in functions.iter_mut() {
+                sort_strings(function.get_mut("vars"));
+
+                let trigger = &mut function["trigger"];
+                sort_strings(trigger.get_mut("methods"));
+                sort_strings(trigger.get_mut("consumes"));
+                sort_strings(trigger.get_mut("cache").and_then(|c| c.get_mut("vary")));
+
+                if let Some(serde_json::Value::Array(params)) = trigger.get_mut("params") {
+                    params.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+                }
+            }
+        }
+
+        sort_strings(value.get_mut("vars"));
+
+        // Sort object keys explicitly in case `serde_json` preserves insertion order
+        let mut json =
+            serde_json::to_string_pretty(&sort_keys(value)).expect("metadata should serialize");
+        json.push('\n');
+        json
+    }
+
+    /// Reads the host interface version from the `__interface` section.
+    ///
+    /// Each function emits the version, so every version in the section must agree.
+    pub(crate) fn read_interface_version(data: &[u8]) -> Result<u32> {
+        let chunks = data.chunks_exact(4);
+        if data.is_empty() || !chunks.remainder().is_empty() {
+            bail!("the section is not a sequence of versions");
+        }
+
+        let mut versions = chunks.map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]));
+
+        let version = versions.next().unwrap();
+        if let Some(other) = versions.find(|v| *v != version) {
+            bail!(
+                "the module was built for both version {} and version {} of the host interface; build it with a single version of the `wasmtime-functions` crate",
+                version,
+                other
+            );
+        }
+
+        Ok(version)
     }
 
     fn read_section_data<'de, T: Deserialize<'de>>(
@@ -0,0 +1,99 @@
+//! Latency benchmarks for the runtime.
+//!
+//! The benchmarks use the reference module in `examples/conformance`, which must be built first:
+//!
+//! ```text
+//! $ (cd examples/conformance && cargo wasi build --release)
+//! $ cargo bench --manifest-path crates/runtime/Cargo.toml --features bench
+//! ```
+//!
+//! Set `WASMTIME_FUNCTIONS_BENCH_MODULE` to benchmark a module at a different path.
+
+use anyhow::{bail, Result};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::sync::Arc;
+use wasmtime_functions_runtime::{EnvironmentProvider, InvocationRequest, LocalServer, Server};
+
+const DEFAULT_MODULE: &str =
+    "../../examples/conformance/target/wasm32-wasi/release/conformance_example.wasm";
+
+struct NoEnvironment;
+
+impl EnvironmentProvider for NoEnvironment {
+    fn var(&self, name: &str) -> Result<String> {
+        bail!("environment variable '{}' is not set", name)
+    }
+}
+
+fn read_module() -> Vec<u8> {
+    let path = std::env::var("WASMTIME_FUNCTIONS_BENCH_MODULE")
+        .unwrap_or_else(|_| DEFAULT_MODULE.to_string());
+
+    std::fs::read(&path).unwrap_or_else(|e| {
+        panic!(
+            "failed to read benchmark module '{}' ({}); build `examples/conformance` first",
+            path, e
+        )
+    })
+}
+
+fn server(module: &[u8]) -> LocalServer {
+    async_std::task::block_on(Server::builder(module, Arc::new(NoEnvironment)).local())
+        .expect("failed to create server")
+}
+
+fn send(server: &LocalServer, req: &InvocationRequest) {
+    let mut res = async_std::task::block_on(server.respond(req.build().unwrap())).unwrap();
+    assert!(
+        res.status().is_success(),
+        "unexpected status {}",
+        res.status()
+    );
+    async_std::task::block_on(res.body_bytes()).unwrap();
+}
+
+fn module(c: &mut Criterion) {
+    let module = read_module();
+    let server = server(&module);
+
+    let mut group = c.benchmark_group("module");
+    group.bench_function("compile", |b| b.iter(|| self::server(&module)));
+    group.bench_function("link", |b| b.iter(|| server.bench_link().unwrap()));
+    group.bench_function("instantiate", |b| {
+        b.iter(|| async_std::task::block_on(server.bench_instantiate()).unwrap())
+    });
+    group.finish();
+}
+
+fn request(c: &mut Criterion) {
+    let module = read_module();
+    let server = server(&module);
+
+    let mut group = c.benchmark_group("request");
+
+    group.bench_function("empty", |b| {
+        let req = InvocationRequest::new("GET", "/empty");
+        b.iter(|| send(&server, &req))
+    });
+
+    group.bench_function("headers", |b| {
+        let req = (0..32).fold(
+            InvocationRequest::new("GET", "/header").header("x-conformance", "present"),
+            |req, i| req.header(format!("x-extra-{}", i), "value"),
+        );
+        b.iter(|| send(&server, &req))
+    });
+
+    for size in [1024, 64 * 1024, 1024 * 1024] {
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("body", size), &size, |b, size| {
+            let req = InvocationRequest::new("POST", "/echo").body(vec![b'x'; *size]);
+            b.iter(|| send(&server, &req))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, module, request);
+criterion_main!(benches);
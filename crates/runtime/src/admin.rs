@@ -0,0 +1,246 @@
+use crate::error::ServerError;
+use crate::listener::{ConnectionOptions, TcpListener};
+use crate::metrics::{self, MountMetrics};
+use crate::routes::RouteTable;
+use crate::server::State;
+use async_trait::async_trait;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tide::http::{headers::AUTHORIZATION, mime};
+use tide::listener::Listener;
+use tide::{Middleware, Next, Request, Response, StatusCode};
+
+#[derive(Clone)]
+pub(crate) struct AdminState {
+    mounts: Arc<Vec<(String, State)>>,
+    token: Option<Arc<String>>,
+}
+
+impl AdminState {
+    fn routes(&self) -> RouteTable {
+        RouteTable::merge(
+            self.mounts
+                .iter()
+                .map(|(prefix, state)| (prefix.as_str(), state.routes())),
+        )
+    }
+
+    fn is_ready(&self) -> bool {
+        self.mounts.iter().all(|(_, state)| state.is_ready())
+    }
+
+    fn metrics(&self) -> String {
+        metrics::render(
+            &self
+                .mounts
+                .iter()
+                .map(|(prefix, state)| MountMetrics {
+                    prefix,
+                    metrics: state.metrics(),
+                    sql: state.sql_metrics(),
+                    circuits: state.circuit_metrics(),
+                    quota: state.quota_metrics(),
+                    ready: state.is_ready(),
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    async fn quotas(&self) -> anyhow::Result<serde_json::Value> {
+        let mut mounts = Vec::new();
+
+        for (prefix, state) in self.mounts.iter() {
+            if let Some(usage) = state.quota_usage().await {
+                mounts.push(serde_json::json!({
+                    "mount": prefix,
+                    "tenants": usage?,
+                }));
+            }
+        }
+
+        Ok(serde_json::Value::Array(mounts))
+    }
+
+    fn memory(&self) -> serde_json::Value {
+        serde_json::Value::Array(
+            self.mounts
+                .iter()
+                .map(|(prefix, state)| {
+                    serde_json::json!({
+                        "mount": prefix,
+                        "functions": state.metrics().memory(),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    fn authorized(&self, req: &Request<Self>) -> bool {
+        let token = match &self.token {
+            Some(token) => token,
+            None => return true,
+        };
+
+        let provided = match req
+            .header(AUTHORIZATION)
+            .and_then(|v| v.as_str().strip_prefix("Bearer "))
+        {
+            Some(provided) => provided,
+            None => return false,
+        };
+
+        token_matches(provided, token)
+    }
+}
+
+/// Determines if a provided token matches the expected token.
+pub(crate) fn token_matches(provided: &str, token: &str) -> bool {
+    // Compare in constant time so that the token can't be discovered by timing responses
+    provided.len() == token.len()
+        && provided
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn json(status: StatusCode, body: serde_json::Value) -> Response {
+    Response::builder(status)
+        .content_type(mime::JSON)
+        .body(body.to_string())
+        .build()
+}
+
+/// A server for the operational endpoints of a runtime server.
+///
+/// Operational endpoints are served on a separate listener so that they aren't mixed into the application's routes.
+pub struct AdminServer {
+    addr: SocketAddr,
+    listener: Box<dyn Listener<AdminState>>,
+}
+
+impl AdminServer {
+    /// Binds a server that serves metrics in the Prometheus text format at `/metrics`.
+    pub(crate) async fn metrics(
+        addr: SocketAddr,
+        mounts: Vec<(String, State)>,
+    ) -> Result<Self, ServerError> {
+        let mut app = tide::with_state(AdminState {
+            mounts: Arc::new(mounts),
+            token: None,
+        });
+
+        app.at("/metrics")
+            .get(|req: Request<AdminState>| async move {
+                Ok(Response::builder(StatusCode::Ok)
+                    .content_type("text/plain; version=0.0.4")
+                    .body(req.state().metrics())
+                    .build())
+            });
+
+        Self::listen(addr, app).await
+    }
+
+    /// Binds a server that serves the admin API.
+    ///
+    /// If a token is given, requests must present it as a bearer token.
+    pub(crate) async fn admin(
+        addr: SocketAddr,
+        mounts: Vec<(String, State)>,
+        token: Option<String>,
+    ) -> Result<Self, ServerError> {
+        let mut app = tide::with_state(AdminState {
+            mounts: Arc::new(mounts),
+            token: token.map(Arc::new),
+        });
+
+        app.with(Authorize);
+
+        app.at("/routes")
+            .get(|req: Request<AdminState>| async move {
+                Ok(json(
+                    StatusCode::Ok,
+                    serde_json::to_value(req.state().routes())?,
+                ))
+            });
+
+        app.at("/ready").get(|req: Request<AdminState>| async move {
+            let ready = req.state().is_ready();
+
+            Ok(json(
+                if ready {
+                    StatusCode::Ok
+                } else {
+                    StatusCode::ServiceUnavailable
+                },
+                serde_json::json!({ "ready": ready }),
+            ))
+        });
+
+        app.at("/quotas")
+            .get(|req: Request<AdminState>| async move {
+                Ok(json(StatusCode::Ok, req.state().quotas().await?))
+            });
+
+        app.at("/memory")
+            .get(|req: Request<AdminState>| async move {
+                Ok(json(StatusCode::Ok, req.state().memory()))
+            });
+
+        Self::listen(addr, app).await
+    }
+
+    async fn listen(addr: SocketAddr, app: tide::Server<AdminState>) -> Result<Self, ServerError> {
+        let mut listener = TcpListener::new(addr, ConnectionOptions::default());
+        listener
+            .bind(app)
+            .await
+            .map_err(|source| ServerError::Bind { addr, source })?;
+
+        Ok(Self {
+            addr: listener.local_addr().unwrap_or(addr),
+            listener: Box::new(listener),
+        })
+    }
+
+    /// Gets the address the server is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Accepts and processes incoming connections.
+    pub async fn accept(&mut self) -> Result<(), ServerError> {
+        self.listener.accept().await.map_err(ServerError::Accept)
+    }
+}
+
+impl fmt::Display for AdminServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.listener
+                .info()
+                .first()
+                .map(|i| i.connection())
+                .unwrap_or("")
+        )
+    }
+}
+
+/// A middleware that rejects requests without the admin token.
+struct Authorize;
+
+#[async_trait]
+impl Middleware<AdminState> for Authorize {
+    async fn handle(&self, req: Request<AdminState>, next: Next<'_, AdminState>) -> tide::Result {
+        if !req.state().authorized(&req) {
+            return Ok(Response::builder(StatusCode::Unauthorized)
+                .header("WWW-Authenticate", "Bearer")
+                .build());
+        }
+
+        Ok(next.run(req).await)
+    }
+}
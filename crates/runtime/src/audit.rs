@@ -0,0 +1,202 @@
+use crate::server::Request;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Represents an audited function invocation.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    /// The time the invocation started, in RFC 3339 format.
+    pub timestamp: String,
+    /// The authenticated principal that made the request, if known.
+    pub principal: Option<String>,
+    /// The name of the function that was invoked.
+    pub function: String,
+    /// The method of the request.
+    pub method: String,
+    /// The path of the request.
+    pub path: String,
+    /// The status code of the response.
+    pub status: u16,
+    /// The error that occurred during the invocation, if any.
+    pub error: Option<String>,
+    /// The host capabilities the function used during the invocation.
+    pub capabilities: CapabilityUsage,
+}
+
+/// Represents the host capabilities a function used during an invocation.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapabilityUsage {
+    /// The hosts that outbound HTTP requests were sent to.
+    pub hosts: BTreeSet<String>,
+    /// The number of SQL statements executed.
+    pub sql: u32,
+    /// The gRPC services that were called.
+    pub grpc_services: BTreeSet<String>,
+    /// The number of operations on the app-scoped cache.
+    pub cache: u32,
+    /// The blob buckets that request parts were uploaded to.
+    pub buckets: BTreeSet<String>,
+}
+
+// The capability usage of an audited request, attached to the request as an extension
+#[derive(Clone, Default)]
+struct AuditedUsage(Arc<Mutex<CapabilityUsage>>);
+
+/// Records the capabilities used by an instance into the usage of its current request.
+///
+/// Recorders are cheap to clone; clones share the request being recorded. Nothing is recorded
+/// for requests that are not audited.
+#[derive(Clone, Default)]
+pub(crate) struct CapabilityRecorder(Arc<Mutex<Option<AuditedUsage>>>);
+
+impl CapabilityRecorder {
+    /// Records the capabilities used into the usage of the given request, if it is audited.
+    pub fn set_request(&self, req: Option<&Request>) {
+        *self.0.lock().unwrap() = req.and_then(|req| req.ext::<AuditedUsage>()).cloned();
+    }
+
+    /// Records the use of a capability.
+    pub fn record<F: FnOnce(&mut CapabilityUsage)>(&self, f: F) {
+        if let Some(usage) = self.0.lock().unwrap().as_ref() {
+            f(&mut usage.0.lock().unwrap());
+        }
+    }
+}
+
+/// Represents an audit record of an invocation that has yet to complete.
+pub(crate) struct PendingRecord {
+    record: AuditRecord,
+    usage: AuditedUsage,
+}
+
+/// Implemented by destinations of audit records.
+///
+/// Audit records are kept separate from the access log so that they can be retained for compliance purposes.
+pub trait AuditSink: Send + Sync {
+    /// Records the given audit record.
+    fn record(&self, record: &AuditRecord) -> Result<()>;
+}
+
+/// An audit sink that appends records to a file as JSON lines.
+pub struct FileAuditSink(Mutex<File>);
+
+impl FileAuditSink {
+    /// Creates a new file audit sink that appends to the given file.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self(Mutex::new(
+            OpenOptions::new().create(true).append(true).open(path)?,
+        )))
+    }
+}
+
+impl AuditSink for FileAuditSink {
+    fn record(&self, record: &AuditRecord) -> Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let mut file = self.0.lock().unwrap();
+        file.write_all(&line)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// An audit sink that sends records to the local syslog daemon.
+#[cfg(unix)]
+pub struct SyslogAuditSink(std::os::unix::net::UnixDatagram);
+
+#[cfg(unix)]
+impl SyslogAuditSink {
+    // The `authpriv` facility with `info` severity
+    const PRIORITY: u8 = 10 * 8 + 6;
+
+    /// Creates a new syslog audit sink connected to `/dev/log`.
+    pub fn new() -> Result<Self> {
+        let socket = std::os::unix::net::UnixDatagram::unbound()?;
+        socket.connect("/dev/log")?;
+        Ok(Self(socket))
+    }
+}
+
+#[cfg(unix)]
+impl AuditSink for SyslogAuditSink {
+    fn record(&self, record: &AuditRecord) -> Result<()> {
+        self.0.send(
+            format!(
+                "<{}>wasmtime-functions[{}]: {}",
+                Self::PRIORITY,
+                std::process::id(),
+                serde_json::to_string(record)?
+            )
+            .as_bytes(),
+        )?;
+        Ok(())
+    }
+}
+
+pub struct Auditor {
+    sink: Arc<dyn AuditSink>,
+    principal_header: Option<String>,
+}
+
+impl Auditor {
+    pub fn new(sink: Arc<dyn AuditSink>, principal_header: Option<String>) -> Self {
+        Self {
+            sink,
+            principal_header,
+        }
+    }
+
+    /// Begins an audit record for the invocation of the given function.
+    ///
+    /// The request is marked so that the capabilities used by the invocation are recorded.
+    pub(crate) fn begin(&self, req: &mut Request, function: &str) -> PendingRecord {
+        let usage = AuditedUsage::default();
+        req.set_ext(usage.clone());
+
+        let record = AuditRecord {
+            timestamp: time::OffsetDateTime::now_utc().format(time::Format::Rfc3339),
+            principal: self
+                .principal_header
+                .as_ref()
+                .and_then(|h| req.header(h.as_str()))
+                .map(|v| v.as_str().to_string()),
+            function: function.to_string(),
+            method: req.method().to_string(),
+            path: req.url().path().to_string(),
+            status: 0,
+            error: None,
+            capabilities: CapabilityUsage::default(),
+        };
+
+        PendingRecord { record, usage }
+    }
+
+    /// Completes the audit record with the outcome of the invocation and records it.
+    pub(crate) fn end(&self, pending: PendingRecord, res: &tide::Result) {
+        let mut record = pending.record;
+        record.capabilities = std::mem::take(&mut *pending.usage.0.lock().unwrap());
+
+        match res {
+            Ok(res) => {
+                record.status = res.status().into();
+                record.error = res.error().map(ToString::to_string);
+            }
+            Err(e) => {
+                record.status = e.status().into();
+                record.error = Some(e.to_string());
+            }
+        }
+
+        if let Err(e) = self.sink.record(&record) {
+            log::error!("Failed to write audit record: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::{anyhow, bail, Context, Result};
+use async_std::io::WriteExt;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// An object written to a blob store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobObject {
+    /// The bucket the object was written to.
+    pub bucket: String,
+    /// The key of the object within its bucket.
+    pub key: String,
+    /// The size of the object in bytes.
+    pub size: u64,
+    /// The content type of the object, if known.
+    pub content_type: Option<String>,
+}
+
+/// Writes the content of an object to a blob store as it is received.
+///
+/// Dropping the writer without finishing it discards the object.
+#[async_trait::async_trait]
+pub trait BlobWriter: Send {
+    /// Writes the next chunk of the object's content.
+    async fn write(&mut self, chunk: &[u8]) -> Result<()>;
+
+    /// Finishes writing the object, making it visible in the store.
+    async fn finish(self: Box<Self>) -> Result<BlobObject>;
+}
+
+/// Stores the objects that functions upload from requests.
+///
+/// Functions name buckets rather than storage locations, so the provider decides where objects are
+/// written and fails writes to any bucket it doesn't know.
+#[async_trait::async_trait]
+pub trait BlobProvider: Send + Sync {
+    /// Creates an object in a bucket, replacing any existing object with the same key once it is finished.
+    async fn create(
+        &self,
+        bucket: &str,
+        key: &str,
+        content_type: Option<&str>,
+    ) -> Result<Box<dyn BlobWriter>>;
+}
+
+/// A blob provider that stores the objects of each bucket as files in a directory.
+///
+/// A key is the path of the file relative to the bucket's directory, using `/` as the separator.
+#[derive(Default)]
+pub struct DirectoryBlobProvider {
+    buckets: HashMap<String, PathBuf>,
+}
+
+impl DirectoryBlobProvider {
+    /// Creates a new directory blob provider with no buckets.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a bucket whose objects are stored in the given directory.
+    pub fn bucket<T: Into<String>, P: Into<PathBuf>>(mut self, name: T, dir: P) -> Self {
+        self.buckets.insert(name.into(), dir.into());
+        self
+    }
+
+    fn path(&self, bucket: &str, key: &str) -> Result<PathBuf> {
+        let dir = self
+            .buckets
+            .get(bucket)
+            .ok_or_else(|| anyhow!("bucket `{}` does not exist", bucket))?;
+
+        // Keys can't escape the bucket's directory
+        if key.is_empty()
+            || key.split('/').any(|segment| {
+                segment.is_empty()
+                    || segment == "."
+                    || segment == ".."
+                    || segment.contains(&['\\', '\0'][..])
+            })
+        {
+            bail!("invalid object key `{}`", key);
+        }
+
+        Ok(key
+            .split('/')
+            .fold(dir.clone(), |path, segment| path.join(segment)))
+    }
+}
+
+#[async_trait::async_trait]
+impl BlobProvider for DirectoryBlobProvider {
+    async fn create(
+        &self,
+        bucket: &str,
+        key: &str,
+        content_type: Option<&str>,
+    ) -> Result<Box<dyn BlobWriter>> {
+        let path = self.path(bucket, key)?;
+        let parent = path.parent().unwrap_or_else(|| Path::new("."));
+
+        async_std::fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("failed to create directory '{}'", parent.display()))?;
+
+        // The content is written to a temporary file so that a partial upload never replaces the object
+        let temp = parent.join(format!(
+            ".{}.{:016x}.upload",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            rand::random::<u64>()
+        ));
+
+        let file = async_std::fs::File::create(&temp)
+            .await
+            .with_context(|| format!("failed to create file '{}'", temp.display()))?;
+
+        Ok(Box::new(FileWriter {
+            file: Some(file),
+            temp,
+            path,
+            object: BlobObject {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+                size: 0,
+                content_type: content_type.map(ToString::to_string),
+            },
+        }))
+    }
+}
+
+struct FileWriter {
+    file: Option<async_std::fs::File>,
+    temp: PathBuf,
+    path: PathBuf,
+    object: BlobObject,
+}
+
+#[async_trait::async_trait]
+impl BlobWriter for FileWriter {
+    async fn write(&mut self, chunk: &[u8]) -> Result<()> {
+        self.file
+            .as_mut()
+            .expect("the file should be open")
+            .write_all(chunk)
+            .await?;
+        self.object.size += chunk.len() as u64;
+        Ok(())
+    }
+
+    async fn finish(mut self: Box<Self>) -> Result<BlobObject> {
+        let mut file = self.file.take().expect("the file should be open");
+        file.flush().await?;
+        file.sync_all().await?;
+        drop(file);
+
+        async_std::fs::rename(&self.temp, &self.path)
+            .await
+            .with_context(|| format!("failed to write file '{}'", self.path.display()))?;
+
+        Ok(self.object.clone())
+    }
+}
+
+impl Drop for FileWriter {
+    fn drop(&mut self) {
+        // Removes the temporary file of an upload that was not finished
+        let _ = std::fs::remove_file(&self.temp);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::server::Request;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tide::http::headers::{HeaderName, HeaderValues, CACHE_CONTROL, SET_COOKIE};
+use tide::http::Method;
+use tide::{Response, StatusCode};
+
+/// Marks a response whose function set or removed a cookie.
+///
+/// Cookies set through the cookie jar are only applied to the response's headers by tide's cookie
+/// middleware once the response leaves the route, so they can't be detected from its headers.
+#[derive(Clone, Copy)]
+pub struct CookiesChanged;
+
+struct Entry {
+    headers: Vec<(HeaderName, HeaderValues)>,
+    body: Vec<u8>,
+    expires: Instant,
+    last_used: u64,
+}
+
+/// An in-memory LRU cache of function responses shared by all routes.
+pub struct ResponseCache {
+    capacity: usize,
+    inner: Mutex<(HashMap<String, Entry>, u64)>,
+}
+
+impl ResponseCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new((HashMap::new(), 0)),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<Response> {
+        let mut inner = self.inner.lock().unwrap();
+        let (entries, clock) = &mut *inner;
+
+        *clock += 1;
+
+        let entry = entries.get_mut(key)?;
+        if entry.expires <= Instant::now() {
+            entries.remove(key);
+            return None;
+        }
+
+        entry.last_used = *clock;
+
+        let mut res = Response::new(StatusCode::Ok);
+        for (name, values) in &entry.headers {
+            res.insert_header(name.clone(), values);
+        }
+        res.set_body(entry.body.clone());
+        Some(res)
+    }
+
+    fn insert(&self, key: String, entry: Entry) {
+        let mut inner = self.inner.lock().unwrap();
+        let (entries, clock) = &mut *inner;
+
+        if !entries.contains_key(&key) && entries.len() >= self.capacity {
+            let now = Instant::now();
+            entries.retain(|_, e| e.expires > now);
+
+            if entries.len() >= self.capacity {
+                if let Some(lru) = entries
+                    .iter()
+                    .min_by_key(|(_, e)| e.last_used)
+                    .map(|(k, _)| k.clone())
+                {
+                    entries.remove(&lru);
+                }
+            }
+        }
+
+        *clock += 1;
+        entries.insert(
+            key,
+            Entry {
+                last_used: *clock,
+                ..entry
+            },
+        );
+    }
+}
+
+/// Caches the responses of a single route.
+pub struct RouteCache {
+    cache: Arc<ResponseCache>,
+    ttl: Duration,
+    vary: Vec<String>,
+}
+
+impl RouteCache {
+    pub fn new(cache: Arc<ResponseCache>, ttl: Duration, vary: Vec<String>) -> Self {
+        Self { cache, ttl, vary }
+    }
+
+    /// Gets the cache key for the given request.
+    ///
+    /// Returns `None` if the request cannot be served from the cache.
+    pub fn key(&self, req: &Request) -> Option<String> {
+        if req.method() != Method::Get {
+            return None;
+        }
+
+        let mut key = req.url().path().to_string();
+        if let Some(query) = req.url().query() {
+            key.push('?');
+            key.push_str(query);
+        }
+
+        for name in &self.vary {
+            key.push('\n');
+            key.push_str(name);
+            key.push(':');
+            if let Some(values) = req.header(name.as_str()) {
+                key.push_str(values.as_str());
+            }
+        }
+
+        Some(key)
+    }
+
+    /// Gets a cached response for the given key.
+    pub fn get(&self, key: &str) -> Option<Response> {
+        self.cache.get(key)
+    }
+
+    /// Caches the given response if it is cacheable.
+    pub async fn insert(&self, key: String, res: &mut Response) -> tide::Result<()> {
+        if res.status() != StatusCode::Ok
+            || res.header(SET_COOKIE).is_some()
+            || res.ext::<CookiesChanged>().is_some()
+        {
+            return Ok(());
+        }
+
+        if let Some(cache_control) = res.header(CACHE_CONTROL) {
+            let cache_control = cache_control.as_str().to_lowercase();
+            if cache_control.contains("no-store") || cache_control.contains("private") {
+                return Ok(());
+            }
+        }
+
+        // A nonce must not be reused, so a response with one is only valid for its request
+        if let Some(policy) = res.header("Content-Security-Policy") {
+            if policy.as_str().contains("'nonce-") {
+                return Ok(());
+            }
+        }
+
+        let body = res.take_body().into_bytes().await?;
+
+        self.cache.insert(
+            key,
+            Entry {
+                headers: res.iter().map(|(n, v)| (n.clone(), v.clone())).collect(),
+                body: body.clone(),
+                expires: Instant::now() + self.ttl,
+                last_used: 0,
+            },
+        );
+
+        res.set_body(body);
+        Ok(())
+    }
+}
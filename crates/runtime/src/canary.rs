@@ -0,0 +1,161 @@
+use crate::server::State;
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tide::Endpoint;
+
+/// Determines how requests are split between the stable and canary versions of a module.
+#[derive(Debug, Clone)]
+pub struct CanaryPolicy {
+    percent: u8,
+    header: Option<(String, String)>,
+    max_error_rate: Option<f64>,
+    min_requests: u64,
+}
+
+impl CanaryPolicy {
+    /// Creates a policy that routes the given percentage of requests to the canary.
+    ///
+    /// Percentages over 100 are treated as 100.
+    pub fn new(percent: u8) -> Self {
+        Self {
+            percent: percent.min(100),
+            header: None,
+            max_error_rate: None,
+            min_requests: 0,
+        }
+    }
+
+    /// Routes requests with a header of the given name and value to the canary, regardless of the percentage.
+    pub fn header<T: Into<String>, U: Into<String>>(mut self, name: T, value: U) -> Self {
+        self.header = Some((name.into(), value.into()));
+        self
+    }
+
+    /// Rolls back to the stable module once the canary's error rate exceeds the given rate.
+    ///
+    /// The error rate is the fraction of the canary's responses with a server error status, and is only
+    /// checked once the canary has served at least `min_requests` requests. After a rollback, every
+    /// request is routed to the stable module until the server is rebuilt.
+    pub fn rollback(mut self, max_error_rate: f64, min_requests: u64) -> Self {
+        self.max_error_rate = Some(max_error_rate);
+        self.min_requests = min_requests;
+        self
+    }
+}
+
+/// Represents the status of a canary deployment.
+#[derive(Debug, Clone, Copy)]
+pub struct CanaryStatus {
+    /// The number of requests routed to the canary.
+    pub requests: u64,
+    /// The number of canary responses with a server error status.
+    pub errors: u64,
+    /// Whether the canary was rolled back because of its error rate.
+    pub rolled_back: bool,
+}
+
+/// Routes requests to either the stable or the canary version of a module.
+pub struct Deployment {
+    policy: CanaryPolicy,
+    stable: tide::Server<State>,
+    canary: tide::Server<State>,
+    canary_state: State,
+    received: AtomicU64,
+    requests: AtomicU64,
+    errors: AtomicU64,
+    rolled_back: AtomicBool,
+}
+
+impl Deployment {
+    pub fn new(
+        policy: CanaryPolicy,
+        stable: tide::Server<State>,
+        canary: tide::Server<State>,
+        canary_state: State,
+    ) -> Self {
+        Self {
+            policy,
+            stable,
+            canary,
+            canary_state,
+            received: AtomicU64::new(0),
+            requests: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            rolled_back: AtomicBool::new(false),
+        }
+    }
+
+    pub fn canary_state(&self) -> &State {
+        &self.canary_state
+    }
+
+    pub fn status(&self) -> CanaryStatus {
+        CanaryStatus {
+            requests: self.requests.load(Ordering::SeqCst),
+            errors: self.errors.load(Ordering::SeqCst),
+            rolled_back: self.rolled_back.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Determines if a request should be routed to the canary.
+    fn select(&self, req: &tide::Request<State>) -> bool {
+        if self.rolled_back.load(Ordering::SeqCst) {
+            return false;
+        }
+
+        if let Some((name, value)) = &self.policy.header {
+            if req.header(name.as_str()).map(|v| v.as_str()) == Some(value.as_str()) {
+                return true;
+            }
+        }
+
+        // Requests are split by count rather than at random so that the split is exact
+        self.received.fetch_add(1, Ordering::SeqCst) % 100 < self.policy.percent as u64
+    }
+
+    /// Records a response from the canary, rolling it back if its error rate is too high.
+    fn record(&self, status: u16) {
+        let requests = self.requests.fetch_add(1, Ordering::SeqCst) + 1;
+        let errors = if status >= 500 {
+            self.errors.fetch_add(1, Ordering::SeqCst) + 1
+        } else {
+            self.errors.load(Ordering::SeqCst)
+        };
+
+        let max = match self.policy.max_error_rate {
+            Some(max) => max,
+            None => return,
+        };
+
+        let rate = errors as f64 / requests as f64;
+        if requests >= self.policy.min_requests
+            && rate > max
+            && !self.rolled_back.swap(true, Ordering::SeqCst)
+        {
+            log::warn!(
+                "Canary error rate of {:.1}% over {} requests exceeds {:.1}%; routing all requests to the stable module.",
+                rate * 100.0,
+                requests,
+                max * 100.0
+            );
+        }
+    }
+}
+
+/// The endpoint that dispatches every request of a canary deployment.
+#[derive(Clone)]
+pub struct DeploymentEndpoint(pub Arc<Deployment>);
+
+#[async_trait]
+impl Endpoint<State> for DeploymentEndpoint {
+    async fn call(&self, req: tide::Request<State>) -> tide::Result {
+        if !self.0.select(&req) {
+            return self.0.stable.call(req).await;
+        }
+
+        let res = self.0.canary.call(req).await?;
+        self.0.record(res.status() as u16);
+        Ok(res)
+    }
+}
@@ -0,0 +1,142 @@
+use crate::error::FunctionResponse;
+use crate::invocation::InvocationRequest;
+use crate::server::Request;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Represents a request that failed, captured so that it can be replayed against the module.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapturedRequest {
+    /// The time the request was received, in RFC 3339 format.
+    pub timestamp: String,
+    /// The name of the function that was invoked.
+    pub function: String,
+    /// The method of the request.
+    pub method: String,
+    /// The path and query of the request, relative to the module's mount prefix.
+    pub path: String,
+    /// The headers of the request.
+    pub headers: Vec<(String, String)>,
+    /// The body of the request, encoded as base64.
+    pub body: String,
+    /// The error that occurred during the invocation.
+    pub error: Option<String>,
+}
+
+impl CapturedRequest {
+    /// Reads a captured request from a file.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read(path)
+            .with_context(|| format!("failed to read captured request '{}'", path.display()))?;
+
+        serde_json::from_slice(&contents)
+            .with_context(|| format!("invalid captured request '{}'", path.display()))
+    }
+
+    /// Creates a request that can be processed by a [`LocalServer`](crate::LocalServer).
+    pub fn to_request(&self) -> Result<http_types::Request> {
+        self.headers
+            .iter()
+            .fold(
+                InvocationRequest::new(self.method.as_str(), self.path.as_str()),
+                |req, (name, value)| req.header(name.as_str(), value.as_str()),
+            )
+            .body(base64::decode(&self.body)?)
+            .build()
+    }
+}
+
+/// Captures the requests of failed invocations to a directory.
+pub struct Capturer {
+    dir: PathBuf,
+    count: AtomicU64,
+}
+
+impl Capturer {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            count: AtomicU64::new(0),
+        }
+    }
+
+    /// Begins capturing the request for the invocation of the given function.
+    ///
+    /// The body of the request is buffered so that it remains available to the function.
+    pub async fn begin(&self, req: &mut Request, function: &str) -> tide::Result<CapturedRequest> {
+        let body = req.take_body().into_bytes().await?;
+
+        let captured = CapturedRequest {
+            timestamp: time::OffsetDateTime::now_utc().format(time::Format::Rfc3339),
+            function: function.to_string(),
+            method: req.method().to_string(),
+            path: match req.url().query() {
+                Some(query) => format!("{}?{}", req.url().path(), query),
+                None => req.url().path().to_string(),
+            },
+            headers: req
+                .iter()
+                .flat_map(|(name, values)| {
+                    values
+                        .iter()
+                        .map(move |v| (name.as_str().to_string(), v.as_str().to_string()))
+                })
+                .collect(),
+            body: base64::encode(&body),
+            error: None,
+        };
+
+        req.set_body(body);
+
+        Ok(captured)
+    }
+
+    /// Completes the capture with the outcome of the invocation, writing it if the invocation failed.
+    ///
+    /// An invocation failed if the function trapped, timed out, or did not return a response.
+    pub fn end(&self, mut captured: CapturedRequest, res: &tide::Result) {
+        captured.error = match res {
+            Err(e) => Some(e.to_string()),
+            Ok(res)
+                if res.status().is_server_error() && res.ext::<FunctionResponse>().is_none() =>
+            {
+                Some(
+                    res.error()
+                        .map(ToString::to_string)
+                        .unwrap_or_else(|| res.status().canonical_reason().to_string()),
+                )
+            }
+            Ok(_) => return,
+        };
+
+        if let Err(e) = self.write(&captured) {
+            log::error!("Failed to capture request: {:?}", e);
+        }
+    }
+
+    fn write(&self, captured: &CapturedRequest) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+
+        let path = self.dir.join(format!(
+            "{}-{}-{}.json",
+            SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis(),
+            captured.function,
+            self.count.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        std::fs::write(&path, serde_json::to_vec_pretty(captured)?)?;
+
+        log::info!(
+            "Captured failed request to function '{}' to '{}'.",
+            captured.function,
+            path.display()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use wasi_common::clocks::{WasiMonotonicClock, WasiSystemClock};
+
+/// Provides the current time to functions.
+pub trait Clock: Send + Sync {
+    /// Gets the current wall clock time.
+    fn now(&self) -> SystemTime;
+
+    /// Gets the time elapsed since an arbitrary fixed point; it must never decrease.
+    fn elapsed(&self) -> Duration;
+}
+
+/// A clock that only advances when told to, for deterministic tests.
+pub struct ManualClock {
+    start: SystemTime,
+    elapsed: Mutex<Duration>,
+}
+
+impl ManualClock {
+    /// Creates a new manual clock starting at the given wall clock time.
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            start,
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Advances the clock by the given duration.
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        self.start + *self.elapsed.lock().unwrap()
+    }
+
+    fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+/// Adapts a clock to the WASI system clock.
+pub struct SystemClock(pub Arc<dyn Clock>);
+
+impl WasiSystemClock for SystemClock {
+    fn resolution(&self) -> Duration {
+        Duration::from_nanos(1)
+    }
+
+    fn now(&self, _precision: Duration) -> cap_std::time::SystemTime {
+        cap_std::time::SystemTime::from_std(self.0.now())
+    }
+}
+
+/// Adapts a clock to the WASI monotonic clock.
+pub struct MonotonicClock {
+    clock: Arc<dyn Clock>,
+    base: Instant,
+}
+
+impl MonotonicClock {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            base: Instant::now(),
+        }
+    }
+}
+
+impl WasiMonotonicClock for MonotonicClock {
+    fn resolution(&self) -> Duration {
+        Duration::from_nanos(1)
+    }
+
+    fn now(&self, _precision: Duration) -> cap_std::time::Instant {
+        cap_std::time::Instant::from_std(self.base + self.clock.elapsed())
+    }
+}
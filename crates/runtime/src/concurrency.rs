@@ -0,0 +1,64 @@
+use async_std::channel::{bounded, Receiver, Sender};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Limits the number of concurrent invocations of a function.
+///
+/// Invocations in excess of the limit wait for a permit; once the queue is full,
+/// excess invocations are shed.
+pub struct ConcurrencyLimiter {
+    sender: Sender<()>,
+    receiver: Receiver<()>,
+    queue: usize,
+    waiting: AtomicUsize,
+}
+
+impl ConcurrencyLimiter {
+    /// Creates a limiter that allows the given number of concurrent invocations.
+    ///
+    /// Panics if the limit is zero; the server builder rejects zero limits.
+    pub fn new(limit: usize, queue: usize) -> Self {
+        let (sender, receiver) = bounded(limit);
+
+        Self {
+            sender,
+            receiver,
+            queue,
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    /// Acquires a permit to invoke the function.
+    ///
+    /// Returns `None` if the invocation should be shed.
+    pub async fn acquire(&self) -> Option<Permit<'_>> {
+        if self.sender.try_send(()).is_ok() {
+            return Some(Permit(self));
+        }
+
+        if self.waiting.fetch_add(1, Ordering::SeqCst) >= self.queue {
+            self.waiting.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+
+        let _waiting = Waiting(self);
+        self.sender.send(()).await.ok().map(|_| Permit(self))
+    }
+}
+
+/// A permit to invoke a function; the permit is released when dropped.
+pub struct Permit<'a>(&'a ConcurrencyLimiter);
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.0.receiver.try_recv().ok();
+    }
+}
+
+// Decrements the waiting count even if the waiting request is dropped
+struct Waiting<'a>(&'a ConcurrencyLimiter);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.waiting.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,113 @@
+use anyhow::{bail, Result};
+use rand::RngCore;
+
+const CONTENT_SECURITY_POLICY: &str = "Content-Security-Policy";
+
+// The number of random bytes in a nonce; the CSP specification recommends at least 128 bits
+const NONCE_BYTES: usize = 16;
+
+// The directives a nonce is appended to
+const NONCE_DIRECTIVES: &[&str] = &["script-src", "style-src"];
+
+/// Generates a nonce of random bytes encoded with Base64.
+pub(crate) fn generate_nonce() -> String {
+    let mut bytes = [0; NONCE_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base64::encode(bytes)
+}
+
+/// The Content-Security-Policy set on the responses of functions.
+///
+/// When a function generated a nonce for its request, the nonce is appended to the policy's
+/// `script-src` and `style-src` directives. A policy without either directive has the nonce
+/// appended to its `default-src` directive, or else gains a `script-src` directive for it.
+///
+/// A directive whose source is `'none'` is left as is: browsers ignore `'none'` alongside other
+/// sources, so appending the nonce would allow what the policy forbids.
+#[derive(Debug, Clone)]
+pub(crate) struct ContentSecurityPolicy {
+    directives: Vec<String>,
+}
+
+impl ContentSecurityPolicy {
+    pub fn new(policy: &str) -> Result<Self> {
+        if !policy.bytes().all(|b| (b' '..=b'~').contains(&b)) {
+            bail!("the policy may only contain visible ASCII characters and spaces");
+        }
+
+        let directives: Vec<_> = policy
+            .split(';')
+            .map(str::trim)
+            .filter(|directive| !directive.is_empty())
+            .map(ToString::to_string)
+            .collect();
+
+        if directives.is_empty() {
+            bail!("the policy has no directives");
+        }
+
+        Ok(Self { directives })
+    }
+
+    /// Sets the policy on a response, unless the function set its own.
+    pub fn apply(&self, res: &mut tide::Response, nonce: Option<&str>) {
+        if res.header(CONTENT_SECURITY_POLICY).is_some() {
+            return;
+        }
+
+        res.insert_header(CONTENT_SECURITY_POLICY, self.value(nonce));
+    }
+
+    fn value(&self, nonce: Option<&str>) -> String {
+        let source = match nonce {
+            Some(nonce) => format!("'nonce-{}'", nonce),
+            None => return self.directives.join("; "),
+        };
+
+        let name = |directive: &str| {
+            directive
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase()
+        };
+
+        let mut targets: Vec<_> = NONCE_DIRECTIVES.to_vec();
+        if !self
+            .directives
+            .iter()
+            .any(|directive| targets.contains(&name(directive).as_str()))
+        {
+            targets = vec!["default-src"];
+        }
+
+        let mut appended = false;
+        let mut directives: Vec<_> = self
+            .directives
+            .iter()
+            .map(|directive| {
+                if !targets.contains(&name(directive).as_str()) {
+                    return directive.clone();
+                }
+
+                appended = true;
+
+                if directive
+                    .split_whitespace()
+                    .skip(1)
+                    .any(|source| source.eq_ignore_ascii_case("'none'"))
+                {
+                    directive.clone()
+                } else {
+                    format!("{} {}", directive, source)
+                }
+            })
+            .collect();
+
+        if !appended {
+            directives.push(format!("script-src {}", source));
+        }
+
+        directives.join("; ")
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::{anyhow, bail, Result};
+use async_std::sync::Mutex;
+use async_std_resolver::AsyncStdResolver;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The cluster domain used by Kubernetes unless the cluster is configured otherwise.
+const DEFAULT_CLUSTER_DOMAIN: &str = "cluster.local";
+
+/// An endpoint of a service resolved by service discovery.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceEndpoint {
+    /// The host name or address of the endpoint.
+    pub host: String,
+    /// The port of the endpoint.
+    pub port: u16,
+    /// The priority of the endpoint; endpoints with a lower value should be preferred.
+    pub priority: u16,
+    /// The relative weight of the endpoint among endpoints with the same priority.
+    pub weight: u16,
+}
+
+impl ServiceEndpoint {
+    /// Creates an endpoint with the default priority and weight.
+    pub fn new<T: Into<String>>(host: T, port: u16) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            priority: 0,
+            weight: 0,
+        }
+    }
+}
+
+/// Resolves the names of services used by functions to their endpoints.
+///
+/// Functions name services rather than hosts, so the provider decides where functions connect.
+#[async_trait::async_trait]
+pub trait DiscoveryProvider: Send + Sync {
+    /// Resolves a service to its endpoints.
+    ///
+    /// An error is returned if the service is not known to the provider.
+    async fn resolve(&self, service: &str) -> Result<Vec<ServiceEndpoint>>;
+}
+
+/// A discovery provider that resolves services to a fixed set of endpoints.
+///
+/// Services without endpoints are passed to the fallback provider, if there is one.
+#[derive(Default)]
+pub struct StaticDiscovery {
+    services: HashMap<String, Vec<ServiceEndpoint>>,
+    fallback: Option<Arc<dyn DiscoveryProvider>>,
+}
+
+impl StaticDiscovery {
+    /// Creates a new static discovery provider with no services.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an endpoint to a service.
+    pub fn endpoint<T: Into<String>>(mut self, service: T, endpoint: ServiceEndpoint) -> Self {
+        self.services
+            .entry(service.into())
+            .or_default()
+            .push(endpoint);
+        self
+    }
+
+    /// Sets the provider that resolves services without static endpoints.
+    pub fn fallback(mut self, provider: Arc<dyn DiscoveryProvider>) -> Self {
+        self.fallback = Some(provider);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl DiscoveryProvider for StaticDiscovery {
+    async fn resolve(&self, service: &str) -> Result<Vec<ServiceEndpoint>> {
+        if let Some(endpoints) = self.services.get(service) {
+            return Ok(endpoints.clone());
+        }
+
+        match &self.fallback {
+            Some(fallback) => fallback.resolve(service).await,
+            None => bail!("service `{}` is not known", service),
+        }
+    }
+}
+
+enum SrvQuery {
+    Domain(String),
+    Kubernetes {
+        namespace: String,
+        port: String,
+        cluster_domain: String,
+    },
+}
+
+/// A discovery provider that resolves services with DNS SRV records.
+///
+/// Records are cached by the resolver for their TTL, so endpoints follow changes to DNS.
+pub struct DnsDiscovery {
+    query: SrvQuery,
+    resolver: Mutex<Option<AsyncStdResolver>>,
+}
+
+impl DnsDiscovery {
+    /// Creates a provider that resolves a service `NAME` with the SRV record `_NAME._tcp.DOMAIN`.
+    pub fn new<T: Into<String>>(domain: T) -> Self {
+        Self::with_query(SrvQuery::Domain(domain.into()))
+    }
+
+    /// Creates a provider that resolves a service `NAME` to the endpoints of the Kubernetes service
+    /// of the same name in the given namespace.
+    ///
+    /// The endpoints are read from the SRV records Kubernetes publishes for the named port of the
+    /// service, so the service must name the port. Headless services resolve to each ready pod.
+    pub fn kubernetes<T: Into<String>, U: Into<String>>(namespace: T, port: U) -> Self {
+        Self::with_query(SrvQuery::Kubernetes {
+            namespace: namespace.into(),
+            port: port.into(),
+            cluster_domain: DEFAULT_CLUSTER_DOMAIN.to_string(),
+        })
+    }
+
+    fn with_query(query: SrvQuery) -> Self {
+        Self {
+            query,
+            resolver: Mutex::new(None),
+        }
+    }
+
+    fn record_name(&self, service: &str) -> Result<String> {
+        // Names are limited to a single DNS label so that functions can't query outside the domain
+        if service.is_empty()
+            || service.len() > 63
+            || service.starts_with('-')
+            || !service
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            bail!("invalid service name `{}`", service);
+        }
+
+        let service = service.to_ascii_lowercase();
+
+        Ok(match &self.query {
+            SrvQuery::Domain(domain) => format!("_{}._tcp.{}.", service, domain),
+            SrvQuery::Kubernetes {
+                namespace,
+                port,
+                cluster_domain,
+            } => format!(
+                "_{}._tcp.{}.{}.svc.{}.",
+                port, service, namespace, cluster_domain
+            ),
+        })
+    }
+
+    async fn resolver(&self) -> Result<AsyncStdResolver> {
+        let mut resolver = self.resolver.lock().await;
+
+        if resolver.is_none() {
+            // The resolver is created on first use as reading the system configuration is asynchronous
+            *resolver = Some(
+                async_std_resolver::resolver_from_system_conf()
+                    .await
+                    .map_err(|e| anyhow!("failed to create DNS resolver: {}", e))?,
+            );
+        }
+
+        Ok(resolver.clone().unwrap())
+    }
+}
+
+#[async_trait::async_trait]
+impl DiscoveryProvider for DnsDiscovery {
+    async fn resolve(&self, service: &str) -> Result<Vec<ServiceEndpoint>> {
+        let name = self.record_name(service)?;
+
+        let lookup = self
+            .resolver()
+            .await?
+            .srv_lookup(name.as_str())
+            .await
+            .map_err(|e| anyhow!("failed to resolve service `{}`: {}", service, e))?;
+
+        let mut endpoints: Vec<_> = lookup
+            .iter()
+            .map(|srv| ServiceEndpoint {
+                host: srv.target().to_utf8().trim_end_matches('.').to_string(),
+                port: srv.port(),
+                priority: srv.priority(),
+                weight: srv.weight(),
+            })
+            .collect();
+
+        endpoints.sort_by_key(|e| e.priority);
+        Ok(endpoints)
+    }
+}
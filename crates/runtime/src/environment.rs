@@ -0,0 +1,157 @@
+use crate::error::ServerError;
+use anyhow::{Context, Result};
+use async_std::sync::Mutex as AsyncMutex;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Provides environment variables to the runtime server.
+pub trait EnvironmentProvider: Send + Sync {
+    /// Gets the environment variable of the given name.
+    fn var(&self, name: &str) -> Result<String>;
+
+    /// Determines if the environment variable of the given name holds a secret.
+    ///
+    /// Secret variables are still given to functions in their environment, but are excluded from
+    /// the configuration functions can read with `wasmtime_functions::config::all`.
+    ///
+    /// Every variable is a secret by default; providers expose variables to the configuration by
+    /// returning `false` for the names they know are safe to read.
+    fn is_secret(&self, _name: &str) -> bool {
+        true
+    }
+}
+
+/// Resolves and caches the environment variables declared by a module.
+///
+/// Each variable is resolved by at most one request at a time; concurrent requests for a variable
+/// that is being resolved wait for its value rather than asking the provider again.
+pub struct Environment {
+    provider: Arc<dyn EnvironmentProvider>,
+    names: Vec<String>,
+    ttl: Option<Duration>,
+    values: Mutex<HashMap<String, (String, Instant)>>,
+    resolving: HashMap<String, AsyncMutex<()>>,
+}
+
+impl Environment {
+    pub fn new(
+        provider: Arc<dyn EnvironmentProvider>,
+        names: Vec<String>,
+        ttl: Option<Duration>,
+    ) -> Self {
+        let resolving = names
+            .iter()
+            .map(|name| (name.clone(), AsyncMutex::new(())))
+            .collect();
+
+        Self {
+            provider,
+            names,
+            ttl,
+            values: Mutex::new(HashMap::new()),
+            resolving,
+        }
+    }
+
+    /// Resolves every variable.
+    ///
+    /// Providers may block, so each variable is resolved on a blocking thread.
+    pub async fn resolve_all(&self) -> Result<(), ServerError> {
+        for name in &self.names {
+            let provider = self.provider.clone();
+            let n = name.clone();
+            let value = async_std::task::spawn_blocking(move || provider.var(&n))
+                .await
+                .map_err(|source| ServerError::MissingVar {
+                    name: name.clone(),
+                    source,
+                })?;
+            self.insert(name, value);
+        }
+
+        Ok(())
+    }
+
+    /// Determines if every variable has a cached value that has not expired.
+    pub fn is_resolved(&self) -> bool {
+        let values = self.values.lock().unwrap();
+        self.names.iter().all(|name| {
+            values
+                .get(name)
+                .map(|(_, resolved)| !self.is_expired(*resolved))
+                .unwrap_or(false)
+        })
+    }
+
+    /// Gets the values of the variables in scope, resolving any that are missing or expired.
+    ///
+    /// When scoped, only the declared variables in the scope are resolved; otherwise, every
+    /// declared variable is.
+    pub async fn vars(&self, scope: Option<&[String]>) -> Result<Vec<(String, String)>> {
+        let names = self
+            .names
+            .iter()
+            .filter(|name| scope.map(|scope| scope.contains(name)).unwrap_or(true));
+
+        let mut vars = Vec::with_capacity(self.names.len());
+
+        for name in names {
+            let value = match self.cached(name) {
+                Some(value) => value,
+                None => self.resolve(name).await?,
+            };
+
+            vars.push((name.clone(), value));
+        }
+
+        Ok(vars)
+    }
+
+    fn cached(&self, name: &str) -> Option<String> {
+        self.values
+            .lock()
+            .unwrap()
+            .get(name)
+            .filter(|(_, resolved)| !self.is_expired(*resolved))
+            .map(|(value, _)| value.clone())
+    }
+
+    async fn resolve(&self, name: &str) -> Result<String> {
+        let _resolving = self.resolving[name].lock().await;
+
+        // Another request may have resolved the variable while this one waited
+        if let Some(value) = self.cached(name) {
+            return Ok(value);
+        }
+
+        log::info!("Resolving environment variable '{}'.", name);
+
+        let provider = self.provider.clone();
+        let n = name.to_string();
+        let value = async_std::task::spawn_blocking(move || provider.var(&n))
+            .await
+            .with_context(|| format!("failed to resolve environment variable '{}'", name))?;
+
+        self.insert(name, value.clone());
+        Ok(value)
+    }
+
+    /// Determines if the variable of the given name holds a secret.
+    pub fn is_secret(&self, name: &str) -> bool {
+        self.provider.is_secret(name)
+    }
+
+    fn insert(&self, name: &str, value: String) {
+        self.values
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), (value, Instant::now()));
+    }
+
+    fn is_expired(&self, resolved: Instant) -> bool {
+        self.ttl
+            .map(|ttl| resolved.elapsed() >= ttl)
+            .unwrap_or(false)
+    }
+}
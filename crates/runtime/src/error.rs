@@ -0,0 +1,360 @@
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tide::{Middleware, Next, Request};
+
+/// Represents an error that occurred while creating or running a server.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ServerError {
+    /// The module is not a valid Wasmtime Functions module.
+    ///
+    /// This includes modules with invalid function metadata and modules that contain no functions.
+    InvalidModule(anyhow::Error),
+    /// An environment variable declared by the module could not be resolved.
+    MissingVar {
+        /// The name of the environment variable.
+        name: String,
+        /// The error returned by the environment provider.
+        source: anyhow::Error,
+    },
+    /// The module's signature could not be verified against the trusted keys.
+    Signature(anyhow::Error),
+    /// The module was built for a version of the host interface this runtime does not support.
+    InterfaceVersion {
+        /// The version of the host interface the module was built for.
+        ///
+        /// This is `None` if the module was built with an SDK that predates interface versioning.
+        module: Option<u32>,
+        /// The oldest version of the host interface supported by the runtime.
+        min: u32,
+        /// The version of the host interface implemented by the runtime.
+        max: u32,
+    },
+    /// The module failed to compile or link.
+    Compile(anyhow::Error),
+    /// A directory to preopen for functions could not be opened.
+    Preopen {
+        /// The host path of the directory.
+        path: PathBuf,
+        /// The underlying I/O error.
+        source: io::Error,
+    },
+    /// The module failed to instantiate while warming up the server.
+    Warmup(anyhow::Error),
+    /// The client used for the outbound gRPC calls of functions could not be created.
+    Grpc(anyhow::Error),
+    /// The templates functions render responses with could not be loaded.
+    Templates(anyhow::Error),
+    /// The translation bundles functions translate messages with could not be loaded.
+    Translations(anyhow::Error),
+    /// The Content-Security-Policy set on the responses of functions is invalid.
+    ContentSecurityPolicy(anyhow::Error),
+    /// A concurrency limit of zero was given, which would reject every invocation.
+    ConcurrencyLimit(anyhow::Error),
+    /// The server failed to bind to the requested address.
+    Bind {
+        /// The address the server attempted to bind to.
+        addr: SocketAddr,
+        /// The underlying I/O error.
+        source: io::Error,
+    },
+    /// The server failed to accept connections.
+    Accept(io::Error),
+    /// No module is mounted at the given path prefix.
+    UnknownMount(String),
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidModule(e) => write!(f, "invalid module: {}", e),
+            Self::MissingVar { name, .. } => {
+                write!(f, "failed to resolve environment variable '{}'", name)
+            }
+            Self::Signature(e) => write!(f, "failed to verify module signature: {}", e),
+            Self::InterfaceVersion {
+                module: None,
+                min,
+                max,
+            } => write!(
+                f,
+                "module predates host interface versioning; this runtime supports host interface versions {} to {}",
+                min, max
+            ),
+            Self::InterfaceVersion {
+                module: Some(version),
+                max,
+                ..
+            } if version > max => write!(
+                f,
+                "module requires host interface version {} but this runtime only supports up to version {}; upgrade the runtime",
+                version, max
+            ),
+            Self::InterfaceVersion {
+                module: Some(version),
+                min,
+                ..
+            } => write!(
+                f,
+                "module was built for host interface version {} but this runtime requires at least version {}; rebuild the module with a newer `wasmtime-functions` crate",
+                version, min
+            ),
+            Self::Compile(e) => write!(f, "failed to compile module: {}", e),
+            Self::Warmup(e) => write!(f, "failed to warm up module: {}", e),
+            Self::Grpc(e) => write!(f, "failed to create gRPC client: {}", e),
+            Self::Templates(e) => write!(f, "failed to load templates: {}", e),
+            Self::Translations(e) => write!(f, "failed to load translations: {}", e),
+            Self::ContentSecurityPolicy(e) => {
+                write!(f, "invalid Content-Security-Policy: {}", e)
+            }
+            Self::ConcurrencyLimit(e) => write!(f, "invalid concurrency limit: {}", e),
+            Self::Preopen { path, .. } => {
+                write!(f, "failed to open directory '{}'", path.display())
+            }
+            Self::Bind { addr, .. } => write!(f, "failed to bind to address '{}'", addr),
+            Self::Accept(_) => write!(f, "failed to accept connections"),
+            Self::UnknownMount(prefix) => write!(f, "no module is mounted at prefix '{}'", prefix),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidModule(_)
+            | Self::Signature(_)
+            | Self::InterfaceVersion { .. }
+            | Self::Compile(_)
+            | Self::Warmup(_)
+            | Self::Grpc(_)
+            | Self::Templates(_)
+            | Self::Translations(_)
+            | Self::ContentSecurityPolicy(_)
+            | Self::ConcurrencyLimit(_)
+            | Self::UnknownMount(_) => None,
+            Self::MissingVar { source, .. } => Some(&**source),
+            Self::Preopen { source, .. } | Self::Bind { source, .. } | Self::Accept(source) => {
+                Some(source)
+            }
+        }
+    }
+}
+
+/// Represents an error that occurred while invoking a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InvocationError {
+    /// The function returned a handle that does not refer to a response.
+    InvalidResponseHandle {
+        /// The name of the function.
+        function: String,
+        /// The handle returned by the function.
+        handle: u32,
+    },
+    /// The function returned the handle of a response that was already returned.
+    ///
+    /// This happens when an instance reused with session affinity returns a response handle
+    /// it returned from a previous invocation.
+    ResponseAlreadyReturned {
+        /// The name of the function.
+        function: String,
+        /// The handle returned by the function.
+        handle: u32,
+    },
+}
+
+impl fmt::Display for InvocationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidResponseHandle { function, handle } => write!(
+                f,
+                "function '{}' returned {} which is not a valid response handle",
+                function, handle
+            ),
+            Self::ResponseAlreadyReturned { function, handle } => write!(
+                f,
+                "function '{}' returned response handle {} which was already returned",
+                function, handle
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InvocationError {}
+
+/// Represents an error response generated by the host rather than by a function.
+///
+/// Host errors include timeouts, traps, functions that fail to return a response, requests
+/// that fail validation, and requests that do not match any route.
+#[derive(Debug)]
+pub struct HostError<'a> {
+    /// The HTTP status code of the error.
+    pub status: u16,
+    /// The standard reason phrase of the status code.
+    pub title: &'a str,
+    /// A message describing the error that is safe to present to clients, if any.
+    pub message: Option<&'a str>,
+    /// The internal details of the error, if any.
+    ///
+    /// The details may contain information about the module that should not be presented to clients.
+    pub details: Option<String>,
+}
+
+/// Represents the body of a rendered host error.
+#[derive(Debug, Clone)]
+pub struct RenderedError {
+    /// The content type of the body.
+    pub content_type: String,
+    /// The body of the response.
+    pub body: Vec<u8>,
+}
+
+/// Implemented by types that render host errors into response bodies.
+pub trait ErrorRenderer: Send + Sync {
+    /// Renders the given host error.
+    fn render(&self, error: &HostError) -> RenderedError;
+}
+
+impl<F> ErrorRenderer for F
+where
+    F: Fn(&HostError) -> RenderedError + Send + Sync,
+{
+    fn render(&self, error: &HostError) -> RenderedError {
+        self(error)
+    }
+}
+
+/// An error renderer that substitutes the error into a template.
+///
+/// The template may contain the `{status}`, `{title}`, and `{message}` placeholders.
+/// If the error has no client-safe message, `{message}` is replaced with the title.
+///
+/// Substituted values are escaped when the content type is HTML, XML, or JSON.
+pub struct TemplateErrorRenderer {
+    content_type: String,
+    template: String,
+}
+
+impl TemplateErrorRenderer {
+    /// Creates a new template error renderer with the given content type and template.
+    pub fn new<T: Into<String>, U: Into<String>>(content_type: T, template: U) -> Self {
+        Self {
+            content_type: content_type.into(),
+            template: template.into(),
+        }
+    }
+
+    fn escape(&self, s: &str) -> String {
+        if self.content_type.contains("json") {
+            let s = serde_json::to_string(s).unwrap();
+            s[1..s.len() - 1].to_string()
+        } else if self.content_type.contains("html") || self.content_type.contains("xml") {
+            s.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+                .replace('\'', "&#39;")
+        } else {
+            s.to_string()
+        }
+    }
+}
+
+impl ErrorRenderer for TemplateErrorRenderer {
+    fn render(&self, error: &HostError) -> RenderedError {
+        RenderedError {
+            content_type: self.content_type.clone(),
+            body: self
+                .template
+                .replace("{status}", &error.status.to_string())
+                .replace("{title}", &self.escape(error.title))
+                .replace(
+                    "{message}",
+                    &self.escape(error.message.unwrap_or(error.title)),
+                )
+                .into_bytes(),
+        }
+    }
+}
+
+/// An error renderer that renders host errors as RFC 7807 problem details.
+///
+/// The body is an `application/problem+json` object with the `about:blank` problem type, the
+/// `title` and `status` of the error, and its client-safe message, if any, as the `detail`.
+/// This matches the responses of functions that return `wasmtime_functions::Problem`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProblemErrorRenderer;
+
+impl ErrorRenderer for ProblemErrorRenderer {
+    fn render(&self, error: &HostError) -> RenderedError {
+        let mut problem = serde_json::json!({
+            "type": "about:blank",
+            "title": error.title,
+            "status": error.status,
+        });
+
+        if let Some(message) = error.message {
+            problem["detail"] = message.into();
+        }
+
+        RenderedError {
+            content_type: "application/problem+json".to_string(),
+            body: serde_json::to_vec(&problem).unwrap(),
+        }
+    }
+}
+
+/// Marks a response as being returned by a function.
+pub struct FunctionResponse;
+
+/// A middleware that renders the bodies of host error responses.
+pub struct ErrorMiddleware(Arc<dyn ErrorRenderer>);
+
+impl ErrorMiddleware {
+    pub fn new(renderer: Arc<dyn ErrorRenderer>) -> Self {
+        Self(renderer)
+    }
+
+    async fn render<'a, State: Clone + Send + Sync + 'static>(
+        &'a self,
+        req: Request<State>,
+        next: Next<'a, State>,
+    ) -> tide::Result {
+        let mut res = next.run(req).await;
+
+        let status = res.status();
+        if res.ext::<FunctionResponse>().is_some()
+            || !(status.is_client_error() || status.is_server_error())
+        {
+            return Ok(res);
+        }
+
+        // Host error responses with a body contain a client-safe message
+        let message = res.take_body().into_string().await.unwrap_or_default();
+
+        let rendered = self.0.render(&HostError {
+            status: status.into(),
+            title: status.canonical_reason(),
+            message: if message.is_empty() {
+                None
+            } else {
+                Some(&message)
+            },
+            details: res.error().map(|e| format!("{:?}", e)),
+        });
+
+        res.set_body(rendered.body);
+        res.insert_header(tide::http::headers::CONTENT_TYPE, rendered.content_type);
+        Ok(res)
+    }
+}
+
+#[async_trait::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for ErrorMiddleware {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        self.render(req, next).await
+    }
+}
@@ -0,0 +1,69 @@
+use tide::http::headers::{ETAG, IF_NONE_MATCH};
+use tide::http::Method;
+use tide::{Body, Middleware, Next, Request, StatusCode};
+
+/// A middleware that generates strong entity tags for buffered responses.
+///
+/// The middleware is applied to the routes of functions declared with the `etag` option, or to every
+/// function route when enabled with [`ServerBuilder::etags`](crate::ServerBuilder::etags).
+///
+/// Requests with a matching `If-None-Match` header receive a `304 Not Modified` response
+/// without the body being sent.
+#[derive(Debug, Default, Clone)]
+pub struct ETagMiddleware;
+
+impl ETagMiddleware {
+    async fn etag<'a, State: Clone + Send + Sync + 'static>(
+        &'a self,
+        req: Request<State>,
+        next: Next<'a, State>,
+    ) -> tide::Result {
+        let method = req.method();
+        if method != Method::Get && method != Method::Head {
+            return Ok(next.run(req).await);
+        }
+
+        let if_none_match = req.header(IF_NONE_MATCH).map(|v| v.as_str().to_string());
+
+        let mut res = next.run(req).await;
+
+        // Only tag successful responses with a known length; the function may have set its own tag
+        if res.status() != StatusCode::Ok || res.len().is_none() || res.header(ETAG).is_some() {
+            return Ok(res);
+        }
+
+        let body = res.take_body().into_bytes().await?;
+
+        // The tag must be stable across processes and builds, so it is a SHA-256 hash of the body
+        let etag = format!("\"{}\"", crate::signing::digest(&body));
+
+        res.insert_header(ETAG, etag.as_str());
+
+        if if_none_match
+            .map(|v| Self::matches(&v, &etag))
+            .unwrap_or(false)
+        {
+            res.set_status(StatusCode::NotModified);
+            res.set_body(Body::empty());
+            return Ok(res);
+        }
+
+        res.set_body(body);
+        Ok(res)
+    }
+
+    fn matches(if_none_match: &str, etag: &str) -> bool {
+        // `If-None-Match` uses the weak comparison function, so ignore any weak indicators
+        if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+    }
+}
+
+#[async_trait::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for ETagMiddleware {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        self.etag(req, next).await
+    }
+}
@@ -0,0 +1,224 @@
+use crate::resilience::{Outcome, Resilience};
+use anyhow::{anyhow, bail, Result};
+use http_types::{Method, Url};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// A host that functions are allowed to send outbound HTTP requests to.
+///
+/// A host is of the form `HOST[:PORT]`, where `HOST` may start with `*.` to allow every subdomain
+/// of the host. Without a port, only the default port of the request's scheme is allowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowedHost {
+    host: String,
+    wildcard: bool,
+    port: Option<u16>,
+}
+
+impl AllowedHost {
+    fn allows(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return false,
+        };
+
+        let host_allowed = if self.wildcard {
+            host.strip_suffix(&self.host)
+                .map(|prefix| prefix.len() > 1 && prefix.ends_with('.'))
+                .unwrap_or(false)
+        } else {
+            host == self.host
+        };
+
+        let port_allowed = match self.port {
+            Some(port) => url.port_or_known_default() == Some(port),
+            None => url.port().is_none(),
+        };
+
+        host_allowed && port_allowed
+    }
+}
+
+impl FromStr for AllowedHost {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (host, port) = match s.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                Some(
+                    port.parse()
+                        .map_err(|_| anyhow!("invalid port in allowed host `{}`", s))?,
+                ),
+            ),
+            None => (s, None),
+        };
+
+        let (host, wildcard) = match host.strip_prefix("*.") {
+            Some(host) => (host, true),
+            None => (host, false),
+        };
+
+        if host.is_empty() || host.contains(|c: char| c == '*' || c == '/' || c.is_whitespace()) {
+            bail!("invalid allowed host `{}`", s);
+        }
+
+        Ok(Self {
+            host: host.to_ascii_lowercase(),
+            wildcard,
+            port,
+        })
+    }
+}
+
+impl fmt::Display for AllowedHost {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.wildcard {
+            write!(f, "*.")?;
+        }
+
+        write!(f, "{}", self.host)?;
+
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The response to an outbound HTTP request.
+pub struct FetchResponse {
+    /// The status code of the response.
+    pub status: u16,
+    /// The headers of the response.
+    pub headers: Vec<(String, String)>,
+    /// The body of the response.
+    pub body: Vec<u8>,
+}
+
+/// Sends the outbound HTTP requests of functions.
+///
+/// Requests are only passed to the provider once they have been checked against the allowed hosts.
+#[async_trait::async_trait]
+pub trait FetchProvider: Send + Sync {
+    /// Sends an outbound HTTP request.
+    async fn send(
+        &self,
+        method: &str,
+        uri: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<FetchResponse>;
+}
+
+/// Sends outbound HTTP requests for functions, enforcing the allowed hosts.
+///
+/// The allowed hosts are passed to each request so that they can be changed while the server is serving.
+pub struct Fetch {
+    provider: Arc<dyn FetchProvider>,
+    resilience: Arc<Resilience>,
+}
+
+impl Fetch {
+    pub fn new(provider: Option<Arc<dyn FetchProvider>>, resilience: Arc<Resilience>) -> Self {
+        Self {
+            provider: provider.unwrap_or_else(|| Arc::new(HttpClient(surf::Client::new()))),
+            resilience,
+        }
+    }
+
+    pub async fn send(
+        &self,
+        allowed: &[AllowedHost],
+        method: &str,
+        uri: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<FetchResponse> {
+        let url = Url::parse(uri)?;
+
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("unsupported scheme `{}`", url.scheme());
+        }
+
+        // Redirects are not followed so that a response can't redirect a function to a host that isn't allowed
+        if !allowed.iter().any(|host| host.allows(&url)) {
+            bail!(
+                "outbound requests to `{}` are not allowed",
+                url.host_str().unwrap_or_default()
+            );
+        }
+
+        let destination = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        // Only requests that can safely be repeated are retried
+        let idempotent = matches!(
+            method.to_ascii_uppercase().as_str(),
+            "GET" | "HEAD" | "OPTIONS" | "TRACE" | "PUT" | "DELETE"
+        );
+
+        self.resilience
+            .call(
+                "fetch",
+                &destination,
+                || self.provider.send(method, uri, headers, body),
+                |res| match res {
+                    Ok(res) if res.status < 500 => Outcome::Success,
+                    Ok(res) if idempotent && matches!(res.status, 502 | 503 | 504) => {
+                        Outcome::RetryableFailure
+                    }
+                    Err(_) if idempotent => Outcome::RetryableFailure,
+                    _ => Outcome::Failure,
+                },
+                |message| anyhow!(message),
+            )
+            .await
+    }
+}
+
+struct HttpClient(surf::Client);
+
+#[async_trait::async_trait]
+impl FetchProvider for HttpClient {
+    async fn send(
+        &self,
+        method: &str,
+        uri: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<FetchResponse> {
+        let url = Url::parse(uri)?;
+
+        let method: Method = method
+            .parse()
+            .map_err(|e: http_types::Error| e.into_inner())?;
+
+        let mut req = surf::Request::new(method, url);
+        for (name, value) in headers {
+            req.append_header(*name, *value);
+        }
+        req.set_body(body.to_vec());
+
+        let mut res = self.0.send(req).await.map_err(|e| e.into_inner())?;
+
+        let headers = res
+            .iter()
+            .flat_map(|(name, values)| {
+                values
+                    .iter()
+                    .map(move |v| (name.as_str().to_string(), v.as_str().to_string()))
+            })
+            .collect();
+
+        Ok(FetchResponse {
+            status: res.status().into(),
+            headers,
+            body: res.body_bytes().await.map_err(|e| e.into_inner())?,
+        })
+    }
+}
@@ -0,0 +1,326 @@
+use crate::resilience::{Outcome, Resilience};
+use anyhow::{anyhow, Result};
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::{AsciiMetadataKey, AsciiMetadataValue, KeyAndValueRef};
+use tonic::transport::Channel;
+
+/// The deadline of a gRPC call when the function doesn't set one.
+const DEFAULT_GRPC_DEADLINE_SECS: u64 = 30;
+
+// The gRPC status codes used by the host itself
+const UNKNOWN: u32 = 2;
+const INVALID_ARGUMENT: u32 = 3;
+const DEADLINE_EXCEEDED: u32 = 4;
+pub const PERMISSION_DENIED: u32 = 7;
+const INTERNAL: u32 = 13;
+const UNAVAILABLE: u32 = 14;
+
+/// The response to a successful unary gRPC call.
+pub struct GrpcResponse {
+    /// The serialized protobuf message of the response.
+    pub payload: Vec<u8>,
+    /// The ASCII metadata of the response, including its trailers.
+    pub metadata: Vec<(String, String)>,
+}
+
+/// The status of a failed gRPC call.
+#[derive(Debug, Clone)]
+pub struct GrpcStatus {
+    /// The gRPC status code.
+    pub code: u32,
+    /// The message describing the failure.
+    pub message: String,
+}
+
+impl GrpcStatus {
+    /// Creates a new status with the given code and message.
+    pub fn new<T: Into<String>>(code: u32, message: T) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Sends the outbound gRPC calls of functions.
+///
+/// Calls are only passed to the provider once the service has been resolved to one of the host's targets.
+#[async_trait::async_trait]
+pub trait GrpcProvider: Send + Sync {
+    /// Makes a unary gRPC call.
+    ///
+    /// The target is the URI of the server and the path is of the form `/PACKAGE.SERVICE/METHOD`.
+    async fn call(
+        &self,
+        target: &str,
+        path: &str,
+        metadata: &[(&str, &str)],
+        payload: &[u8],
+        deadline: Duration,
+    ) -> Result<GrpcResponse, GrpcStatus>;
+}
+
+/// Sends outbound gRPC calls for functions, resolving services to the host's targets.
+pub struct Grpc {
+    services: HashMap<String, String>,
+    provider: Arc<dyn GrpcProvider>,
+    resilience: Arc<Resilience>,
+}
+
+impl Grpc {
+    pub fn new(
+        services: HashMap<String, String>,
+        provider: Option<Arc<dyn GrpcProvider>>,
+        resilience: Arc<Resilience>,
+    ) -> Result<Self> {
+        Ok(Self {
+            services,
+            resilience,
+            provider: match provider {
+                Some(provider) => provider,
+                None => TonicClient::shared()?,
+            },
+        })
+    }
+
+    pub async fn call(
+        &self,
+        service: &str,
+        method: &str,
+        metadata: &[(&str, &str)],
+        payload: &[u8],
+        deadline: Option<Duration>,
+    ) -> Result<GrpcResponse, GrpcStatus> {
+        // Functions name services rather than servers so that only the host decides where calls go
+        let target = self.services.get(service).ok_or_else(|| {
+            GrpcStatus::new(
+                PERMISSION_DENIED,
+                format!("calls to service `{}` are not allowed", service),
+            )
+        })?;
+
+        // The method is part of the path, so it must not be able to reach another service's methods
+        if !is_identifier(method) {
+            return Err(GrpcStatus::new(
+                INVALID_ARGUMENT,
+                format!("invalid method name `{}`", method),
+            ));
+        }
+
+        let deadline = deadline.unwrap_or_else(|| Duration::from_secs(DEFAULT_GRPC_DEADLINE_SECS));
+        let path = format!("/{}/{}", service, method);
+
+        let expires = Instant::now() + deadline;
+
+        // The deadline covers every attempt, and is also enforced here in case the provider doesn't honor it
+        async_std::future::timeout(
+            deadline,
+            self.resilience.call(
+                "grpc",
+                service,
+                || {
+                    let remaining = expires.saturating_duration_since(Instant::now());
+                    self.provider
+                        .call(target, &path, metadata, payload, remaining)
+                },
+                |res| match res {
+                    Ok(_) => Outcome::Success,
+                    Err(status) if status.code == UNAVAILABLE => Outcome::RetryableFailure,
+                    Err(status)
+                        if matches!(status.code, UNKNOWN | DEADLINE_EXCEEDED | INTERNAL) =>
+                    {
+                        Outcome::Failure
+                    }
+                    // Other statuses are answers from the service rather than failures of it
+                    Err(_) => Outcome::Success,
+                },
+                |message| GrpcStatus::new(UNAVAILABLE, message),
+            ),
+        )
+        .await
+        .unwrap_or_else(|_| {
+            Err(GrpcStatus::new(
+                DEADLINE_EXCEEDED,
+                format!("call to `{}` exceeded its deadline", path),
+            ))
+        })
+    }
+}
+
+/// Determines if a name is a valid protobuf identifier.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Makes gRPC calls with tonic.
+///
+/// Tonic requires a Tokio runtime, so calls are made on a runtime dedicated to the client. A single
+/// client is shared by every server in the process so that each doesn't start its own runtime.
+struct TonicClient {
+    runtime: tokio::runtime::Runtime,
+    channels: Mutex<HashMap<String, Channel>>,
+}
+
+impl TonicClient {
+    /// Gets the client shared by every server, creating it on first use.
+    fn shared() -> Result<Arc<Self>> {
+        static CLIENT: OnceCell<Arc<TonicClient>> = OnceCell::new();
+
+        CLIENT
+            .get_or_try_init(|| {
+                Ok(Arc::new(Self {
+                    runtime: tokio::runtime::Builder::new_multi_thread()
+                        .thread_name("grpc-client")
+                        .enable_all()
+                        .build()
+                        .map_err(|e| anyhow!("failed to create the gRPC client runtime: {}", e))?,
+                    channels: Mutex::new(HashMap::new()),
+                }))
+            })
+            .map(Clone::clone)
+    }
+
+    async fn channel(&self, target: &str) -> Result<Channel, GrpcStatus> {
+        if let Some(channel) = self.channels.lock().unwrap().get(target) {
+            return Ok(channel.clone());
+        }
+
+        let endpoint = Channel::from_shared(target.to_string()).map_err(|e| {
+            GrpcStatus::new(INTERNAL, format!("invalid target `{}`: {}", target, e))
+        })?;
+
+        let channel = self
+            .runtime
+            .spawn(async move { endpoint.connect().await })
+            .await
+            .map_err(|e| GrpcStatus::new(INTERNAL, e.to_string()))?
+            .map_err(|e| {
+                GrpcStatus::new(
+                    UNAVAILABLE,
+                    format!("failed to connect to `{}`: {}", target, e),
+                )
+            })?;
+
+        // Channels reconnect on their own, so a connected channel is kept for the target
+        self.channels
+            .lock()
+            .unwrap()
+            .insert(target.to_string(), channel.clone());
+
+        Ok(channel)
+    }
+}
+
+#[async_trait::async_trait]
+impl GrpcProvider for TonicClient {
+    async fn call(
+        &self,
+        target: &str,
+        path: &str,
+        metadata: &[(&str, &str)],
+        payload: &[u8],
+        deadline: Duration,
+    ) -> Result<GrpcResponse, GrpcStatus> {
+        let path: PathAndQuery = path.parse().map_err(|_| {
+            GrpcStatus::new(INVALID_ARGUMENT, format!("invalid method path `{}`", path))
+        })?;
+
+        let mut request = tonic::Request::new(payload.to_vec());
+        request.set_timeout(deadline);
+
+        for (name, value) in metadata {
+            let name: AsciiMetadataKey = name.parse().map_err(|_| {
+                GrpcStatus::new(
+                    INVALID_ARGUMENT,
+                    format!("invalid metadata name `{}`", name),
+                )
+            })?;
+            let value: AsciiMetadataValue = value.parse().map_err(|_| {
+                GrpcStatus::new(
+                    INVALID_ARGUMENT,
+                    format!("invalid value for metadata `{}`", name),
+                )
+            })?;
+            request.metadata_mut().append(name, value);
+        }
+
+        let mut client = tonic::client::Grpc::new(self.channel(target).await?);
+
+        let response = self
+            .runtime
+            .spawn(async move {
+                client
+                    .ready()
+                    .await
+                    .map_err(|e| tonic::Status::unavailable(e.to_string()))?;
+                client.unary(request, path, RawCodec).await
+            })
+            .await
+            .map_err(|e| GrpcStatus::new(INTERNAL, e.to_string()))?
+            .map_err(|status| GrpcStatus::new(status.code() as u32, status.message()))?;
+
+        let metadata = response
+            .metadata()
+            .iter()
+            .filter_map(|entry| match entry {
+                KeyAndValueRef::Ascii(name, value) => value
+                    .to_str()
+                    .ok()
+                    .map(|value| (name.as_str().to_string(), value.to_string())),
+                KeyAndValueRef::Binary(..) => None,
+            })
+            .collect();
+
+        Ok(GrpcResponse {
+            payload: response.into_inner(),
+            metadata,
+        })
+    }
+}
+
+/// Passes serialized protobuf messages through as they are; functions do their own serialization.
+#[derive(Default)]
+struct RawCodec;
+
+impl Codec for RawCodec {
+    type Encode = Vec<u8>;
+    type Decode = Vec<u8>;
+    type Encoder = RawCodec;
+    type Decoder = RawCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        RawCodec
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        RawCodec
+    }
+}
+
+impl Encoder for RawCodec {
+    type Item = Vec<u8>;
+    type Error = tonic::Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        bytes::BufMut::put_slice(dst, &item);
+        Ok(())
+    }
+}
+
+impl Decoder for RawCodec {
+    type Item = Vec<u8>;
+    type Error = tonic::Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        let mut item = vec![0; bytes::Buf::remaining(src)];
+        bytes::Buf::copy_to_slice(src, &mut item);
+        Ok(Some(item))
+    }
+}
@@ -0,0 +1,98 @@
+use http_types::headers::{HeaderName, HeaderValues, COOKIE};
+
+/// The casing of the header names listed to functions.
+///
+/// Header names are case-insensitive and the original casing of a request's header names is not
+/// preserved by the HTTP server, so names are listed in one of these normalized forms. Lookups of
+/// a single header by name are always case-insensitive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderCase {
+    /// Names are lowercase (e.g. `content-type`), as in HTTP/2; this is the default.
+    Lower,
+    /// Each word of a name is capitalized (e.g. `Content-Type`).
+    Title,
+}
+
+impl Default for HeaderCase {
+    fn default() -> Self {
+        Self::Lower
+    }
+}
+
+/// How the values of a header repeated in a request are presented to functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateHeaders {
+    /// Each value is listed separately and a lookup by name returns the first value; this is the default.
+    Separate,
+    /// The values are combined into a single comma-separated value, as permitted by RFC 7230.
+    ///
+    /// `Cookie` headers are combined with semicolons instead, as required by RFC 6265.
+    Combine,
+}
+
+impl Default for DuplicateHeaders {
+    fn default() -> Self {
+        Self::Separate
+    }
+}
+
+/// Determines how request headers are presented to functions.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HeaderPolicy {
+    pub case: HeaderCase,
+    pub duplicates: DuplicateHeaders,
+}
+
+impl HeaderPolicy {
+    /// Gets the value of a header as seen by functions.
+    pub fn value(&self, name: &HeaderName, values: &HeaderValues) -> String {
+        match self.duplicates {
+            DuplicateHeaders::Separate => values.as_str().to_string(),
+            DuplicateHeaders::Combine => values
+                .iter()
+                .map(|v| v.as_str())
+                .collect::<Vec<_>>()
+                .join(if name == &COOKIE { "; " } else { ", " }),
+        }
+    }
+
+    /// Lists the headers of a request as seen by functions.
+    pub fn list<'a, I>(&self, headers: I) -> Vec<(String, String)>
+    where
+        I: IntoIterator<Item = (&'a HeaderName, &'a HeaderValues)>,
+    {
+        let mut list = Vec::new();
+
+        for (name, values) in headers {
+            let display = self.name(name);
+            match self.duplicates {
+                DuplicateHeaders::Separate => list.extend(
+                    values
+                        .iter()
+                        .map(|v| (display.clone(), v.as_str().to_string())),
+                ),
+                DuplicateHeaders::Combine => list.push((display, self.value(name, values))),
+            }
+        }
+
+        list
+    }
+
+    fn name(&self, name: &HeaderName) -> String {
+        match self.case {
+            HeaderCase::Lower => name.as_str().to_string(),
+            HeaderCase::Title => name
+                .as_str()
+                .split('-')
+                .map(|word| {
+                    let mut chars = word.chars();
+                    match chars.next() {
+                        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                        None => String::new(),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("-"),
+        }
+    }
+}
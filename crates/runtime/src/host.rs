@@ -1,35 +1,178 @@
+use crate::audit::CapabilityRecorder;
+use crate::blob::BlobProvider;
+use crate::cache::CookiesChanged;
+use crate::csp;
+use crate::discovery::DiscoveryProvider;
+use crate::error::InvocationError;
+use crate::fetch::Fetch;
+use crate::grpc::Grpc;
+use crate::headers::HeaderPolicy;
+use crate::i18n::{preferred_languages, Translations};
+use crate::kv::{KvProvider, HOST_KEY_PREFIX};
+use crate::multipart::{self, MultipartReader};
+use crate::reload::ServerConfig;
+use crate::server::{HostCalls, RequestExtensions};
+use crate::signing::UrlSigner;
+use crate::sql::SqlValue;
+use crate::templates::Templates;
+use crate::trace::{redact_query, summarize_args, Bytes, TraceMode, Tracer};
 use anyhow::Result;
 use http_types::cookies::SameSite;
-use std::cell::RefCell;
+use http_types::headers::{HeaderName, SET_COOKIE};
+use std::cell::{Cell, RefCell};
 use std::convert::TryFrom;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 use wasmtime::Linker;
 use wasmtime_wasi::WasiCtx;
 
 witx_bindgen_wasmtime::import!({
-    paths: ["crates/runtime/witx/functions.witx"],
-    async: ["request::body"]
+    paths: [
+        "crates/runtime/witx/functions.witx",
+        "crates/runtime/witx/sql.witx",
+        "crates/runtime/witx/fetch.witx",
+        "crates/runtime/witx/grpc.witx",
+        "crates/runtime/witx/discovery.witx",
+        "crates/runtime/witx/templates.witx",
+        "crates/runtime/witx/i18n.witx",
+        "crates/runtime/witx/signing.witx",
+        "crates/runtime/witx/cache.witx",
+        "crates/runtime/witx/config.witx"
+    ],
+    async: [
+        "request::body",
+        "request::upload_part",
+        "execute",
+        "query",
+        "send",
+        "call",
+        "resolve",
+        "app_get",
+        "app_set",
+        "app_remove"
+    ]
 });
 
 type Tables = functions::FunctionsTables<Host>;
 
+// The Unix timestamp of the last second of year 9999.
+const MAX_COOKIE_EXPIRES: i64 = 253_402_300_799;
+
+// The largest value functions may store in the app-scoped cache.
+const MAX_CACHE_VALUE_BYTES: usize = 1024 * 1024;
+
 pub struct Context {
     host: Host,
     request_handle: u32,
     tables: Tables,
+    sql: SqlHost,
+    fetch: FetchHost,
+    grpc: GrpcHost,
+    discovery: DiscoveryHost,
+    templates: TemplatesHost,
+    i18n: I18nHost,
+    signing: SigningHost,
+    cache: CacheHost,
+    config: ConfigHost,
+    tracer: Tracer,
+    capabilities: CapabilityRecorder,
+    env_scope: Option<Arc<Vec<String>>>,
+    memory_growth_streak: u32,
     wasi: WasiCtx,
 }
 
+/// Represents the value of a setting visible to functions.
+#[derive(Debug, Clone)]
+pub enum Setting {
+    Text(String),
+    Integer(i64),
+    Boolean(bool),
+    Duration(Duration),
+}
+
 impl Context {
-    pub fn new(req: crate::server::Request, wasi: WasiCtx) -> Self {
+    pub fn new(
+        req: Option<crate::server::Request>,
+        sql: Option<Arc<crate::sql::Sql>>,
+        fetch: Option<Arc<Fetch>>,
+        grpc: Option<Arc<Grpc>>,
+        discovery: Option<Arc<dyn DiscoveryProvider>>,
+        templates: Option<Arc<Templates>>,
+        wasi: WasiCtx,
+    ) -> Self {
         let mut tables = Tables::default();
 
         // Insert a placeholder request resource
         let request_handle = tables.request_table.insert(Request);
 
+        let tracer = Tracer::default();
+        let capabilities = CapabilityRecorder::default();
+        capabilities.set_request(req.as_ref());
+
         Self {
-            host: Host(req),
+            host: Host {
+                request: req,
+                headers: HeaderPolicy::default(),
+                blobs: None,
+                multipart: None,
+                translations: None,
+                nonce: None,
+                abort: None,
+                tracer: tracer.clone(),
+                capabilities: capabilities.clone(),
+            },
             request_handle,
             tables,
+            sql: SqlHost {
+                sql,
+                function: Arc::new(String::new()),
+                calls: 0,
+                tracer: tracer.clone(),
+                capabilities: capabilities.clone(),
+            },
+            fetch: FetchHost {
+                fetch,
+                server_config: None,
+                calls: 0,
+                tracer: tracer.clone(),
+                capabilities: capabilities.clone(),
+            },
+            grpc: GrpcHost {
+                grpc,
+                calls: 0,
+                tracer: tracer.clone(),
+                capabilities: capabilities.clone(),
+            },
+            discovery: DiscoveryHost {
+                provider: discovery,
+                tracer: tracer.clone(),
+            },
+            templates: TemplatesHost {
+                templates,
+                tracer: tracer.clone(),
+            },
+            i18n: I18nHost {
+                translations: None,
+                tracer: tracer.clone(),
+            },
+            signing: SigningHost {
+                signer: None,
+                tracer: tracer.clone(),
+            },
+            cache: CacheHost {
+                provider: None,
+                tracer: tracer.clone(),
+                capabilities: capabilities.clone(),
+            },
+            config: ConfigHost {
+                tracer: tracer.clone(),
+                ..Default::default()
+            },
+            tracer,
+            capabilities,
+            env_scope: None,
+            memory_growth_streak: 0,
             wasi,
         }
     }
@@ -38,17 +181,153 @@ impl Context {
         self.request_handle
     }
 
-    pub fn take_response(&self, handle: u32) -> Option<tide::Response> {
-        self.tables.response_table.get(handle).map(|r| {
-            let mut res = r.inner.take().unwrap();
-            res.set_body(r.body.take());
-            res
-        })
+    /// Gets the environment variables the instance was scoped to, or `None` if it has every variable.
+    pub fn env_scope(&self) -> Option<&Arc<Vec<String>>> {
+        self.env_scope.as_ref()
+    }
+
+    pub fn set_request(&mut self, req: crate::server::Request) {
+        self.capabilities.set_request(Some(&req));
+        self.host.request = Some(req);
+        self.host.multipart = None;
+        self.host.nonce = None;
+        self.renew_request_handle();
+    }
+
+    /// Inserts a new handle for the current request, which is needed to pass it to the guest again.
+    pub fn renew_request_handle(&mut self) -> u32 {
+        // The guest drops the previous request resource, so insert a new placeholder
+        self.request_handle = self.tables.request_table.insert(Request);
+        self.request_handle
+    }
+
+    pub fn set_function(&mut self, function: Arc<String>, settings: Arc<Vec<(String, Setting)>>) {
+        self.tracer.set_function(function.clone());
+        self.sql.function = function;
+        self.config.settings = settings;
+    }
+
+    /// Sets the server configuration the current request is processed with.
+    pub fn set_server_config(&mut self, config: Arc<ServerConfig>) {
+        self.fetch.server_config = Some(config.clone());
+        self.config.server_config = Some(config);
+    }
+
+    /// Gets the nonce generated for the current request, if the function asked for one.
+    pub fn nonce(&self) -> Option<&str> {
+        self.host.nonce.as_deref()
+    }
+
+    /// Takes the response the function aborted with, if it called `abort`.
+    pub fn take_abort(&mut self) -> Option<tide::Response> {
+        self.host.abort.take()
+    }
+
+    /// Records whether an invocation grew the instance's memory.
+    ///
+    /// Returns the number of consecutive invocations, up to and including this one, that grew it.
+    pub fn record_memory_growth(&mut self, grew: bool) -> u32 {
+        self.memory_growth_streak = if grew {
+            self.memory_growth_streak.saturating_add(1)
+        } else {
+            0
+        };
+        self.memory_growth_streak
+    }
+
+    /// Sets the environment variables the instance was scoped to.
+    pub fn set_env_scope(&mut self, scope: Option<Arc<Vec<String>>>) {
+        self.env_scope = scope;
+    }
+
+    /// Sets how request headers are presented to functions.
+    pub fn set_header_policy(&mut self, policy: HeaderPolicy) {
+        self.host.headers = policy;
+    }
+
+    /// Sets the provider that stores the request parts functions upload.
+    pub fn set_blob_provider(&mut self, provider: Option<Arc<dyn BlobProvider>>) {
+        self.host.blobs = provider;
+    }
+
+    /// Sets the translation bundles functions negotiate locales and translate messages with.
+    pub fn set_translations(&mut self, translations: Option<Arc<Translations>>) {
+        self.host.translations = translations.clone();
+        self.i18n.translations = translations;
+    }
+
+    /// Sets the signer functions sign URLs with.
+    pub fn set_url_signer(&mut self, signer: Option<Arc<UrlSigner>>) {
+        self.signing.signer = signer;
+    }
+
+    /// Sets the provider of the app-scoped cache shared by every instance.
+    pub fn set_kv_provider(&mut self, provider: Option<Arc<dyn KvProvider>>) {
+        self.cache.provider = provider;
+    }
+
+    /// Sets how the host calls of the current request are traced.
+    pub fn set_tracing(&mut self, mode: TraceMode) {
+        self.tracer.set_mode(mode);
+    }
+
+    /// Completes the tracing of the current request, logging any held calls if it failed.
+    pub fn finish_tracing(&mut self, failed: bool) {
+        self.tracer.finish(failed);
+    }
+
+    /// Sets the environment variables visible to functions through the configuration API.
+    ///
+    /// Secret variables should be excluded; they remain available in the WASI environment.
+    pub fn set_config_vars(&mut self, vars: Vec<(String, String)>) {
+        self.config.vars = vars;
+    }
+
+    /// Gets the number of calls made to host services by the instance.
+    pub fn host_calls(&self) -> HostCalls {
+        HostCalls {
+            sql: self.sql.calls,
+            fetch: self.fetch.calls,
+            grpc: self.grpc.calls,
+        }
+    }
+
+    pub fn take_response(
+        &self,
+        function: &str,
+        handle: u32,
+    ) -> Result<tide::Response, InvocationError> {
+        let r = self.tables.response_table.get(handle).ok_or_else(|| {
+            InvocationError::InvalidResponseHandle {
+                function: function.to_string(),
+                handle,
+            }
+        })?;
+
+        let mut res = r
+            .inner
+            .take()
+            .ok_or_else(|| InvocationError::ResponseAlreadyReturned {
+                function: function.to_string(),
+                handle,
+            })?;
+
+        res.set_body(r.body.take());
+        Ok(res)
     }
 
     pub fn add_to_linker(linker: &mut Linker<Self>) -> Result<()> {
         wasmtime_wasi::add_to_linker(linker, |s| &mut s.wasi)?;
         functions::add_functions_to_linker(linker, |s| (&mut s.host, &mut s.tables))?;
+        sql::add_sql_to_linker(linker, |s| &mut s.sql)?;
+        fetch::add_fetch_to_linker(linker, |s| &mut s.fetch)?;
+        grpc::add_grpc_to_linker(linker, |s| &mut s.grpc)?;
+        discovery::add_discovery_to_linker(linker, |s| &mut s.discovery)?;
+        templates::add_templates_to_linker(linker, |s| &mut s.templates)?;
+        i18n::add_i18n_to_linker(linker, |s| &mut s.i18n)?;
+        signing::add_signing_to_linker(linker, |s| &mut s.signing)?;
+        cache::add_cache_to_linker(linker, |s| &mut s.cache)?;
+        config::add_config_to_linker(linker, |s| &mut s.config)?;
 
         Ok(())
     }
@@ -71,6 +350,8 @@ unsafe impl Sync for Response {}
 #[derive(Debug)]
 pub struct Cookie {
     inner: RefCell<http_types::Cookie<'static>>,
+    priority: Cell<Option<&'static str>>,
+    partitioned: Cell<bool>,
 }
 
 // This is temporarily needed as a reference to the resource is captured
@@ -78,7 +359,120 @@ pub struct Cookie {
 // TODO: remove this in the future
 unsafe impl Sync for Cookie {}
 
-struct Host(crate::server::Request);
+impl Cookie {
+    // Encodes the cookie with the attributes the cookie jar doesn't support, if any are set.
+    fn encode_extended(&self) -> Option<String> {
+        if self.priority.get().is_none() && !self.partitioned.get() {
+            return None;
+        }
+
+        let mut encoded = self.inner.borrow().encoded().to_string();
+        if let Some(priority) = self.priority.get() {
+            encoded.push_str("; Priority=");
+            encoded.push_str(priority);
+        }
+        if self.partitioned.get() {
+            encoded.push_str("; Partitioned");
+        }
+        Some(encoded)
+    }
+}
+
+// Calls into the host, tracing the call when tracing is enabled for the request.
+//
+// The arguments are only summarized when tracing, and before the call in case it consumes them.
+// Values that may be sensitive, such as header and cookie values, are summarized by their length.
+macro_rules! traced {
+    ($tracer:expr, $name:literal, [$($arg:expr),*], $call:expr) => {
+        traced!($tracer, $name, [$($arg),*], $call, |result| result)
+    };
+    ($tracer:expr, $name:literal, [$($arg:expr),*], $call:expr, |$result:ident| $summary:expr) => {{
+        let start = $tracer.begin();
+        let args = start.map(|_| summarize_args(&[$(&$arg as &dyn std::fmt::Debug),*]));
+        let result = $call;
+        if let (Some(start), Some(args)) = (start, args) {
+            let $result = &result;
+            $tracer.end(start, $name, &args, &$summary);
+        }
+        result
+    }};
+}
+
+// The request is only absent for contexts that are never used to invoke a function
+struct Host {
+    request: Option<crate::server::Request>,
+    headers: HeaderPolicy,
+    blobs: Option<Arc<dyn BlobProvider>>,
+    multipart: Option<MultipartReader<http_types::Body>>,
+    translations: Option<Arc<Translations>>,
+    nonce: Option<String>,
+    abort: Option<tide::Response>,
+    tracer: Tracer,
+    capabilities: CapabilityRecorder,
+}
+
+impl Host {
+    fn request(&mut self) -> &mut crate::server::Request {
+        self.request.as_mut().expect("a request should be present")
+    }
+
+    async fn upload_part(
+        &mut self,
+        name: &str,
+        bucket: &str,
+        key: &str,
+    ) -> Result<functions::UploadedObject, String> {
+        let blobs = self
+            .blobs
+            .clone()
+            .ok_or_else(|| "uploads are not allowed".to_string())?;
+
+        self.capabilities.record(|usage| {
+            usage.buckets.insert(bucket.to_string());
+        });
+
+        // The body is read as the parts are uploaded, so parts must be uploaded in the order they were sent
+        if self.multipart.is_none() {
+            let boundary = self
+                .request()
+                .header("Content-Type")
+                .and_then(|v| multipart::boundary(v.as_str()))
+                .ok_or_else(|| "the request body is not multipart/form-data".to_string())?;
+
+            let body = self.request().take_body();
+            self.multipart = Some(MultipartReader::new(body, &boundary));
+        }
+
+        let reader = self.multipart.as_mut().unwrap();
+
+        let part = loop {
+            match reader.next_part().await.map_err(|e| e.to_string())? {
+                Some(part) if part.name == name => break part,
+                Some(_) => continue,
+                None => return Err(format!("the request has no part named `{}`", name)),
+            }
+        };
+
+        let mut writer = blobs
+            .create(bucket, key, part.content_type.as_deref())
+            .await
+            .map_err(|e| format!("{:#}", e))?;
+
+        while let Some(chunk) = reader.chunk().await.map_err(|e| e.to_string())? {
+            writer.write(&chunk).await.map_err(|e| format!("{:#}", e))?;
+        }
+
+        let object = writer.finish().await.map_err(|e| format!("{:#}", e))?;
+
+        Ok(functions::UploadedObject {
+            bucket: object.bucket,
+            key: object.key,
+            size: object.size,
+            content_type: object.content_type,
+            filename: part.filename,
+        })
+    }
+}
 
 #[witx_bindgen_wasmtime::async_trait]
 impl functions::Functions for Host {
@@ -87,123 +481,929 @@ impl functions::Functions for Host {
     type Response = Response;
 
     fn request_method(&mut self, _: &Self::Request) -> String {
-        self.0.method().to_string()
+        traced!(self.tracer, "request::method", [], {
+            self.request().method().to_string()
+        })
     }
 
     fn request_uri(&mut self, _: &Self::Request) -> String {
-        self.0.url().as_str().to_string()
+        traced!(
+            self.tracer,
+            "request::uri",
+            [],
+            self.request().url().as_str().to_string(),
+            |result| redact_query(result)
+        )
+    }
+
+    fn request_uri_parts(&mut self, _: &Self::Request) -> functions::UriParts {
+        traced!(
+            self.tracer,
+            "request::uri_parts",
+            [],
+            {
+                let url = self.request().url();
+                functions::UriParts {
+                    scheme: url.scheme().to_string(),
+                    authority: url.host_str().map(|host| match url.port() {
+                        Some(port) => format!("{}:{}", host, port),
+                        None => host.to_string(),
+                    }),
+                    path: url.path().to_string(),
+                    query: url.query().map(ToString::to_string),
+                }
+            },
+            |parts| parts.path
+        )
     }
 
     fn request_header(&mut self, _: &Self::Request, name: &str) -> Option<String> {
-        self.0.header(name).map(|v| v.as_str().to_string())
+        traced!(
+            self.tracer,
+            "request::header",
+            [name],
+            {
+                let policy = self.headers;
+                let name = HeaderName::from_str(name).ok();
+                name.and_then(|name| {
+                    self.request()
+                        .header(&name)
+                        .map(|values| policy.value(&name, values))
+                })
+            },
+            |result| result.as_ref().map(|value| Bytes(value.len()))
+        )
+    }
+
+    fn request_headers(&mut self, _: &Self::Request) -> Vec<(String, String)> {
+        traced!(
+            self.tracer,
+            "request::headers",
+            [],
+            {
+                let policy = self.headers;
+                policy.list(self.request().iter())
+            },
+            |result| result.iter().map(|(name, _)| name).collect::<Vec<_>>()
+        )
     }
 
     fn request_cookie(&mut self, _: &Self::Request, name: &str) -> Option<String> {
-        self.0.cookie(name).map(|c| c.value().to_string())
+        traced!(
+            self.tracer,
+            "request::cookie",
+            [name],
+            self.request().cookie(name).map(|c| c.value().to_string()),
+            |result| result.as_ref().map(|value| Bytes(value.len()))
+        )
     }
 
     fn request_param(&mut self, _: &Self::Request, name: &str) -> Option<String> {
-        self.0.param(name).map(ToString::to_string).ok()
+        traced!(self.tracer, "request::param", [name], {
+            self.request().param(name).map(ToString::to_string).ok()
+        })
+    }
+
+    fn request_extension(&mut self, _: &Self::Request, name: &str) -> Option<String> {
+        traced!(
+            self.tracer,
+            "request::extension",
+            [name],
+            self.request()
+                .ext::<RequestExtensions>()
+                .and_then(|extensions| extensions.get(name))
+                .map(ToString::to_string),
+            |result| result.as_ref().map(|value| Bytes(value.len()))
+        )
+    }
+
+    fn request_locale(&mut self, _: &Self::Request) -> Option<String> {
+        traced!(self.tracer, "request::locale", [], {
+            let translations = self.translations.clone();
+            let accept_language = self
+                .request()
+                .header("Accept-Language")
+                .map(|values| values.as_str().to_string());
+
+            // Without bundles to negotiate with, the client's most preferred language is used
+            match translations {
+                Some(translations) => Some(translations.negotiate(accept_language.as_deref())),
+                None => {
+                    accept_language.and_then(|value| preferred_languages(&value).into_iter().next())
+                }
+            }
+        })
+    }
+
+    fn request_nonce(&mut self, _: &Self::Request) -> String {
+        traced!(
+            self.tracer,
+            "request::nonce",
+            [],
+            // The nonce is generated once so that it is the same for the rest of the request
+            self.nonce.get_or_insert_with(csp::generate_nonce).clone(),
+            |result| Bytes(result.len())
+        )
     }
 
     async fn request_body(&mut self, _: &Self::Request) -> Result<Vec<u8>, String> {
-        self.0.body_bytes().await.map_err(|e| e.to_string())
+        traced!(
+            self.tracer,
+            "request::body",
+            [],
+            self.request().body_bytes().await.map_err(|e| e.to_string()),
+            |result| result.as_ref().map(|bytes| Bytes(bytes.len()))
+        )
+    }
+
+    async fn request_upload_part(
+        &mut self,
+        _: &Self::Request,
+        part: &str,
+        bucket: &str,
+        key: &str,
+    ) -> Result<functions::UploadedObject, String> {
+        traced!(
+            self.tracer,
+            "request::upload_part",
+            [part, bucket, key],
+            self.upload_part(part, bucket, key).await,
+            |result| result.as_ref().map(|object| Bytes(object.size as usize))
+        )
     }
 
     fn response_new(&mut self, status: functions::HttpStatus) -> Result<Self::Response, String> {
-        Ok(Response {
-            inner: RefCell::new(Some(tide::Response::new(
-                tide::StatusCode::try_from(status).map_err(|e| e.to_string())?,
-            ))),
-            body: RefCell::new(Vec::new()),
-        })
+        traced!(
+            self.tracer,
+            "response::new",
+            [status],
+            (|| -> Result<_, String> {
+                Ok(Response {
+                    inner: RefCell::new(Some(tide::Response::new(
+                        tide::StatusCode::try_from(status).map_err(|e| e.to_string())?,
+                    ))),
+                    body: RefCell::new(Vec::new()),
+                })
+            })(),
+            |result| result.as_ref().map(|_| "response")
+        )
     }
 
     fn response_status(&mut self, response: &Self::Response) -> functions::HttpStatus {
-        functions::HttpStatus::from(response.inner.borrow().as_ref().unwrap().status())
+        traced!(self.tracer, "response::status", [], {
+            functions::HttpStatus::from(response.inner.borrow().as_ref().unwrap().status())
+        })
+    }
+
+    fn response_set_status(
+        &mut self,
+        response: &Self::Response,
+        status: functions::HttpStatus,
+    ) -> Result<functions::HttpStatus, String> {
+        traced!(
+            self.tracer,
+            "response::set_status",
+            [status],
+            (|| -> Result<_, String> {
+                let status = tide::StatusCode::try_from(status).map_err(|e| e.to_string())?;
+                let mut inner = response.inner.borrow_mut();
+                let inner = inner.as_mut().unwrap();
+                let previous = inner.status();
+                inner.set_status(status);
+                Ok(functions::HttpStatus::from(previous))
+            })()
+        )
     }
 
     fn response_header(&mut self, response: &Self::Response, name: &str) -> Option<String> {
-        response
-            .inner
-            .borrow()
-            .as_ref()
-            .unwrap()
-            .header(name)
-            .map(|v| v.as_str().to_string())
+        traced!(
+            self.tracer,
+            "response::header",
+            [name],
+            response
+                .inner
+                .borrow()
+                .as_ref()
+                .unwrap()
+                .header(name)
+                .map(|v| v.as_str().to_string()),
+            |result| result.as_ref().map(|value| Bytes(value.len()))
+        )
     }
 
     fn response_set_header(&mut self, response: &Self::Response, name: &str, value: &str) {
-        response
-            .inner
-            .borrow_mut()
-            .as_mut()
-            .unwrap()
-            .insert_header(name, value);
+        traced!(
+            self.tracer,
+            "response::set_header",
+            [name, Bytes(value.len())],
+            {
+                response
+                    .inner
+                    .borrow_mut()
+                    .as_mut()
+                    .unwrap()
+                    .insert_header(name, value);
+            }
+        )
+    }
+
+    fn response_headers(&mut self, response: &Self::Response) -> Vec<(String, String)> {
+        traced!(
+            self.tracer,
+            "response::headers",
+            [],
+            {
+                let response = response.inner.borrow();
+                let mut headers = Vec::new();
+                for (name, values) in response.as_ref().unwrap().iter() {
+                    headers.extend(
+                        values
+                            .iter()
+                            .map(|v| (name.as_str().to_string(), v.as_str().to_string())),
+                    );
+                }
+                headers
+            },
+            |result| result.iter().map(|(name, _)| name).collect::<Vec<_>>()
+        )
+    }
+
+    fn response_remove_header(&mut self, response: &Self::Response, name: &str) -> Option<String> {
+        traced!(
+            self.tracer,
+            "response::remove_header",
+            [name],
+            response
+                .inner
+                .borrow_mut()
+                .as_mut()
+                .unwrap()
+                .remove_header(name)
+                .map(|v| v.as_str().to_string()),
+            |result| result.as_ref().map(|value| Bytes(value.len()))
+        )
     }
 
     fn response_add_cookie(&mut self, response: &Self::Response, cookie: &Self::Cookie) {
-        response
-            .inner
-            .borrow_mut()
-            .as_mut()
-            .unwrap()
-            .insert_cookie(cookie.inner.borrow().clone());
+        traced!(
+            self.tracer,
+            "response::add_cookie",
+            [cookie.inner.borrow().name()],
+            {
+                let mut response = response.inner.borrow_mut();
+                let response = response.as_mut().unwrap();
+
+                response.insert_ext(CookiesChanged);
+
+                // Cookies with attributes unknown to the cookie jar bypass it
+                match cookie.encode_extended() {
+                    Some(encoded) => response.append_header(SET_COOKIE, encoded),
+                    None => response.insert_cookie(cookie.inner.borrow().clone()),
+                }
+            }
+        )
     }
 
     fn response_remove_cookie(&mut self, response: &Self::Response, cookie: &Self::Cookie) {
-        response
-            .inner
-            .borrow_mut()
-            .as_mut()
-            .unwrap()
-            .remove_cookie(cookie.inner.borrow().clone());
+        traced!(
+            self.tracer,
+            "response::remove_cookie",
+            [cookie.inner.borrow().name()],
+            {
+                let mut response = response.inner.borrow_mut();
+                let response = response.as_mut().unwrap();
+                response.insert_ext(CookiesChanged);
+                response.remove_cookie(cookie.inner.borrow().clone());
+            }
+        )
     }
 
     fn response_body(&mut self, response: &Self::Response) -> Vec<u8> {
-        response.body.borrow().clone()
+        traced!(
+            self.tracer,
+            "response::body",
+            [],
+            response.body.borrow().clone(),
+            |result| Bytes(result.len())
+        )
     }
 
     fn response_set_body(&mut self, response: &Self::Response, body: &[u8]) {
-        let mut b = response.body.borrow_mut();
-        b.resize(body.len(), 0);
-        b.copy_from_slice(body);
+        traced!(self.tracer, "response::set_body", [Bytes(body.len())], {
+            let mut b = response.body.borrow_mut();
+            b.resize(body.len(), 0);
+            b.copy_from_slice(body);
+        })
     }
 
     fn cookie_new(&mut self, name: &str, value: &str) -> Self::Cookie {
-        Cookie {
-            inner: RefCell::new(http_types::Cookie::new(name.to_string(), value.to_string())),
-        }
+        traced!(
+            self.tracer,
+            "cookie::new",
+            [name, Bytes(value.len())],
+            Cookie {
+                inner: RefCell::new(http_types::Cookie::new(name.to_string(), value.to_string())),
+                priority: Cell::new(None),
+                partitioned: Cell::new(false),
+            },
+            |_result| "cookie"
+        )
     }
 
     fn cookie_set_http_only(&mut self, cookie: &Self::Cookie, enabled: bool) {
-        cookie.inner.borrow_mut().set_http_only(Some(enabled))
+        traced!(self.tracer, "cookie::set_http_only", [enabled], {
+            cookie.inner.borrow_mut().set_http_only(Some(enabled))
+        })
     }
 
     fn cookie_set_secure(&mut self, cookie: &Self::Cookie, enabled: bool) {
-        cookie.inner.borrow_mut().set_secure(Some(enabled))
+        traced!(self.tracer, "cookie::set_secure", [enabled], {
+            cookie.inner.borrow_mut().set_secure(Some(enabled))
+        })
     }
 
     fn cookie_set_max_age(&mut self, cookie: &Self::Cookie, age: i64) {
-        cookie
-            .inner
-            .borrow_mut()
-            .set_max_age(Some(time::Duration::seconds(age)))
+        traced!(self.tracer, "cookie::set_max_age", [age], {
+            cookie
+                .inner
+                .borrow_mut()
+                .set_max_age(Some(time::Duration::seconds(age)))
+        })
+    }
+
+    fn cookie_set_expires(&mut self, cookie: &Self::Cookie, timestamp: i64) {
+        traced!(self.tracer, "cookie::set_expires", [timestamp], {
+            // The cookie jar can't represent times past the end of year 9999
+            let timestamp = timestamp.clamp(0, MAX_COOKIE_EXPIRES);
+            cookie
+                .inner
+                .borrow_mut()
+                .set_expires(time::OffsetDateTime::from_unix_timestamp(timestamp))
+        })
     }
 
     fn cookie_set_same_site(&mut self, cookie: &Self::Cookie, policy: functions::SameSitePolicy) {
-        cookie.inner.borrow_mut().set_same_site(match policy {
+        let policy = match policy {
             functions::SameSitePolicy::Strict => SameSite::Strict,
             functions::SameSitePolicy::Lax => SameSite::Lax,
             functions::SameSitePolicy::None => SameSite::None,
-        });
+        };
+
+        traced!(self.tracer, "cookie::set_same_site", [policy], {
+            cookie.inner.borrow_mut().set_same_site(policy);
+        })
     }
 
     fn cookie_set_domain(&mut self, cookie: &Self::Cookie, domain: &str) {
-        cookie.inner.borrow_mut().set_domain(domain.to_string());
+        traced!(self.tracer, "cookie::set_domain", [domain], {
+            cookie.inner.borrow_mut().set_domain(domain.to_string());
+        })
     }
 
     fn cookie_set_path(&mut self, cookie: &Self::Cookie, path: &str) {
-        cookie.inner.borrow_mut().set_path(path.to_string());
+        traced!(self.tracer, "cookie::set_path", [path], {
+            cookie.inner.borrow_mut().set_path(path.to_string());
+        })
+    }
+
+    fn cookie_set_priority(&mut self, cookie: &Self::Cookie, priority: functions::CookiePriority) {
+        let priority = match priority {
+            functions::CookiePriority::Low => "Low",
+            functions::CookiePriority::Medium => "Medium",
+            functions::CookiePriority::High => "High",
+        };
+
+        traced!(self.tracer, "cookie::set_priority", [priority], {
+            cookie.priority.set(Some(priority))
+        })
+    }
+
+    fn cookie_set_partitioned(&mut self, cookie: &Self::Cookie, enabled: bool) {
+        traced!(self.tracer, "cookie::set_partitioned", [enabled], {
+            cookie.partitioned.set(enabled)
+        })
+    }
+
+    fn abort(&mut self, status: functions::HttpStatus, message: &str) {
+        traced!(self.tracer, "abort", [status, message], {
+            // The guest exits once the call returns, so the response is recorded for the server
+            self.abort = Some(
+                tide::Response::builder(
+                    tide::StatusCode::try_from(status)
+                        .unwrap_or(tide::StatusCode::InternalServerError),
+                )
+                .content_type(tide::http::mime::PLAIN)
+                .body(message)
+                .build(),
+            );
+        })
+    }
+}
+
+struct SqlHost {
+    sql: Option<Arc<crate::sql::Sql>>,
+    function: Arc<String>,
+    calls: u32,
+    tracer: Tracer,
+    capabilities: CapabilityRecorder,
+}
+
+impl SqlHost {
+    fn get(&self) -> Result<&crate::sql::Sql, String> {
+        self.sql
+            .as_deref()
+            .ok_or_else(|| "no SQL provider is configured".to_string())
+    }
+
+    fn from_param(param: sql::ValueParam<'_>) -> SqlValue {
+        match param {
+            sql::ValueParam::Null => SqlValue::Null,
+            sql::ValueParam::Integer(i) => SqlValue::Integer(i),
+            sql::ValueParam::Real(r) => SqlValue::Real(r),
+            sql::ValueParam::Text(s) => SqlValue::Text(s.to_string()),
+            sql::ValueParam::Blob(b) => SqlValue::Blob(b.to_vec()),
+        }
+    }
+
+    async fn query_rows(
+        &self,
+        statement: &str,
+        params: &[SqlValue],
+        idempotent: bool,
+    ) -> Result<Vec<Vec<SqlValue>>, String> {
+        self.get()?
+            .query(&self.function, statement, params, idempotent)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    fn to_rows(rows: Vec<Vec<SqlValue>>) -> Vec<Vec<sql::ValueResult>> {
+        rows.into_iter()
+            .map(|row| row.into_iter().map(Self::to_result).collect())
+            .collect()
+    }
+
+    fn to_result(value: SqlValue) -> sql::ValueResult {
+        match value {
+            SqlValue::Null => sql::ValueResult::Null,
+            SqlValue::Integer(i) => sql::ValueResult::Integer(i),
+            SqlValue::Real(r) => sql::ValueResult::Real(r),
+            SqlValue::Text(s) => sql::ValueResult::Text(s),
+            SqlValue::Blob(b) => sql::ValueResult::Blob(b),
+        }
+    }
+}
+
+#[witx_bindgen_wasmtime::async_trait]
+impl sql::Sql for SqlHost {
+    async fn execute(
+        &mut self,
+        statement: &str,
+        params: Vec<sql::ValueParam<'_>>,
+    ) -> Result<u64, String> {
+        let params: Vec<_> = params.into_iter().map(Self::from_param).collect();
+        self.calls += 1;
+        self.capabilities.record(|usage| usage.sql += 1);
+
+        traced!(
+            self.tracer,
+            "sql::execute",
+            [statement, format!("{} params", params.len())],
+            async {
+                self.get()?
+                    .execute(&self.function, statement, &params)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            .await
+        )
+    }
+
+    async fn query(
+        &mut self,
+        statement: &str,
+        params: Vec<sql::ValueParam<'_>>,
+    ) -> Result<Vec<Vec<sql::ValueResult>>, String> {
+        let params: Vec<_> = params.into_iter().map(Self::from_param).collect();
+        self.calls += 1;
+        self.capabilities.record(|usage| usage.sql += 1);
+
+        let rows = traced!(
+            self.tracer,
+            "sql::query",
+            [statement, format!("{} params", params.len())],
+            self.query_rows(statement, &params, false).await,
+            |result| result.as_ref().map(|rows| format!("{} rows", rows.len()))
+        )?;
+
+        Ok(Self::to_rows(rows))
+    }
+
+    async fn query_idempotent(
+        &mut self,
+        statement: &str,
+        params: Vec<sql::ValueParam<'_>>,
+    ) -> Result<Vec<Vec<sql::ValueResult>>, String> {
+        let params: Vec<_> = params.into_iter().map(Self::from_param).collect();
+        self.calls += 1;
+        self.capabilities.record(|usage| usage.sql += 1);
+
+        let rows = traced!(
+            self.tracer,
+            "sql::query_idempotent",
+            [statement, format!("{} params", params.len())],
+            self.query_rows(statement, &params, true).await,
+            |result| result.as_ref().map(|rows| format!("{} rows", rows.len()))
+        )?;
+
+        Ok(Self::to_rows(rows))
+    }
+}
+
+struct FetchHost {
+    fetch: Option<Arc<Fetch>>,
+    server_config: Option<Arc<ServerConfig>>,
+    calls: u32,
+    tracer: Tracer,
+    capabilities: CapabilityRecorder,
+}
+
+#[witx_bindgen_wasmtime::async_trait]
+impl fetch::Fetch for FetchHost {
+    async fn send(
+        &mut self,
+        method: &str,
+        uri: &str,
+        headers: Vec<(&str, &str)>,
+        body: &[u8],
+    ) -> Result<fetch::FetchResponse, String> {
+        self.calls += 1;
+        if let Some(host) = http_types::Url::parse(uri)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+        {
+            self.capabilities.record(|usage| {
+                usage.hosts.insert(host);
+            });
+        }
+
+        let res = traced!(
+            self.tracer,
+            "fetch::send",
+            [method, redact_query(uri), Bytes(body.len())],
+            async {
+                let allowed = self
+                    .server_config
+                    .as_ref()
+                    .map(|config| config.allowed_hosts.as_slice())
+                    .unwrap_or_default();
+
+                self.fetch
+                    .as_deref()
+                    .ok_or_else(|| "outbound requests are not allowed".to_string())?
+                    .send(allowed, method, uri, &headers, body)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            .await,
+            |result| {
+                result
+                    .as_ref()
+                    .map(|res| format!("{} with {:?}", res.status, Bytes(res.body.len())))
+            }
+        )?;
+
+        Ok(fetch::FetchResponse {
+            status: res.status,
+            headers: res.headers,
+            body: res.body,
+        })
+    }
+}
+
+struct GrpcHost {
+    grpc: Option<Arc<Grpc>>,
+    calls: u32,
+    tracer: Tracer,
+    capabilities: CapabilityRecorder,
+}
+
+#[witx_bindgen_wasmtime::async_trait]
+impl grpc::Grpc for GrpcHost {
+    // Metadata is not traced as it commonly carries credentials
+    async fn call(
+        &mut self,
+        service: &str,
+        method: &str,
+        metadata: Vec<(&str, &str)>,
+        payload: &[u8],
+        deadline_ms: Option<u64>,
+    ) -> Result<grpc::GrpcResponse, grpc::GrpcStatus> {
+        self.calls += 1;
+        self.capabilities.record(|usage| {
+            usage.grpc_services.insert(service.to_string());
+        });
+
+        let res = traced!(
+            self.tracer,
+            "grpc::call",
+            [service, method, Bytes(payload.len())],
+            async {
+                self.grpc
+                    .as_deref()
+                    .ok_or_else(|| {
+                        crate::grpc::GrpcStatus::new(
+                            crate::grpc::PERMISSION_DENIED,
+                            "gRPC calls are not allowed",
+                        )
+                    })?
+                    .call(
+                        service,
+                        method,
+                        &metadata,
+                        payload,
+                        deadline_ms.map(Duration::from_millis),
+                    )
+                    .await
+            }
+            .await,
+            |result| {
+                result
+                    .as_ref()
+                    .map(|res| format!("{:?}", Bytes(res.payload.len())))
+                    .map_err(|status| format!("status {}: {}", status.code, status.message))
+            }
+        )
+        .map_err(|status| grpc::GrpcStatus {
+            code: status.code,
+            message: status.message,
+        })?;
+
+        Ok(grpc::GrpcResponse {
+            payload: res.payload,
+            metadata: res.metadata,
+        })
+    }
+}
+
+struct DiscoveryHost {
+    provider: Option<Arc<dyn DiscoveryProvider>>,
+    tracer: Tracer,
+}
+
+#[witx_bindgen_wasmtime::async_trait]
+impl discovery::Discovery for DiscoveryHost {
+    async fn resolve(&mut self, service: &str) -> Result<Vec<discovery::Endpoint>, String> {
+        let endpoints = traced!(
+            self.tracer,
+            "discovery::resolve",
+            [service],
+            async {
+                self.provider
+                    .as_deref()
+                    .ok_or_else(|| "service discovery is not configured".to_string())?
+                    .resolve(service)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            .await,
+            |result| result
+                .as_ref()
+                .map(|endpoints| format!("{} endpoints", endpoints.len()))
+        )?;
+
+        Ok(endpoints
+            .into_iter()
+            .map(|e| discovery::Endpoint {
+                host: e.host,
+                port: e.port,
+                priority: e.priority,
+                weight: e.weight,
+            })
+            .collect())
+    }
+}
+
+struct TemplatesHost {
+    templates: Option<Arc<Templates>>,
+    tracer: Tracer,
+}
+
+impl templates::Templates for TemplatesHost {
+    fn render(&mut self, name: &str, context: &str) -> Result<String, String> {
+        traced!(
+            self.tracer,
+            "templates::render",
+            [name, Bytes(context.len())],
+            self.templates
+                .as_deref()
+                .ok_or_else(|| "no templates are configured".to_string())
+                .and_then(|templates| templates
+                    .render(name, context)
+                    .map_err(|e| format!("{:#}", e))),
+            |result| result.as_ref().map(|output| Bytes(output.len()))
+        )
+    }
+}
+
+struct I18nHost {
+    translations: Option<Arc<Translations>>,
+    tracer: Tracer,
+}
+
+impl i18n::I18n for I18nHost {
+    fn translate(
+        &mut self,
+        key: &str,
+        locale: &str,
+        args: Vec<(&str, &str)>,
+    ) -> Result<String, String> {
+        traced!(
+            self.tracer,
+            "i18n::translate",
+            [key, locale, args.len()],
+            self.translations
+                .as_deref()
+                .ok_or_else(|| "no translations are configured".to_string())
+                .and_then(|translations| translations
+                    .translate(key, locale, &args)
+                    .map_err(|e| format!("{:#}", e))),
+            |result| result.as_ref().map(|output| Bytes(output.len()))
+        )
+    }
+}
+
+struct SigningHost {
+    signer: Option<Arc<UrlSigner>>,
+    tracer: Tracer,
+}
+
+impl signing::Signing for SigningHost {
+    fn sign_url(&mut self, path: &str, expires_in: u64) -> Result<String, String> {
+        traced!(
+            self.tracer,
+            "signing::sign_url",
+            [path, expires_in],
+            self.signer
+                .as_deref()
+                .ok_or_else(|| "no URL signing key is configured".to_string())
+                .and_then(|signer| signer
+                    .sign(path, Duration::from_secs(expires_in))
+                    .map_err(|e| format!("{:#}", e))),
+            |result| result.as_ref().map(|_| "<signed>")
+        )
+    }
+}
+
+struct CacheHost {
+    provider: Option<Arc<dyn KvProvider>>,
+    tracer: Tracer,
+    capabilities: CapabilityRecorder,
+}
+
+impl CacheHost {
+    fn provider(&self) -> Result<&dyn KvProvider, String> {
+        self.provider
+            .as_deref()
+            .ok_or_else(|| "the app cache is not available".to_string())
+    }
+
+    fn key(key: &str) -> Result<&str, String> {
+        if key.starts_with(HOST_KEY_PREFIX) {
+            return Err(format!(
+                "keys starting with '{}' are reserved by the host",
+                HOST_KEY_PREFIX
+            ));
+        }
+
+        Ok(key)
+    }
+}
+
+// Cached values are not traced as they may be sensitive
+#[witx_bindgen_wasmtime::async_trait]
+impl cache::Cache for CacheHost {
+    async fn app_get(&mut self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        self.capabilities.record(|usage| usage.cache += 1);
+
+        traced!(
+            self.tracer,
+            "cache::app_get",
+            [key],
+            async {
+                self.provider()?
+                    .get(Self::key(key)?)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            .await,
+            |result| result
+                .as_ref()
+                .map(|value| value.as_ref().map(|value| Bytes(value.len())))
+        )
+    }
+
+    async fn app_set(&mut self, key: &str, value: Vec<u8>, ttl: u64) -> Result<(), String> {
+        self.capabilities.record(|usage| usage.cache += 1);
+
+        traced!(
+            self.tracer,
+            "cache::app_set",
+            [key, Bytes(value.len()), ttl],
+            async {
+                if value.len() > MAX_CACHE_VALUE_BYTES {
+                    return Err(format!(
+                        "the value of {} bytes exceeds the maximum of {} bytes",
+                        value.len(),
+                        MAX_CACHE_VALUE_BYTES
+                    ));
+                }
+
+                self.provider()?
+                    .set(Self::key(key)?, value, Duration::from_secs(ttl))
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            .await
+        )
+    }
+
+    async fn app_remove(&mut self, key: &str) -> Result<(), String> {
+        self.capabilities.record(|usage| usage.cache += 1);
+
+        traced!(
+            self.tracer,
+            "cache::app_remove",
+            [key],
+            async {
+                self.provider()?
+                    .remove(Self::key(key)?)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            .await
+        )
+    }
+}
+
+#[derive(Default)]
+struct ConfigHost {
+    vars: Vec<(String, String)>,
+    settings: Arc<Vec<(String, Setting)>>,
+    server_config: Option<Arc<ServerConfig>>,
+    tracer: Tracer,
+}
+
+impl config::Config for ConfigHost {
+    // Configuration values are not traced as they may be sensitive
+    fn vars(&mut self) -> Vec<(String, String)> {
+        traced!(
+            self.tracer,
+            "config::vars",
+            [],
+            self.vars.clone(),
+            |result| format!("{} vars", result.len())
+        )
+    }
+
+    fn settings(&mut self) -> Vec<(String, config::Setting)> {
+        traced!(
+            self.tracer,
+            "config::settings",
+            [],
+            self.get_settings(),
+            |result| format!("{} settings", result.len())
+        )
+    }
+}
+
+impl ConfigHost {
+    fn get_settings(&self) -> Vec<(String, config::Setting)> {
+        // The settings that can be changed while the server is serving are those of the request
+        let reloadable = self.server_config.as_ref().map(|config| {
+            vec![
+                ("timeout".to_string(), Setting::Duration(config.timeout)),
+                (
+                    "outbound_requests".to_string(),
+                    Setting::Boolean(!config.allowed_hosts.is_empty()),
+                ),
+            ]
+        });
+
+        self.settings
+            .iter()
+            .chain(reloadable.iter().flatten())
+            .map(|(name, setting)| {
+                (
+                    name.clone(),
+                    match setting {
+                        Setting::Text(s) => config::Setting::Text(s.clone()),
+                        Setting::Integer(i) => config::Setting::Integer(*i),
+                        Setting::Boolean(b) => config::Setting::Boolean(*b),
+                        Setting::Duration(d) => config::Setting::Duration(d.as_millis() as u64),
+                    },
+                )
+            })
+            .collect()
     }
 }
@@ -0,0 +1,227 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The extension of the translation bundles loaded from the translations directory.
+const BUNDLE_EXTENSION: &str = "json";
+
+/// The translation bundles functions translate messages with.
+///
+/// Bundles are loaded once when the server is built rather than on every request.
+pub struct Translations {
+    default_locale: String,
+    // Keyed by the lowercase locale tag
+    bundles: HashMap<String, (String, HashMap<String, String>)>,
+}
+
+impl Translations {
+    /// Loads every `.json` bundle in the directory.
+    ///
+    /// A bundle is named by its locale tag, such as `fr-CA.json`, and contains a JSON object of
+    /// messages; nested objects are flattened into keys joined with `.`, such as `errors.not_found`.
+    /// The default locale must have a bundle as it is used when no other locale matches.
+    pub fn load(dir: &Path, default_locale: &str) -> Result<Self> {
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("failed to read directory '{}'", dir.display()))?;
+
+        let mut bundles = HashMap::new();
+
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() || path.extension().and_then(|e| e.to_str()) != Some(BUNDLE_EXTENSION)
+            {
+                continue;
+            }
+
+            let locale = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(stem) => stem.replace('_', "-"),
+                None => continue,
+            };
+
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read bundle '{}'", path.display()))?;
+
+            let value: serde_json::Value = serde_json::from_str(&contents)
+                .with_context(|| format!("bundle '{}' is not valid JSON", path.display()))?;
+
+            let mut messages = HashMap::new();
+            Self::flatten(&value, "", &mut messages)
+                .map_err(|e| anyhow!("invalid bundle '{}': {}", path.display(), e))?;
+
+            bundles.insert(locale.to_lowercase(), (locale, messages));
+        }
+
+        if bundles.is_empty() {
+            bail!("no `.{}` bundles were found", BUNDLE_EXTENSION);
+        }
+
+        if !bundles.contains_key(&default_locale.to_lowercase()) {
+            bail!(
+                "there is no bundle for the default locale `{}`",
+                default_locale
+            );
+        }
+
+        Ok(Self {
+            default_locale: default_locale.to_string(),
+            bundles,
+        })
+    }
+
+    fn flatten(
+        value: &serde_json::Value,
+        prefix: &str,
+        messages: &mut HashMap<String, String>,
+    ) -> Result<()> {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map {
+                    let key = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}.{}", prefix, key)
+                    };
+                    Self::flatten(value, &key, messages)?;
+                }
+            }
+            serde_json::Value::String(message) if !prefix.is_empty() => {
+                messages.insert(prefix.to_string(), message.clone());
+            }
+            _ if prefix.is_empty() => bail!("the bundle is not a JSON object"),
+            _ => bail!("message `{}` is not a string", prefix),
+        }
+
+        Ok(())
+    }
+
+    /// Gets the bundle for a locale tag, falling back to less specific tags (e.g. `fr-CA` to `fr`).
+    fn bundle(&self, locale: &str) -> Option<&(String, HashMap<String, String>)> {
+        let mut tag = locale.replace('_', "-").to_lowercase();
+
+        loop {
+            if let Some(bundle) = self.bundles.get(&tag) {
+                return Some(bundle);
+            }
+
+            match tag.rfind('-') {
+                Some(pos) => tag.truncate(pos),
+                None => return None,
+            }
+        }
+    }
+
+    /// Negotiates the locale of a request from the value of its `Accept-Language` header.
+    ///
+    /// The most preferred language with a bundle is chosen; a language matches a more specific
+    /// bundle when no other bundle does (e.g. `fr` matches `fr-CA`). The default locale is
+    /// returned when no language matches.
+    pub fn negotiate(&self, accept_language: Option<&str>) -> String {
+        for tag in preferred_languages(accept_language.unwrap_or_default()) {
+            if let Some((locale, _)) = self.bundle(&tag) {
+                return locale.clone();
+            }
+
+            let language = format!("{}-", tag.to_lowercase());
+            let mut matches: Vec<_> = self
+                .bundles
+                .iter()
+                .filter(|(key, _)| key.starts_with(&language))
+                .map(|(_, (locale, _))| locale)
+                .collect();
+
+            // Sorted so that the match doesn't depend on the order the bundles were loaded in
+            matches.sort();
+
+            if let Some(locale) = matches.first() {
+                return locale.to_string();
+            }
+        }
+
+        self.default_locale.clone()
+    }
+
+    /// Translates the message with the given key into a locale.
+    ///
+    /// Messages missing from the locale's bundle are taken from less specific locales and then the
+    /// default locale. Placeholders such as `{name}` are replaced with the argument of the same name;
+    /// `{{` and `}}` are literal braces.
+    pub fn translate(&self, key: &str, locale: &str, args: &[(&str, &str)]) -> Result<String> {
+        let message = self
+            .bundle(locale)
+            .and_then(|(_, messages)| messages.get(key))
+            .or_else(|| {
+                self.bundle(&self.default_locale)
+                    .and_then(|(_, messages)| messages.get(key))
+            })
+            .ok_or_else(|| anyhow!("message `{}` does not exist", key))?;
+
+        let mut output = String::with_capacity(message.len());
+        let mut chars = message.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    output.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    output.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+
+                    if !closed {
+                        bail!("message `{}` has an unterminated placeholder", key);
+                    }
+
+                    let value = args
+                        .iter()
+                        .find(|(n, _)| *n == name)
+                        .map(|(_, v)| v)
+                        .ok_or_else(|| anyhow!("message `{}` requires argument `{}`", key, name))?;
+                    output.push_str(value);
+                }
+                c => output.push(c),
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+/// Gets the language tags of an `Accept-Language` header value in order of preference.
+///
+/// Tags with a quality of zero and the `*` wildcard are omitted.
+pub fn preferred_languages(accept_language: &str) -> Vec<String> {
+    let mut languages: Vec<(String, f32)> = accept_language
+        .split(',')
+        .filter_map(|entry| {
+            let mut params = entry.split(';');
+            let tag = params.next()?.trim();
+
+            let quality = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            if tag.is_empty() || tag == "*" || quality <= 0.0 {
+                return None;
+            }
+
+            Some((tag.to_string(), quality))
+        })
+        .collect();
+
+    // The sort is stable, so tags of equal quality keep the order they were sent in
+    languages.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    languages.into_iter().map(|(tag, _)| tag).collect()
+}
@@ -0,0 +1,308 @@
+use crate::error::FunctionResponse;
+use crate::kv::{KvProvider, HOST_KEY_PREFIX};
+use crate::server::Request;
+use crate::signing::digest;
+use anyhow::Result;
+use async_std::sync::Mutex as AsyncMutex;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tide::http::headers::HeaderName;
+use tide::http::Method;
+use tide::{Response, StatusCode};
+
+/// The header clients use to make a request idempotent.
+pub const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+/// The header added to responses replayed for a retried request.
+const IDEMPOTENT_REPLAYED: &str = "Idempotent-Replayed";
+
+/// The longest idempotency key accepted.
+const MAX_KEY_LENGTH: usize = 255;
+
+/// The header that scopes idempotency keys to a client by default.
+const DEFAULT_SCOPE_HEADER: &str = "Authorization";
+
+/// The largest response body stored for an idempotency key.
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// The number of locks reservations are serialized with.
+const RESERVATION_LOCKS: usize = 64;
+
+/// A response stored for an idempotency key.
+#[derive(Serialize, Deserialize)]
+struct StoredResponse {
+    // The method and target of the request that produced the response
+    fingerprint: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    // Encoded with Base64 so that the record stays compact as JSON
+    body: String,
+}
+
+/// The state of an idempotency key that was already used.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "state")]
+enum IdempotencyRecord {
+    /// The first request with the key is still being processed.
+    InProgress { fingerprint: String },
+    /// The first request with the key completed with the given response.
+    Completed(StoredResponse),
+}
+
+/// A key reserved for a request being processed.
+///
+/// The reservation is released if it is dropped without being completed, such as when the client
+/// disconnects before the function responds, so that a retry is processed again.
+pub struct Reservation {
+    key: String,
+    fingerprint: String,
+    store: Option<Arc<dyn KvProvider>>,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some(store) = self.store.take() {
+            let key = std::mem::take(&mut self.key);
+            async_std::task::spawn(async move {
+                if let Err(e) = store.remove(&key).await {
+                    log::error!("Failed to release idempotency key: {:#}", e);
+                }
+            });
+        }
+    }
+}
+
+/// Replays the responses of requests retried with the same idempotency key.
+///
+/// Records are stored with the server's kv provider, so they are shared between servers when the
+/// provider is. The provider has no atomic insert, so reservations are only exclusive between the
+/// requests of one server.
+pub struct Idempotency {
+    store: Arc<dyn KvProvider>,
+    ttl: Duration,
+    scope_header: String,
+    locks: Vec<AsyncMutex<()>>,
+}
+
+impl Idempotency {
+    pub fn new(store: Arc<dyn KvProvider>, ttl: Duration, scope_header: Option<String>) -> Self {
+        Self {
+            store,
+            ttl,
+            scope_header: scope_header.unwrap_or_else(|| DEFAULT_SCOPE_HEADER.to_string()),
+            locks: (0..RESERVATION_LOCKS)
+                .map(|_| AsyncMutex::new(()))
+                .collect(),
+        }
+    }
+
+    /// Begins processing a request.
+    ///
+    /// Returns the response to send instead of invoking the function, or the reservation to
+    /// complete once the function responds. Requests without a key, requests with a safe method,
+    /// and requests from a client that can't be identified are processed as usual.
+    pub async fn begin(
+        &self,
+        req: &Request,
+        function: &str,
+    ) -> std::result::Result<Option<Reservation>, Response> {
+        if matches!(
+            req.method(),
+            Method::Get | Method::Head | Method::Options | Method::Trace
+        ) {
+            return Ok(None);
+        }
+
+        let key = match req.header(IDEMPOTENCY_KEY) {
+            Some(key) => key.as_str(),
+            None => return Ok(None),
+        };
+
+        if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+            return Err(error_response(
+                StatusCode::BadRequest,
+                "the idempotency key must be between 1 and 255 characters",
+            ));
+        }
+
+        // Keys are scoped to the client so that a client can't replay another client's response
+        // by reusing its key; without a scope, the request is not replayed at all
+        let scope = match self.scope(req) {
+            Some(scope) => scope,
+            None => return Ok(None),
+        };
+
+        let mut fingerprint = format!("{} {}", req.method(), req.url().path());
+        if let Some(query) = req.url().query() {
+            fingerprint.push('?');
+            fingerprint.push_str(query);
+        }
+
+        // The key is hashed so that the scope's credentials are not kept in the store
+        let key = format!(
+            "{}idempotency/{}",
+            HOST_KEY_PREFIX,
+            digest(format!("{}\n{}\n{}", function, scope, key).as_bytes())
+        );
+
+        let record = match self.reserve(&key, &fingerprint).await {
+            Ok(record) => record,
+            Err(e) => {
+                // Processing the request anyway could repeat a side effect meant to happen once
+                log::error!("Failed to reserve idempotency key: {:#}", e);
+                return Err(error_response(
+                    StatusCode::ServiceUnavailable,
+                    "the idempotency key could not be checked",
+                ));
+            }
+        };
+
+        match record {
+            None => Ok(Some(Reservation {
+                key,
+                fingerprint,
+                store: Some(self.store.clone()),
+            })),
+            Some(IdempotencyRecord::InProgress {
+                fingerprint: existing,
+            })
+            | Some(IdempotencyRecord::Completed(StoredResponse {
+                fingerprint: existing,
+                ..
+            })) if existing != fingerprint => Err(error_response(
+                StatusCode::UnprocessableEntity,
+                "the idempotency key was already used for a different request",
+            )),
+            Some(IdempotencyRecord::InProgress { .. }) => Err(error_response(
+                StatusCode::Conflict,
+                "a request with the idempotency key is still being processed",
+            )),
+            Some(IdempotencyRecord::Completed(stored)) => Err(replay(stored)),
+        }
+    }
+
+    /// Completes the processing of a request that reserved a key.
+    ///
+    /// Server errors and responses with large bodies are not stored, so that a retry is processed
+    /// again. A response that can't be stored is logged rather than failing the request, as the
+    /// function has already run.
+    pub async fn end(&self, mut reservation: Reservation, res: &mut tide::Result) {
+        let res = match res {
+            Ok(res) if !res.status().is_server_error() => res,
+            // Dropping the reservation releases it
+            _ => return,
+        };
+
+        let body = match res.take_body().into_bytes().await {
+            Ok(body) => body,
+            Err(e) => {
+                log::error!("Failed to read response for idempotency key: {:#}", e);
+                return;
+            }
+        };
+
+        if body.len() > MAX_BODY_SIZE {
+            res.set_body(body);
+            return;
+        }
+
+        let record = IdempotencyRecord::Completed(StoredResponse {
+            fingerprint: std::mem::take(&mut reservation.fingerprint),
+            status: res.status() as u16,
+            headers: res
+                .iter()
+                .flat_map(|(name, values)| {
+                    values
+                        .iter()
+                        .map(move |v| (name.as_str().to_string(), v.as_str().to_string()))
+                })
+                .collect(),
+            body: base64::encode(&body),
+        });
+
+        res.set_body(body);
+
+        if let Err(e) = self.put(&reservation.key, &record).await {
+            log::error!("Failed to store response for idempotency key: {:#}", e);
+            return;
+        }
+
+        // The reservation was replaced by the stored response rather than released
+        reservation.store = None;
+    }
+
+    // Gets the client scope of a request: the scope header, or else the client's address
+    fn scope(&self, req: &Request) -> Option<String> {
+        if let Some(values) = req.header(self.scope_header.as_str()) {
+            return Some(format!("header:{}", values.as_str()));
+        }
+
+        // The port differs between connections, so retries are matched by the client's IP address
+        let addr = req.peer_addr()?;
+        Some(match addr.parse::<SocketAddr>() {
+            Ok(addr) => format!("addr:{}", addr.ip()),
+            Err(_) => format!("addr:{}", addr),
+        })
+    }
+
+    async fn reserve(&self, key: &str, fingerprint: &str) -> Result<Option<IdempotencyRecord>> {
+        // Serialize reservations of the same key, as the provider can't insert atomically
+        let index = key
+            .bytes()
+            .fold(0usize, |h, b| h.wrapping_mul(31) ^ b as usize);
+        let _lock = self.locks[index % self.locks.len()].lock().await;
+
+        if let Some(value) = self.store.get(key).await? {
+            match serde_json::from_slice(&value) {
+                Ok(record) => return Ok(Some(record)),
+                Err(e) => log::warn!("Ignoring invalid idempotency record: {}", e),
+            }
+        }
+
+        self.put(
+            key,
+            &IdempotencyRecord::InProgress {
+                fingerprint: fingerprint.to_string(),
+            },
+        )
+        .await?;
+
+        Ok(None)
+    }
+
+    async fn put(&self, key: &str, record: &IdempotencyRecord) -> Result<()> {
+        self.store
+            .set(key, serde_json::to_vec(record)?, self.ttl)
+            .await
+    }
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let mut res = Response::new(
+        StatusCode::try_from(stored.status).unwrap_or(StatusCode::InternalServerError),
+    );
+
+    for (name, value) in &stored.headers {
+        if let Ok(name) = HeaderName::from_string(name.clone()) {
+            res.append_header(name, value.as_str());
+        }
+    }
+
+    res.insert_header(IDEMPOTENT_REPLAYED, "true");
+    res.set_body(base64::decode(&stored.body).unwrap_or_default());
+    res.insert_ext(FunctionResponse);
+    res
+}
+
+fn error_response(status: StatusCode, message: &'static str) -> Response {
+    let mut res = tide::Response::builder(status)
+        .content_type(tide::http::mime::PLAIN)
+        .body(message)
+        .build();
+
+    res.set_error(anyhow::anyhow!("{}", message));
+    res
+}
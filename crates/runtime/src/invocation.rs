@@ -0,0 +1,104 @@
+use crate::server::RequestExtensions;
+use anyhow::{anyhow, Context, Result};
+use http_types::{Method, Request, Url};
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+/// Builds a synthetic request to process with a [`LocalServer`](crate::LocalServer).
+#[derive(Debug, Clone)]
+pub struct InvocationRequest {
+    method: String,
+    path: String,
+    params: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    extensions: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+    remote_addr: Option<SocketAddr>,
+}
+
+impl InvocationRequest {
+    /// Creates a new request with the given method and path.
+    ///
+    /// The path may include a query string.
+    pub fn new<M: Into<String>, P: Into<String>>(method: M, path: P) -> Self {
+        Self {
+            method: method.into(),
+            path: path.into(),
+            params: Vec::new(),
+            headers: Vec::new(),
+            extensions: Vec::new(),
+            body: None,
+            remote_addr: None,
+        }
+    }
+
+    /// Adds a header to the request.
+    pub fn header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Appends a query parameter to the request's path.
+    pub fn param<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.params.push((name.into(), value.into()));
+        self
+    }
+
+    /// Attaches an extension to the request, as the hook set with
+    /// [`ServerBuilder::on_request`](crate::ServerBuilder::on_request) would.
+    pub fn extension<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.extensions.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the body of the request.
+    ///
+    /// Unless a `Content-Type` header was added, the content type is `application/octet-stream`.
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Sets the address of the client that sent the request.
+    pub fn remote_addr(mut self, addr: SocketAddr) -> Self {
+        self.remote_addr = Some(addr);
+        self
+    }
+
+    /// Builds the request.
+    pub fn build(&self) -> Result<Request> {
+        let method = Method::from_str(&self.method)
+            .map_err(|e| anyhow!("invalid method '{}': {}", self.method, e))?;
+
+        let mut url = Url::parse("http://localhost")?
+            .join(&self.path)
+            .with_context(|| format!("invalid request path '{}'", self.path))?;
+
+        if !self.params.is_empty() {
+            url.query_pairs_mut().extend_pairs(&self.params);
+        }
+
+        let mut req = Request::new(method, url);
+
+        for (name, value) in &self.headers {
+            req.append_header(name.as_str(), value.as_str());
+        }
+
+        // The body is set after the headers so that an explicit content type is kept
+        if let Some(body) = &self.body {
+            req.set_body(body.clone());
+        }
+
+        req.set_peer_addr(self.remote_addr);
+
+        if !self.extensions.is_empty() {
+            let mut extensions = RequestExtensions::default();
+            for (name, value) in &self.extensions {
+                extensions.insert(name.as_str(), value.as_str());
+            }
+            req.set_ext(extensions);
+        }
+
+        Ok(req)
+    }
+}
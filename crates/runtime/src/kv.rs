@@ -0,0 +1,110 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The prefix of the keys the server stores its own values under.
+///
+/// Functions can't get or set keys with the prefix through the cache API.
+pub(crate) const HOST_KEY_PREFIX: &str = "__host/";
+
+/// Stores the values functions cache in the app-scoped tier of the cache API.
+///
+/// Values are shared by every instance of the module, so a value cached by one request is
+/// available to the next regardless of which instance serves it. The server also stores the
+/// responses replayed for idempotency keys with the provider.
+#[async_trait::async_trait]
+pub trait KvProvider: Send + Sync {
+    /// Gets the value of a key, or `None` if the key isn't set or has expired.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Sets the value of a key, which expires after the given time-to-live.
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) -> Result<()>;
+
+    /// Removes a key, if it is set.
+    async fn remove(&self, key: &str) -> Result<()>;
+}
+
+struct Entry {
+    value: Vec<u8>,
+    // A value without an expiry has a time-to-live too long to represent
+    expires: Option<Instant>,
+}
+
+impl Entry {
+    fn is_expired(&self, now: Instant) -> bool {
+        matches!(self.expires, Some(expires) if expires <= now)
+    }
+}
+
+/// A key-value provider that keeps values in memory.
+///
+/// Values are not shared between servers and are lost when the server is dropped. When the
+/// provider is full, expired values are evicted first and then the value closest to expiring.
+pub struct MemoryKvProvider {
+    capacity: usize,
+    values: Mutex<HashMap<String, Entry>>,
+}
+
+impl MemoryKvProvider {
+    /// Creates a new in-memory provider that holds at most the given number of values.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            values: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl KvProvider for MemoryKvProvider {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let mut values = self.values.lock().unwrap();
+
+        match values.get(key) {
+            Some(entry) if entry.is_expired(Instant::now()) => {
+                values.remove(key);
+                Ok(None)
+            }
+            Some(entry) => Ok(Some(entry.value.clone())),
+            None => Ok(None),
+        }
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) -> Result<()> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+
+        let mut values = self.values.lock().unwrap();
+        let now = Instant::now();
+
+        if !values.contains_key(key) && values.len() >= self.capacity {
+            values.retain(|_, entry| !entry.is_expired(now));
+
+            if values.len() >= self.capacity {
+                if let Some(key) = values
+                    .iter()
+                    .min_by_key(|(_, entry)| (entry.expires.is_none(), entry.expires))
+                    .map(|(k, _)| k.clone())
+                {
+                    values.remove(&key);
+                }
+            }
+        }
+
+        values.insert(
+            key.to_string(),
+            Entry {
+                value,
+                expires: now.checked_add(ttl),
+            },
+        );
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<()> {
+        self.values.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
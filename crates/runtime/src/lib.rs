@@ -4,8 +4,94 @@
 
 #![deny(missing_docs)]
 
+mod admin;
+mod audit;
+mod blob;
+mod cache;
+mod canary;
+mod capture;
+mod clock;
+mod concurrency;
+mod csp;
+mod discovery;
+mod environment;
+mod error;
+mod etag;
+mod fetch;
+mod grpc;
+mod headers;
 mod host;
+mod i18n;
+mod idempotency;
+mod invocation;
+mod kv;
+mod limits;
+mod listener;
 mod log;
+mod metrics;
+mod mirror;
+mod multipart;
+mod preopen;
+mod quota;
+mod registry;
+mod reload;
+mod resilience;
+mod routes;
 mod server;
+mod session;
+mod signature;
+mod signing;
+mod sql;
+mod templates;
+mod trace;
+mod validate;
 
-pub use server::{EnvironmentProvider, Server};
+pub use admin::AdminServer;
+#[cfg(unix)]
+pub use audit::SyslogAuditSink;
+pub use audit::{AuditRecord, AuditSink, CapabilityUsage, FileAuditSink};
+pub use blob::{BlobObject, BlobProvider, BlobWriter, DirectoryBlobProvider};
+pub use canary::{CanaryPolicy, CanaryStatus};
+pub use capture::CapturedRequest;
+pub use clock::{Clock, ManualClock};
+pub use discovery::{DiscoveryProvider, DnsDiscovery, ServiceEndpoint, StaticDiscovery};
+pub use environment::EnvironmentProvider;
+pub use error::{
+    ErrorRenderer, HostError, InvocationError, ProblemErrorRenderer, RenderedError, ServerError,
+    TemplateErrorRenderer,
+};
+pub use fetch::{AllowedHost, FetchProvider, FetchResponse};
+pub use grpc::{GrpcProvider, GrpcResponse, GrpcStatus};
+pub use headers::{DuplicateHeaders, HeaderCase};
+pub use invocation::InvocationRequest;
+pub use kv::{KvProvider, MemoryKvProvider};
+pub use metrics::FunctionStats;
+pub use mirror::{MirrorPolicy, MirrorStatus, MirrorTarget};
+pub use quota::{
+    MemoryQuotaStore, QuotaLimits, QuotaPolicy, QuotaStore, QuotaUsage, SHARED_TENANT,
+};
+pub use registry::{Registry, RouteParams};
+pub use reload::ServerConfig;
+pub use resilience::{CircuitBreakerPolicy, RetryPolicy};
+pub use routes::{FunctionInfo, Route, RouteTable};
+pub use server::{
+    HostCalls, Interruption, InvocationReport, InvocationStats, LocalServer, OptLevel,
+    RequestExtensions, Server, ServerBuilder,
+};
+#[cfg(feature = "postgres")]
+pub use sql::PostgresProvider;
+#[cfg(feature = "sqlite")]
+pub use sql::SqliteProvider;
+pub use sql::{SqlMetrics, SqlProvider, SqlValue};
+pub use trace::TraceSampling;
+
+/// The version of the host interface implemented by the runtime.
+///
+/// Modules record the version of the host interface they were built for; modules built for a
+/// newer version are rejected when the server is built.
+pub const HOST_INTERFACE_VERSION: u32 = 17;
+
+/// The oldest version of the host interface supported by the runtime.
+///
+/// Modules built for an older version are rejected unless [`ServerBuilder::allow_older_sdk`] is set.
+pub const MIN_INTERFACE_VERSION: u32 = 1;
@@ -0,0 +1,83 @@
+use crate::server::State;
+use async_std::io::{self, Read};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tide::http::mime;
+use tide::{Body, Middleware, Next, Request, Response, StatusCode};
+
+/// A middleware that limits the size of request bodies to the server's configured maximum.
+///
+/// Requests with a declared length over the limit receive a `413 Payload Too Large` response;
+/// reading a body of unknown length fails once the limit is exceeded.
+#[derive(Debug, Clone)]
+pub struct BodyLimitMiddleware;
+
+#[async_trait::async_trait]
+impl Middleware<State> for BodyLimitMiddleware {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        // The limit is read for each request as it can be changed while the server is serving
+        let limit = match req.state().config().max_body_size {
+            Some(limit) => limit,
+            None => return Ok(next.run(req).await),
+        };
+
+        let body = req.take_body();
+
+        match body.len() {
+            Some(len) if len as u64 > limit => {
+                let mut res = Response::builder(StatusCode::PayloadTooLarge)
+                    .content_type(mime::PLAIN)
+                    .body(format!("the request body exceeds {} bytes", limit))
+                    .build();
+                res.set_error(anyhow::anyhow!(
+                    "request body of {} bytes exceeds the limit of {} bytes",
+                    len,
+                    limit
+                ));
+                return Ok(res);
+            }
+            len => {
+                let mime = body.mime().clone();
+                let mut limited = Body::from_reader(
+                    io::BufReader::new(LimitedReader {
+                        inner: body,
+                        remaining: limit,
+                    }),
+                    len,
+                );
+                limited.set_mime(mime);
+                req.set_body(limited);
+            }
+        }
+
+        Ok(next.run(req).await)
+    }
+}
+
+/// A reader that fails once more than the given number of bytes are read.
+struct LimitedReader<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read + Unpin> Read for LimitedReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        // Read one byte past the limit so that an oversized body can be detected
+        let max = self.remaining.saturating_add(1).min(buf.len() as u64) as usize;
+        let read = futures::ready!(Pin::new(&mut self.inner).poll_read(cx, &mut buf[..max]))?;
+
+        if read as u64 > self.remaining {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the request body exceeds the maximum size",
+            )));
+        }
+
+        self.remaining -= read as u64;
+        Poll::Ready(Ok(read))
+    }
+}
@@ -0,0 +1,284 @@
+use async_h1::server::{decode, Encoder};
+use async_std::future::timeout;
+use async_std::io;
+use async_std::net::{TcpListener as AsyncTcpListener, TcpStream};
+use async_std::prelude::*;
+use async_std::task;
+use socket2::{Domain, Socket, Type};
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tide::http::headers::CONNECTION;
+use tide::listener::{ListenInfo, Listener};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_BACKLOG: u32 = 128;
+
+/// The options that control how the server handles connections.
+#[derive(Debug, Clone)]
+pub struct ConnectionOptions {
+    pub keep_alive: bool,
+    pub keep_alive_timeout: Duration,
+    pub max_requests: Option<usize>,
+    pub header_read_timeout: Duration,
+    pub nodelay: bool,
+    pub backlog: u32,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        Self {
+            keep_alive: true,
+            keep_alive_timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            max_requests: None,
+            header_read_timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            nodelay: false,
+            backlog: DEFAULT_BACKLOG,
+        }
+    }
+}
+
+/// A TCP listener that applies the connection options.
+///
+/// This is used in place of tide's TCP listener as it exposes none of these options.
+pub struct TcpListener<State> {
+    addr: SocketAddr,
+    options: ConnectionOptions,
+    listener: Option<AsyncTcpListener>,
+    server: Option<tide::Server<State>>,
+    info: Option<ListenInfo>,
+    active: Arc<AtomicUsize>,
+    received: Arc<AtomicU64>,
+}
+
+impl<State> TcpListener<State> {
+    pub fn new(addr: SocketAddr, options: ConnectionOptions) -> Self {
+        Self {
+            addr,
+            options,
+            listener: None,
+            server: None,
+            info: None,
+            active: Arc::new(AtomicUsize::new(0)),
+            received: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Creates a listener that accepts connections on an already-bound socket.
+    pub fn from_std(
+        listener: std::net::TcpListener,
+        options: ConnectionOptions,
+    ) -> io::Result<Self> {
+        listener.set_nonblocking(true)?;
+
+        let mut this = Self::new(listener.local_addr()?, options);
+        this.listener = Some(listener.into());
+        Ok(this)
+    }
+
+    /// Gets the counter of requests being processed by the listener.
+    pub fn active_requests(&self) -> Arc<AtomicUsize> {
+        self.active.clone()
+    }
+
+    /// Gets the counter of requests received by the listener.
+    pub fn received_requests(&self) -> Arc<AtomicU64> {
+        self.received.clone()
+    }
+
+    /// Gets the address the listener binds to.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Gets the local address of the listener once bound.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.as_ref().and_then(|l| l.local_addr().ok())
+    }
+}
+
+impl<State: Clone + Send + Sync + 'static> TcpListener<State> {
+    async fn serve(
+        server: tide::Server<State>,
+        stream: TcpStream,
+        options: ConnectionOptions,
+        active: Arc<AtomicUsize>,
+        received: Arc<AtomicU64>,
+    ) -> tide::http::Result<()> {
+        let local_addr = stream.local_addr().ok();
+        let peer_addr = stream.peer_addr().ok();
+        let mut requests = 0;
+
+        loop {
+            // Wait for the start of the next request, closing the connection if idle
+            let mut buf = [0u8; 1];
+            let idle_timeout = if requests == 0 {
+                options.header_read_timeout
+            } else {
+                options.keep_alive_timeout
+            };
+
+            match timeout(idle_timeout, stream.peek(&mut buf)).await {
+                Ok(Ok(n)) if n > 0 => {}
+                _ => return Ok(()),
+            }
+
+            let (mut req, mut body) =
+                match timeout(options.header_read_timeout, decode(stream.clone())).await {
+                    Ok(Ok(Some(r))) => r,
+                    Ok(Ok(None)) | Err(_) => return Ok(()),
+                    Ok(Err(e)) => return Err(e),
+                };
+
+            requests += 1;
+            received.fetch_add(1, Ordering::Relaxed);
+
+            active.fetch_add(1, Ordering::SeqCst);
+            let _active = Active(&active);
+
+            req.set_local_addr(local_addr);
+            req.set_peer_addr(peer_addr);
+
+            let mut close = !options.keep_alive
+                || options.max_requests.map(|m| requests >= m).unwrap_or(false)
+                || req
+                    .header(CONNECTION)
+                    .map(|c| c.as_str().eq_ignore_ascii_case("close"))
+                    .unwrap_or(false);
+
+            let method = req.method();
+            let mut res: tide::http::Response = server.respond(req).await?;
+
+            if close {
+                res.insert_header(CONNECTION, "close");
+            } else {
+                close = res
+                    .header(CONNECTION)
+                    .map(|c| c.as_str().eq_ignore_ascii_case("close"))
+                    .unwrap_or(false);
+            }
+
+            io::copy(&mut Encoder::new(res, method), &mut stream.clone()).await?;
+            io::copy(&mut body, &mut io::sink()).await?;
+
+            if close {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<State: Clone + Send + Sync + 'static> Listener<State> for TcpListener<State> {
+    async fn bind(&mut self, server: tide::Server<State>) -> io::Result<()> {
+        assert!(self.server.is_none(), "`bind` should only be called once");
+        self.server = Some(server);
+
+        // A listener created from an existing socket is already bound
+        if self.listener.is_none() {
+            let socket = Socket::new(Domain::for_address(self.addr), Type::STREAM, None)?;
+            socket.set_reuse_address(true)?;
+            socket.bind(&self.addr.into())?;
+            socket.listen(self.options.backlog as i32)?;
+
+            let listener: std::net::TcpListener = socket.into();
+            self.listener = Some(listener.into());
+        }
+
+        self.info = Some(ListenInfo::new(self.to_string(), "tcp".to_string(), false));
+
+        Ok(())
+    }
+
+    async fn accept(&mut self) -> io::Result<()> {
+        let server = self
+            .server
+            .take()
+            .expect("`Listener::bind` must be called before `Listener::accept`");
+        let listener = self
+            .listener
+            .take()
+            .expect("`Listener::bind` must be called before `Listener::accept`");
+
+        let mut incoming = listener.incoming();
+
+        while let Some(stream) = incoming.next().await {
+            match stream {
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::ConnectionRefused
+                            | io::ErrorKind::ConnectionAborted
+                            | io::ErrorKind::ConnectionReset
+                    ) =>
+                {
+                    continue
+                }
+                Err(e) => {
+                    let delay = Duration::from_millis(500);
+                    log::error!(
+                        "Failed to accept connection: {}. Pausing for {:?}.",
+                        e,
+                        delay
+                    );
+                    task::sleep(delay).await;
+                }
+                Ok(stream) => {
+                    if self.options.nodelay {
+                        if let Err(e) = stream.set_nodelay(true) {
+                            log::warn!("Failed to set TCP_NODELAY on connection: {}", e);
+                        }
+                    }
+
+                    let server = server.clone();
+                    let options = self.options.clone();
+                    let active = self.active.clone();
+                    let received = self.received.clone();
+
+                    task::spawn(async move {
+                        if let Err(e) = Self::serve(server, stream, options, active, received).await
+                        {
+                            log::error!("Failed to process connection: {}", e);
+                        }
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn info(&self) -> Vec<ListenInfo> {
+        self.info.iter().cloned().collect()
+    }
+}
+
+// Decrements the active request count once a response is sent or the request is abandoned
+struct Active<'a>(&'a AtomicUsize);
+
+impl Drop for Active<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl<State> fmt::Debug for TcpListener<State> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TcpListener")
+            .field("addr", &self.addr)
+            .field("options", &self.options)
+            .field("listener", &self.listener)
+            .finish()
+    }
+}
+
+impl<State> fmt::Display for TcpListener<State> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.local_addr() {
+            Some(addr) => write!(f, "http://{}", addr),
+            None => write!(f, "http://{}", self.addr),
+        }
+    }
+}
@@ -1,5 +1,55 @@
+use std::io::Write;
 use tide::{Middleware, Next, Request};
 
+/// The log target of records written by functions.
+const GUEST_TARGET: &str = "wasmtime_functions_runtime::guest";
+
+/// Writes the output of a function to the log, one record per line.
+pub struct GuestOutput {
+    level: log::Level,
+    buffer: Vec<u8>,
+}
+
+impl GuestOutput {
+    pub fn new(level: log::Level) -> Self {
+        Self {
+            level,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn emit(&self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        log::log!(target: GUEST_TARGET, self.level, "{}", line.trim_end_matches('\r'));
+    }
+}
+
+impl Write for GuestOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<_> = self.buffer.drain(..=end).collect();
+            self.emit(&line[..end]);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for GuestOutput {
+    fn drop(&mut self) {
+        // Log any trailing output that wasn't terminated by a newline
+        if !self.buffer.is_empty() {
+            self.emit(&self.buffer);
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct LogMiddleware;
 
@@ -0,0 +1,499 @@
+use crate::quota::QuotaMetrics;
+use crate::resilience::CircuitMetrics;
+use crate::sql::SqlMetrics;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const PREFIX: &str = "wasmtime_functions";
+
+/// A snapshot of the invocation statistics of a function.
+#[derive(Debug, Clone, Default)]
+pub struct FunctionStats {
+    /// The number of times the function was invoked.
+    pub invocations: u64,
+    /// The total time spent in invocations of the function.
+    pub invocation_time: Duration,
+    /// The number of responses served from the response cache without invoking the function.
+    pub cache_hits: u64,
+    /// The number of requests shed because the function was at capacity.
+    pub shed: u64,
+    /// The number of invocations that exhausted an injected fuel limit.
+    pub fuel_exhausted: u64,
+    /// The total number of bytes invocations grew the memory of the function's instances by.
+    pub memory_growth: u64,
+    /// The largest memory of an instance in bytes after an invocation of the function.
+    pub peak_memory: u64,
+    /// The number of instances suspected of leaking memory because their memory grew on many
+    /// consecutive invocations of the function.
+    pub suspected_leaks: u64,
+    /// The number of responses to requests for the function's routes by status code.
+    pub responses: BTreeMap<u16, u64>,
+}
+
+/// Records metrics for the invocations of a function.
+#[derive(Default)]
+pub struct FunctionMetrics {
+    responses: Mutex<BTreeMap<u16, u64>>,
+    invocations: AtomicU64,
+    invocation_micros: AtomicU64,
+    cache_hits: AtomicU64,
+    shed: AtomicU64,
+    fuel_exhausted: AtomicU64,
+    memory_growth: AtomicU64,
+    peak_memory: AtomicU64,
+    suspected_leaks: AtomicU64,
+}
+
+impl FunctionMetrics {
+    /// Records an invocation of the function.
+    pub fn invoked(&self, status: u16, elapsed: Duration) {
+        self.invocations.fetch_add(1, Ordering::Relaxed);
+        self.invocation_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        self.responded(status);
+    }
+
+    /// Records a response served from the response cache.
+    pub fn cache_hit(&self, status: u16) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        self.responded(status);
+    }
+
+    /// Records a request that was shed because the function was at capacity.
+    pub fn shed(&self, status: u16) {
+        self.shed.fetch_add(1, Ordering::Relaxed);
+        self.responded(status);
+    }
+
+    /// Records an invocation that exhausted an injected fuel limit.
+    ///
+    /// The invocation itself is recorded with [`invoked`](Self::invoked).
+    pub fn fuel_exhausted(&self) {
+        self.fuel_exhausted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records the size of an instance's memory after an invocation and how much it grew by.
+    pub fn memory(&self, size: u64, growth: u64) {
+        self.memory_growth.fetch_add(growth, Ordering::Relaxed);
+        self.peak_memory.fetch_max(size, Ordering::Relaxed);
+    }
+
+    /// Records an instance suspected of leaking memory.
+    pub fn leak_suspected(&self) {
+        self.suspected_leaks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a request that was rejected without invoking the function.
+    pub fn rejected(&self, status: u16) {
+        self.responded(status);
+    }
+
+    fn responded(&self, status: u16) {
+        *self.responses.lock().unwrap().entry(status).or_default() += 1;
+    }
+
+    /// Gets a snapshot of the function's statistics.
+    pub fn stats(&self) -> FunctionStats {
+        FunctionStats {
+            invocations: self.invocations.load(Ordering::Relaxed),
+            invocation_time: Duration::from_micros(self.invocation_micros.load(Ordering::Relaxed)),
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            shed: self.shed.load(Ordering::Relaxed),
+            fuel_exhausted: self.fuel_exhausted.load(Ordering::Relaxed),
+            memory_growth: self.memory_growth.load(Ordering::Relaxed),
+            peak_memory: self.peak_memory.load(Ordering::Relaxed),
+            suspected_leaks: self.suspected_leaks.load(Ordering::Relaxed),
+            responses: self.responses.lock().unwrap().clone(),
+        }
+    }
+}
+
+/// The metrics of the functions of a module.
+#[derive(Default)]
+pub struct Metrics(Mutex<BTreeMap<String, Arc<FunctionMetrics>>>);
+
+impl Metrics {
+    /// Gets the metrics of the given function.
+    pub fn function(&self, name: &str) -> Arc<FunctionMetrics> {
+        self.0
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Gets the memory usage of every function.
+    pub fn memory(&self) -> Vec<FunctionMemory> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, metrics)| FunctionMemory {
+                function: name.clone(),
+                memory_growth: metrics.memory_growth.load(Ordering::Relaxed),
+                peak_memory: metrics.peak_memory.load(Ordering::Relaxed),
+                suspected_leaks: metrics.suspected_leaks.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+/// The memory usage of a function's instances.
+#[derive(serde::Serialize)]
+pub struct FunctionMemory {
+    pub function: String,
+    pub memory_growth: u64,
+    pub peak_memory: u64,
+    pub suspected_leaks: u64,
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn labels(mount: &str, function: Option<&str>, extra: &[(&str, &str)]) -> String {
+    let mut labels = Vec::new();
+
+    if !mount.is_empty() {
+        labels.push(format!("mount=\"{}\"", escape(mount)));
+    }
+
+    if let Some(function) = function {
+        labels.push(format!("function=\"{}\"", escape(function)));
+    }
+
+    for (name, value) in extra {
+        labels.push(format!("{}=\"{}\"", name, escape(value)));
+    }
+
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+/// The metrics of a mounted module.
+pub struct MountMetrics<'a> {
+    pub prefix: &'a str,
+    pub metrics: &'a Metrics,
+    pub sql: Option<SqlMetrics>,
+    pub circuits: Vec<CircuitMetrics>,
+    pub quota: Option<QuotaMetrics>,
+    pub ready: bool,
+}
+
+/// Renders metrics in the Prometheus text exposition format.
+pub fn render(mounts: &[MountMetrics]) -> String {
+    let mut out = String::new();
+
+    let mut family = |name: &str, ty: &str, help: &str, samples: Vec<(String, String, String)>| {
+        writeln!(out, "# HELP {}_{} {}", PREFIX, name, help).unwrap();
+        writeln!(out, "# TYPE {}_{} {}", PREFIX, name, ty).unwrap();
+
+        for (suffix, labels, value) in samples {
+            writeln!(out, "{}_{}{}{} {}", PREFIX, name, suffix, labels, value).unwrap();
+        }
+    };
+
+    let functions: Vec<_> = mounts
+        .iter()
+        .flat_map(|m| {
+            m.metrics
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(name, metrics)| (m.prefix, name.clone(), metrics.clone()))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    family(
+        "ready",
+        "gauge",
+        "Whether every environment variable of the module has been resolved.",
+        mounts
+            .iter()
+            .map(|m| {
+                (
+                    String::new(),
+                    labels(m.prefix, None, &[]),
+                    (m.ready as u8).to_string(),
+                )
+            })
+            .collect(),
+    );
+
+    family(
+        "responses_total",
+        "counter",
+        "The number of responses sent by status code.",
+        functions
+            .iter()
+            .flat_map(|(mount, name, metrics)| {
+                metrics
+                    .responses
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|(status, count)| {
+                        (
+                            String::new(),
+                            labels(mount, Some(name), &[("status", &status.to_string())]),
+                            count.to_string(),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect(),
+    );
+
+    family(
+        "invocation_seconds",
+        "summary",
+        "The time spent invoking functions.",
+        functions
+            .iter()
+            .flat_map(|(mount, name, metrics)| {
+                let labels = labels(mount, Some(name), &[]);
+                vec![
+                    (
+                        "_sum".to_string(),
+                        labels.clone(),
+                        (metrics.invocation_micros.load(Ordering::Relaxed) as f64 / 1e6)
+                            .to_string(),
+                    ),
+                    (
+                        "_count".to_string(),
+                        labels,
+                        metrics.invocations.load(Ordering::Relaxed).to_string(),
+                    ),
+                ]
+            })
+            .collect(),
+    );
+
+    family(
+        "cache_hits_total",
+        "counter",
+        "The number of responses served from the response cache.",
+        functions
+            .iter()
+            .map(|(mount, name, metrics)| {
+                (
+                    String::new(),
+                    labels(mount, Some(name), &[]),
+                    metrics.cache_hits.load(Ordering::Relaxed).to_string(),
+                )
+            })
+            .collect(),
+    );
+
+    family(
+        "shed_total",
+        "counter",
+        "The number of requests shed because the function was at capacity.",
+        functions
+            .iter()
+            .map(|(mount, name, metrics)| {
+                (
+                    String::new(),
+                    labels(mount, Some(name), &[]),
+                    metrics.shed.load(Ordering::Relaxed).to_string(),
+                )
+            })
+            .collect(),
+    );
+
+    family(
+        "fuel_exhausted_total",
+        "counter",
+        "The number of invocations that exhausted an injected fuel limit.",
+        functions
+            .iter()
+            .map(|(mount, name, metrics)| {
+                (
+                    String::new(),
+                    labels(mount, Some(name), &[]),
+                    metrics.fuel_exhausted.load(Ordering::Relaxed).to_string(),
+                )
+            })
+            .collect(),
+    );
+
+    family(
+        "memory_growth_bytes_total",
+        "counter",
+        "The number of bytes invocations grew the memory of instances by.",
+        functions
+            .iter()
+            .map(|(mount, name, metrics)| {
+                (
+                    String::new(),
+                    labels(mount, Some(name), &[]),
+                    metrics.memory_growth.load(Ordering::Relaxed).to_string(),
+                )
+            })
+            .collect(),
+    );
+
+    family(
+        "memory_peak_bytes",
+        "gauge",
+        "The largest memory of an instance after an invocation.",
+        functions
+            .iter()
+            .map(|(mount, name, metrics)| {
+                (
+                    String::new(),
+                    labels(mount, Some(name), &[]),
+                    metrics.peak_memory.load(Ordering::Relaxed).to_string(),
+                )
+            })
+            .collect(),
+    );
+
+    family(
+        "memory_leaks_suspected_total",
+        "counter",
+        "The number of instances whose memory grew on many consecutive invocations.",
+        functions
+            .iter()
+            .map(|(mount, name, metrics)| {
+                (
+                    String::new(),
+                    labels(mount, Some(name), &[]),
+                    metrics.suspected_leaks.load(Ordering::Relaxed).to_string(),
+                )
+            })
+            .collect(),
+    );
+
+    let sql: Vec<_> = mounts
+        .iter()
+        .filter_map(|m| m.sql.map(|sql| (labels(m.prefix, None, &[]), sql)))
+        .collect();
+
+    if !sql.is_empty() {
+        let counter = |f: fn(&SqlMetrics) -> String| {
+            sql.iter()
+                .map(|(labels, metrics)| (String::new(), labels.clone(), f(metrics)))
+                .collect::<Vec<_>>()
+        };
+
+        family(
+            "sql_statements_total",
+            "counter",
+            "The number of SQL statements executed.",
+            counter(|m| m.statements.to_string()),
+        );
+        family(
+            "sql_failures_total",
+            "counter",
+            "The number of SQL statements that failed, including those that timed out.",
+            counter(|m| m.failures.to_string()),
+        );
+        family(
+            "sql_timeouts_total",
+            "counter",
+            "The number of SQL statements that timed out.",
+            counter(|m| m.timeouts.to_string()),
+        );
+        family(
+            "sql_seconds_total",
+            "counter",
+            "The time spent executing SQL statements.",
+            counter(|m| m.total_time.as_secs_f64().to_string()),
+        );
+    }
+
+    let circuits: Vec<_> = mounts
+        .iter()
+        .flat_map(|m| {
+            m.circuits.iter().map(move |c| {
+                (
+                    labels(
+                        m.prefix,
+                        None,
+                        &[("binding", c.binding), ("destination", &c.destination)],
+                    ),
+                    c,
+                )
+            })
+        })
+        .collect();
+
+    if !circuits.is_empty() {
+        let sample = |f: fn(&CircuitMetrics) -> String| {
+            circuits
+                .iter()
+                .map(|(labels, metrics)| (String::new(), labels.clone(), f(metrics)))
+                .collect::<Vec<_>>()
+        };
+
+        family(
+            "outbound_circuit_state",
+            "gauge",
+            "The state of the circuit of an outbound destination: 0 when closed, 1 when open, and 2 when half-open.",
+            sample(|m| m.state.to_string()),
+        );
+        family(
+            "outbound_failures_total",
+            "counter",
+            "The number of failed attempts at outbound calls.",
+            sample(|m| m.failures.to_string()),
+        );
+        family(
+            "outbound_retries_total",
+            "counter",
+            "The number of outbound calls retried after a failed attempt.",
+            sample(|m| m.retries.to_string()),
+        );
+        family(
+            "outbound_rejections_total",
+            "counter",
+            "The number of outbound calls rejected because the destination's circuit was open.",
+            sample(|m| m.rejections.to_string()),
+        );
+    }
+
+    let quotas: Vec<_> = mounts
+        .iter()
+        .filter_map(|m| m.quota.map(|quota| (m.prefix, quota)))
+        .collect();
+
+    if !quotas.is_empty() {
+        family(
+            "quota_rejections_total",
+            "counter",
+            "The number of requests rejected because the tenant exhausted a quota.",
+            quotas
+                .iter()
+                .flat_map(|(mount, metrics)| {
+                    vec![
+                        (
+                            String::new(),
+                            labels(mount, None, &[("quota", "requests")]),
+                            metrics.rejected_requests.to_string(),
+                        ),
+                        (
+                            String::new(),
+                            labels(mount, None, &[("quota", "fuel")]),
+                            metrics.rejected_fuel.to_string(),
+                        ),
+                    ]
+                })
+                .collect(),
+        );
+    }
+
+    out
+}
@@ -0,0 +1,272 @@
+use crate::server::{ServerBuilder, State};
+use async_trait::async_trait;
+use http_types::{StatusCode, Url};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tide::Endpoint;
+
+/// The headers that are not copied to mirrored requests as they only apply to the original connection.
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "content-length",
+];
+
+/// The headers that are removed from requests mirrored to a URL unless credentials are forwarded.
+const CREDENTIAL_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-api-key",
+    "x-auth-token",
+];
+
+/// The maximum time a mirrored request sent to a URL may take.
+const MIRROR_URL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Determines which requests are mirrored to a shadow target.
+#[derive(Debug, Clone)]
+pub struct MirrorPolicy {
+    percent: u8,
+    max_pending: usize,
+    forward_credentials: bool,
+}
+
+impl MirrorPolicy {
+    /// Creates a policy that mirrors the given percentage of requests.
+    ///
+    /// Percentages over 100 are treated as 100.
+    pub fn new(percent: u8) -> Self {
+        Self {
+            percent: percent.min(100),
+            max_pending: 64,
+            forward_credentials: false,
+        }
+    }
+
+    /// Sets the maximum number of mirrored requests processed at once.
+    ///
+    /// Requests that would exceed the maximum are not mirrored, so that a slow shadow target can't
+    /// exhaust the server's resources. Defaults to 64.
+    pub fn max_pending(mut self, max: usize) -> Self {
+        self.max_pending = max.max(1);
+        self
+    }
+
+    /// Sets whether credential headers are forwarded to a [`MirrorTarget::Url`].
+    ///
+    /// By default, `Authorization`, `Proxy-Authorization`, `Cookie`, `X-Api-Key`, and `X-Auth-Token`
+    /// are removed from requests mirrored to a URL, so that users' credentials are not sent to a server
+    /// outside of the application. Requests mirrored to a module always keep their headers.
+    pub fn forward_credentials(mut self, enabled: bool) -> Self {
+        self.forward_credentials = enabled;
+        self
+    }
+}
+
+/// The target that requests are mirrored to.
+pub enum MirrorTarget<'a> {
+    /// Mirror requests to another module, such as a rewritten version of the application.
+    ///
+    /// The module's functions run with their own bindings, so any side effects of a mirrored request
+    /// happen twice; configure the module with separate databases and allowed hosts.
+    Module(ServerBuilder<'a>),
+    /// Mirror requests to an HTTP server, with the path and query of each request appended to the URL.
+    ///
+    /// Credential headers are removed unless forwarded with [`MirrorPolicy::forward_credentials`].
+    Url(Url),
+}
+
+/// Represents the status of request mirroring.
+#[derive(Debug, Clone, Copy)]
+pub struct MirrorStatus {
+    /// The number of requests mirrored whose shadow response has been received.
+    pub mirrored: u64,
+    /// The number of shadow responses whose status code differed from the primary response.
+    pub mismatched: u64,
+    /// The number of mirrored requests that failed without a response.
+    pub failed: u64,
+    /// The number of sampled requests that were not mirrored as too many were pending.
+    pub dropped: u64,
+}
+
+pub enum Shadow {
+    Module(tide::Server<State>, State),
+    Url(Url, surf::Client),
+}
+
+/// Mirrors a sample of requests to a shadow target, discarding the shadow responses.
+pub struct Mirror {
+    policy: MirrorPolicy,
+    primary: tide::Server<State>,
+    shadow: Shadow,
+    received: AtomicU64,
+    pending: AtomicUsize,
+    mirrored: AtomicU64,
+    mismatched: AtomicU64,
+    failed: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl Mirror {
+    pub fn new(policy: MirrorPolicy, primary: tide::Server<State>, shadow: Shadow) -> Self {
+        Self {
+            policy,
+            primary,
+            shadow,
+            received: AtomicU64::new(0),
+            pending: AtomicUsize::new(0),
+            mirrored: AtomicU64::new(0),
+            mismatched: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Gets the state of the shadow module, if requests are mirrored to a module.
+    pub fn shadow_state(&self) -> Option<&State> {
+        match &self.shadow {
+            Shadow::Module(_, state) => Some(state),
+            Shadow::Url(..) => None,
+        }
+    }
+
+    pub fn status(&self) -> MirrorStatus {
+        MirrorStatus {
+            mirrored: self.mirrored.load(Ordering::SeqCst),
+            mismatched: self.mismatched.load(Ordering::SeqCst),
+            failed: self.failed.load(Ordering::SeqCst),
+            dropped: self.dropped.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Determines if a request should be mirrored, reserving a pending slot if so.
+    fn select(&self) -> bool {
+        // Requests are sampled by count rather than at random so that the sample is exact
+        if self.received.fetch_add(1, Ordering::SeqCst) % 100 >= self.policy.percent as u64 {
+            return false;
+        }
+
+        if self.pending.fetch_add(1, Ordering::SeqCst) >= self.policy.max_pending {
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+            self.dropped.fetch_add(1, Ordering::SeqCst);
+            return false;
+        }
+
+        true
+    }
+
+    /// Sends a mirrored request to the shadow target and compares its status with the primary's.
+    async fn send(&self, req: http_types::Request, primary: StatusCode) {
+        let method = req.method();
+        let path = req.url().path().to_string();
+
+        let status = match &self.shadow {
+            Shadow::Module(app, _) => app
+                .respond::<_, http_types::Response>(req)
+                .await
+                .map(|res| res.status())
+                .map_err(|e| e.into_inner()),
+            Shadow::Url(base, client) => {
+                let mut url = base.clone();
+                url.set_path(&format!(
+                    "{}{}",
+                    base.path().trim_end_matches('/'),
+                    req.url().path()
+                ));
+                url.set_query(req.url().query());
+
+                let mut req = req;
+                req.remove_header("host");
+
+                if !self.policy.forward_credentials {
+                    for name in CREDENTIAL_HEADERS {
+                        req.remove_header(*name);
+                    }
+                }
+                *req.url_mut() = url;
+
+                match async_std::future::timeout(MIRROR_URL_TIMEOUT, client.send(req)).await {
+                    Ok(res) => res.map(|res| res.status()).map_err(|e| e.into_inner()),
+                    Err(_) => Err(anyhow::anyhow!("the request timed out")),
+                }
+            }
+        };
+
+        self.pending.fetch_sub(1, Ordering::SeqCst);
+
+        match status {
+            Ok(status) => {
+                self.mirrored.fetch_add(1, Ordering::SeqCst);
+
+                if status != primary {
+                    self.mismatched.fetch_add(1, Ordering::SeqCst);
+                    log::warn!(
+                        "Mirrored request {} {} returned status {} but the primary returned {}.",
+                        method,
+                        path,
+                        status as u16,
+                        primary as u16
+                    );
+                }
+            }
+            Err(e) => {
+                self.failed.fetch_add(1, Ordering::SeqCst);
+                log::warn!("Mirrored request {} {} failed: {:#}", method, path, e);
+            }
+        }
+    }
+}
+
+/// The endpoint that dispatches every request of a mirrored server.
+#[derive(Clone)]
+pub struct MirrorEndpoint(pub Arc<Mirror>);
+
+#[async_trait]
+impl Endpoint<State> for MirrorEndpoint {
+    async fn call(&self, mut req: tide::Request<State>) -> tide::Result {
+        if !self.0.select() {
+            return self.0.primary.call(req).await;
+        }
+
+        let body = match req.body_bytes().await {
+            Ok(body) => body,
+            Err(e) => {
+                self.0.pending.fetch_sub(1, Ordering::SeqCst);
+                return Err(e);
+            }
+        };
+
+        let mut shadow = http_types::Request::new(req.method(), req.url().clone());
+        for (name, values) in req.iter() {
+            if HOP_BY_HOP_HEADERS.contains(&name.as_str()) {
+                continue;
+            }
+
+            for value in values {
+                shadow.append_header(name.clone(), value.as_str());
+            }
+        }
+        shadow.set_peer_addr(req.peer_addr());
+        shadow.set_local_addr(req.local_addr());
+        shadow.set_body(body.clone());
+        req.set_body(body);
+
+        let res = self.0.primary.call(req).await;
+        let status = match &res {
+            Ok(res) => res.status(),
+            Err(e) => e.status(),
+        };
+
+        // The shadow request is sent after the primary responds so that it can't delay the response
+        let mirror = self.0.clone();
+        async_std::task::spawn(async move { mirror.send(shadow, status).await });
+
+        res
+    }
+}
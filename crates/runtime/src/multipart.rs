@@ -0,0 +1,185 @@
+use anyhow::{bail, Result};
+use async_std::io::{Read, ReadExt};
+
+// The size of the reads from the request body.
+const READ_SIZE: usize = 16 * 1024;
+
+// The maximum size of the headers of a part.
+const MAX_PART_HEADERS: usize = 8 * 1024;
+
+/// The headers of a part of a `multipart/form-data` body.
+#[derive(Debug, Clone, Default)]
+pub struct Part {
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+}
+
+/// Gets the boundary from the `Content-Type` of a request, if it is `multipart/form-data`.
+pub fn boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+
+    if !params
+        .next()?
+        .trim()
+        .eq_ignore_ascii_case("multipart/form-data")
+    {
+        return None;
+    }
+
+    params.find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if name.trim().eq_ignore_ascii_case("boundary") {
+            Some(value.trim().trim_matches('"').to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Reads the parts of a `multipart/form-data` body as it is received.
+///
+/// The content of a part is read in chunks so that a part is never held in memory in its entirety.
+pub struct MultipartReader<R> {
+    body: R,
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    in_part: bool,
+    done: bool,
+}
+
+impl<R: Read + Unpin> MultipartReader<R> {
+    pub fn new(body: R, boundary: &str) -> Self {
+        Self {
+            body,
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            // The first delimiter isn't preceded by a line break, so one is assumed
+            buf: b"\r\n".to_vec(),
+            in_part: false,
+            done: false,
+        }
+    }
+
+    async fn fill(&mut self) -> Result<()> {
+        let mut chunk = [0; READ_SIZE];
+        let n = self.body.read(&mut chunk).await?;
+        if n == 0 {
+            bail!("the multipart body ended unexpectedly");
+        }
+
+        self.buf.extend_from_slice(&chunk[..n]);
+        Ok(())
+    }
+
+    fn find(&self, needle: &[u8]) -> Option<usize> {
+        self.buf.windows(needle.len()).position(|w| w == needle)
+    }
+
+    /// Advances to the next part, skipping the rest of the current part.
+    ///
+    /// Returns `None` once every part has been read.
+    pub async fn next_part(&mut self) -> Result<Option<Part>> {
+        while self.in_part {
+            self.chunk().await?;
+        }
+
+        if self.done {
+            return Ok(None);
+        }
+
+        // Skip to the end of the next delimiter; only the preamble precedes the first one
+        loop {
+            if let Some(pos) = self.find(&self.delimiter) {
+                self.buf.drain(..pos + self.delimiter.len());
+                break;
+            }
+
+            let keep = self.delimiter.len() - 1;
+            if self.buf.len() > keep {
+                self.buf.drain(..self.buf.len() - keep);
+            }
+
+            self.fill().await?;
+        }
+
+        while self.buf.len() < 2 {
+            self.fill().await?;
+        }
+
+        if self.buf.starts_with(b"--") {
+            self.done = true;
+            return Ok(None);
+        }
+
+        let headers = loop {
+            if let Some(pos) = self.find(b"\r\n\r\n") {
+                let headers = String::from_utf8_lossy(&self.buf[..pos]).into_owned();
+                self.buf.drain(..pos + 4);
+                break headers;
+            }
+
+            if self.buf.len() > MAX_PART_HEADERS {
+                bail!("the headers of a multipart part are too large");
+            }
+
+            self.fill().await?;
+        };
+
+        let mut part = Part::default();
+
+        for line in headers.split("\r\n") {
+            let (name, value) = match line.split_once(':') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => continue,
+            };
+
+            if name.eq_ignore_ascii_case("content-type") {
+                part.content_type = Some(value.to_string());
+            } else if name.eq_ignore_ascii_case("content-disposition") {
+                for param in value.split(';').skip(1) {
+                    let (name, value) = match param.split_once('=') {
+                        Some((name, value)) => (name.trim(), value.trim().trim_matches('"')),
+                        None => continue,
+                    };
+
+                    match name {
+                        "name" => part.name = value.to_string(),
+                        "filename" => part.filename = Some(value.to_string()),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        self.in_part = true;
+        Ok(Some(part))
+    }
+
+    /// Reads the next chunk of the content of the current part.
+    ///
+    /// Returns `None` once the content of the part has been read.
+    pub async fn chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        if !self.in_part {
+            return Ok(None);
+        }
+
+        loop {
+            match self.find(&self.delimiter) {
+                Some(0) => {
+                    self.in_part = false;
+                    return Ok(None);
+                }
+                Some(pos) => return Ok(Some(self.buf.drain(..pos).collect())),
+                None => {
+                    // The end of the buffer may be the start of a delimiter, so it is held back
+                    let keep = self.delimiter.len() - 1;
+                    if self.buf.len() > keep {
+                        return Ok(Some(self.buf.drain(..self.buf.len() - keep).collect()));
+                    }
+                }
+            }
+
+            self.fill().await?;
+        }
+    }
+}
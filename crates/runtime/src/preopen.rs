@@ -0,0 +1,54 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use wasi_common::dir::{DirCaps, DirEntry};
+use wasi_common::file::FileCaps;
+use wasi_common::{WasiCtx, WasiDir};
+use wasmtime_wasi::sync::{ambient_authority, Dir};
+
+/// Represents a host directory made available to functions.
+pub struct Preopen {
+    dir: Dir,
+    guest_path: PathBuf,
+    read_only: bool,
+}
+
+impl Preopen {
+    /// Opens the given host directory to be preopened at the given guest path.
+    pub fn open(host_path: &Path, guest_path: PathBuf, read_only: bool) -> std::io::Result<Self> {
+        Ok(Self {
+            dir: Dir::open_ambient_dir(host_path, ambient_authority())?,
+            guest_path,
+            read_only,
+        })
+    }
+
+    /// Adds the directory to the given WASI context.
+    pub fn push(&self, ctx: &mut WasiCtx) -> Result<()> {
+        let dir: Box<dyn WasiDir> = Box::new(wasmtime_wasi::sync::dir::Dir::from_cap_std(
+            self.dir.try_clone()?,
+        ));
+
+        if self.read_only {
+            // Grant only the capabilities needed to traverse the directory and read files
+            ctx.table().push(Box::new(DirEntry::new(
+                DirCaps::OPEN
+                    | DirCaps::READDIR
+                    | DirCaps::READLINK
+                    | DirCaps::PATH_FILESTAT_GET
+                    | DirCaps::FILESTAT_GET,
+                FileCaps::READ
+                    | FileCaps::SEEK
+                    | FileCaps::TELL
+                    | FileCaps::FILESTAT_GET
+                    | FileCaps::FADVISE
+                    | FileCaps::POLL_READWRITE,
+                Some(self.guest_path.clone()),
+                dir,
+            )))?;
+        } else {
+            ctx.push_preopened_dir(dir, &self.guest_path)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,358 @@
+use crate::server::{InvocationStats, Request};
+use crate::signing::digest;
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tide::{Response, StatusCode};
+
+/// The header that identifies the tenant of a request by default.
+pub const DEFAULT_QUOTA_HEADER: &str = "X-Api-Key";
+
+/// The tenant that requests without the key of a configured tenant are attributed to.
+pub const SHARED_TENANT: &str = "*";
+
+/// The number of tenants an in-memory store keeps usage for by default.
+const DEFAULT_MAX_TENANTS: usize = 10_000;
+
+/// The requests and fuel a tenant may use per window.
+///
+/// A limit of `None` is unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuotaLimits {
+    /// The number of requests a tenant may make per window.
+    pub requests: Option<u64>,
+    /// The fuel a tenant's function invocations may consume per window.
+    ///
+    /// Fuel is only measured when the server uses fuel interruption.
+    pub fuel: Option<u64>,
+}
+
+impl QuotaLimits {
+    /// Creates limits of the given number of requests and amount of fuel per window.
+    pub fn new(requests: Option<u64>, fuel: Option<u64>) -> Self {
+        Self { requests, fuel }
+    }
+}
+
+/// Determines how requests are attributed to tenants and how much each tenant may use.
+///
+/// Requests are attributed to the configured tenant whose key is the value of a header, such as an
+/// API key. Requests without the header, or with a key that is not configured, are attributed to a
+/// single shared tenant, so clients can't gain quota by making up keys.
+///
+/// Tenants are identified by a SHA-256 hash of their key, so keys are not kept by quota stores or
+/// exposed by the admin API; the shared tenant is identified as [`SHARED_TENANT`].
+#[derive(Debug, Clone)]
+pub struct QuotaPolicy {
+    window: Duration,
+    header: String,
+    default: QuotaLimits,
+    // The limits of configured tenants, by the hash of their key
+    tenants: HashMap<String, QuotaLimits>,
+}
+
+impl QuotaPolicy {
+    /// Creates a policy that applies the given limits per window to the shared tenant.
+    ///
+    /// Windows are fixed intervals aligned to the Unix epoch rather than sliding, so a tenant's usage
+    /// is reset at the start of each window. Windows shorter than a second are treated as one second.
+    pub fn new(window: Duration, limits: QuotaLimits) -> Self {
+        Self {
+            window: window.max(Duration::from_secs(1)),
+            header: DEFAULT_QUOTA_HEADER.to_string(),
+            default: limits,
+            tenants: HashMap::new(),
+        }
+    }
+
+    /// Sets the header that identifies the tenant of a request.
+    ///
+    /// Defaults to `X-Api-Key`.
+    pub fn header<T: Into<String>>(mut self, name: T) -> Self {
+        self.header = name.into();
+        self
+    }
+
+    /// Configures a tenant with the given key and limits, such as a tenant on a paid tier.
+    ///
+    /// Requests with the key count against the tenant's own quota rather than the shared tenant's.
+    pub fn tenant<T: AsRef<str>>(mut self, key: T, limits: QuotaLimits) -> Self {
+        self.tenants.insert(digest(key.as_ref().as_bytes()), limits);
+        self
+    }
+
+    /// Gets the tenant a request with the given key is attributed to.
+    fn tenant_of(&self, key: Option<&str>) -> String {
+        key.map(|key| digest(key.as_bytes()))
+            .filter(|tenant| self.tenants.contains_key(tenant))
+            .unwrap_or_else(|| SHARED_TENANT.to_string())
+    }
+
+    fn limits(&self, tenant: &str) -> QuotaLimits {
+        self.tenants.get(tenant).copied().unwrap_or(self.default)
+    }
+}
+
+/// The requests and fuel a tenant used in a window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuotaUsage {
+    /// The number of requests made.
+    pub requests: u64,
+    /// The fuel consumed by function invocations.
+    pub fuel: u64,
+}
+
+/// Stores the usage of each tenant so that quotas can be enforced.
+///
+/// Windows are numbered by the number of whole windows elapsed since the Unix epoch, so servers
+/// sharing a store with the same policy agree on the current window.
+#[async_trait::async_trait]
+pub trait QuotaStore: Send + Sync {
+    /// Adds to a tenant's usage in a window, returning the usage after the addition.
+    ///
+    /// The usage of earlier windows may be discarded.
+    async fn add(&self, tenant: &str, window: u64, usage: QuotaUsage) -> Result<QuotaUsage>;
+
+    /// Gets the usage of every tenant with usage in a window.
+    async fn usage(&self, window: u64) -> Result<Vec<(String, QuotaUsage)>>;
+}
+
+/// A quota store that keeps usage in memory.
+///
+/// Usage is not shared between servers and is lost when the server is dropped. The store keeps
+/// usage for a bounded number of tenants; usage of a tenant that doesn't fit is not counted.
+pub struct MemoryQuotaStore {
+    max_tenants: usize,
+    tenants: Mutex<HashMap<String, (u64, QuotaUsage)>>,
+}
+
+impl MemoryQuotaStore {
+    /// Creates a new in-memory quota store that keeps usage for up to 10,000 tenants.
+    pub fn new() -> Self {
+        Self::with_max_tenants(DEFAULT_MAX_TENANTS)
+    }
+
+    /// Creates a new in-memory quota store that keeps usage for up to the given number of tenants.
+    pub fn with_max_tenants(max_tenants: usize) -> Self {
+        Self {
+            max_tenants,
+            tenants: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for MemoryQuotaStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait::async_trait]
+impl QuotaStore for MemoryQuotaStore {
+    async fn add(&self, tenant: &str, window: u64, usage: QuotaUsage) -> Result<QuotaUsage> {
+        let mut tenants = self.tenants.lock().unwrap();
+
+        if !tenants.contains_key(tenant) && tenants.len() >= self.max_tenants {
+            tenants.retain(|_, (w, _)| *w >= window);
+
+            if tenants.len() >= self.max_tenants {
+                bail!("the quota store is full");
+            }
+        }
+
+        let (w, total) = tenants
+            .entry(tenant.to_string())
+            .or_insert((window, QuotaUsage::default()));
+
+        // Fuel reported for a window that has since ended no longer counts
+        if *w > window {
+            return Ok(QuotaUsage::default());
+        }
+
+        if *w < window {
+            *w = window;
+            *total = QuotaUsage::default();
+        }
+
+        total.requests += usage.requests;
+        total.fuel += usage.fuel;
+
+        Ok(*total)
+    }
+
+    async fn usage(&self, window: u64) -> Result<Vec<(String, QuotaUsage)>> {
+        Ok(self
+            .tenants
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, (w, _))| *w == window)
+            .map(|(tenant, (_, usage))| (tenant.clone(), *usage))
+            .collect())
+    }
+}
+
+/// The number of requests rejected for exceeding a quota.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuotaMetrics {
+    pub rejected_requests: u64,
+    pub rejected_fuel: u64,
+}
+
+/// The usage and limits of a tenant in the current window.
+#[derive(serde::Serialize)]
+pub struct TenantUsage {
+    /// The hash of the tenant's key, or [`SHARED_TENANT`].
+    pub tenant: String,
+    pub requests: u64,
+    pub fuel: u64,
+    pub request_limit: Option<u64>,
+    pub fuel_limit: Option<u64>,
+}
+
+/// A request admitted by the quotas, whose fuel is added to the tenant's usage once it completes.
+pub struct Admission {
+    tenant: String,
+    window: u64,
+}
+
+/// Enforces the quotas of tenants before functions are invoked.
+///
+/// The policy is passed to each call rather than held so that it can be changed while the server
+/// is serving; usage is kept in the store regardless of the policy.
+pub struct Quotas {
+    store: Arc<dyn QuotaStore>,
+    rejected_requests: AtomicU64,
+    rejected_fuel: AtomicU64,
+}
+
+impl Quotas {
+    pub fn new(store: Arc<dyn QuotaStore>) -> Self {
+        Self {
+            store,
+            rejected_requests: AtomicU64::new(0),
+            rejected_fuel: AtomicU64::new(0),
+        }
+    }
+
+    /// Gets the current window and the number of seconds until it ends.
+    fn window(policy: &QuotaPolicy) -> (u64, u64) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let window = policy.window.as_secs();
+
+        (now / window, window - now % window)
+    }
+
+    /// Admits a request, counting it against its tenant's quota.
+    ///
+    /// Returns the `429 Too Many Requests` response to send if the tenant has exhausted its quota.
+    pub async fn admit(
+        &self,
+        policy: &QuotaPolicy,
+        req: &Request,
+    ) -> std::result::Result<Admission, Response> {
+        let tenant = policy.tenant_of(req.header(policy.header.as_str()).map(|v| v.as_str()));
+        let limits = policy.limits(&tenant);
+        let (window, remaining) = Self::window(policy);
+
+        let usage = match self
+            .store
+            .add(
+                &tenant,
+                window,
+                QuotaUsage {
+                    requests: 1,
+                    fuel: 0,
+                },
+            )
+            .await
+        {
+            Ok(usage) => usage,
+            Err(e) => {
+                // An unavailable store shouldn't take down every tenant, so the request is admitted
+                log::error!("Failed to update quota usage: {:#}", e);
+                return Ok(Admission { tenant, window });
+            }
+        };
+
+        let exceeded = if matches!(limits.requests, Some(limit) if usage.requests > limit) {
+            self.rejected_requests.fetch_add(1, Ordering::Relaxed);
+            "the request quota for this window has been exhausted"
+        } else if matches!(limits.fuel, Some(limit) if usage.fuel >= limit) {
+            self.rejected_fuel.fetch_add(1, Ordering::Relaxed);
+            "the compute quota for this window has been exhausted"
+        } else {
+            return Ok(Admission { tenant, window });
+        };
+
+        let mut res = Response::builder(StatusCode::TooManyRequests)
+            .header(tide::http::headers::RETRY_AFTER, remaining.to_string())
+            .content_type(tide::http::mime::PLAIN)
+            .body(exceeded)
+            .build();
+
+        res.set_error(anyhow::anyhow!("{}", exceeded));
+        Err(res)
+    }
+
+    /// Adds the fuel consumed by an admitted request to its tenant's usage.
+    pub async fn complete(&self, admission: Admission, res: &tide::Result) {
+        let fuel = match res
+            .as_ref()
+            .ok()
+            .and_then(|res| res.ext::<InvocationStats>())
+            .and_then(|stats| stats.fuel)
+        {
+            Some(fuel) if fuel > 0 => fuel,
+            _ => return,
+        };
+
+        if let Err(e) = self
+            .store
+            .add(
+                &admission.tenant,
+                admission.window,
+                QuotaUsage { requests: 0, fuel },
+            )
+            .await
+        {
+            log::error!("Failed to update quota usage: {:#}", e);
+        }
+    }
+
+    pub fn metrics(&self) -> QuotaMetrics {
+        QuotaMetrics {
+            rejected_requests: self.rejected_requests.load(Ordering::Relaxed),
+            rejected_fuel: self.rejected_fuel.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Gets the usage of every tenant with usage in the current window.
+    pub async fn usage(&self, policy: &QuotaPolicy) -> Result<Vec<TenantUsage>> {
+        let (window, _) = Self::window(policy);
+        let mut usage: Vec<_> = self
+            .store
+            .usage(window)
+            .await?
+            .into_iter()
+            .map(|(tenant, usage)| {
+                let limits = policy.limits(&tenant);
+                TenantUsage {
+                    tenant,
+                    requests: usage.requests,
+                    fuel: usage.fuel,
+                    request_limit: limits.requests,
+                    fuel_limit: limits.fuel,
+                }
+            })
+            .collect();
+
+        usage.sort_by(|a, b| a.tenant.cmp(&b.tenant));
+        Ok(usage)
+    }
+}
@@ -0,0 +1,142 @@
+use crate::error::FunctionResponse;
+use crate::metrics::FunctionMetrics;
+use crate::server::State;
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use http_types::Method;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+
+type NativeHandler = Arc<
+    dyn Fn(http_types::Request) -> BoxFuture<'static, Result<http_types::Response>> + Send + Sync,
+>;
+
+/// The path parameters of a request to a native route.
+///
+/// The parameters are an extension of the request passed to the route's handler, so they are
+/// read with `req.ext().get::<RouteParams>()`.
+#[derive(Debug, Clone, Default)]
+pub struct RouteParams(HashMap<String, String>);
+
+impl RouteParams {
+    /// Gets the value of a path parameter, such as `id` for a route of `/users/:id`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+}
+
+/// A route served by a handler implemented by the embedder rather than by the module.
+#[derive(Clone)]
+pub(crate) struct NativeRoute {
+    pub name: String,
+    pub method: Option<Method>,
+    pub path: String,
+    handler: NativeHandler,
+}
+
+/// The native routes served alongside the module's functions.
+///
+/// Native routes share the server's router with the module's functions, so an application can
+/// serve a few performance-critical endpoints from the host while the rest run in WebAssembly.
+/// A native route is named like a function; its name appears in the route table and metrics.
+#[derive(Clone, Default)]
+pub struct Registry {
+    routes: Vec<NativeRoute>,
+}
+
+impl Registry {
+    /// Creates a new registry with no routes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a handler for requests to a path with the given method, or any method if `None`.
+    ///
+    /// Paths use the same syntax as function routes, such as `/users/:id`; path parameters are
+    /// passed to the handler as a [`RouteParams`] extension of the request. A route may not handle
+    /// requests that a function of the module also handles.
+    pub fn route<N, P, F, Fut>(
+        mut self,
+        name: N,
+        method: Option<Method>,
+        path: P,
+        handler: F,
+    ) -> Self
+    where
+        N: Into<String>,
+        P: Into<String>,
+        F: Fn(http_types::Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<http_types::Response>> + Send + 'static,
+    {
+        self.routes.push(NativeRoute {
+            name: name.into(),
+            method,
+            path: path.into(),
+            handler: Arc::new(move |req| Box::pin(handler(req))),
+        });
+        self
+    }
+
+    pub(crate) fn routes(&self) -> &[NativeRoute] {
+        &self.routes
+    }
+}
+
+/// The endpoint of a native route.
+#[derive(Clone)]
+pub(crate) struct NativeEndpoint {
+    handler: NativeHandler,
+    params: Arc<Vec<String>>,
+    metrics: Arc<FunctionMetrics>,
+}
+
+impl NativeEndpoint {
+    pub fn new(route: &NativeRoute, metrics: Arc<FunctionMetrics>) -> Self {
+        Self {
+            handler: route.handler.clone(),
+            params: Arc::new(
+                route
+                    .path
+                    .split('/')
+                    .filter_map(|segment| segment.strip_prefix(':'))
+                    .map(ToString::to_string)
+                    .collect(),
+            ),
+            metrics,
+        }
+    }
+}
+
+#[async_trait]
+impl tide::Endpoint<State> for NativeEndpoint {
+    async fn call(&self, req: tide::Request<State>) -> tide::Result {
+        let params = RouteParams(
+            self.params
+                .iter()
+                .filter_map(|name| Some((name.clone(), req.param(name).ok()?.to_string())))
+                .collect(),
+        );
+
+        let mut req: http_types::Request = req.into();
+        req.ext_mut().insert(params);
+
+        let start = Instant::now();
+        let res = (self.handler)(req).await;
+
+        self.metrics.invoked(
+            match &res {
+                Ok(res) => res.status() as u16,
+                Err(_) => 500,
+            },
+            start.elapsed(),
+        );
+
+        // Responses from native routes are the application's, like a function's response
+        let mut res = tide::Response::from(res?);
+        res.insert_ext(FunctionResponse);
+        Ok(res)
+    }
+}
@@ -0,0 +1,30 @@
+use crate::fetch::AllowedHost;
+use crate::quota::QuotaPolicy;
+use std::time::Duration;
+
+/// The settings of a server that can be changed while it is serving.
+///
+/// Each mounted module has its own settings. Get the current settings of a module with
+/// [`Server::config`](crate::Server::config), change them, and apply them with
+/// [`Server::apply_config`](crate::Server::apply_config). The module is not reloaded and
+/// requests being processed keep the settings they started with, so every request observes either
+/// the old or the new settings but never a mix of both.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// The time a function may execute before it is interrupted.
+    pub timeout: Duration,
+    /// The time the tasks a function spawned may execute after its response is sent.
+    pub task_timeout: Duration,
+    /// The maximum size of request bodies in bytes, or `None` for no limit.
+    pub max_body_size: Option<u64>,
+    /// The hosts functions may send outbound HTTP requests to.
+    pub allowed_hosts: Vec<AllowedHost>,
+    /// The quotas of tenants, or `None` if requests are not counted against quotas.
+    ///
+    /// Usage is kept in the server's quota store, so it carries over when the policy changes.
+    pub quota: Option<QuotaPolicy>,
+    /// The maximum level of messages logged.
+    ///
+    /// The level applies to every logger in the process, not only to the server's messages.
+    pub log_level: log::LevelFilter,
+}
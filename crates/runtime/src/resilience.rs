@@ -0,0 +1,241 @@
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Determines how failed outbound calls of functions are retried.
+///
+/// Only calls that are safe to repeat are retried: outbound HTTP requests with an idempotent
+/// method, SQL queries, and gRPC calls that failed because the server was unavailable.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Creates a policy that makes up to the given number of attempts for each call.
+    ///
+    /// The delay between attempts starts at 50 milliseconds and doubles with each attempt, up to one second.
+    pub fn new(attempts: u32) -> Self {
+        Self {
+            attempts: attempts.max(1),
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+
+    /// Sets the delay before the first retry and the maximum delay between attempts.
+    ///
+    /// A random delay of up to the computed backoff is used so that retries are spread out.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    fn delay(&self, retry: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .checked_mul(1 << retry.min(16))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+
+        rand::thread_rng().gen_range(Duration::from_millis(0)..=backoff)
+    }
+}
+
+/// Determines when calls to a failing destination are rejected without being attempted.
+///
+/// A destination's circuit opens after the given number of consecutive failures. While open,
+/// calls to the destination fail immediately; once the open duration elapses, a single call is
+/// let through, and the circuit closes if it succeeds or opens again if it fails.
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitBreakerPolicy {
+    failure_threshold: u32,
+    open_duration: Duration,
+}
+
+impl CircuitBreakerPolicy {
+    /// Creates a policy that opens a circuit after the given number of consecutive failures,
+    /// keeping it open for the given duration.
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            open_duration,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum CircuitState {
+    #[default]
+    Closed,
+    Open(Instant),
+    HalfOpen,
+}
+
+#[derive(Default)]
+struct Circuit {
+    state: CircuitState,
+    consecutive_failures: u32,
+    failures: u64,
+    retries: u64,
+    rejections: u64,
+}
+
+/// A snapshot of the circuit of an outbound destination.
+pub struct CircuitMetrics {
+    pub binding: &'static str,
+    pub destination: String,
+    // `0` when closed, `1` when open, and `2` when half-open
+    pub state: u8,
+    pub failures: u64,
+    pub retries: u64,
+    pub rejections: u64,
+}
+
+/// The outcome of an attempt at an outbound call.
+pub enum Outcome {
+    Success,
+    Failure,
+    RetryableFailure,
+}
+
+/// Applies the retry and circuit breaker policies to the outbound calls of functions.
+pub struct Resilience {
+    retry: Option<RetryPolicy>,
+    breaker: Option<CircuitBreakerPolicy>,
+    circuits: Mutex<BTreeMap<(&'static str, String), Circuit>>,
+}
+
+impl Resilience {
+    pub fn new(retry: Option<RetryPolicy>, breaker: Option<CircuitBreakerPolicy>) -> Self {
+        Self {
+            retry,
+            breaker,
+            circuits: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Makes an outbound call to a destination of a binding.
+    ///
+    /// The outcome of each attempt is determined with `classify`, and `rejected` creates the error
+    /// returned when the destination's circuit is open.
+    pub async fn call<T, E, F, Fut>(
+        &self,
+        binding: &'static str,
+        destination: &str,
+        mut attempt: F,
+        classify: impl Fn(&Result<T, E>) -> Outcome,
+        rejected: impl Fn(String) -> E,
+    ) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let key = (binding, destination.to_string());
+        let attempts = self.retry.map(|r| r.attempts).unwrap_or(1);
+        let mut retry = 0;
+
+        loop {
+            if !self.allow(&key) {
+                return Err(rejected(format!(
+                    "calls to `{}` are failing; the circuit is open",
+                    destination
+                )));
+            }
+
+            let result = attempt().await;
+            let outcome = classify(&result);
+            let retrying = matches!(outcome, Outcome::RetryableFailure) && retry + 1 < attempts;
+
+            self.record(&key, !matches!(outcome, Outcome::Success), retrying);
+
+            if !retrying {
+                return result;
+            }
+
+            async_std::task::sleep(self.retry.unwrap().delay(retry)).await;
+            retry += 1;
+        }
+    }
+
+    /// Determines if a call to the destination can be attempted.
+    fn allow(&self, key: &(&'static str, String)) -> bool {
+        let breaker = match &self.breaker {
+            Some(breaker) => breaker,
+            None => return true,
+        };
+
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(key.clone()).or_default();
+
+        match circuit.state {
+            CircuitState::Closed => true,
+            CircuitState::Open(since) if since.elapsed() >= breaker.open_duration => {
+                circuit.state = CircuitState::HalfOpen;
+                true
+            }
+            CircuitState::Open(_) | CircuitState::HalfOpen => {
+                circuit.rejections += 1;
+                false
+            }
+        }
+    }
+
+    fn record(&self, key: &(&'static str, String), failed: bool, retrying: bool) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(key.clone()).or_default();
+
+        if retrying {
+            circuit.retries += 1;
+        }
+
+        if !failed {
+            circuit.consecutive_failures = 0;
+            circuit.state = CircuitState::Closed;
+            return;
+        }
+
+        circuit.failures += 1;
+        circuit.consecutive_failures += 1;
+
+        if let Some(breaker) = &self.breaker {
+            let open = circuit.state == CircuitState::HalfOpen
+                || circuit.consecutive_failures >= breaker.failure_threshold;
+
+            if open && !matches!(circuit.state, CircuitState::Open(_)) {
+                log::warn!(
+                    "Opening the circuit for {} calls to `{}` after {} consecutive failures.",
+                    key.0,
+                    key.1,
+                    circuit.consecutive_failures
+                );
+                circuit.state = CircuitState::Open(Instant::now());
+            }
+        }
+    }
+
+    pub fn metrics(&self) -> Vec<CircuitMetrics> {
+        self.circuits
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((binding, destination), circuit)| CircuitMetrics {
+                binding,
+                destination: destination.clone(),
+                state: match circuit.state {
+                    CircuitState::Closed => 0,
+                    CircuitState::Open(_) => 1,
+                    CircuitState::HalfOpen => 2,
+                },
+                failures: circuit.failures,
+                retries: circuit.retries,
+                rejections: circuit.rejections,
+            })
+            .collect()
+    }
+}
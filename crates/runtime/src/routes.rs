@@ -0,0 +1,194 @@
+use crate::metrics::FunctionStats;
+use crate::registry::NativeRoute;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+use wasmtime_functions_metadata::{Function, FunctionTrigger, Metadata};
+
+/// Represents a route to a function.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Route {
+    /// The method of the route, or `None` if the route matches any method.
+    pub method: Option<String>,
+    /// The path of the route.
+    pub path: String,
+    /// The name of the function the route invokes.
+    pub function: String,
+    /// Whether the route is a native route implemented by the host rather than by the module.
+    pub native: bool,
+    /// The maximum number of concurrent invocations of the function, if limited.
+    pub concurrency: Option<usize>,
+    /// The time-to-live of cached responses in seconds, if responses are cached.
+    pub cache_ttl: Option<u32>,
+}
+
+/// Represents a function loaded by a server along with its live statistics.
+#[derive(Clone)]
+pub struct FunctionInfo {
+    /// The path prefix the function's module is mounted at, or an empty string if it isn't mounted.
+    pub mount: String,
+    /// The metadata of the function read from the module.
+    pub metadata: Function,
+    /// The routes that invoke the function, including the mount prefix.
+    pub routes: Vec<Route>,
+    /// The maximum number of concurrent invocations of the function, if limited.
+    ///
+    /// This reflects limits set on the server builder, which take precedence over the metadata.
+    pub concurrency: Option<usize>,
+    /// The maximum time an invocation of the function may take.
+    pub timeout: Duration,
+    /// The fuel each invocation of the function may consume, if limited.
+    pub fuel_limit: Option<u64>,
+    /// The invocation statistics of the function at the time it was enumerated.
+    pub stats: FunctionStats,
+}
+
+/// Represents the table of routes served by a module.
+///
+/// The table is displayed as an aligned table of the routes.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RouteTable(Vec<Route>);
+
+impl RouteTable {
+    /// Creates the route table of the given WebAssembly module.
+    pub fn from_module_bytes<T: AsRef<[u8]>>(bytes: &T) -> Result<Self> {
+        Ok(Self::new(
+            &Metadata::from_module_bytes(bytes)?.functions,
+            &[],
+            &HashMap::new(),
+        ))
+    }
+
+    pub(crate) fn new(
+        functions: &[Function],
+        native: &[NativeRoute],
+        concurrency: &HashMap<String, usize>,
+    ) -> Self {
+        let mut routes = Vec::new();
+
+        for function in functions {
+            match &function.trigger {
+                FunctionTrigger::Http {
+                    path,
+                    methods,
+                    cache,
+                    ..
+                } => {
+                    let route = |method: Option<String>| Route {
+                        method,
+                        path: path.clone(),
+                        function: function.name.clone(),
+                        native: false,
+                        concurrency: concurrency
+                            .get(&function.name)
+                            .copied()
+                            .or_else(|| function.concurrency.map(|c| c as usize)),
+                        cache_ttl: cache.as_ref().map(|c| c.ttl),
+                    };
+
+                    if methods.is_empty() {
+                        routes.push(route(None));
+                    } else {
+                        routes.extend(methods.iter().map(|m| route(Some(m.to_string()))));
+                    }
+                }
+            }
+        }
+
+        routes.extend(native.iter().map(|route| Route {
+            method: route.method.map(|m| m.to_string()),
+            path: route.path.clone(),
+            function: route.name.clone(),
+            native: true,
+            concurrency: None,
+            cache_ttl: None,
+        }));
+
+        Self(routes)
+    }
+
+    /// Merges the route tables of modules mounted at the given path prefixes.
+    pub(crate) fn merge<'a>(tables: impl Iterator<Item = (&'a str, &'a RouteTable)>) -> Self {
+        let mut routes = Vec::new();
+
+        for (prefix, table) in tables {
+            let prefix = prefix.trim_end_matches('/');
+            routes.extend(table.0.iter().map(|route| Route {
+                path: format!("{}{}", prefix, route.path),
+                ..route.clone()
+            }));
+        }
+
+        Self(routes)
+    }
+
+    /// Gets the routes in the table.
+    pub fn routes(&self) -> &[Route] {
+        &self.0
+    }
+}
+
+impl fmt::Display for RouteTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rows = vec![[
+            "METHOD".to_string(),
+            "PATH".to_string(),
+            "FUNCTION".to_string(),
+            "LIMITS".to_string(),
+        ]];
+
+        for route in &self.0 {
+            let mut limits = Vec::new();
+
+            if let Some(concurrency) = route.concurrency {
+                limits.push(format!("concurrency={}", concurrency));
+            }
+
+            if let Some(ttl) = route.cache_ttl {
+                limits.push(format!("cache={}s", ttl));
+            }
+
+            rows.push([
+                route.method.clone().unwrap_or_else(|| "*".to_string()),
+                route.path.clone(),
+                if route.native {
+                    format!("{} (native)", route.function)
+                } else {
+                    route.function.clone()
+                },
+                limits.join(" "),
+            ]);
+        }
+
+        let mut widths = [0; 3];
+        for row in &rows {
+            for (width, column) in widths.iter_mut().zip(row.iter()) {
+                *width = (*width).max(column.len());
+            }
+        }
+
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            let line = format!(
+                "{:<w0$}  {:<w1$}  {:<w2$}  {}",
+                row[0],
+                row[1],
+                row[2],
+                row[3],
+                w0 = widths[0],
+                w1 = widths[1],
+                w2 = widths[2],
+            );
+
+            write!(f, "{}", line.trim_end())?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,21 +1,104 @@
-use crate::host::Context;
-use anyhow::{anyhow, bail, Context as _, Result};
+use crate::admin::AdminServer;
+use crate::audit::{AuditSink, Auditor};
+use crate::blob::BlobProvider;
+use crate::cache::{ResponseCache, RouteCache};
+use crate::canary::{CanaryPolicy, CanaryStatus, Deployment, DeploymentEndpoint};
+use crate::capture::Capturer;
+use crate::clock::{Clock, MonotonicClock, SystemClock};
+use crate::concurrency::ConcurrencyLimiter;
+use crate::csp::ContentSecurityPolicy;
+use crate::discovery::DiscoveryProvider;
+use crate::environment::{Environment, EnvironmentProvider};
+use crate::error::{ErrorMiddleware, ErrorRenderer, FunctionResponse, ServerError};
+use crate::fetch::{AllowedHost, Fetch, FetchProvider};
+use crate::grpc::{Grpc, GrpcProvider};
+use crate::headers::{DuplicateHeaders, HeaderCase, HeaderPolicy};
+use crate::host::{Context, Setting};
+use crate::i18n::Translations;
+use crate::idempotency::Idempotency;
+use crate::kv::{KvProvider, MemoryKvProvider};
+use crate::limits::BodyLimitMiddleware;
+use crate::listener::{ConnectionOptions, TcpListener};
+use crate::log::GuestOutput;
+use crate::metrics::{FunctionMetrics, Metrics};
+use crate::mirror::{Mirror, MirrorEndpoint, MirrorPolicy, MirrorStatus, MirrorTarget, Shadow};
+use crate::preopen::Preopen;
+use crate::quota::{MemoryQuotaStore, QuotaMetrics, QuotaPolicy, QuotaStore, Quotas, TenantUsage};
+use crate::registry::{NativeEndpoint, NativeRoute, Registry};
+use crate::reload::ServerConfig;
+use crate::resilience::{CircuitBreakerPolicy, CircuitMetrics, Resilience, RetryPolicy};
+use crate::routes::{FunctionInfo, RouteTable};
+use crate::session::Sessions;
+use crate::signature;
+use crate::signing::UrlSigner;
+use crate::sql::{Sql, SqlMetrics, SqlProvider};
+use crate::templates::Templates;
+use crate::trace::{TraceMode, TraceSampling};
+use crate::validate::RequestValidator;
+use crate::{HOST_INTERFACE_VERSION, MIN_INTERFACE_VERSION};
+use anyhow::{anyhow, Context as _, Result};
 use async_trait::async_trait;
+use http_types::headers::{HeaderName, HeaderValue};
+use rand::{rngs::StdRng, SeedableRng};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::fmt;
+use std::future::Future;
 use std::net::SocketAddr;
-use std::sync::Arc;
-use wasmtime::{Config, Engine, Instance, Linker, Module, Store};
-use wasmtime_functions_metadata::{FunctionTrigger, Metadata};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tide::listener::Listener;
+use wasi_common::pipe::WritePipe;
+use wasmtime::{
+    Config, Engine, ExternType, Instance, InterruptHandle, Linker, Module, Store, Trap, TypedFunc,
+    ValType,
+};
+use wasmtime_functions_metadata::{Function, FunctionTrigger, Metadata};
 use wasmtime_wasi::sync::WasiCtxBuilder;
 
-const FUNCTION_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_FUNCTION_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_TASK_TIMEOUT_SECS: u64 = 10;
 
-/// Provides environment variables to the runtime server.
-pub trait EnvironmentProvider {
-    /// Gets the environment variable of the given name.
-    fn var(&self, name: &str) -> Result<String>;
-}
+const DEFAULT_RESPONSE_CACHE_CAPACITY: usize = 1024;
+const DEFAULT_APP_CACHE_CAPACITY: usize = 10_000;
+const DEFAULT_SQL_STATEMENT_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_LOCALE: &str = "en";
+
+// The time an interrupted function is given to unwind before its invocation is abandoned.
+const FUNCTION_INTERRUPT_GRACE_SECS: u64 = 5;
+
+// The fuel injected each time a function yields to the host when using fuel interruption.
+const FUEL_YIELD_INTERVAL: u64 = 10000;
+
+const DEFAULT_EPOCH_TICK_MS: u64 = 10;
+
+// The number of consecutive invocations that grow an instance's memory before it is suspected of leaking.
+const MEMORY_LEAK_STREAK: u32 = 10;
+
+// The guest path of the coverage directory and the export called to write coverage data to it.
+const COVERAGE_GUEST_DIR: &str = "/coverage";
+const COVERAGE_EXPORT: &str = "__wasmtime_functions_dump_coverage";
+
+// The exports that report and run the tasks a function spawned to run after its response.
+const TASKS_PENDING_EXPORT: &str = "__wasmtime_functions_pending_tasks";
+const TASKS_RUN_EXPORT: &str = "__wasmtime_functions_run_tasks";
+
+// The export of the module's middleware, which is passed the response of every function.
+const MIDDLEWARE_EXPORT: &str = "__wasmtime_functions_middleware";
+
+// The request header that enables tracing of a request's host calls when it contains the debug token.
+const DEBUG_HEADER: &str = "x-debug";
+const DEFAULT_INSTANCE_POOL_SIZE: u32 = 1000;
+
+// The maximum number of sessions kept without an instance pool, which is half the default pool.
+const DEFAULT_MAX_SESSIONS: usize = 500;
+
+// The interval at which a draining server checks for outstanding requests.
+const DRAIN_POLL_INTERVAL_MS: u64 = 100;
 
 pub type Request = tide::Request<State>;
 
@@ -27,25 +110,215 @@ pub struct State {
 struct StateInner {
     module: Module,
     linker: Linker<Context>,
-    env: Vec<(String, String)>,
+    environment: Environment,
     inherit_stdout: bool,
+    log_stdout: bool,
+    preopens: Vec<Preopen>,
+    clock: Option<Arc<dyn Clock>>,
+    random_seed: Option<u64>,
+    config: RwLock<Arc<ServerConfig>>,
+    on_config_change: Option<ConfigCallback>,
+    interruption: Interruption,
+    fuel_limit: Option<u64>,
+    epoch_tick: Duration,
+    sessions: Option<Sessions>,
+    auditor: Option<Auditor>,
+    capturer: Option<Capturer>,
+    coverage: bool,
+    on_invocation: Option<InvocationCallback>,
+    on_request: Option<RequestHook>,
+    trace_host_calls: bool,
+    trace_sampling: Option<TraceSampling>,
+    debug_token: Option<String>,
+    header_policy: HeaderPolicy,
+    sql: Option<Arc<Sql>>,
+    fetch: Arc<Fetch>,
+    grpc: Option<Arc<Grpc>>,
+    discovery: Option<Arc<dyn DiscoveryProvider>>,
+    templates: Option<Arc<Templates>>,
+    translations: Option<Arc<Translations>>,
+    signer: Option<Arc<UrlSigner>>,
+    csp: Option<ContentSecurityPolicy>,
+    blobs: Option<Arc<dyn BlobProvider>>,
+    kv: Arc<dyn KvProvider>,
+    idempotency: Option<Idempotency>,
+    quotas: Quotas,
+    resilience: Arc<Resilience>,
+    functions: Vec<Function>,
+    routes: RouteTable,
+    metrics: Metrics,
+}
+
+impl State {
+    pub(crate) fn routes(&self) -> &RouteTable {
+        &self.inner.routes
+    }
+
+    /// Gets the configuration that new requests are processed with.
+    pub(crate) fn config(&self) -> Arc<ServerConfig> {
+        self.inner.config()
+    }
+
+    /// Replaces the configuration that new requests are processed with.
+    pub(crate) fn apply_config(&self, config: &ServerConfig) {
+        *self.inner.config.write().unwrap() = Arc::new(config.clone());
+    }
+
+    /// Notifies the embedder that a configuration was applied.
+    pub(crate) fn notify_config_change(&self, config: &ServerConfig) {
+        if let Some(callback) = &self.inner.on_config_change {
+            callback(config);
+        }
+    }
+
+    /// Gets the functions of the module, with the paths of their routes prefixed by the given mount prefix.
+    pub(crate) fn functions(&self, prefix: &str) -> Vec<FunctionInfo> {
+        let routes = RouteTable::merge(std::iter::once((prefix, &self.inner.routes)));
+
+        self.inner
+            .functions
+            .iter()
+            .map(|function| {
+                let routes: Vec<_> = routes
+                    .routes()
+                    .iter()
+                    .filter(|route| route.function == function.name)
+                    .cloned()
+                    .collect();
+
+                FunctionInfo {
+                    mount: prefix.to_string(),
+                    metadata: function.clone(),
+                    // Every route of a function has the same limits
+                    concurrency: routes.first().and_then(|route| route.concurrency),
+                    routes,
+                    timeout: self.config().timeout,
+                    fuel_limit: self.inner.fuel_limit,
+                    stats: self.inner.metrics.function(&function.name).stats(),
+                }
+            })
+            .collect()
+    }
+
+    pub(crate) fn is_ready(&self) -> bool {
+        self.inner.environment.is_resolved()
+    }
+
+    pub(crate) fn metrics(&self) -> &Metrics {
+        &self.inner.metrics
+    }
+
+    pub(crate) fn sql_metrics(&self) -> Option<SqlMetrics> {
+        self.inner.sql.as_ref().map(|sql| sql.metrics())
+    }
+
+    pub(crate) fn circuit_metrics(&self) -> Vec<CircuitMetrics> {
+        self.inner.resilience.metrics()
+    }
+
+    pub(crate) fn quota_metrics(&self) -> Option<QuotaMetrics> {
+        self.config()
+            .quota
+            .as_ref()
+            .map(|_| self.inner.quotas.metrics())
+    }
+
+    pub(crate) async fn quota_usage(&self) -> Option<Result<Vec<TenantUsage>>> {
+        match &self.config().quota {
+            Some(policy) => Some(self.inner.quotas.usage(policy).await),
+            None => None,
+        }
+    }
 }
 
 impl StateInner {
-    pub async fn instantiate(&self, request: Request) -> Result<(Store<Context>, Instance)> {
+    fn config(&self) -> Arc<ServerConfig> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Determines if a request asks for its host calls to be traced with the debug token.
+    ///
+    /// The debug header is removed from the request so that the token isn't visible to functions.
+    fn debug_requested(&self, req: &mut Request) -> bool {
+        let token = match &self.debug_token {
+            Some(token) => token,
+            None => return false,
+        };
+
+        match req.remove_header(DEBUG_HEADER) {
+            Some(values) => crate::admin::token_matches(values.last().as_str(), token),
+            None => false,
+        }
+    }
+
+    /// Instantiates the module, optionally for processing a request.
+    ///
+    /// When scoped, the instance's environment only contains the given variables.
+    pub async fn instantiate(
+        &self,
+        request: Option<Request>,
+        env_scope: Option<Arc<Vec<String>>>,
+    ) -> Result<(Store<Context>, Instance)> {
         let mut wasi_ctx = WasiCtxBuilder::new();
 
-        if self.inherit_stdout {
+        if self.log_stdout {
+            wasi_ctx = wasi_ctx
+                .stdout(Box::new(WritePipe::new(GuestOutput::new(log::Level::Info))))
+                .stderr(Box::new(WritePipe::new(GuestOutput::new(log::Level::Warn))));
+        } else if self.inherit_stdout {
             wasi_ctx = wasi_ctx.inherit_stdout().inherit_stderr();
         }
 
-        wasi_ctx = wasi_ctx.envs(&self.env)?;
+        let vars = self
+            .environment
+            .vars(env_scope.as_deref().map(Vec::as_slice))
+            .await?;
+        wasi_ctx = wasi_ctx.envs(&vars)?;
 
-        let mut store = Store::new(
-            self.module.engine(),
-            Context::new(request, wasi_ctx.build()),
+        if self.coverage {
+            wasi_ctx = wasi_ctx.env("WASMTIME_FUNCTIONS_COVERAGE_DIR", COVERAGE_GUEST_DIR)?;
+        }
+
+        let mut wasi = wasi_ctx.build();
+        for preopen in &self.preopens {
+            preopen.push(&mut wasi)?;
+        }
+
+        if let Some(clock) = &self.clock {
+            wasi.clocks.system = Box::new(SystemClock(clock.clone()));
+            wasi.clocks.monotonic = Box::new(MonotonicClock::new(clock.clone()));
+        }
+
+        // Each instance is seeded the same so that every invocation observes the same sequence
+        if let Some(seed) = self.random_seed {
+            wasi.random = RefCell::new(Box::new(StdRng::seed_from_u64(seed)));
+        }
+
+        let mut context = Context::new(
+            request,
+            self.sql.clone(),
+            Some(self.fetch.clone()),
+            self.grpc.clone(),
+            self.discovery.clone(),
+            self.templates.clone(),
+            wasi,
+        );
+        context.set_header_policy(self.header_policy);
+        context.set_blob_provider(self.blobs.clone());
+        context.set_translations(self.translations.clone());
+        context.set_url_signer(self.signer.clone());
+        context.set_kv_provider(Some(self.kv.clone()));
+        context.set_env_scope(env_scope);
+        context.set_config_vars(
+            vars.into_iter()
+                .filter(|(name, _)| !self.environment.is_secret(name))
+                .collect(),
         );
-        store.out_of_fuel_async_yield(u64::MAX, 10000);
+
+        let mut store = Store::new(self.module.engine(), context);
+        if self.interruption == Interruption::Fuel {
+            limit_fuel(&mut store, self.fuel_limit);
+        }
 
         let instance = self
             .linker
@@ -56,144 +329,2804 @@ impl StateInner {
     }
 }
 
+/// Configures a store to yield to the host periodically as it consumes fuel.
+///
+/// The function traps once it has yielded enough times to exhaust the limit, if any.
+fn limit_fuel(store: &mut Store<Context>, limit: Option<u64>) {
+    // Limits lower than the yield interval are injected at once so that they are enforced exactly
+    let interval = limit.map_or(FUEL_YIELD_INTERVAL, |limit| {
+        limit.clamp(1, FUEL_YIELD_INTERVAL)
+    });
+
+    store.out_of_fuel_async_yield(
+        limit
+            .map(|limit| (limit + interval - 1) / interval)
+            .unwrap_or(u64::MAX),
+        interval,
+    );
+}
+
+/// The mechanism used to interrupt functions that exceed their timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interruption {
+    /// Functions consume fuel as they execute and periodically yield to the host, which interrupts
+    /// them once they time out.
+    ///
+    /// Metering fuel slows execution, but allows limiting the fuel an instance may consume.
+    Fuel,
+    /// Functions are not metered; a separate task checks the deadline of an invocation at every tick
+    /// and interrupts the function once it has passed.
+    ///
+    /// Wasmtime 0.30 has no epoch-based interruption, so the function is interrupted through its
+    /// store's interrupt handle, which it checks at loop headers and function entries. This avoids
+    /// the cost of metering fuel, but timeouts are only as precise as the tick interval.
+    Epoch,
+}
+
+/// The level of optimization applied when compiling a module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptLevel {
+    /// No optimizations are applied, which compiles the module fastest.
+    None,
+    /// Optimizations are applied for execution speed.
+    Speed,
+    /// Optimizations are applied for both execution speed and code size.
+    SpeedAndSize,
+}
+
+/// Statistics about a function invocation.
+///
+/// The statistics are attached as an extension to responses returned by [`LocalServer::respond`].
+#[derive(Debug, Clone, Copy)]
+pub struct InvocationStats {
+    /// The time taken to instantiate the module, or `None` if an existing instance was reused.
+    pub instantiation: Option<Duration>,
+    /// The time taken to execute the function.
+    pub execution: Duration,
+    /// The fuel consumed by the function, if fuel consumption is enabled.
+    pub fuel: Option<u64>,
+    /// The number of bytes the instance's memory grew by during the invocation.
+    pub memory_growth: u64,
+    /// The size of the instance's memory in bytes after the invocation.
+    pub memory: u64,
+    /// The calls made to host services during the invocation.
+    pub host_calls: HostCalls,
+}
+
+/// The number of calls a function made to host services.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HostCalls {
+    /// The number of SQL statements executed.
+    pub sql: u32,
+    /// The number of outbound HTTP requests sent.
+    pub fetch: u32,
+    /// The number of outbound gRPC calls made.
+    pub grpc: u32,
+}
+
+/// Reports the outcome and resource usage of a function invocation.
+///
+/// Reports are passed to the callback set with [`ServerBuilder::on_invocation`] and attached as an
+/// extension to responses returned by [`LocalServer::respond`].
+#[derive(Debug, Clone)]
+pub struct InvocationReport {
+    /// The name of the function that was invoked.
+    pub function: String,
+    /// The status code of the response; a trap is reported as `500`.
+    pub status: u16,
+    /// Whether the function was interrupted for exceeding its timeout.
+    pub timed_out: bool,
+    /// Whether the function trapped after exhausting the fuel limit injected with
+    /// [`ServerBuilder::inject_fuel_limit`].
+    pub fuel_exhausted: bool,
+    /// The statistics about the invocation.
+    pub stats: InvocationStats,
+}
+
+// Marks the response of a function that aborted.
+struct Aborted;
+
+type InvocationCallback = Arc<dyn Fn(&InvocationReport) + Send + Sync>;
+
+/// Represents the values attached to a request by the host for functions to read.
+///
+/// Extensions are set by the hook given to [`ServerBuilder::on_request`] and read by functions
+/// with `Request::extension`, such as the identity of a user authenticated by the host.
+#[derive(Debug, Clone, Default)]
+pub struct RequestExtensions(HashMap<String, String>);
+
+impl RequestExtensions {
+    /// Sets an extension, replacing any previous value with the same name.
+    pub fn insert<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) {
+        self.0.insert(name.into(), value.into());
+    }
+
+    /// Gets the value of an extension.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    /// Removes an extension, returning its previous value.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.0.remove(name)
+    }
+}
+
+type RequestHook = Arc<dyn Fn(&http_types::Request, &mut RequestExtensions) + Send + Sync>;
+
+type ConfigCallback = Arc<dyn Fn(&ServerConfig) + Send + Sync>;
+
+// The resources used by an instance at a point in time
+struct Usage {
+    fuel: Option<u64>,
+    memory: u64,
+    host_calls: HostCalls,
+}
+
+impl Usage {
+    fn measure(store: &mut Store<Context>, instance: Instance) -> Self {
+        Self {
+            fuel: store.fuel_consumed(),
+            memory: instance
+                .get_memory(&mut *store, "memory")
+                .map(|memory| memory.data_size(&*store) as u64)
+                .unwrap_or(0),
+            host_calls: store.data().host_calls(),
+        }
+    }
+
+    fn since(&self, before: &Self) -> (Option<u64>, u64, HostCalls) {
+        (
+            self.fuel
+                .and_then(|after| before.fuel.map(|before| after - before)),
+            self.memory.saturating_sub(before.memory),
+            HostCalls {
+                sql: self.host_calls.sql - before.host_calls.sql,
+                fetch: self.host_calls.fetch - before.host_calls.fetch,
+                grpc: self.host_calls.grpc - before.host_calls.grpc,
+            },
+        )
+    }
+}
+
 #[derive(Clone)]
 struct Endpoint {
     function: Arc<String>,
+    settings: Arc<Vec<(String, Setting)>>,
+    validator: Arc<RequestValidator>,
+    env_scope: Option<Arc<Vec<String>>>,
+    limiter: Option<Arc<ConcurrencyLimiter>>,
+    server_limiter: Option<Arc<ConcurrencyLimiter>>,
+    cache: Option<Arc<RouteCache>>,
+    headers: Arc<Vec<(HeaderName, HeaderValue)>>,
+    injected_fuel: Option<u64>,
+    signer: Option<Arc<UrlSigner>>,
+    trace_ratio: f64,
+    metrics: Arc<FunctionMetrics>,
 }
 
 impl Endpoint {
-    async fn invoke_function(&self, req: tide::Request<State>) -> tide::Result {
+    async fn invoke_function(
+        &self,
+        mut req: tide::Request<State>,
+        config: Arc<ServerConfig>,
+    ) -> tide::Result {
         let state = req.state().inner.clone();
-        let (mut store, instance) = state.instantiate(req).await?;
+        let task_timeout = config.task_timeout;
+        let trace = if state.debug_requested(&mut req) || state.trace_host_calls {
+            TraceMode::On
+        } else {
+            match &state.trace_sampling {
+                Some(sampling) => sampling.sample(self.trace_ratio),
+                None => TraceMode::Off,
+            }
+        };
 
-        let entry = instance.get_typed_func::<u32, u32, _>(&mut store, &self.function)?;
+        if let Some(hook) = &state.on_request {
+            let mut extensions = req.ext::<RequestExtensions>().cloned().unwrap_or_default();
+            hook(req.as_ref(), &mut extensions);
+            req.set_ext(extensions);
+        }
 
-        let req = store.data().request_handle();
+        if let Some(sessions) = &state.sessions {
+            if let Some(id) = sessions.session_id(&req) {
+                let session = sessions.get(&id);
+                let mut instance = session.lock().await;
 
-        log::info!("Invoking function '{}'.", self.function);
+                // An instance scoped to another function's variables can't be reused
+                if let Some((store, _)) = instance.as_ref() {
+                    if store.data().env_scope() != self.env_scope.as_ref() {
+                        *instance = None;
+                    }
+                }
 
-        let res = entry
-            .call_async(&mut store, req)
-            .await
-            .with_context(|| format!("call to function '{}' trapped", self.function))?;
+                let instantiation = match instance.as_mut() {
+                    Some((store, _)) => {
+                        store.data_mut().set_request(req);
+                        None
+                    }
+                    None => {
+                        let start = Instant::now();
+                        *instance =
+                            Some(state.instantiate(Some(req), self.env_scope.clone()).await?);
+                        Some(start.elapsed())
+                    }
+                };
 
-        store
-            .data()
-            .take_response(res)
-            .ok_or_else(|| tide::Error::from(anyhow!("function did not return a HTTP response")))
+                let (store, inst) = instance.as_mut().unwrap();
+                let res = self
+                    .invoke(&state, &config, store, *inst, instantiation, trace)
+                    .await;
+                Self::finish_tracing(store, &res);
+
+                // Don't reuse an instance that failed as its state may be inconsistent
+                if !Self::completed(&res) {
+                    *instance = None;
+                    sessions.remove(&id);
+                } else if Self::has_pending_tasks(store, *inst).await {
+                    let (state, function) = (state.clone(), self.function.clone());
+                    let session = session.clone();
+
+                    // The tasks run once this request releases the instance
+                    async_std::task::spawn(async move {
+                        let mut instance = session.lock().await;
+                        if let Some((store, inst)) = instance.as_mut() {
+                            if !Self::run_tasks(&state, task_timeout, &function, store, *inst).await
+                            {
+                                *instance = None;
+                                if let Some(sessions) = &state.sessions {
+                                    sessions.remove(&id);
+                                }
+                            }
+                        }
+                    });
+                }
+
+                return res;
+            }
+        }
+
+        let start = Instant::now();
+        let (mut store, instance) = state.instantiate(Some(req), self.env_scope.clone()).await?;
+        let instantiation = start.elapsed();
+
+        let res = self
+            .invoke(
+                &state,
+                &config,
+                &mut store,
+                instance,
+                Some(instantiation),
+                trace,
+            )
+            .await;
+        Self::finish_tracing(&mut store, &res);
+
+        if Self::completed(&res) && Self::has_pending_tasks(&mut store, instance).await {
+            let function = self.function.clone();
+            async_std::task::spawn(async move {
+                Self::run_tasks(&state, task_timeout, &function, &mut store, instance).await;
+            });
+        }
+
+        res
     }
-}
 
-#[async_trait]
-impl tide::Endpoint<State> for Endpoint {
-    async fn call(&self, req: tide::Request<State>) -> tide::Result {
-        use async_std::prelude::FutureExt;
+    /// Determines if an invocation completed normally, leaving its instance in a consistent state.
+    ///
+    /// An instance that failed or aborted may have stopped in the middle of updating its state.
+    fn completed(res: &tide::Result) -> bool {
+        matches!(res, Ok(res) if res.error().is_none() && res.ext::<Aborted>().is_none())
+    }
+
+    /// Records the memory used by an invocation, flagging an instance whose memory keeps growing.
+    ///
+    /// Linear memory never shrinks, but an instance that is reused should stop growing once its
+    /// allocator has enough memory for a request; one that grows on every invocation is likely leaking.
+    fn record_memory(&self, store: &mut Store<Context>, stats: &InvocationStats) {
+        self.metrics.memory(stats.memory, stats.memory_growth);
+
+        let streak = store
+            .data_mut()
+            .record_memory_growth(stats.memory_growth > 0);
 
-        self.invoke_function(req)
-            .timeout(std::time::Duration::from_secs(FUNCTION_TIMEOUT_SECS))
-            .await?
+        if streak == MEMORY_LEAK_STREAK {
+            self.metrics.leak_suspected();
+            log::warn!(
+                "Function '{}' grew the memory of an instance on {} consecutive invocations to {} bytes; it may be leaking memory.",
+                self.function,
+                MEMORY_LEAK_STREAK,
+                stats.memory
+            );
+        }
     }
-}
 
-/// The Wasmtime Functions HTTP server.
-///
-/// This server is used to host the given WebAssembly module and route requests to Wasmtime functions.
-pub struct Server(Box<dyn tide::listener::Listener<State>>);
+    /// Completes the tracing of an invocation, logging the calls held for a failed invocation.
+    fn finish_tracing(store: &mut Store<Context>, res: &tide::Result) {
+        let failed = match res {
+            Ok(res) => res.status().is_server_error(),
+            Err(_) => true,
+        };
 
-impl Server {
-    /// Creates a runtime server.
-    pub async fn new<A: Into<SocketAddr>>(
-        addr: A,
-        module: &[u8],
-        environment: &dyn EnvironmentProvider,
-        debug_info: bool,
-        inherit_stdout: bool,
-    ) -> Result<Self> {
-        let metadata = Metadata::from_module_bytes(&module)?;
+        store.data_mut().finish_tracing(failed);
+    }
 
-        if metadata.functions.is_empty() {
-            bail!("module contains no Wasmtime functions");
+    /// Determines if the function spawned tasks to run after its response.
+    async fn has_pending_tasks(store: &mut Store<Context>, instance: Instance) -> bool {
+        match instance.get_typed_func::<(), u32, _>(&mut *store, TASKS_PENDING_EXPORT) {
+            Ok(pending) => matches!(pending.call_async(&mut *store, ()).await, Ok(n) if n > 0),
+            Err(_) => false,
         }
+    }
+
+    /// Runs the tasks the function spawned to run after its response.
+    ///
+    /// Returns `false` if the tasks trapped or timed out, leaving the instance in an unknown state.
+    async fn run_tasks(
+        state: &StateInner,
+        timeout: Duration,
+        function: &str,
+        store: &mut Store<Context>,
+        instance: Instance,
+    ) -> bool {
+        let run = match instance.get_typed_func::<(), u32, _>(&mut *store, TASKS_RUN_EXPORT) {
+            Ok(run) => run,
+            Err(e) => {
+                log::warn!(
+                    "Function '{}' has pending tasks but they cannot be run: {}",
+                    function,
+                    e
+                );
+                return false;
+            }
+        };
+
+        let interrupt = match store.interrupt_handle() {
+            Ok(interrupt) => interrupt,
+            Err(e) => {
+                log::warn!("Failed to run the tasks of function '{}': {}", function, e);
+                return false;
+            }
+        };
+
+        let start = Instant::now();
+        let call = run.call_async(&mut *store, ());
 
-        let mut env = Vec::new();
-        for name in metadata.vars {
-            let value = environment.var(&name)?;
-            env.push((name, value));
+        match Self::call_with_timeout(state, interrupt, timeout, function, call).await {
+            Some(Ok(count)) => {
+                log::info!(
+                    "Ran {} task(s) of function '{}' in {:?}.",
+                    count,
+                    function,
+                    start.elapsed()
+                );
+                true
+            }
+            Some(Err(e)) => {
+                log::warn!("A task of function '{}' trapped: {:?}", function, e);
+                false
+            }
+            None => {
+                log::warn!(
+                    "The tasks of function '{}' timed out after {:?}.",
+                    function,
+                    timeout
+                );
+                false
+            }
         }
+    }
 
-        let mut config = Config::default();
+    /// Awaits a call into an instance, interrupting it if it exceeds the given timeout.
+    ///
+    /// Returns `None` if the call timed out.
+    async fn call_with_timeout<T>(
+        state: &StateInner,
+        interrupt: InterruptHandle,
+        timeout: Duration,
+        function: &str,
+        call: impl Future<Output = Result<T, Trap>>,
+    ) -> Option<Result<T, Trap>> {
+        use futures::future::{select, Either};
 
-        config.allocation_strategy(wasmtime::InstanceAllocationStrategy::pooling());
-        config.debug_info(debug_info);
-        config.consume_fuel(true);
-        config.async_support(true);
+        // Without fuel the function does not yield to the host while executing guest code, so its
+        // deadline is checked by a separate task
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let (ticker, interrupt) = match state.interruption {
+            Interruption::Epoch => (
+                Some(async_std::task::spawn(Self::tick(
+                    interrupt,
+                    timeout,
+                    state.epoch_tick,
+                    interrupted.clone(),
+                ))),
+                None,
+            ),
+            Interruption::Fuel => (None, Some(interrupt)),
+        };
 
-        let engine = Engine::new(&config)?;
-        let module = Module::new(&engine, module)?;
+        futures::pin_mut!(call);
 
-        let mut linker = Linker::new(&engine);
-        Context::add_to_linker(&mut linker)?;
+        let res = match select(call, futures_timer::Delay::new(timeout)).await {
+            Either::Left((res, _)) => Some(res),
+            Either::Right((_, call)) => {
+                // Interrupt the guest so that it traps at the next opportunity rather than
+                // dropping the invocation while it is still executing; with epoch interruption,
+                // the ticker interrupts it
+                if let Some(interrupt) = interrupt {
+                    interrupt.interrupt();
+                }
 
-        let mut app = tide::with_state(State {
-            inner: Arc::new(StateInner {
-                module,
-                linker,
-                env,
-                inherit_stdout,
-            }),
-        });
+                // A guest blocked in a host call only traps once the call returns, so bound the wait
+                if async_std::future::timeout(
+                    Duration::from_secs(FUNCTION_INTERRUPT_GRACE_SECS),
+                    call,
+                )
+                .await
+                .is_err()
+                {
+                    log::warn!(
+                        "Function '{}' did not unwind after being interrupted.",
+                        function
+                    );
+                }
 
-        app.with(crate::log::LogMiddleware);
+                None
+            }
+        };
 
-        for function in metadata.functions {
-            match &function.trigger {
-                FunctionTrigger::Http { path, methods } => {
-                    let mut route = app.at(path);
+        if let Some(ticker) = ticker {
+            ticker.cancel().await;
+        }
 
-                    let endpoint = Endpoint {
-                        function: Arc::new(function.name.clone()),
-                    };
+        if interrupted.load(Ordering::SeqCst) {
+            None
+        } else {
+            res
+        }
+    }
 
-                    if methods.is_empty() {
-                        log::info!(
-                            "Adding route for function '{}' at '{}'.",
-                            function.name,
-                            path,
-                        );
-                        route.all(endpoint);
-                    } else {
-                        for method in methods {
-                            log::info!(
-                                "Adding route for function '{}' at '{}' ({}).",
-                                function.name,
-                                path,
-                                method
-                            );
-                            http_types::Method::try_from(method.as_ref())
-                                .map(|m| route.method(m, endpoint.clone()))
-                                .ok();
-                        }
-                    }
+    async fn invoke(
+        &self,
+        state: &StateInner,
+        config: &Arc<ServerConfig>,
+        store: &mut Store<Context>,
+        instance: Instance,
+        instantiation: Option<Duration>,
+        trace: TraceMode,
+    ) -> tide::Result {
+        let entry = instance.get_typed_func::<u32, u32, _>(&mut *store, &self.function)?;
+        let middleware = Self::middleware(store, instance)?;
+
+        store
+            .data_mut()
+            .set_function(self.function.clone(), self.settings.clone());
+        store.data_mut().set_server_config(config.clone());
+        store.data_mut().set_tracing(trace);
+
+        let req = store.data().request_handle();
+        let interrupt = store.interrupt_handle()?;
+
+        log::info!("Invoking function '{}'.", self.function);
+
+        if let Some(fuel) = self.injected_fuel {
+            // Fuel left over from earlier calls into the instance would count towards the limit
+            let remaining = store.consume_fuel(0)?;
+            store.consume_fuel(remaining)?;
+            limit_fuel(store, Some(fuel));
+        }
+
+        let before = Usage::measure(store, instance);
+        let start = Instant::now();
+
+        // The middleware runs within the function's timeout
+        let call = async {
+            let res = entry.call_async(&mut *store, req).await?;
+            match middleware {
+                Some(middleware) => {
+                    let req = store.data_mut().renew_request_handle();
+                    middleware.call_async(&mut *store, (req, res)).await
+                }
+                None => Ok(res),
+            }
+        };
+        let res =
+            Self::call_with_timeout(state, interrupt, config.timeout, &self.function, call).await;
+
+        if self.injected_fuel.is_some() {
+            // A session's instance may next be used by a function without an injected limit
+            limit_fuel(store, state.fuel_limit);
+        }
+
+        let execution = start.elapsed();
+        let after = Usage::measure(store, instance);
+        let (fuel, memory_growth, host_calls) = after.since(&before);
+        let stats = InvocationStats {
+            instantiation,
+            execution,
+            fuel,
+            memory_growth,
+            memory: after.memory,
+            host_calls,
+        };
+
+        self.record_memory(store, &stats);
+
+        // A function that aborted exited the instance after recording its response
+        if let (Some(Err(_)), Some(mut res)) = (&res, store.data_mut().take_abort()) {
+            if let Some(csp) = &state.csp {
+                csp.apply(&mut res, store.data().nonce());
+            }
+
+            let report = self.report(state, res.status() as u16, false, false, stats);
+            res.insert_ext(FunctionResponse);
+            res.insert_ext(Aborted);
+            res.insert_ext(stats);
+            res.insert_ext(report);
+            return Ok(res);
+        }
+
+        let res = match res {
+            Some(Err(_)) if self.fuel_exhausted(fuel) => {
+                let mut res = self.fuel_exhausted_response();
+                let report = self.report(state, res.status() as u16, false, true, stats);
+                res.insert_ext(stats);
+                res.insert_ext(report);
+                return Ok(res);
+            }
+            Some(res) => {
+                if res.is_err() {
+                    self.report(state, 500, false, false, stats);
                 }
+
+                res.with_context(|| format!("call to function '{}' trapped", self.function))?
+            }
+            None => {
+                let mut res = self.timeout_response(config.timeout);
+                let report = self.report(state, res.status() as u16, true, false, stats);
+                res.insert_ext(stats);
+                res.insert_ext(report);
+                return Ok(res);
             }
+        };
+
+        if state.coverage {
+            Self::dump_coverage(store, instance).await;
+        }
+
+        let mut res = match store.data().take_response(&self.function, res) {
+            Ok(res) => res,
+            Err(e) => {
+                self.report(state, 500, false, false, stats);
+                return Err(e.into());
+            }
+        };
+
+        if let Some(csp) = &state.csp {
+            csp.apply(&mut res, store.data().nonce());
         }
 
-        Ok(Self(Box::new(app.bind(addr.into()).await?)))
+        let report = self.report(state, res.status() as u16, false, false, stats);
+
+        res.insert_ext(FunctionResponse);
+        res.insert_ext(stats);
+        res.insert_ext(report);
+
+        Ok(res)
     }
 
-    /// Accepts and processes incoming connections.
-    pub async fn accept(&mut self) -> Result<()> {
-        self.0.accept().await?;
-        Ok(())
+    /// Gets the module's middleware, if it has one.
+    fn middleware(
+        store: &mut Store<Context>,
+        instance: Instance,
+    ) -> Result<Option<TypedFunc<(u32, u32), u32>>> {
+        match instance.get_func(&mut *store, MIDDLEWARE_EXPORT) {
+            Some(func) => Ok(Some(func.typed(&*store)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Creates the report of an invocation and passes it to the invocation callback, if any.
+    fn report(
+        &self,
+        state: &StateInner,
+        status: u16,
+        timed_out: bool,
+        fuel_exhausted: bool,
+        stats: InvocationStats,
+    ) -> InvocationReport {
+        let report = InvocationReport {
+            function: self.function.to_string(),
+            status,
+            timed_out,
+            fuel_exhausted,
+            stats,
+        };
+
+        if let Some(callback) = &state.on_invocation {
+            callback(&report);
+        }
+
+        report
+    }
+
+    /// Calls the module's coverage export, if present, so that it writes its coverage data.
+    async fn dump_coverage(store: &mut Store<Context>, instance: Instance) {
+        let dump = match instance.get_typed_func::<(), (), _>(&mut *store, COVERAGE_EXPORT) {
+            Ok(dump) => dump,
+            Err(_) => {
+                log::debug!(
+                    "Module does not export '{}'; no coverage data was written.",
+                    COVERAGE_EXPORT
+                );
+                return;
+            }
+        };
+
+        if let Err(e) = dump.call_async(&mut *store, ()).await {
+            log::warn!("Failed to write coverage data: {:?}", e);
+        }
+    }
+
+    async fn tick(
+        interrupt: InterruptHandle,
+        timeout: Duration,
+        tick: Duration,
+        interrupted: Arc<AtomicBool>,
+    ) {
+        let deadline = Instant::now() + timeout;
+
+        while Instant::now() < deadline {
+            async_std::task::sleep(tick).await;
+        }
+
+        interrupted.store(true, Ordering::SeqCst);
+        interrupt.interrupt();
+    }
+
+    fn shed_response(&self) -> tide::Response {
+        let mut res = tide::Response::builder(tide::StatusCode::ServiceUnavailable)
+            .header(tide::http::headers::RETRY_AFTER, "1")
+            .content_type(tide::http::mime::PLAIN)
+            .body("the function is at capacity")
+            .build();
+
+        res.set_error(anyhow!(
+            "function '{}' is at its concurrency limit",
+            self.function
+        ));
+
+        res
+    }
+
+    // Adds the route's static headers to a response returned by the function, unless it set them itself
+    fn add_headers(&self, res: &mut tide::Result) {
+        if let Ok(res) = res {
+            if res.ext::<FunctionResponse>().is_none() {
+                return;
+            }
+
+            for (name, value) in self.headers.iter() {
+                if res.header(name).is_none() {
+                    res.insert_header(name, value.clone());
+                }
+            }
+        }
+    }
+
+    fn timeout_response(&self, timeout: Duration) -> tide::Response {
+        let mut res = tide::Response::builder(tide::StatusCode::GatewayTimeout)
+            .content_type(tide::http::mime::PLAIN)
+            .body("the function did not complete in the allotted time")
+            .build();
+
+        res.set_error(anyhow!(
+            "function '{}' timed out after {:?}",
+            self.function,
+            timeout
+        ));
+
+        res
+    }
+
+    /// Determines if a trap was caused by the function exhausting its injected fuel limit.
+    fn fuel_exhausted(&self, consumed: Option<u64>) -> bool {
+        matches!((self.injected_fuel, consumed), (Some(limit), Some(consumed)) if consumed >= limit)
+    }
+
+    fn fuel_exhausted_response(&self) -> tide::Response {
+        let limit = self.injected_fuel.unwrap_or_default();
+
+        log::warn!(
+            "Function '{}' exhausted its injected fuel limit of {}.",
+            self.function,
+            limit
+        );
+        self.metrics.fuel_exhausted();
+
+        let mut res = tide::Response::builder(tide::StatusCode::InternalServerError)
+            .content_type(tide::http::mime::PLAIN)
+            .body("the function exhausted its fuel")
+            .build();
+
+        res.set_error(anyhow!(
+            "function '{}' exhausted its injected fuel limit of {}",
+            self.function,
+            limit
+        ));
+
+        res
     }
 }
 
-impl fmt::Display for Server {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            self.0.info().first().map(|i| i.connection()).unwrap_or("")
-        )
+#[async_trait]
+impl tide::Endpoint<State> for Endpoint {
+    async fn call(&self, mut req: tide::Request<State>) -> tide::Result {
+        if let Some(signer) = &self.signer {
+            if let Err(reason) = signer.verify(req.url()) {
+                let mut res = tide::Response::builder(tide::StatusCode::Forbidden)
+                    .content_type(tide::http::mime::PLAIN)
+                    .body(reason)
+                    .build();
+
+                res.set_error(anyhow!("{}", reason));
+                self.metrics.rejected(res.status() as u16);
+                return Ok(res);
+            }
+        }
+
+        if let Some(res) = self.validator.validate(&req) {
+            return Ok(res);
+        }
+
+        let state = req.state().inner.clone();
+
+        // The request is processed with this configuration even if another is applied meanwhile
+        let config = state.config();
+
+        let admission = match &config.quota {
+            Some(policy) => match state.quotas.admit(policy, &req).await {
+                Ok(admission) => Some(admission),
+                Err(res) => {
+                    self.metrics.rejected(res.status() as u16);
+                    return Ok(res);
+                }
+            },
+            None => None,
+        };
+
+        let cache_key = self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.key(&req).map(|key| (cache, key)));
+
+        if let Some((cache, key)) = &cache_key {
+            if let Some(mut res) = cache.get(key) {
+                self.metrics.cache_hit(res.status() as u16);
+                res.insert_ext(FunctionResponse);
+                return Ok(res);
+            }
+        }
+
+        let mut permits = Vec::new();
+
+        for limiter in self.limiter.iter().chain(self.server_limiter.iter()) {
+            match limiter.acquire().await {
+                Some(permit) => permits.push(permit),
+                None => {
+                    let res = self.shed_response();
+                    self.metrics.shed(res.status() as u16);
+                    return Ok(res);
+                }
+            }
+        }
+
+        let reservation = match &state.idempotency {
+            Some(idempotency) => match idempotency.begin(&req, &self.function).await {
+                Ok(reservation) => reservation,
+                Err(res) => return Ok(res),
+            },
+            None => None,
+        };
+
+        let record = state
+            .auditor
+            .as_ref()
+            .map(|a| a.begin(&mut req, &self.function));
+
+        let captured = match &state.capturer {
+            Some(capturer) => Some(capturer.begin(&mut req, &self.function).await?),
+            None => None,
+        };
+
+        let start = std::time::Instant::now();
+        let mut res = self.invoke_function(req, config).await;
+
+        self.metrics.invoked(
+            match &res {
+                Ok(res) => res.status() as u16,
+                Err(e) => e.status() as u16,
+            },
+            start.elapsed(),
+        );
+
+        self.add_headers(&mut res);
+
+        if let Some(admission) = admission {
+            state.quotas.complete(admission, &res).await;
+        }
+
+        if let (Some(idempotency), Some(reservation)) = (&state.idempotency, reservation) {
+            idempotency.end(reservation, &mut res).await;
+        }
+
+        if let (Ok(res), Some((cache, key))) = (&mut res, cache_key) {
+            // The function has already run, so a response that can't be cached is still sent
+            if let Err(e) = cache.insert(key, res).await {
+                log::error!("Failed to cache response: {}", e);
+            }
+        }
+
+        if let (Some(auditor), Some(record)) = (&state.auditor, record) {
+            auditor.end(record, &res);
+        }
+
+        if let (Some(capturer), Some(captured)) = (&state.capturer, captured) {
+            capturer.end(captured, &res);
+        }
+
+        res
+    }
+}
+
+/// Used for building a Wasmtime Functions HTTP server.
+pub struct ServerBuilder<'a> {
+    module: &'a [u8],
+    environment: Arc<dyn EnvironmentProvider>,
+    lazy_environment: Option<Option<Duration>>,
+    warmup: bool,
+    debug_info: bool,
+    inherit_stdout: bool,
+    log_stdout: bool,
+    preopens: Vec<(PathBuf, PathBuf, bool)>,
+    clock: Option<Arc<dyn Clock>>,
+    random_seed: Option<u64>,
+    timeout: Duration,
+    task_timeout: Duration,
+    interruption: Interruption,
+    fuel_limit: Option<u64>,
+    epoch_tick: Duration,
+    opt_level: OptLevel,
+    compilation_cache: bool,
+    instance_pool: Option<u32>,
+    etags: bool,
+    session_affinity: Option<(String, Duration)>,
+    max_sessions: Option<usize>,
+    audit_sink: Option<Arc<dyn AuditSink>>,
+    audit_principal_header: Option<String>,
+    capture_failures: Option<PathBuf>,
+    coverage_dir: Option<PathBuf>,
+    on_invocation: Option<InvocationCallback>,
+    on_request: Option<RequestHook>,
+    on_config_change: Option<ConfigCallback>,
+    trace_host_calls: bool,
+    trace_sampling: Option<TraceSampling>,
+    debug_token: Option<String>,
+    registry: Registry,
+    header_case: HeaderCase,
+    duplicate_headers: DuplicateHeaders,
+    error_renderer: Option<Arc<dyn ErrorRenderer>>,
+    connection: ConnectionOptions,
+    function_concurrency: HashMap<String, usize>,
+    injected_fuel: HashMap<String, u64>,
+    concurrency_queue: usize,
+    max_concurrency: Option<usize>,
+    max_body_size: Option<u64>,
+    response_cache_capacity: usize,
+    sql_provider: Option<Arc<dyn SqlProvider>>,
+    sql_timeout: Duration,
+    sql_function_timeouts: HashMap<String, Duration>,
+    fetch_provider: Option<Arc<dyn FetchProvider>>,
+    allowed_hosts: Vec<AllowedHost>,
+    grpc_provider: Option<Arc<dyn GrpcProvider>>,
+    grpc_services: HashMap<String, String>,
+    discovery_provider: Option<Arc<dyn DiscoveryProvider>>,
+    blob_provider: Option<Arc<dyn BlobProvider>>,
+    kv_provider: Option<Arc<dyn KvProvider>>,
+    templates_dir: Option<PathBuf>,
+    translations_dir: Option<PathBuf>,
+    default_locale: String,
+    url_signing_key: Option<Vec<u8>>,
+    content_security_policy: Option<String>,
+    idempotency_ttl: Option<Duration>,
+    idempotency_scope_header: Option<String>,
+    quota_policy: Option<QuotaPolicy>,
+    quota_store: Option<Arc<dyn QuotaStore>>,
+    retry_policy: Option<RetryPolicy>,
+    circuit_breaker: Option<CircuitBreakerPolicy>,
+    trusted_keys: Vec<Vec<u8>>,
+    signature: Option<&'a [u8]>,
+    precompiled: Option<&'a [u8]>,
+    allow_older_sdk: bool,
+}
+
+impl<'a> ServerBuilder<'a> {
+    /// Creates a new server builder for the given WebAssembly module.
+    pub fn new(module: &'a [u8], environment: Arc<dyn EnvironmentProvider>) -> Self {
+        Self {
+            module,
+            environment,
+            lazy_environment: None,
+            warmup: false,
+            debug_info: false,
+            inherit_stdout: false,
+            log_stdout: false,
+            preopens: Vec::new(),
+            clock: None,
+            random_seed: None,
+            timeout: Duration::from_secs(DEFAULT_FUNCTION_TIMEOUT_SECS),
+            task_timeout: Duration::from_secs(DEFAULT_TASK_TIMEOUT_SECS),
+            interruption: Interruption::Fuel,
+            fuel_limit: None,
+            epoch_tick: Duration::from_millis(DEFAULT_EPOCH_TICK_MS),
+            opt_level: OptLevel::Speed,
+            compilation_cache: false,
+            instance_pool: Some(DEFAULT_INSTANCE_POOL_SIZE),
+            etags: false,
+            session_affinity: None,
+            max_sessions: None,
+            audit_sink: None,
+            audit_principal_header: None,
+            capture_failures: None,
+            coverage_dir: None,
+            on_invocation: None,
+            on_request: None,
+            on_config_change: None,
+            trace_host_calls: false,
+            trace_sampling: None,
+            debug_token: None,
+            registry: Registry::default(),
+            header_case: HeaderCase::default(),
+            duplicate_headers: DuplicateHeaders::default(),
+            error_renderer: None,
+            connection: ConnectionOptions::default(),
+            function_concurrency: HashMap::new(),
+            injected_fuel: HashMap::new(),
+            concurrency_queue: usize::MAX,
+            max_concurrency: None,
+            max_body_size: None,
+            response_cache_capacity: DEFAULT_RESPONSE_CACHE_CAPACITY,
+            sql_provider: None,
+            sql_timeout: Duration::from_secs(DEFAULT_SQL_STATEMENT_TIMEOUT_SECS),
+            sql_function_timeouts: HashMap::new(),
+            fetch_provider: None,
+            allowed_hosts: Vec::new(),
+            grpc_provider: None,
+            grpc_services: HashMap::new(),
+            discovery_provider: None,
+            blob_provider: None,
+            kv_provider: None,
+            templates_dir: None,
+            translations_dir: None,
+            default_locale: DEFAULT_LOCALE.to_string(),
+            url_signing_key: None,
+            content_security_policy: None,
+            idempotency_ttl: None,
+            idempotency_scope_header: None,
+            quota_policy: None,
+            quota_store: None,
+            retry_policy: None,
+            circuit_breaker: None,
+            trusted_keys: Vec::new(),
+            signature: None,
+            precompiled: None,
+            allow_older_sdk: false,
+        }
+    }
+
+    /// Enables lazy resolution of the environment variables declared by the module.
+    ///
+    /// By default, every declared variable is resolved when the server is built.
+    ///
+    /// In lazy mode, variables are instead resolved when first needed by a function invocation and
+    /// cached for the given time-to-live; if no time-to-live is given, resolved values are cached indefinitely.
+    /// Only the variables available to the invoked function are resolved, and concurrent invocations
+    /// that need the same variable share a single resolution.
+    ///
+    /// Use [`Server::is_ready`] to determine if every variable has been resolved.
+    pub fn lazy_environment(mut self, ttl: Option<Duration>) -> Self {
+        self.lazy_environment = Some(ttl);
+        self
+    }
+
+    /// Sets whether or not the server is warmed up before it binds its listener.
+    ///
+    /// Warming up resolves every environment variable declared by the module, even in lazy mode,
+    /// and instantiates the module once, so that errors fail the server's creation with
+    /// [`ServerError::Warmup`] or [`ServerError::MissingVar`] rather than failing the first requests.
+    ///
+    /// Defaults to `false`.
+    pub fn warmup(mut self, enabled: bool) -> Self {
+        self.warmup = enabled;
+        self
+    }
+
+    /// Sets whether or not debug information is generated for the module.
+    pub fn debug_info(mut self, enabled: bool) -> Self {
+        self.debug_info = enabled;
+        self
+    }
+
+    /// Sets the level of optimization applied when compiling the module.
+    ///
+    /// Defaults to [`OptLevel::Speed`].
+    pub fn opt_level(mut self, level: OptLevel) -> Self {
+        self.opt_level = level;
+        self
+    }
+
+    /// Sets whether or not compiled code is cached on disk using the default Wasmtime cache configuration.
+    ///
+    /// Defaults to disabled.
+    pub fn compilation_cache(mut self, enabled: bool) -> Self {
+        self.compilation_cache = enabled;
+        self
+    }
+
+    /// Sets the number of instances preallocated by the pooling instance allocator.
+    ///
+    /// If `None`, instances are allocated on demand rather than from a pool.
+    ///
+    /// Defaults to 1000.
+    pub fn instance_pool(mut self, size: Option<u32>) -> Self {
+        self.instance_pool = size;
+        self
+    }
+
+    /// Sets whether or not functions inherit the stdout and stderr of the host.
+    pub fn inherit_stdout(mut self, enabled: bool) -> Self {
+        self.inherit_stdout = enabled;
+        self
+    }
+
+    /// Sets whether or not the stdout and stderr of functions are written to the log.
+    ///
+    /// Each line of output is logged as a separate record; output to stderr is logged as a warning.
+    ///
+    /// This takes precedence over [`ServerBuilder::inherit_stdout`].
+    pub fn log_stdout(mut self, enabled: bool) -> Self {
+        self.log_stdout = enabled;
+        self
+    }
+
+    /// Makes a host directory available to functions at the given guest path.
+    ///
+    /// If `read_only` is true, functions may read files in the directory but not create or modify them.
+    pub fn preopened_dir<H: Into<PathBuf>, G: Into<PathBuf>>(
+        mut self,
+        host_path: H,
+        guest_path: G,
+        read_only: bool,
+    ) -> Self {
+        self.preopens
+            .push((host_path.into(), guest_path.into(), read_only));
+        self
+    }
+
+    /// Sets the clock that provides the current time to functions.
+    ///
+    /// Defaults to the host's clocks. Use a [`ManualClock`](crate::ManualClock) to control time in tests.
+    /// Timeouts and sleeps are still measured in real time.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Seeds the random number generator available to functions.
+    ///
+    /// Every instance uses a generator with the same seed, so functions observe the same sequence of
+    /// random numbers on each invocation. By default, random numbers are cryptographically secure.
+    pub fn random_seed(mut self, seed: u64) -> Self {
+        self.random_seed = Some(seed);
+        self
+    }
+
+    /// Sets the maximum time a function may execute before a `504 Gateway Timeout` response is returned.
+    ///
+    /// Defaults to 60 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum time the tasks a function spawns to run after its response may execute.
+    ///
+    /// Tasks that exceed the timeout are interrupted; as their response was already sent, the
+    /// failure is only logged. Defaults to 10 seconds.
+    pub fn task_timeout(mut self, timeout: Duration) -> Self {
+        self.task_timeout = timeout;
+        self
+    }
+
+    /// Sets the mechanism used to interrupt functions that exceed their timeout.
+    ///
+    /// Defaults to [`Interruption::Fuel`].
+    pub fn interruption(mut self, interruption: Interruption) -> Self {
+        self.interruption = interruption;
+        self
+    }
+
+    /// Sets the maximum fuel an instance may consume before its function traps.
+    ///
+    /// This only applies to [`Interruption::Fuel`]. Defaults to no limit.
+    pub fn fuel_limit(mut self, fuel: u64) -> Self {
+        self.fuel_limit = Some(fuel);
+        self
+    }
+
+    /// Injects an artificially low fuel limit into every invocation of the given function.
+    ///
+    /// This is intended for testing that an application degrades gracefully when a function is
+    /// metered out: an invocation that exhausts the limit receives a `500 Internal Server Error`
+    /// response that is logged, reported, and counted as fuel exhaustion rather than as a trap.
+    ///
+    /// This only applies to [`Interruption::Fuel`].
+    pub fn inject_fuel_limit<T: Into<String>>(mut self, function: T, fuel: u64) -> Self {
+        self.injected_fuel.insert(function.into(), fuel);
+        self
+    }
+
+    /// Sets the interval at which the deadlines of invocations are checked.
+    ///
+    /// This only applies to [`Interruption::Epoch`]. Defaults to 10 milliseconds.
+    pub fn epoch_tick(mut self, tick: Duration) -> Self {
+        self.epoch_tick = tick;
+        self
+    }
+
+    /// Sets whether or not entity tags are generated for the successful `GET` and `HEAD` responses of every function.
+    ///
+    /// Tags are always generated for functions declared with the `etag` option. Requests with a matching
+    /// `If-None-Match` header receive a `304 Not Modified` response.
+    pub fn etags(mut self, enabled: bool) -> Self {
+        self.etags = enabled;
+        self
+    }
+
+    /// Enables session affinity for function instances.
+    ///
+    /// Requests carrying the given session cookie are routed to an instance that is kept alive
+    /// between requests of the same session, allowing functions to keep per-session state in memory.
+    ///
+    /// Instances that are not used within the idle timeout are evicted. An instance is also
+    /// discarded when a function invocation fails. The number of sessions is bounded by
+    /// [`max_sessions`](Self::max_sessions).
+    ///
+    /// Note that session identifiers are supplied by the client; applications using this mode
+    /// should use unguessable session identifiers.
+    pub fn session_affinity<T: Into<String>>(mut self, cookie: T, idle_timeout: Duration) -> Self {
+        self.session_affinity = Some((cookie.into(), idle_timeout));
+        self
+    }
+
+    /// Sets the maximum number of sessions whose instances are kept alive.
+    ///
+    /// A new session evicts the least recently used session once the maximum is reached, so that
+    /// clients sending made-up session cookies can't exhaust the instance pool.
+    ///
+    /// Defaults to half the instance pool, or 500 if instances are not pooled.
+    pub fn max_sessions(mut self, count: usize) -> Self {
+        self.max_sessions = Some(count);
+        self
+    }
+
+    /// Sets the sink that receives an audit record for every function invocation.
+    pub fn audit_sink(mut self, sink: Arc<dyn AuditSink>) -> Self {
+        self.audit_sink = Some(sink);
+        self
+    }
+
+    /// Sets the request header that identifies the authenticated principal in audit records.
+    ///
+    /// The header is expected to be set by a trusted proxy that authenticates requests.
+    pub fn audit_principal_header<T: Into<String>>(mut self, name: T) -> Self {
+        self.audit_principal_header = Some(name.into());
+        self
+    }
+
+    /// Captures the requests of failed invocations to the given directory.
+    ///
+    /// An invocation failed if the function trapped, timed out, or did not return a response.
+    /// Captured requests can be replayed with [`CapturedRequest::to_request`](crate::CapturedRequest::to_request).
+    ///
+    /// Request bodies are buffered in memory when capturing is enabled.
+    pub fn capture_failures<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.capture_failures = Some(dir.into());
+        self
+    }
+
+    /// Collects coverage data written by functions to the given directory.
+    ///
+    /// The directory is preopened for writing at `/coverage`, and its guest path is given to functions in
+    /// the `WASMTIME_FUNCTIONS_COVERAGE_DIR` environment variable. After each successful invocation, the
+    /// module's `__wasmtime_functions_dump_coverage` export is called, if present, so that an instrumented
+    /// module (e.g. one using `minicov`) can write its LLVM profile data there.
+    pub fn coverage_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.coverage_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets a callback that receives the report of each function invocation.
+    ///
+    /// The callback is called on the task processing the request, so it should return quickly.
+    pub fn on_invocation<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&InvocationReport) + Send + Sync + 'static,
+    {
+        self.on_invocation = Some(Arc::new(callback));
+        self
+    }
+
+    /// Sets a hook that attaches extensions to each request before its function is invoked.
+    ///
+    /// Functions read the extensions with `Request::extension`; use this to pass values computed
+    /// by the host, such as an authenticated user's identity, without adding headers to the request.
+    pub fn on_request<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&http_types::Request, &mut RequestExtensions) + Send + Sync + 'static,
+    {
+        self.on_request = Some(Arc::new(hook));
+        self
+    }
+
+    /// Sets a callback that is notified when a configuration is applied with [`Server::apply_config`].
+    ///
+    /// The callback is called once each time a configuration is applied to the module built by this
+    /// builder, after the configuration is applied, so requests received from then on are processed
+    /// with it; use this to reconfigure state kept by the embedder.
+    pub fn on_config_change<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&ServerConfig) + Send + Sync + 'static,
+    {
+        self.on_config_change = Some(Arc::new(callback));
+        self
+    }
+
+    /// Sets whether every call functions make to the host is logged.
+    ///
+    /// Each call is logged with its name, a summary of its arguments and result, and its duration.
+    /// Values that may be sensitive, such as header, cookie, and SQL parameter values and the
+    /// queries of URIs, are summarized by their length or redacted.
+    /// Use [`debug_token`](Self::debug_token) to trace individual requests instead.
+    pub fn trace_host_calls(mut self, enabled: bool) -> Self {
+        self.trace_host_calls = enabled;
+        self
+    }
+
+    /// Sets which requests have the calls their functions make to the host traced.
+    ///
+    /// Sampling keeps high-traffic routes from flooding the log while rare routes, and failed
+    /// requests if enabled, remain fully traced. Tracing every call with
+    /// [`trace_host_calls`](Self::trace_host_calls) takes precedence over sampling.
+    pub fn trace_sampling(mut self, sampling: TraceSampling) -> Self {
+        self.trace_sampling = Some(sampling);
+        self
+    }
+
+    /// Sets the native routes served alongside the module's functions.
+    ///
+    /// The server fails to build if a native route handles requests that a function also handles,
+    /// or is named the same as a function.
+    pub fn registry(mut self, registry: Registry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Sets the token that enables tracing of a request's host calls.
+    ///
+    /// Requests with an `X-Debug` header containing the token are traced as with
+    /// [`trace_host_calls`](Self::trace_host_calls). The header is removed before the request is
+    /// passed to the function so that functions can't observe the token.
+    pub fn debug_token<T: Into<String>>(mut self, token: T) -> Self {
+        self.debug_token = Some(token.into());
+        self
+    }
+
+    /// Sets the casing of the header names listed to functions.
+    ///
+    /// The HTTP server does not preserve the casing of header names, so functions see lowercase
+    /// names by default. Functions that proxy requests to servers expecting conventionally cased
+    /// names can use [`HeaderCase::Title`] instead.
+    pub fn header_case(mut self, case: HeaderCase) -> Self {
+        self.header_case = case;
+        self
+    }
+
+    /// Sets how the values of headers repeated in a request are presented to functions.
+    ///
+    /// By default, each value is listed separately and looking up a header by name returns its
+    /// first value.
+    pub fn duplicate_headers(mut self, duplicates: DuplicateHeaders) -> Self {
+        self.duplicate_headers = duplicates;
+        self
+    }
+
+    /// Sets the renderer used for the bodies of host error responses.
+    ///
+    /// Host errors include timeouts, traps, functions that fail to return a response, requests
+    /// that fail validation, and requests that do not match any route. Use [`ProblemErrorRenderer`](crate::ProblemErrorRenderer)
+    /// to render them as RFC 7807 problem details.
+    pub fn error_renderer(mut self, renderer: Arc<dyn ErrorRenderer>) -> Self {
+        self.error_renderer = Some(renderer);
+        self
+    }
+
+    /// Sets the maximum number of concurrent invocations of the given function.
+    ///
+    /// This overrides any concurrency limit declared in the function's metadata. The limit must be
+    /// greater than zero.
+    pub fn function_concurrency<T: Into<String>>(mut self, function: T, limit: usize) -> Self {
+        self.function_concurrency.insert(function.into(), limit);
+        self
+    }
+
+    /// Sets the maximum number of invocations that may wait for a function at its concurrency limit.
+    ///
+    /// Invocations in excess of the queue size receive a `503 Service Unavailable` response.
+    ///
+    /// Defaults to no limit.
+    pub fn concurrency_queue(mut self, size: usize) -> Self {
+        self.concurrency_queue = size;
+        self
+    }
+
+    /// Sets the maximum number of concurrent function invocations across the server.
+    ///
+    /// Invocations in excess of the limit wait for a permit, subject to the same queue as function concurrency limits.
+    /// The limit must be greater than zero.
+    ///
+    /// Defaults to no limit.
+    pub fn max_concurrency(mut self, limit: usize) -> Self {
+        self.max_concurrency = Some(limit);
+        self
+    }
+
+    /// Sets the maximum size of a request body in bytes.
+    ///
+    /// Requests with a larger declared body receive a `413 Payload Too Large` response.
+    ///
+    /// Defaults to no limit.
+    pub fn max_body_size(mut self, bytes: u64) -> Self {
+        self.max_body_size = Some(bytes);
+        self
+    }
+
+    /// Sets the maximum number of responses held by the response cache.
+    ///
+    /// Only the responses of routes that opt into caching in their metadata are cached.
+    /// When the cache is full, the least recently used response is evicted.
+    ///
+    /// Defaults to 1024.
+    pub fn response_cache_capacity(mut self, capacity: usize) -> Self {
+        self.response_cache_capacity = capacity;
+        self
+    }
+
+    /// Sets the SQL provider used by functions that access a SQL database.
+    ///
+    /// Functions that execute SQL statements without a configured provider receive an error.
+    pub fn sql_provider(mut self, provider: Arc<dyn SqlProvider>) -> Self {
+        self.sql_provider = Some(provider);
+        self
+    }
+
+    /// Sets the maximum time a SQL statement may execute.
+    ///
+    /// Defaults to 30 seconds.
+    pub fn sql_statement_timeout(mut self, timeout: Duration) -> Self {
+        self.sql_timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum time a SQL statement executed by the given function may execute.
+    ///
+    /// This overrides the timeout set with [`ServerBuilder::sql_statement_timeout`] for the function.
+    pub fn function_sql_statement_timeout<T: Into<String>>(
+        mut self,
+        function: T,
+        timeout: Duration,
+    ) -> Self {
+        self.sql_function_timeouts.insert(function.into(), timeout);
+        self
+    }
+
+    /// Allows functions to send outbound HTTP requests to the given host.
+    ///
+    /// By default, functions may not send outbound requests to any host.
+    pub fn allow_host(mut self, host: AllowedHost) -> Self {
+        self.allowed_hosts.push(host);
+        self
+    }
+
+    /// Sets the provider that sends the outbound HTTP requests of functions.
+    ///
+    /// Requests are still checked against the allowed hosts before being passed to the provider.
+    /// By default, requests are sent with an HTTP client.
+    pub fn fetch_provider(mut self, provider: Arc<dyn FetchProvider>) -> Self {
+        self.fetch_provider = Some(provider);
+        self
+    }
+
+    /// Allows functions to make gRPC calls to the given service, sending them to the given target.
+    ///
+    /// The service is the fully-qualified name of a protobuf service, such as `orders.v1.Orders`,
+    /// and the target is the URI of the server that implements it, such as `http://orders:50051`.
+    /// Functions only name the service, so the host decides which server receives the calls.
+    /// By default, functions may not make gRPC calls to any service.
+    pub fn grpc_service<T: Into<String>, U: Into<String>>(mut self, service: T, target: U) -> Self {
+        self.grpc_services.insert(service.into(), target.into());
+        self
+    }
+
+    /// Sets the provider that makes the outbound gRPC calls of functions.
+    ///
+    /// Calls are still limited to the services added with [`grpc_service`](Self::grpc_service).
+    /// By default, calls are made with a tonic client.
+    pub fn grpc_provider(mut self, provider: Arc<dyn GrpcProvider>) -> Self {
+        self.grpc_provider = Some(provider);
+        self
+    }
+
+    /// Sets the provider that resolves the services functions look up with service discovery,
+    /// such as a [`StaticDiscovery`](crate::StaticDiscovery) or a [`DnsDiscovery`](crate::DnsDiscovery).
+    ///
+    /// By default, service discovery is not available to functions.
+    pub fn discovery_provider(mut self, provider: Arc<dyn DiscoveryProvider>) -> Self {
+        self.discovery_provider = Some(provider);
+        self
+    }
+
+    /// Sets the provider that stores the parts of `multipart/form-data` requests functions upload,
+    /// such as a [`DirectoryBlobProvider`](crate::DirectoryBlobProvider).
+    ///
+    /// Uploaded parts are streamed from the request to the provider without being read into the
+    /// function's memory. By default, functions can't upload parts.
+    pub fn blob_provider(mut self, provider: Arc<dyn BlobProvider>) -> Self {
+        self.blob_provider = Some(provider);
+        self
+    }
+
+    /// Sets the provider of the app-scoped cache that functions share through `cache::app`.
+    ///
+    /// Use a shared provider when requests are served by more than one server. By default, values
+    /// are cached in memory by a [`MemoryKvProvider`](crate::MemoryKvProvider) holding up to 10,000 values.
+    pub fn kv_provider(mut self, provider: Arc<dyn KvProvider>) -> Self {
+        self.kv_provider = Some(provider);
+        self
+    }
+
+    /// Sets the directory of the Handlebars templates functions render responses with.
+    ///
+    /// Every `.hbs` file in the directory and its subdirectories is parsed when the server is built,
+    /// and is named by its path relative to the directory without the extension, such as `pages/index`.
+    /// By default, functions have no templates to render.
+    pub fn templates_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.templates_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the directory of the translation bundles functions translate messages with.
+    ///
+    /// Every `.json` file in the directory is loaded when the server is built and is named by its
+    /// locale tag, such as `fr-CA.json`. Requests are negotiated to the locale of a bundle from their
+    /// `Accept-Language` header. By default, functions have no messages to translate.
+    pub fn translations_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.translations_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the locale used when a request's languages match no translation bundle.
+    ///
+    /// The translations directory must contain a bundle for the locale. Defaults to `en`.
+    pub fn default_locale<T: Into<String>>(mut self, locale: T) -> Self {
+        self.default_locale = locale.into();
+        self
+    }
+
+    /// Sets the key that URLs signed by functions are signed and verified with.
+    ///
+    /// Functions sign URLs with `sign_url` to hand out temporary links to routes declared with the
+    /// `signed` option; the server rejects requests to those routes with `403 Forbidden` unless the
+    /// URL's signature is valid and it has not expired. A module with signed routes requires a key.
+    ///
+    /// Changing the key invalidates every URL signed with the previous key.
+    pub fn url_signing_key<T: Into<Vec<u8>>>(mut self, key: T) -> Self {
+        self.url_signing_key = Some(key.into());
+        self
+    }
+
+    /// Sets the Content-Security-Policy header of responses from functions.
+    ///
+    /// When a function asks for its request's nonce with `Request::nonce`, the nonce is appended to
+    /// the policy's `script-src` and `style-src` directives, so inline scripts and styles marked
+    /// with the nonce are allowed. A policy without either directive has the nonce appended to its
+    /// `default-src` directive, or else gains a `script-src` directive for it. Responses for which
+    /// the function set its own policy are left unchanged, and responses with a nonce are never
+    /// cached. By default, no policy is set.
+    pub fn content_security_policy<T: Into<String>>(mut self, policy: T) -> Self {
+        self.content_security_policy = Some(policy.into());
+        self
+    }
+
+    /// Replays the responses of requests retried with the same `Idempotency-Key` header for the given time-to-live.
+    ///
+    /// The first response to a request with a key is stored, and requests to the same function with
+    /// the key receive the stored response with an `Idempotent-Replayed` header instead of invoking
+    /// the function again. A retry that arrives while the first request is still being processed
+    /// is rejected with `409 Conflict`, and reusing a key for a different method or target is
+    /// rejected with `422 Unprocessable Entity`. Server errors are not stored so that they can be
+    /// retried. Requests with a safe method, such as `GET`, are never replayed. Keys are scoped to
+    /// the client that sent them; see [`idempotency_scope_header`](Self::idempotency_scope_header).
+    ///
+    /// Responses are stored with the provider set with [`kv_provider`](Self::kv_provider), so use a
+    /// shared provider when requests are served by more than one server. Responses with a body
+    /// larger than 1 MiB are not stored.
+    ///
+    /// By default, the `Idempotency-Key` header is passed to functions without being acted upon.
+    pub fn idempotency(mut self, ttl: Duration) -> Self {
+        self.idempotency_ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the request header that identifies the client an idempotency key belongs to.
+    ///
+    /// A stored response is only replayed for requests with the same value of the header, so a
+    /// client can't receive another client's response by reusing its key. Values are hashed before
+    /// they are passed to the store. By default, keys are scoped by the header set with
+    /// [`audit_principal_header`](Self::audit_principal_header), or else by `Authorization`.
+    ///
+    /// Requests without the header are scoped by the IP address of the client. Behind a proxy, every
+    /// client has the proxy's address, so set a header that identifies clients.
+    pub fn idempotency_scope_header<T: Into<String>>(mut self, name: T) -> Self {
+        self.idempotency_scope_header = Some(name.into());
+        self
+    }
+
+    /// Enforces per-tenant quotas of requests and fuel before functions are invoked.
+    ///
+    /// Each request counts against the quota of the configured tenant identified by the policy's
+    /// header, or else against the quota shared by every other request.
+    /// Requests from a tenant that has made its allowed number of requests, or whose invocations have
+    /// consumed its allowed fuel, in the current window are rejected with `429 Too Many Requests`
+    /// and a `Retry-After` header until the next window. Fuel is only measured with
+    /// [`Interruption::Fuel`], and the invocation that exhausts a tenant's fuel is not interrupted.
+    ///
+    /// Usage is exposed by the admin API at `/quotas`. By default, requests are not limited.
+    pub fn quota(mut self, policy: QuotaPolicy) -> Self {
+        self.quota_policy = Some(policy);
+        self
+    }
+
+    /// Sets the store of the usage counted against quotas.
+    ///
+    /// Use a shared store when requests are served by more than one server.
+    /// By default, usage is stored in a [`MemoryQuotaStore`].
+    pub fn quota_store(mut self, store: Arc<dyn QuotaStore>) -> Self {
+        self.quota_store = Some(store);
+        self
+    }
+
+    /// Sets the policy for retrying the failed outbound HTTP requests, SQL queries, and gRPC calls of functions.
+    ///
+    /// Only SQL queries a function marks as idempotent are retried; other statements may not be safe to repeat.
+    /// Retries count against the function's timeout, and SQL statement timeouts apply to each attempt.
+    /// By default, failed calls are not retried.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Sets the policy for rejecting outbound calls to a failing destination without attempting them.
+    ///
+    /// Circuits are kept per destination: the host and port of outbound HTTP requests, the service
+    /// of gRPC calls, and the database of SQL statements. The state of each circuit is exposed in
+    /// the server's metrics. By default, calls are always attempted.
+    pub fn circuit_breaker(mut self, policy: CircuitBreakerPolicy) -> Self {
+        self.circuit_breaker = Some(policy);
+        self
+    }
+
+    /// Adds a trusted Ed25519 public key used to verify the module's signature.
+    ///
+    /// When any trusted key is added, the module must be signed by one of the trusted keys
+    /// or the server will fail to build; the signature is verified before the module is compiled.
+    /// Precompiled code cannot be verified, so building a server with both trusted keys and
+    /// [`ServerBuilder::precompiled`] fails.
+    pub fn trusted_key<T: Into<Vec<u8>>>(mut self, key: T) -> Self {
+        self.trusted_keys.push(key.into());
+        self
+    }
+
+    /// Sets a detached Ed25519 signature of the module.
+    ///
+    /// If a detached signature is not set, the signature is read from the module's `__signature`
+    /// custom section, which signs the module with that section removed.
+    pub fn module_signature(mut self, signature: &'a [u8]) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    /// Sets the precompiled code of the module, as produced by [`ServerBuilder::precompile`].
+    ///
+    /// The module itself is still required as it contains the metadata of the module's functions.
+    /// Precompiled code cannot be used with [`ServerBuilder::trusted_key`].
+    ///
+    /// # Safety
+    ///
+    /// The precompiled code is not validated before it is executed; it must come from a trusted
+    /// call to [`ServerBuilder::precompile`] with the same module and server configuration.
+    pub unsafe fn precompiled(mut self, code: &'a [u8]) -> Self {
+        self.precompiled = Some(code);
+        self
+    }
+
+    /// Sets whether or not modules built for an older host interface are allowed to run.
+    ///
+    /// By default, a module built for a host interface older than [`MIN_INTERFACE_VERSION`] fails
+    /// to build the server; when allowed, a warning is logged instead and the module may still fail
+    /// to link. Modules built for a newer host interface than [`HOST_INTERFACE_VERSION`] are always rejected.
+    pub fn allow_older_sdk(mut self, allow: bool) -> Self {
+        self.allow_older_sdk = allow;
+        self
+    }
+
+    /// Sets whether or not connections are kept alive between requests.
+    ///
+    /// Defaults to `true`.
+    pub fn keep_alive(mut self, enabled: bool) -> Self {
+        self.connection.keep_alive = enabled;
+        self
+    }
+
+    /// Sets the time an idle connection is kept alive while waiting for its next request.
+    ///
+    /// Defaults to 60 seconds.
+    pub fn keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.connection.keep_alive_timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum number of requests served on a single connection before it is closed.
+    ///
+    /// Defaults to no limit.
+    pub fn max_requests_per_connection(mut self, max: usize) -> Self {
+        self.connection.max_requests = Some(max);
+        self
+    }
+
+    /// Sets the time allowed to read the headers of a request.
+    ///
+    /// Defaults to 60 seconds.
+    pub fn header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.connection.header_read_timeout = timeout;
+        self
+    }
+
+    /// Sets whether or not `TCP_NODELAY` is set on accepted connections.
+    ///
+    /// Defaults to `false`.
+    pub fn tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.connection.nodelay = enabled;
+        self
+    }
+
+    /// Sets the maximum number of pending connections for the listening socket.
+    ///
+    /// Defaults to 128.
+    pub fn listen_backlog(mut self, backlog: u32) -> Self {
+        self.connection.backlog = backlog;
+        self
+    }
+
+    /// Builds the server and binds it to the given address.
+    pub async fn bind<A: Into<SocketAddr>>(self, addr: A) -> Result<Server, ServerError> {
+        let (app, state, connection) = self.prepare().await?;
+        let listener = TcpListener::new(addr.into(), connection);
+        Server::listen(listener, app, vec![(String::new(), state)]).await
+    }
+
+    /// Builds the server and accepts connections on an already-bound listener.
+    ///
+    /// This is useful for sockets passed to the process, such as with systemd socket activation.
+    pub async fn listen(self, listener: std::net::TcpListener) -> Result<Server, ServerError> {
+        let (app, state, connection) = self.prepare().await?;
+        let listener = TcpListener::from_std(listener, connection).map_err(ServerError::Accept)?;
+        Server::listen(listener, app, vec![(String::new(), state)]).await
+    }
+
+    /// Builds a server that processes requests directly rather than listening for connections.
+    pub async fn local(self) -> Result<LocalServer, ServerError> {
+        let (app, _, _) = self.prepare().await?;
+        Ok(LocalServer { app })
+    }
+
+    /// Validates the server configuration without binding the server.
+    ///
+    /// This verifies the module's signature, compiles the module, reads its metadata, checks that
+    /// the module exports each declared function with the expected signature, checks for
+    /// conflicting routes, verifies every import of the module can be satisfied, and resolves the
+    /// environment variables declared by the module.
+    pub async fn validate(self) -> Result<(), ServerError> {
+        let (_, state, _) = self.build()?;
+        let inner = &state.inner;
+
+        let mut store = Store::new(
+            inner.module.engine(),
+            Context::new(
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                WasiCtxBuilder::new().build(),
+            ),
+        );
+
+        inner
+            .linker
+            .instantiate_pre(&mut store, &inner.module)
+            .map_err(ServerError::Compile)?;
+
+        if !inner.environment.is_resolved() {
+            inner.environment.resolve_all().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Compiles the module ahead of time, returning the precompiled code.
+    ///
+    /// The precompiled code can be used with [`ServerBuilder::precompiled`] so that the module
+    /// does not need to be compiled when the server is built.
+    pub fn precompile(self) -> Result<Vec<u8>, ServerError> {
+        self.verify_signature()?;
+
+        self.engine()?
+            .precompile_module(self.module)
+            .map_err(ServerError::Compile)
+    }
+
+    fn verify_signature(&self) -> Result<(), ServerError> {
+        if self.trusted_keys.is_empty() {
+            return Ok(());
+        }
+
+        // Precompiled code is executed instead of the module, so a verified module signature
+        // says nothing about the code that actually runs
+        if self.precompiled.is_some() {
+            return Err(ServerError::Signature(anyhow!(
+                "precompiled code cannot be verified against the trusted keys"
+            )));
+        }
+
+        signature::verify(self.module, self.signature, &self.trusted_keys)
+            .map_err(ServerError::Signature)
+    }
+
+    fn engine(&self) -> Result<Engine, ServerError> {
+        let mut config = Config::default();
+
+        config.allocation_strategy(match self.instance_pool {
+            Some(count) => wasmtime::InstanceAllocationStrategy::Pooling {
+                strategy: wasmtime::PoolingAllocationStrategy::default(),
+                module_limits: wasmtime::ModuleLimits::default(),
+                instance_limits: wasmtime::InstanceLimits {
+                    count,
+                    ..Default::default()
+                },
+            },
+            None => wasmtime::InstanceAllocationStrategy::OnDemand,
+        });
+        config.cranelift_opt_level(match self.opt_level {
+            OptLevel::None => wasmtime::OptLevel::None,
+            OptLevel::Speed => wasmtime::OptLevel::Speed,
+            OptLevel::SpeedAndSize => wasmtime::OptLevel::SpeedAndSize,
+        });
+        config.debug_info(self.debug_info);
+        config.consume_fuel(self.interruption == Interruption::Fuel);
+        config.interruptable(true);
+        config.async_support(true);
+
+        if self.compilation_cache {
+            config
+                .cache_config_load_default()
+                .map_err(ServerError::Compile)?;
+        }
+
+        Engine::new(&config).map_err(ServerError::Compile)
+    }
+
+    fn check_interface_version(&self, version: Option<u32>) -> Result<(), ServerError> {
+        let error = ServerError::InterfaceVersion {
+            module: version,
+            min: MIN_INTERFACE_VERSION,
+            max: HOST_INTERFACE_VERSION,
+        };
+
+        match version {
+            Some(v) if v > HOST_INTERFACE_VERSION => Err(error),
+            Some(v) if v >= MIN_INTERFACE_VERSION => Ok(()),
+            _ if self.allow_older_sdk => {
+                log::warn!("{}; continuing because older SDKs are allowed", error);
+                Ok(())
+            }
+            _ => Err(error),
+        }
+    }
+
+    // Builds the server, then resolves the environment and warms up the module as configured
+    async fn prepare(self) -> Result<(tide::Server<State>, State, ConnectionOptions), ServerError> {
+        let resolve = self.warmup || self.lazy_environment.is_none();
+        let warmup = self.warmup;
+        let (app, state, connection) = self.build()?;
+
+        if resolve {
+            state.inner.environment.resolve_all().await?;
+        }
+
+        if warmup {
+            state
+                .inner
+                .instantiate(None, None)
+                .await
+                .map_err(ServerError::Warmup)?;
+        }
+
+        Ok((app, state, connection))
+    }
+
+    fn build(self) -> Result<(tide::Server<State>, State, ConnectionOptions), ServerError> {
+        self.verify_signature()?;
+
+        if self.max_concurrency == Some(0) {
+            return Err(ServerError::ConcurrencyLimit(anyhow!(
+                "the maximum concurrency must be greater than zero"
+            )));
+        }
+
+        if let Some((function, _)) = self.function_concurrency.iter().find(|(_, l)| **l == 0) {
+            return Err(ServerError::ConcurrencyLimit(anyhow!(
+                "the concurrency limit of function '{}' must be greater than zero",
+                function
+            )));
+        }
+
+        let metadata =
+            Metadata::from_module_bytes(&self.module).map_err(ServerError::InvalidModule)?;
+
+        if metadata.functions.is_empty() {
+            return Err(ServerError::InvalidModule(anyhow!(
+                "module contains no Wasmtime functions"
+            )));
+        }
+
+        self.check_interface_version(metadata.interface_version)?;
+
+        Self::check_functions(self.module, &metadata, self.registry.routes())?;
+
+        let environment = Environment::new(
+            self.environment,
+            metadata.vars,
+            self.lazy_environment.flatten(),
+        );
+
+        let engine = self.engine()?;
+        let module = match self.precompiled {
+            // Safety: the caller of `precompiled` guarantees the code is trusted
+            Some(code) => unsafe { Module::deserialize(&engine, code) },
+            None => Module::new(&engine, self.module),
+        }
+        .map_err(ServerError::Compile)?;
+
+        if let Some(dir) = &self.coverage_dir {
+            std::fs::create_dir_all(dir).map_err(|source| ServerError::Preopen {
+                path: dir.clone(),
+                source,
+            })?;
+        }
+
+        let preopens = self
+            .preopens
+            .iter()
+            .map(|(host_path, guest_path, read_only)| (host_path, guest_path.clone(), *read_only))
+            .chain(
+                self.coverage_dir
+                    .iter()
+                    .map(|dir| (dir, PathBuf::from(COVERAGE_GUEST_DIR), false)),
+            )
+            .map(|(host_path, guest_path, read_only)| {
+                Preopen::open(host_path, guest_path, read_only).map_err(|source| {
+                    ServerError::Preopen {
+                        path: host_path.clone(),
+                        source,
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut linker = Linker::new(&engine);
+        Context::add_to_linker(&mut linker).map_err(ServerError::Compile)?;
+
+        let routes = RouteTable::new(
+            &metadata.functions,
+            self.registry.routes(),
+            &self.function_concurrency,
+        );
+        let audit_principal_header = self.audit_principal_header;
+        let sql_timeout = self.sql_timeout;
+        let sql_function_timeouts = self.sql_function_timeouts;
+        let max_sessions = match (self.max_sessions, self.instance_pool) {
+            (Some(count), _) => count,
+            (None, Some(pool)) => pool as usize / 2,
+            (None, None) => DEFAULT_MAX_SESSIONS,
+        };
+        let idempotency_scope_header = self
+            .idempotency_scope_header
+            .or_else(|| audit_principal_header.clone());
+        let quota_store = self.quota_store;
+        let kv: Arc<dyn KvProvider> = self
+            .kv_provider
+            .unwrap_or_else(|| Arc::new(MemoryKvProvider::new(DEFAULT_APP_CACHE_CAPACITY)));
+
+        let templates = match &self.templates_dir {
+            Some(dir) => Some(Arc::new(
+                Templates::load(dir).map_err(ServerError::Templates)?,
+            )),
+            None => None,
+        };
+
+        let translations = match &self.translations_dir {
+            Some(dir) => Some(Arc::new(
+                Translations::load(dir, &self.default_locale).map_err(ServerError::Translations)?,
+            )),
+            None => None,
+        };
+
+        let signer = self
+            .url_signing_key
+            .map(|key| Arc::new(UrlSigner::new(key, self.clock.clone())));
+
+        let csp = match &self.content_security_policy {
+            Some(policy) => Some(
+                ContentSecurityPolicy::new(policy).map_err(ServerError::ContentSecurityPolicy)?,
+            ),
+            None => None,
+        };
+
+        let resilience = Arc::new(Resilience::new(self.retry_policy, self.circuit_breaker));
+
+        let grpc = if self.grpc_services.is_empty() {
+            None
+        } else {
+            Some(Arc::new(
+                Grpc::new(self.grpc_services, self.grpc_provider, resilience.clone())
+                    .map_err(ServerError::Grpc)?,
+            ))
+        };
+
+        let state = State {
+            inner: Arc::new(StateInner {
+                module,
+                linker,
+                environment,
+                inherit_stdout: self.inherit_stdout,
+                log_stdout: self.log_stdout,
+                preopens,
+                clock: self.clock,
+                random_seed: self.random_seed,
+                config: RwLock::new(Arc::new(ServerConfig {
+                    timeout: self.timeout,
+                    task_timeout: self.task_timeout,
+                    max_body_size: self.max_body_size,
+                    allowed_hosts: self.allowed_hosts,
+                    quota: self.quota_policy,
+                    log_level: log::max_level(),
+                })),
+                on_config_change: self.on_config_change,
+                interruption: self.interruption,
+                fuel_limit: self.fuel_limit,
+                epoch_tick: self.epoch_tick,
+                sessions: self.session_affinity.map(|(cookie, idle_timeout)| {
+                    Sessions::new(cookie, idle_timeout, max_sessions)
+                }),
+                auditor: self
+                    .audit_sink
+                    .map(|sink| Auditor::new(sink, audit_principal_header)),
+                capturer: self.capture_failures.map(Capturer::new),
+                coverage: self.coverage_dir.is_some(),
+                on_invocation: self.on_invocation,
+                on_request: self.on_request,
+                trace_host_calls: self.trace_host_calls,
+                trace_sampling: self.trace_sampling.clone(),
+                debug_token: self.debug_token,
+                header_policy: HeaderPolicy {
+                    case: self.header_case,
+                    duplicates: self.duplicate_headers,
+                },
+                sql: self.sql_provider.map(|provider| {
+                    Arc::new(Sql::new(
+                        provider,
+                        sql_timeout,
+                        sql_function_timeouts,
+                        resilience.clone(),
+                    ))
+                }),
+                fetch: Arc::new(Fetch::new(self.fetch_provider, resilience.clone())),
+                grpc,
+                discovery: self.discovery_provider,
+                blobs: self.blob_provider,
+                kv: kv.clone(),
+                templates,
+                translations,
+                signer,
+                csp,
+                idempotency: self
+                    .idempotency_ttl
+                    .map(|ttl| Idempotency::new(kv, ttl, idempotency_scope_header)),
+                quotas: Quotas::new(
+                    quota_store.unwrap_or_else(|| Arc::new(MemoryQuotaStore::new())),
+                ),
+                resilience,
+                functions: metadata.functions.clone(),
+                routes,
+                metrics: Metrics::default(),
+            }),
+        };
+
+        let mut app = tide::with_state(state.clone());
+
+        app.with(crate::log::LogMiddleware);
+
+        if let Some(renderer) = self.error_renderer {
+            app.with(ErrorMiddleware::new(renderer));
+        }
+
+        app.with(BodyLimitMiddleware);
+
+        Self::check_middleware(&state.inner.module)?;
+
+        let concurrency_queue = self.concurrency_queue;
+        let server_limiter = self
+            .max_concurrency
+            .map(|limit| Arc::new(ConcurrencyLimiter::new(limit, concurrency_queue)));
+        let response_cache = Arc::new(ResponseCache::new(self.response_cache_capacity));
+
+        for function in metadata.functions {
+            match &function.trigger {
+                FunctionTrigger::Http {
+                    path,
+                    methods,
+                    consumes,
+                    params,
+                    cache,
+                    headers,
+                    signed,
+                    etag,
+                } => {
+                    let headers = headers
+                        .iter()
+                        .map(|(name, value)| {
+                            Ok((HeaderName::from_str(name)?, HeaderValue::from_str(value)?))
+                        })
+                        .collect::<tide::Result<Vec<_>>>()
+                        .map_err(|e| {
+                            ServerError::InvalidModule(anyhow!(
+                                "function '{}' has an invalid response header: {}",
+                                function.name,
+                                e
+                            ))
+                        })?;
+
+                    let signer = match (signed, &state.inner.signer) {
+                        (false, _) => None,
+                        (true, Some(signer)) => Some(signer.clone()),
+                        (true, None) => {
+                            return Err(ServerError::InvalidModule(anyhow!(
+                                "function '{}' requires signed URLs but no URL signing key is configured",
+                                function.name
+                            )))
+                        }
+                    };
+
+                    let mut route = app.at(path);
+
+                    if *etag || self.etags {
+                        route.with(crate::etag::ETagMiddleware);
+                    }
+
+                    let limit = self
+                        .function_concurrency
+                        .get(&function.name)
+                        .copied()
+                        .or_else(|| function.concurrency.map(|c| c as usize));
+
+                    if limit == Some(0) {
+                        return Err(ServerError::InvalidModule(anyhow!(
+                            "function '{}' declares a concurrency limit of zero",
+                            function.name
+                        )));
+                    }
+
+                    let mut settings = vec![
+                        ("function".to_string(), Setting::Text(function.name.clone())),
+                        ("path".to_string(), Setting::Text(path.clone())),
+                    ];
+
+                    if let Some(limit) = limit {
+                        settings.push(("concurrency".to_string(), Setting::Integer(limit as i64)));
+                    }
+
+                    if let Some(cache) = cache {
+                        settings.push((
+                            "cache_ttl".to_string(),
+                            Setting::Duration(Duration::from_secs(cache.ttl as u64)),
+                        ));
+                    }
+
+                    if let Some(sql) = &state.inner.sql {
+                        settings.push((
+                            "sql_statement_timeout".to_string(),
+                            Setting::Duration(sql.timeout(&function.name)),
+                        ));
+                    }
+
+                    if let Some(fuel) = state.inner.fuel_limit {
+                        settings.push(("fuel_limit".to_string(), Setting::Integer(fuel as i64)));
+                    }
+
+                    let injected_fuel = self
+                        .injected_fuel
+                        .get(&function.name)
+                        .copied()
+                        .filter(|_| state.inner.interruption == Interruption::Fuel);
+
+                    if let Some(fuel) = injected_fuel {
+                        settings.push((
+                            "injected_fuel_limit".to_string(),
+                            Setting::Integer(fuel as i64),
+                        ));
+                    }
+
+                    settings.push((
+                        "grpc_calls".to_string(),
+                        Setting::Boolean(state.inner.grpc.is_some()),
+                    ));
+
+                    let endpoint = Endpoint {
+                        function: Arc::new(function.name.clone()),
+                        settings: Arc::new(settings),
+                        env_scope: function.vars.clone().map(Arc::new),
+                        validator: Arc::new(RequestValidator::new(
+                            consumes.clone(),
+                            params.clone(),
+                        )),
+                        limiter: limit.map(|limit| {
+                            Arc::new(ConcurrencyLimiter::new(limit, concurrency_queue))
+                        }),
+                        server_limiter: server_limiter.clone(),
+                        cache: cache.as_ref().map(|cache| {
+                            Arc::new(RouteCache::new(
+                                response_cache.clone(),
+                                Duration::from_secs(cache.ttl as u64),
+                                cache.vary.clone(),
+                            ))
+                        }),
+                        headers: Arc::new(headers),
+                        injected_fuel,
+                        signer,
+                        trace_ratio: self
+                            .trace_sampling
+                            .as_ref()
+                            .map(|sampling| sampling.ratio(&function.name, function.trace_sample))
+                            .unwrap_or_default(),
+                        metrics: state.inner.metrics.function(&function.name),
+                    };
+
+                    if methods.is_empty() {
+                        route.all(endpoint);
+                    } else {
+                        for method in methods {
+                            let method =
+                                http_types::Method::try_from(method.as_ref()).map_err(|_| {
+                                    ServerError::InvalidModule(anyhow!(
+                                        "function '{}' declares an invalid method '{}'",
+                                        function.name,
+                                        method.as_ref()
+                                    ))
+                                })?;
+                            route.method(method, endpoint.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        for route in self.registry.routes() {
+            let endpoint = NativeEndpoint::new(route, state.inner.metrics.function(&route.name));
+            let mut app_route = app.at(&route.path);
+
+            if self.etags {
+                app_route.with(crate::etag::ETagMiddleware);
+            }
+
+            match route.method {
+                Some(method) => {
+                    app_route.method(method, endpoint);
+                }
+                None => {
+                    app_route.all(endpoint);
+                }
+            }
+        }
+
+        Ok((app, state, self.connection))
+    }
+
+    // Checks that the module's middleware, if any, has the signature expected by the runtime
+    fn check_middleware(module: &Module) -> Result<(), ServerError> {
+        match module.get_export(MIDDLEWARE_EXPORT) {
+            None => Ok(()),
+            Some(ExternType::Func(ty))
+                if ty.params().eq([ValType::I32, ValType::I32])
+                    && ty.results().eq([ValType::I32]) =>
+            {
+                Ok(())
+            }
+            Some(_) => Err(ServerError::InvalidModule(anyhow!(
+                "module exports '{}' but it is not a middleware function of type `(i32, i32) -> i32`",
+                MIDDLEWARE_EXPORT
+            ))),
+        }
+    }
+
+    // Checks that every function is exported by the module with the signature its trigger requires
+    // and that no two functions or native routes handle the same route
+    fn check_functions(
+        module: &[u8],
+        metadata: &Metadata,
+        native: &[NativeRoute],
+    ) -> Result<(), ServerError> {
+        let functions = &metadata.functions;
+
+        let violations = metadata
+            .validate_exports(&module)
+            .map_err(ServerError::InvalidModule)?;
+
+        if !violations.is_empty() {
+            return Err(ServerError::InvalidModule(anyhow!(
+                "module does not match its function metadata: {}",
+                violations
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            )));
+        }
+
+        let mut handlers: Vec<(&str, &str, Vec<Option<&str>>)> = Vec::new();
+
+        for function in functions {
+            match &function.trigger {
+                FunctionTrigger::Http { path, methods, .. } => {
+                    let methods = if methods.is_empty() {
+                        vec![None]
+                    } else {
+                        methods.iter().map(|m| Some(m.as_ref())).collect()
+                    };
+
+                    handlers.push((&function.name, path, methods));
+                }
+            }
+        }
+
+        for route in native {
+            if functions.iter().any(|f| f.name == route.name) {
+                return Err(ServerError::InvalidModule(anyhow!(
+                    "native route '{}' has the same name as a function of the module",
+                    route.name
+                )));
+            }
+
+            handlers.push((
+                &route.name,
+                &route.path,
+                vec![route.method.as_ref().map(|m| m.as_ref())],
+            ));
+        }
+
+        let mut routes: HashMap<(String, Option<&str>), &str> = HashMap::new();
+
+        for (name, path, methods) in handlers {
+            // Parameter names don't affect matching, so `/a/:x` and `/a/:y` are the same route
+            let route = path
+                .split('/')
+                .map(|segment| match segment.chars().next() {
+                    Some(':') => ":",
+                    Some('*') => "*",
+                    _ => segment,
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+
+            for method in methods {
+                let conflict = routes
+                    .iter()
+                    .find(|((r, m), _)| {
+                        *r == route && (m.is_none() || method.is_none() || *m == method)
+                    })
+                    .map(|(_, f)| *f);
+
+                if let Some(other) = conflict {
+                    return Err(ServerError::InvalidModule(anyhow!(
+                        "functions '{}' and '{}' both handle {} requests to '{}'",
+                        other,
+                        name,
+                        method.unwrap_or("all"),
+                        path
+                    )));
+                }
+
+                routes.insert((route.clone(), method), name);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The Wasmtime Functions HTTP server.
+///
+/// This server is used to host the given WebAssembly module and route requests to Wasmtime functions.
+pub struct Server {
+    addr: SocketAddr,
+    listener: Box<dyn tide::listener::Listener<State>>,
+    active: Arc<AtomicUsize>,
+    received: Arc<AtomicU64>,
+    mounts: Vec<(String, State)>,
+    deployment: Option<Arc<Deployment>>,
+    mirror: Option<Arc<Mirror>>,
+}
+
+impl Server {
+    /// Creates a runtime server.
+    pub async fn new<A: Into<SocketAddr>>(
+        addr: A,
+        module: &[u8],
+        environment: Arc<dyn EnvironmentProvider>,
+        debug_info: bool,
+        inherit_stdout: bool,
+    ) -> Result<Self, ServerError> {
+        ServerBuilder::new(module, environment)
+            .debug_info(debug_info)
+            .inherit_stdout(inherit_stdout)
+            .bind(addr)
+            .await
+    }
+
+    /// Creates a builder for configuring a runtime server.
+    pub fn builder(module: &[u8], environment: Arc<dyn EnvironmentProvider>) -> ServerBuilder<'_> {
+        ServerBuilder::new(module, environment)
+    }
+
+    /// Creates a runtime server that serves multiple modules, each mounted at a path prefix.
+    ///
+    /// Requests are dispatched to the module mounted at the matching prefix, with the prefix removed from the request path.
+    ///
+    /// The connection options of the first builder are used for the server.
+    pub async fn mount<A: Into<SocketAddr>>(
+        addr: A,
+        mounts: Vec<(String, ServerBuilder<'_>)>,
+    ) -> Result<Self, ServerError> {
+        let addr = addr.into();
+        Self::mount_with(mounts, |connection| Ok(TcpListener::new(addr, connection))).await
+    }
+
+    /// Creates a runtime server that serves multiple modules, accepting connections on an already-bound listener.
+    ///
+    /// See [`Server::mount`] for how requests are dispatched to the mounted modules.
+    pub async fn mount_listener(
+        listener: std::net::TcpListener,
+        mounts: Vec<(String, ServerBuilder<'_>)>,
+    ) -> Result<Self, ServerError> {
+        Self::mount_with(mounts, |connection| {
+            TcpListener::from_std(listener, connection).map_err(ServerError::Accept)
+        })
+        .await
+    }
+
+    /// Creates a runtime server that splits requests between a stable and a canary version of a module,
+    /// accepting connections on an already-bound listener.
+    ///
+    /// Requests are routed to the canary according to the given policy; the connection options of the
+    /// stable builder are used for the server. The routes, metrics, and admin API of the server are
+    /// those of the stable module; use [`Server::canary_status`] to monitor the canary.
+    pub async fn canary_listener(
+        listener: std::net::TcpListener,
+        stable: ServerBuilder<'_>,
+        canary: ServerBuilder<'_>,
+        policy: CanaryPolicy,
+    ) -> Result<Self, ServerError> {
+        let (stable_app, stable_state, connection) = stable.prepare().await?;
+        let (canary_app, canary_state, _) = canary.prepare().await?;
+
+        let deployment = Arc::new(Deployment::new(
+            policy,
+            stable_app,
+            canary_app,
+            canary_state,
+        ));
+
+        let mut app = tide::with_state(stable_state.clone());
+        app.at("/").all(DeploymentEndpoint(deployment.clone()));
+        app.at("*").all(DeploymentEndpoint(deployment.clone()));
+
+        let listener = TcpListener::from_std(listener, connection).map_err(ServerError::Accept)?;
+        let mut server = Self::listen(listener, app, vec![(String::new(), stable_state)]).await?;
+        server.deployment = Some(deployment);
+        Ok(server)
+    }
+
+    /// Creates a runtime server that mirrors a sample of its requests to a shadow target, accepting
+    /// connections on an already-bound listener.
+    ///
+    /// Every request is served by the primary module. Requests sampled by the policy are also sent to
+    /// the target once the primary responds; the target's responses are discarded, and their status
+    /// codes are compared with the primary's. Use [`Server::mirror_status`] to monitor the comparison.
+    pub async fn mirror_listener(
+        listener: std::net::TcpListener,
+        primary: ServerBuilder<'_>,
+        target: MirrorTarget<'_>,
+        policy: MirrorPolicy,
+    ) -> Result<Self, ServerError> {
+        let (primary_app, primary_state, connection) = primary.prepare().await?;
+
+        let shadow = match target {
+            MirrorTarget::Module(builder) => {
+                let (app, state, _) = builder.prepare().await?;
+                Shadow::Module(app, state)
+            }
+            MirrorTarget::Url(url) => Shadow::Url(url, surf::Client::new()),
+        };
+
+        let mirror = Arc::new(Mirror::new(policy, primary_app, shadow));
+
+        let mut app = tide::with_state(primary_state.clone());
+        app.at("/").all(MirrorEndpoint(mirror.clone()));
+        app.at("*").all(MirrorEndpoint(mirror.clone()));
+
+        let listener = TcpListener::from_std(listener, connection).map_err(ServerError::Accept)?;
+        let mut server = Self::listen(listener, app, vec![(String::new(), primary_state)]).await?;
+        server.mirror = Some(mirror);
+        Ok(server)
+    }
+
+    async fn mount_with(
+        mounts: Vec<(String, ServerBuilder<'_>)>,
+        listener: impl FnOnce(ConnectionOptions) -> Result<TcpListener<State>, ServerError>,
+    ) -> Result<Self, ServerError> {
+        let mut root: Option<(tide::Server<State>, ConnectionOptions)> = None;
+        let mut states = Vec::with_capacity(mounts.len());
+
+        for (prefix, builder) in mounts {
+            if states.iter().any(|(p, _)| p == &prefix) {
+                return Err(ServerError::InvalidModule(anyhow!(
+                    "multiple modules are mounted at prefix '{}'",
+                    prefix
+                )));
+            }
+
+            let (app, state, connection) = builder.prepare().await?;
+
+            let (root_app, _) =
+                root.get_or_insert_with(|| (tide::with_state(state.clone()), connection));
+            root_app.at(&prefix).nest(app);
+
+            states.push((prefix, state));
+        }
+
+        let (app, connection) =
+            root.ok_or_else(|| ServerError::InvalidModule(anyhow!("no modules to mount")))?;
+
+        Self::listen(listener(connection)?, app, states).await
+    }
+
+    async fn listen(
+        mut listener: TcpListener<State>,
+        app: tide::Server<State>,
+        mounts: Vec<(String, State)>,
+    ) -> Result<Self, ServerError> {
+        let addr = listener.addr();
+        listener
+            .bind(app)
+            .await
+            .map_err(|source| ServerError::Bind { addr, source })?;
+
+        let server = Self {
+            addr: listener.local_addr().unwrap_or(addr),
+            active: listener.active_requests(),
+            received: listener.received_requests(),
+            listener: Box::new(listener),
+            mounts,
+            deployment: None,
+            mirror: None,
+        };
+
+        log::info!("Serving routes:\n{}", server.routes());
+
+        Ok(server)
+    }
+
+    /// Gets the address the server is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Gets the table of routes served by the server.
+    ///
+    /// The paths of routes served by mounted modules include the mount prefix.
+    pub fn routes(&self) -> RouteTable {
+        RouteTable::merge(
+            self.mounts
+                .iter()
+                .map(|(prefix, state)| (prefix.as_str(), state.routes())),
+        )
+    }
+
+    /// Gets the functions loaded by the server with their limits and invocation statistics.
+    ///
+    /// The functions of mounted modules are included, with the paths of their routes prefixed by the
+    /// mount prefix; the functions of canary and shadow modules are not.
+    pub fn functions(&self) -> Vec<FunctionInfo> {
+        self.mounts
+            .iter()
+            .flat_map(|(prefix, state)| state.functions(prefix))
+            .collect()
+    }
+
+    /// Determines if the server is ready to process requests.
+    ///
+    /// The server is ready once every environment variable declared by its modules has been resolved.
+    pub fn is_ready(&self) -> bool {
+        self.states().all(State::is_ready)
+    }
+
+    /// Gets the status of the canary, if the server was created with [`Server::canary_listener`].
+    pub fn canary_status(&self) -> Option<CanaryStatus> {
+        self.deployment.as_ref().map(|d| d.status())
+    }
+
+    /// Gets the status of request mirroring, if the server was created with [`Server::mirror_listener`].
+    pub fn mirror_status(&self) -> Option<MirrorStatus> {
+        self.mirror.as_ref().map(|m| m.status())
+    }
+
+    /// Gets the settings the module mounted at the given prefix processes new requests with.
+    ///
+    /// A module that is not mounted, such as one served with [`Server::builder`], is mounted at the
+    /// empty prefix. Returns `None` if no module is mounted at the prefix.
+    pub fn config(&self, mount: &str) -> Option<ServerConfig> {
+        let (_, state) = self.mounts.iter().find(|(prefix, _)| prefix == mount)?;
+        let mut config = (*state.config()).clone();
+        config.log_level = log::max_level();
+        Some(config)
+    }
+
+    /// Applies new settings to the module mounted at the given prefix without restarting it.
+    ///
+    /// The settings of other mounted modules are unchanged, but the log level applies to the whole
+    /// process. The canary and shadow modules receive the settings of the module they are deployed
+    /// with. Requests being processed complete with the settings they started with; requests received
+    /// afterwards are processed with the new settings. Instances and their caches are kept.
+    pub fn apply_config(&self, mount: &str, config: ServerConfig) -> Result<(), ServerError> {
+        let (_, state) = self
+            .mounts
+            .iter()
+            .find(|(prefix, _)| prefix == mount)
+            .ok_or_else(|| ServerError::UnknownMount(mount.to_string()))?;
+
+        log::set_max_level(config.log_level);
+
+        state.apply_config(&config);
+
+        // The canary and shadow modules are only deployed alongside a single module
+        for state in self.deployed_states() {
+            state.apply_config(&config);
+        }
+
+        state.notify_config_change(&config);
+
+        log::info!("Applied a new server configuration to mount '{}'.", mount);
+
+        Ok(())
+    }
+
+    // Gets the states of every module served, including the canary and shadow modules
+    fn states(&self) -> impl Iterator<Item = &State> {
+        self.mounts
+            .iter()
+            .map(|(_, state)| state)
+            .chain(self.deployed_states())
+    }
+
+    // Gets the states of the canary and shadow modules
+    fn deployed_states(&self) -> impl Iterator<Item = &State> {
+        self.deployment
+            .as_ref()
+            .map(|d| d.canary_state())
+            .into_iter()
+            .chain(self.mirror.as_ref().and_then(|m| m.shadow_state()))
+    }
+
+    /// Gets the metrics for SQL statements executed by functions.
+    ///
+    /// Metrics are combined across mounted modules.
+    ///
+    /// Returns `None` if no SQL provider is configured.
+    pub fn sql_metrics(&self) -> Option<SqlMetrics> {
+        self.mounts
+            .iter()
+            .filter_map(|(_, state)| state.sql_metrics())
+            .reduce(|total, metrics| SqlMetrics {
+                statements: total.statements + metrics.statements,
+                failures: total.failures + metrics.failures,
+                timeouts: total.timeouts + metrics.timeouts,
+                total_time: total.total_time + metrics.total_time,
+            })
+    }
+
+    /// Binds a separate listener that serves the metrics of the server in the Prometheus text format at `/metrics`.
+    pub async fn bind_metrics<A: Into<SocketAddr>>(
+        &self,
+        addr: A,
+    ) -> Result<AdminServer, ServerError> {
+        AdminServer::metrics(addr.into(), self.mounts.clone()).await
+    }
+
+    /// Binds a separate listener that serves the admin API of the server.
+    ///
+    /// The admin API serves the route table at `/routes`, the readiness of the server at `/ready`, the
+    /// usage of each tenant counted against quotas at `/quotas`, and the memory usage of each function's
+    /// instances at `/memory`.
+    ///
+    /// If a token is given, requests must include it in an `Authorization: Bearer` header.
+    pub async fn bind_admin<A: Into<SocketAddr>>(
+        &self,
+        addr: A,
+        token: Option<String>,
+    ) -> Result<AdminServer, ServerError> {
+        AdminServer::admin(addr.into(), self.mounts.clone(), token).await
+    }
+
+    /// Gets a counter of the requests the server has received.
+    ///
+    /// The counter is shared with the server, so it can be read while the server accepts connections.
+    pub fn received_requests(&self) -> Arc<AtomicU64> {
+        self.received.clone()
+    }
+
+    /// Accepts and processes incoming connections.
+    pub async fn accept(&mut self) -> Result<(), ServerError> {
+        self.listener.accept().await.map_err(ServerError::Accept)
+    }
+
+    /// Waits for the requests being processed to complete, up to the given timeout.
+    ///
+    /// To shut down gracefully, drop the future returned by [`Server::accept`] so that new connections are refused
+    /// and then drain the server.
+    ///
+    /// Returns `false` if requests were still being processed when the timeout elapsed.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+
+        while self.active.load(Ordering::SeqCst) > 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+
+            async_std::task::sleep(Duration::from_millis(DRAIN_POLL_INTERVAL_MS)).await;
+        }
+
+        true
+    }
+}
+
+impl fmt::Display for Server {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.listener
+                .info()
+                .first()
+                .map(|i| i.connection())
+                .unwrap_or("")
+        )
+    }
+}
+
+/// A Wasmtime Functions server that processes requests without listening for connections.
+///
+/// This is useful for invoking functions from tests and scripts.
+#[derive(Clone)]
+pub struct LocalServer {
+    app: tide::Server<State>,
+}
+
+impl LocalServer {
+    /// Processes a request, routing it to the matching function.
+    pub async fn respond(&self, req: http_types::Request) -> Result<http_types::Response> {
+        self.app.respond(req).await.map_err(|e| e.into_inner())
+    }
+
+    /// Defines the host functions in a new linker, as is done when the server is built.
+    ///
+    /// This is only intended for benchmarks.
+    #[cfg(feature = "bench")]
+    pub fn bench_link(&self) -> Result<()> {
+        let mut linker = Linker::new(self.app.state().inner.module.engine());
+        Context::add_to_linker(&mut linker)
+    }
+
+    /// Instantiates the module without invoking a function.
+    ///
+    /// This is only intended for benchmarks.
+    #[cfg(feature = "bench")]
+    pub async fn bench_instantiate(&self) -> Result<()> {
+        self.app
+            .state()
+            .inner
+            .instantiate(None, None)
+            .await
+            .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmtime_functions_metadata::fixture::ModuleBuilder;
+    use wasmtime_functions_metadata::Method;
+
+    struct NoEnvironment;
+
+    impl EnvironmentProvider for NoEnvironment {
+        fn var(&self, name: &str) -> Result<String> {
+            anyhow::bail!("environment variable '{}' is not set", name)
+        }
+    }
+
+    #[test]
+    fn failed_warmup_aborts_before_binding() {
+        // The start function traps, so the module can't be instantiated
+        let module = ModuleBuilder::new()
+            .http_function("f", "/", &[Method::Get])
+            .wat("(func $trap unreachable) (start $trap)")
+            .build()
+            .unwrap();
+
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let result = async_std::task::block_on(
+            Server::builder(&module, Arc::new(NoEnvironment))
+                .warmup(true)
+                .bind(addr),
+        );
+
+        assert!(matches!(result, Err(ServerError::Warmup(_))));
+        assert!(std::net::TcpStream::connect(addr).is_err());
     }
 }
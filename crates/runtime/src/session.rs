@@ -0,0 +1,115 @@
+use crate::host::Context;
+use crate::server::Request;
+use async_std::sync::Mutex as AsyncMutex;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use wasmtime::{Instance, Store};
+
+/// Represents an instance that is kept alive between requests of the same session.
+///
+/// The instance is `None` until the first request of the session instantiates it.
+pub type SessionInstance = Arc<AsyncMutex<Option<(Store<Context>, Instance)>>>;
+
+type Instances = Mutex<HashMap<String, (Instant, SessionInstance)>>;
+
+/// Tracks the instances kept alive for sessions.
+///
+/// Sessions are identified by the value of a request cookie; instances that have not
+/// been used within the idle timeout are evicted by a periodic sweep. At most `max_sessions`
+/// instances are kept, so a new session evicts the least recently used session when full.
+pub struct Sessions {
+    cookie: String,
+    idle_timeout: Duration,
+    max_sessions: usize,
+    instances: Arc<Instances>,
+}
+
+impl Sessions {
+    pub fn new(cookie: String, idle_timeout: Duration, max_sessions: usize) -> Self {
+        let instances = Arc::new(Instances::default());
+
+        // Sweep on an interval rather than only when a session is used, so the instances of
+        // abandoned sessions are returned to the pool even when no requests arrive
+        let weak = Arc::downgrade(&instances);
+        async_std::task::spawn(async move {
+            loop {
+                async_std::task::sleep(idle_timeout).await;
+                match Weak::upgrade(&weak) {
+                    Some(instances) => Self::evict_idle(&instances, idle_timeout),
+                    None => break,
+                }
+            }
+        });
+
+        Self {
+            cookie,
+            idle_timeout,
+            max_sessions: max_sessions.max(1),
+            instances,
+        }
+    }
+
+    /// Gets the session identifier of the given request, if it has one.
+    pub fn session_id(&self, req: &Request) -> Option<String> {
+        req.cookie(&self.cookie).map(|c| c.value().to_string())
+    }
+
+    /// Gets the instance for the given session.
+    ///
+    /// If the session is new and the maximum number of sessions is reached, idle instances are
+    /// evicted and then, if still full, the least recently used session is evicted.
+    pub fn get(&self, id: &str) -> SessionInstance {
+        let now = Instant::now();
+
+        if let Some((last_used, instance)) = self.instances.lock().unwrap().get_mut(id) {
+            *last_used = now;
+            return instance.clone();
+        }
+
+        Self::evict_idle(&self.instances, self.idle_timeout);
+
+        let mut instances = self.instances.lock().unwrap();
+
+        while instances.len() >= self.max_sessions {
+            let oldest = instances
+                .iter()
+                .min_by_key(|(_, (last_used, _))| *last_used)
+                .map(|(id, _)| id.clone());
+
+            match oldest {
+                Some(oldest) => {
+                    log::info!("Evicted the least recently used session instance.");
+                    instances.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+
+        let (_, instance) = instances
+            .entry(id.to_string())
+            .or_insert_with(|| (now, Arc::new(AsyncMutex::new(None))));
+
+        instance.clone()
+    }
+
+    /// Removes the instance for the given session.
+    pub fn remove(&self, id: &str) {
+        self.instances.lock().unwrap().remove(id);
+    }
+
+    fn evict_idle(instances: &Instances, idle_timeout: Duration) {
+        let mut instances = instances.lock().unwrap();
+        let now = Instant::now();
+
+        let before = instances.len();
+        instances.retain(|_, (last_used, _)| now.duration_since(*last_used) < idle_timeout);
+
+        if instances.len() != before {
+            log::info!(
+                "Evicted {} idle session instance(s).",
+                before - instances.len()
+            );
+        }
+    }
+}
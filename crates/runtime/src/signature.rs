@@ -0,0 +1,100 @@
+use anyhow::{anyhow, bail, Result};
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+
+/// The name of the custom section that holds an embedded module signature.
+///
+/// An embedded signature signs the module with the signature section removed.
+pub const SIGNATURE_SECTION: &str = "__signature";
+
+/// Verifies the signature of a module against the given trusted Ed25519 public keys.
+///
+/// If a detached signature is not given, the signature is read from the module's signature section.
+pub fn verify(module: &[u8], detached: Option<&[u8]>, keys: &[Vec<u8>]) -> Result<()> {
+    let keys = keys
+        .iter()
+        .map(|k| PublicKey::from_bytes(k).map_err(|e| anyhow!("invalid trusted key: {}", e)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let (signature, signed) = match detached {
+        Some(signature) => (Cow::Borrowed(signature), Cow::Borrowed(module)),
+        None => {
+            let (signature, signed) = split_signature(module)?;
+            (Cow::Owned(signature), Cow::Owned(signed))
+        }
+    };
+
+    let signature = Signature::try_from(signature.as_ref())
+        .map_err(|_| anyhow!("the module signature is malformed"))?;
+
+    if !keys.iter().any(|k| k.verify(&signed, &signature).is_ok()) {
+        bail!("the module signature does not match any trusted key");
+    }
+
+    Ok(())
+}
+
+// Splits a module into its embedded signature and the bytes that were signed
+fn split_signature(module: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    if module.len() < 8 || &module[0..4] != b"\0asm" {
+        bail!("the module is not a valid WebAssembly module");
+    }
+
+    let mut signed = module[..8].to_vec();
+    let mut signature = None;
+    let mut offset = 8;
+
+    while offset < module.len() {
+        let start = offset;
+        let id = module[offset];
+        offset += 1;
+
+        let size = read_u32(module, &mut offset)? as usize;
+        let end = offset
+            .checked_add(size)
+            .filter(|end| *end <= module.len())
+            .ok_or_else(|| anyhow!("the module is not a valid WebAssembly module"))?;
+
+        if id == 0 {
+            let mut name = offset;
+            let len = read_u32(module, &mut name)? as usize;
+
+            if name + len <= end && &module[name..name + len] == SIGNATURE_SECTION.as_bytes() {
+                if signature.is_some() {
+                    bail!("the module contains multiple signatures");
+                }
+
+                signature = Some(module[name + len..end].to_vec());
+                offset = end;
+                continue;
+            }
+        }
+
+        signed.extend_from_slice(&module[start..end]);
+        offset = end;
+    }
+
+    Ok((
+        signature.ok_or_else(|| anyhow!("the module is not signed"))?,
+        signed,
+    ))
+}
+
+fn read_u32(bytes: &[u8], offset: &mut usize) -> Result<u32> {
+    let mut result = 0u32;
+
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes
+            .get(*offset)
+            .ok_or_else(|| anyhow!("the module is not a valid WebAssembly module"))?;
+        *offset += 1;
+
+        result |= ((byte & 0x7F) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+    }
+
+    bail!("the module is not a valid WebAssembly module")
+}
@@ -0,0 +1,298 @@
+use crate::resilience::{Outcome, Resilience};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Represents a value bound to or returned from a SQL statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    /// The SQL `NULL` value.
+    Null,
+    /// A signed integer value.
+    Integer(i64),
+    /// A floating point value.
+    Real(f64),
+    /// A text value.
+    Text(String),
+    /// A binary value.
+    Blob(Vec<u8>),
+}
+
+/// Provides access to a SQL database for functions.
+#[async_trait::async_trait]
+pub trait SqlProvider: Send + Sync {
+    /// Executes a statement, returning the number of rows affected.
+    async fn execute(&self, statement: &str, params: &[SqlValue]) -> Result<u64>;
+
+    /// Executes a query, returning the resulting rows.
+    async fn query(&self, statement: &str, params: &[SqlValue]) -> Result<Vec<Vec<SqlValue>>>;
+}
+
+/// A snapshot of the metrics for SQL statements executed by functions.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SqlMetrics {
+    /// The number of statements executed.
+    pub statements: u64,
+    /// The number of statements that failed, including those that timed out.
+    pub failures: u64,
+    /// The number of statements that timed out.
+    pub timeouts: u64,
+    /// The total time spent executing statements.
+    pub total_time: Duration,
+}
+
+/// Applies statement timeouts and records metrics for a SQL provider.
+pub struct Sql {
+    provider: Arc<dyn SqlProvider>,
+    timeout: Duration,
+    function_timeouts: HashMap<String, Duration>,
+    resilience: Arc<Resilience>,
+    statements: AtomicU64,
+    failures: AtomicU64,
+    timeouts: AtomicU64,
+    total_micros: AtomicU64,
+}
+
+impl Sql {
+    pub fn new(
+        provider: Arc<dyn SqlProvider>,
+        timeout: Duration,
+        function_timeouts: HashMap<String, Duration>,
+        resilience: Arc<Resilience>,
+    ) -> Self {
+        Self {
+            provider,
+            timeout,
+            function_timeouts,
+            resilience,
+            statements: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            timeouts: AtomicU64::new(0),
+            total_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub async fn execute(
+        &self,
+        function: &str,
+        statement: &str,
+        params: &[SqlValue],
+    ) -> Result<u64> {
+        // Statements may not be safe to repeat, so only queries are retried
+        self.run(function, false, || self.provider.execute(statement, params))
+            .await
+    }
+
+    /// Executes a query, retrying it on failure only if the function marked it idempotent.
+    ///
+    /// A query may modify the database, such as `UPDATE ... RETURNING`, so it is not assumed to be safe to repeat.
+    pub async fn query(
+        &self,
+        function: &str,
+        statement: &str,
+        params: &[SqlValue],
+        idempotent: bool,
+    ) -> Result<Vec<Vec<SqlValue>>> {
+        self.run(function, idempotent, || {
+            self.provider.query(statement, params)
+        })
+        .await
+    }
+
+    /// Gets the statement timeout of the given function.
+    pub fn timeout(&self, function: &str) -> Duration {
+        self.function_timeouts
+            .get(function)
+            .copied()
+            .unwrap_or(self.timeout)
+    }
+
+    pub fn metrics(&self) -> SqlMetrics {
+        SqlMetrics {
+            statements: self.statements.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            total_time: Duration::from_micros(self.total_micros.load(Ordering::Relaxed)),
+        }
+    }
+
+    async fn run<T, F, Fut>(&self, function: &str, retryable: bool, mut statement: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        let timeout = self.timeout(function);
+
+        self.resilience
+            .call(
+                "sql",
+                "database",
+                || {
+                    let statement = statement();
+                    async move {
+                        let start = Instant::now();
+                        let res = match async_std::future::timeout(timeout, statement).await {
+                            Ok(res) => res,
+                            Err(_) => {
+                                self.timeouts.fetch_add(1, Ordering::Relaxed);
+                                Err(anyhow!("statement timed out after {:?}", timeout))
+                            }
+                        };
+
+                        self.statements.fetch_add(1, Ordering::Relaxed);
+                        self.total_micros
+                            .fetch_add(start.elapsed().as_micros() as u64, Ordering::Relaxed);
+
+                        if let Err(e) = &res {
+                            self.failures.fetch_add(1, Ordering::Relaxed);
+                            log::warn!("SQL statement for function '{}' failed: {}", function, e);
+                        }
+
+                        res
+                    }
+                },
+                |res| match res {
+                    Ok(_) => Outcome::Success,
+                    Err(_) if retryable => Outcome::RetryableFailure,
+                    Err(_) => Outcome::Failure,
+                },
+                |message| anyhow!(message),
+            )
+            .await
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+macro_rules! bind_params {
+    ($query:expr, $params:expr) => {{
+        let mut query = $query;
+        for param in $params {
+            query = match param {
+                SqlValue::Null => query.bind(None::<i64>),
+                SqlValue::Integer(i) => query.bind(*i),
+                SqlValue::Real(r) => query.bind(*r),
+                SqlValue::Text(s) => query.bind(s.clone()),
+                SqlValue::Blob(b) => query.bind(b.clone()),
+            };
+        }
+        query
+    }};
+}
+
+/// A SQL provider backed by a pool of SQLite connections.
+#[cfg(feature = "sqlite")]
+pub struct SqliteProvider(sqlx::SqlitePool);
+
+#[cfg(feature = "sqlite")]
+impl SqliteProvider {
+    /// Connects to the SQLite database at the given URL with the given maximum number of pooled connections.
+    pub async fn connect(url: &str, max_connections: u32) -> Result<Self> {
+        Ok(Self(
+            sqlx::sqlite::SqlitePoolOptions::new()
+                .max_connections(max_connections)
+                .connect(url)
+                .await?,
+        ))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[async_trait::async_trait]
+impl SqlProvider for SqliteProvider {
+    async fn execute(&self, statement: &str, params: &[SqlValue]) -> Result<u64> {
+        Ok(bind_params!(sqlx::query(statement), params)
+            .execute(&self.0)
+            .await?
+            .rows_affected())
+    }
+
+    async fn query(&self, statement: &str, params: &[SqlValue]) -> Result<Vec<Vec<SqlValue>>> {
+        use sqlx::{Row, TypeInfo, ValueRef};
+
+        let rows = bind_params!(sqlx::query(statement), params)
+            .fetch_all(&self.0)
+            .await?;
+
+        rows.iter()
+            .map(|row| {
+                (0..row.len())
+                    .map(|i| -> Result<SqlValue> {
+                        let value = row.try_get_raw(i)?;
+                        if value.is_null() {
+                            return Ok(SqlValue::Null);
+                        }
+
+                        Ok(match value.type_info().name() {
+                            "INTEGER" | "BOOLEAN" => SqlValue::Integer(row.try_get(i)?),
+                            "REAL" => SqlValue::Real(row.try_get(i)?),
+                            "BLOB" => SqlValue::Blob(row.try_get(i)?),
+                            _ => SqlValue::Text(row.try_get(i)?),
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect()
+    }
+}
+
+/// A SQL provider backed by a pool of Postgres connections.
+#[cfg(feature = "postgres")]
+pub struct PostgresProvider(sqlx::PgPool);
+
+#[cfg(feature = "postgres")]
+impl PostgresProvider {
+    /// Connects to the Postgres database at the given URL with the given maximum number of pooled connections.
+    pub async fn connect(url: &str, max_connections: u32) -> Result<Self> {
+        Ok(Self(
+            sqlx::postgres::PgPoolOptions::new()
+                .max_connections(max_connections)
+                .connect(url)
+                .await?,
+        ))
+    }
+}
+
+#[cfg(feature = "postgres")]
+#[async_trait::async_trait]
+impl SqlProvider for PostgresProvider {
+    async fn execute(&self, statement: &str, params: &[SqlValue]) -> Result<u64> {
+        Ok(bind_params!(sqlx::query(statement), params)
+            .execute(&self.0)
+            .await?
+            .rows_affected())
+    }
+
+    async fn query(&self, statement: &str, params: &[SqlValue]) -> Result<Vec<Vec<SqlValue>>> {
+        use sqlx::{Row, TypeInfo, ValueRef};
+
+        let rows = bind_params!(sqlx::query(statement), params)
+            .fetch_all(&self.0)
+            .await?;
+
+        rows.iter()
+            .map(|row| {
+                (0..row.len())
+                    .map(|i| -> Result<SqlValue> {
+                        let value = row.try_get_raw(i)?;
+                        if value.is_null() {
+                            return Ok(SqlValue::Null);
+                        }
+
+                        Ok(match value.type_info().name() {
+                            "INT2" => SqlValue::Integer(row.try_get::<i16, _>(i)?.into()),
+                            "INT4" => SqlValue::Integer(row.try_get::<i32, _>(i)?.into()),
+                            "INT8" => SqlValue::Integer(row.try_get(i)?),
+                            "BOOL" => SqlValue::Integer(row.try_get::<bool, _>(i)?.into()),
+                            "FLOAT4" => SqlValue::Real(row.try_get::<f32, _>(i)?.into()),
+                            "FLOAT8" => SqlValue::Real(row.try_get(i)?),
+                            "BYTEA" => SqlValue::Blob(row.try_get(i)?),
+                            _ => SqlValue::Text(row.try_get(i)?),
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect()
+    }
+}
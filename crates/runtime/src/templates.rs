@@ -0,0 +1,83 @@
+use anyhow::{anyhow, bail, Context, Result};
+use handlebars::Handlebars;
+use std::path::Path;
+
+/// The extension of the template files loaded from the templates directory.
+const TEMPLATE_EXTENSION: &str = "hbs";
+
+/// The Handlebars templates functions render responses with.
+///
+/// Templates are parsed once when the server is built rather than on every request.
+pub struct Templates {
+    registry: Handlebars<'static>,
+}
+
+impl Templates {
+    /// Loads every `.hbs` file in the directory and its subdirectories.
+    ///
+    /// A template is named by its path relative to the directory without the extension, using `/`
+    /// as the separator; for example, `pages/index.hbs` is named `pages/index`.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut registry = Handlebars::new();
+
+        // Missing fields are errors rather than empty output so that mistakes in templates are visible
+        registry.set_strict_mode(true);
+
+        Self::load_dir(&mut registry, dir, "")?;
+
+        if registry.get_templates().is_empty() {
+            bail!("no `.{}` templates were found", TEMPLATE_EXTENSION);
+        }
+
+        Ok(Self { registry })
+    }
+
+    fn load_dir(registry: &mut Handlebars<'static>, dir: &Path, prefix: &str) -> Result<()> {
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("failed to read directory '{}'", dir.display()))?;
+
+        for entry in entries {
+            let path = entry?.path();
+            let stem = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(stem) => stem,
+                None => continue,
+            };
+
+            if path.is_dir() {
+                let name = path.file_name().and_then(|s| s.to_str()).unwrap_or(stem);
+                Self::load_dir(registry, &path, &format!("{}{}/", prefix, name))?;
+                continue;
+            }
+
+            if path.extension().and_then(|e| e.to_str()) != Some(TEMPLATE_EXTENSION) {
+                continue;
+            }
+
+            let name = format!("{}{}", prefix, stem);
+            let template = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read template '{}'", path.display()))?;
+
+            registry
+                .register_template_string(&name, template)
+                .map_err(|e| anyhow!("invalid template '{}': {}", path.display(), e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Renders a template with the given JSON context.
+    ///
+    /// Values substituted with `{{...}}` are HTML-escaped; use `{{{...}}}` for raw values.
+    pub fn render(&self, name: &str, context: &str) -> Result<String> {
+        if !self.registry.has_template(name) {
+            bail!("template `{}` does not exist", name);
+        }
+
+        let context: serde_json::Value =
+            serde_json::from_str(context).context("template context is not valid JSON")?;
+
+        self.registry
+            .render(name, &context)
+            .map_err(|e| anyhow!("failed to render template `{}`: {}", name, e))
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+// The maximum length of a traced argument or result; longer values are truncated.
+const MAX_SUMMARY_LEN: usize = 128;
+
+/// Determines which requests have the calls their functions make to the host traced.
+///
+/// A sampled request is traced as it is invoked. When errors are always traced, the calls of a
+/// request that was not sampled are held until its invocation completes and are only logged if the
+/// invocation failed, so that rare failures are fully traced without tracing every request.
+#[derive(Debug, Clone)]
+pub struct TraceSampling {
+    ratio: f64,
+    errors: bool,
+    functions: HashMap<String, f64>,
+}
+
+impl TraceSampling {
+    /// Creates a policy that traces the given ratio of requests, from `0.0` (none) to `1.0` (all).
+    ///
+    /// A function may declare its own ratio with the `trace_sample` route option, such as to trace
+    /// every request to a rarely used route; the declared ratio replaces the given ratio.
+    pub fn new(ratio: f64) -> Self {
+        Self {
+            ratio: clamp_ratio(ratio),
+            errors: false,
+            functions: HashMap::new(),
+        }
+    }
+
+    /// Sets whether requests whose invocation fails are traced even if they were not sampled.
+    ///
+    /// An invocation fails if the function traps, times out, or responds with a server error.
+    pub fn errors(mut self, enabled: bool) -> Self {
+        self.errors = enabled;
+        self
+    }
+
+    /// Traces the given ratio of the requests to a function.
+    ///
+    /// This overrides any ratio declared in the function's metadata.
+    pub fn function<T: Into<String>>(mut self, function: T, ratio: f64) -> Self {
+        self.functions.insert(function.into(), clamp_ratio(ratio));
+        self
+    }
+
+    /// Gets the ratio of a function's requests that are traced.
+    pub(crate) fn ratio(&self, function: &str, declared: Option<f64>) -> f64 {
+        self.functions
+            .get(function)
+            .copied()
+            .or_else(|| declared.map(clamp_ratio))
+            .unwrap_or(self.ratio)
+    }
+
+    /// Determines how the calls of a request to a function with the given ratio are traced.
+    pub(crate) fn sample(&self, ratio: f64) -> TraceMode {
+        if ratio > 0.0 && rand::random::<f64>() < ratio {
+            TraceMode::On
+        } else if self.errors {
+            TraceMode::OnError
+        } else {
+            TraceMode::Off
+        }
+    }
+}
+
+fn clamp_ratio(ratio: f64) -> f64 {
+    if ratio.is_nan() {
+        0.0
+    } else {
+        ratio.clamp(0.0, 1.0)
+    }
+}
+
+/// Determines how the host calls of an invocation are traced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceMode {
+    /// The calls are not traced.
+    Off,
+    /// The calls are logged as they are made.
+    On,
+    /// The calls are held until the invocation completes and logged only if it failed.
+    OnError,
+}
+
+/// Traces the calls an instance makes to the host while enabled.
+///
+/// Tracers are cheap to clone; clones share whether tracing is enabled.
+#[derive(Clone, Default)]
+pub struct Tracer(Arc<TracerState>);
+
+#[derive(Default)]
+struct TracerState {
+    enabled: AtomicBool,
+    function: Mutex<Arc<String>>,
+    held: Mutex<Option<Vec<String>>>,
+}
+
+impl Tracer {
+    /// Sets how calls are traced, such as for the request being invoked.
+    ///
+    /// Any calls held from a previous invocation are discarded.
+    pub fn set_mode(&self, mode: TraceMode) {
+        *self.0.held.lock().unwrap() = match mode {
+            TraceMode::OnError => Some(Vec::new()),
+            _ => None,
+        };
+        self.0
+            .enabled
+            .store(mode != TraceMode::Off, Ordering::Relaxed);
+    }
+
+    /// Completes the tracing of an invocation, logging any held calls if it failed.
+    ///
+    /// Tracing is disabled if the calls were held.
+    pub fn finish(&self, failed: bool) {
+        let held = match self.0.held.lock().unwrap().take() {
+            Some(held) => held,
+            None => return,
+        };
+
+        self.0.enabled.store(false, Ordering::Relaxed);
+
+        if failed {
+            for message in held {
+                log::info!("{}", message);
+            }
+        }
+    }
+
+    /// Sets the function that traced calls are attributed to.
+    pub fn set_function(&self, function: Arc<String>) {
+        *self.0.function.lock().unwrap() = function;
+    }
+
+    /// Begins tracing a call, returning when it started if tracing is enabled.
+    pub fn begin(&self) -> Option<Instant> {
+        if self.0.enabled.load(Ordering::Relaxed) {
+            Some(Instant::now())
+        } else {
+            None
+        }
+    }
+
+    /// Logs a traced call.
+    pub fn end(&self, start: Instant, name: &str, args: &str, result: &dyn fmt::Debug) {
+        let elapsed = start.elapsed();
+        let message = format!(
+            "Function '{}' called '{}({})' -> {} in {:?}.",
+            self.0.function.lock().unwrap(),
+            name,
+            args,
+            summarize(result),
+            elapsed
+        );
+
+        match self.0.held.lock().unwrap().as_mut() {
+            Some(held) => held.push(message),
+            None => log::info!("{}", message),
+        }
+    }
+}
+
+/// Summarizes the arguments of a traced call.
+pub fn summarize_args(args: &[&dyn fmt::Debug]) -> String {
+    args.iter()
+        .map(|arg| summarize(*arg))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Formats a value for a trace, truncating it without formatting the rest of the value.
+fn summarize(value: &dyn fmt::Debug) -> String {
+    let mut writer = Truncated(String::new());
+    if write!(writer, "{:?}", value).is_err() {
+        writer.0.push_str("...");
+    }
+    writer.0
+}
+
+struct Truncated(String);
+
+impl Write for Truncated {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let remaining = MAX_SUMMARY_LEN - self.0.len();
+        if s.len() <= remaining {
+            self.0.push_str(s);
+            return Ok(());
+        }
+
+        let mut end = remaining;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        self.0.push_str(&s[..end]);
+        Err(fmt::Error)
+    }
+}
+
+/// Summarizes a byte buffer by its length rather than its contents.
+pub struct Bytes(pub usize);
+
+impl fmt::Debug for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{} bytes>", self.0)
+    }
+}
+
+/// Redacts the query of a URI for a trace, as queries commonly carry tokens and signatures.
+pub fn redact_query(uri: &str) -> String {
+    match uri.split_once('?') {
+        Some((path, _)) => format!("{}?<redacted>", path),
+        None => uri.to_string(),
+    }
+}
@@ -0,0 +1,82 @@
+use crate::server::Request;
+use tide::http::{headers::CONTENT_TYPE, mime};
+use tide::{Response, StatusCode};
+use wasmtime_functions_metadata::Parameter;
+
+/// Validates requests against the constraints declared in a function's metadata.
+///
+/// Validation occurs before the module is instantiated so that invalid requests don't consume
+/// any WebAssembly execution.
+pub struct RequestValidator {
+    consumes: Vec<String>,
+    params: Vec<Parameter>,
+}
+
+impl RequestValidator {
+    pub fn new(consumes: Vec<String>, params: Vec<Parameter>) -> Self {
+        Self { consumes, params }
+    }
+
+    /// Validates the given request, returning the rejection response if the request is invalid.
+    pub fn validate(&self, req: &Request) -> Option<Response> {
+        if !self.consumes.is_empty() {
+            let content_type = req.header(CONTENT_TYPE).map(|v| {
+                v.as_str()
+                    .split(';')
+                    .next()
+                    .unwrap_or("")
+                    .trim()
+                    .to_lowercase()
+            });
+
+            match content_type {
+                Some(ty) if self.accepts(&ty) => {}
+                Some(ty) => {
+                    return Some(Self::reject(
+                        StatusCode::UnsupportedMediaType,
+                        format!("unsupported content type '{}'", ty),
+                    ))
+                }
+                None => {
+                    return Some(Self::reject(
+                        StatusCode::UnsupportedMediaType,
+                        "a content type is required".to_string(),
+                    ))
+                }
+            }
+        }
+
+        for param in &self.params {
+            if let Ok(value) = req.param(&param.name) {
+                if !param.ty.is_valid(value) {
+                    return Some(Self::reject(
+                        StatusCode::BadRequest,
+                        format!("parameter '{}' must be of type {}", param.name, param.ty),
+                    ));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn accepts(&self, content_type: &str) -> bool {
+        self.consumes.iter().any(|c| {
+            c == content_type
+                || c == "*/*"
+                || c.strip_suffix("/*")
+                    .map(|t| content_type.split('/').next() == Some(t))
+                    .unwrap_or(false)
+        })
+    }
+
+    fn reject(status: StatusCode, message: String) -> Response {
+        let mut res = Response::builder(status)
+            .content_type(mime::PLAIN)
+            .body(message.as_str())
+            .build();
+
+        res.set_error(tide::Error::from_str(status, message));
+        res
+    }
+}
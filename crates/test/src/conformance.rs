@@ -0,0 +1,285 @@
+//! A conformance suite for implementations of the host interface.
+//!
+//! The suite is a set of request and response expectations for the reference module in
+//! `examples/conformance`. Any host that serves the reference module can be verified by
+//! implementing [`Target`] and calling [`run`]:
+//!
+//! ```ignore
+//! use wasmtime_functions_test::{conformance, TestHost};
+//!
+//! let host = TestHost::from_module(&std::fs::read("conformance_example.wasm")?)?;
+//! let report = conformance::run(&host);
+//!
+//! assert!(report.is_success(), "{}", report);
+//! ```
+
+use crate::{TestHost, TestResponse};
+use anyhow::Result;
+use std::fmt;
+
+/// Represents a case of the conformance suite.
+pub struct Case {
+    /// The name of the case.
+    pub name: &'static str,
+    /// The method of the request.
+    pub method: &'static str,
+    /// The path and query of the request.
+    pub path: &'static str,
+    /// The headers of the request.
+    pub headers: &'static [(&'static str, &'static str)],
+    /// The body of the request.
+    pub body: &'static [u8],
+    /// The expected status code of the response.
+    pub status: u16,
+    /// The headers expected in the response, with values the response header must contain.
+    pub expected_headers: &'static [(&'static str, &'static str)],
+    /// The expected body of the response, if the body is checked.
+    pub expected_body: Option<&'static [u8]>,
+}
+
+/// The cases of the conformance suite.
+pub const CASES: &[Case] = &[
+    Case {
+        name: "request method",
+        method: "PUT",
+        path: "/method",
+        headers: &[],
+        body: b"",
+        status: 200,
+        expected_headers: &[("content-type", "text/plain")],
+        expected_body: Some(b"PUT"),
+    },
+    Case {
+        name: "request uri",
+        method: "GET",
+        path: "/uri?a=1&b=two",
+        headers: &[],
+        body: b"",
+        status: 200,
+        expected_headers: &[],
+        expected_body: Some(b"/uri?a=1&b=two"),
+    },
+    Case {
+        name: "route parameter",
+        method: "GET",
+        path: "/params/world",
+        headers: &[],
+        body: b"",
+        status: 200,
+        expected_headers: &[],
+        expected_body: Some(b"world"),
+    },
+    Case {
+        name: "request header",
+        method: "GET",
+        path: "/header",
+        headers: &[("X-Conformance", "present")],
+        body: b"",
+        status: 200,
+        expected_headers: &[],
+        expected_body: Some(b"present"),
+    },
+    Case {
+        name: "request cookie",
+        method: "GET",
+        path: "/cookie",
+        headers: &[("Cookie", "other=1; session=xyz")],
+        body: b"",
+        status: 200,
+        expected_headers: &[],
+        expected_body: Some(b"xyz"),
+    },
+    Case {
+        name: "request body",
+        method: "POST",
+        path: "/echo",
+        headers: &[("Content-Type", "application/json")],
+        body: b"{\"hello\":\"world\"}",
+        status: 200,
+        expected_headers: &[("content-type", "application/json")],
+        expected_body: Some(b"{\"hello\":\"world\"}"),
+    },
+    Case {
+        name: "response status and header",
+        method: "GET",
+        path: "/status/202",
+        headers: &[],
+        body: b"",
+        status: 202,
+        expected_headers: &[("x-status", "202")],
+        expected_body: Some(b""),
+    },
+    Case {
+        name: "response cookie",
+        method: "GET",
+        path: "/set-cookie",
+        headers: &[],
+        body: b"",
+        status: 200,
+        expected_headers: &[("set-cookie", "session=abc")],
+        expected_body: None,
+    },
+    Case {
+        name: "function error",
+        method: "GET",
+        path: "/error",
+        headers: &[],
+        body: b"",
+        status: 500,
+        expected_headers: &[],
+        expected_body: Some(b"function failed"),
+    },
+    Case {
+        name: "empty response",
+        method: "GET",
+        path: "/empty",
+        headers: &[],
+        body: b"",
+        status: 204,
+        expected_headers: &[],
+        expected_body: Some(b""),
+    },
+    Case {
+        name: "unmatched route",
+        method: "GET",
+        path: "/does-not-exist",
+        headers: &[],
+        body: b"",
+        status: 404,
+        expected_headers: &[],
+        expected_body: None,
+    },
+];
+
+/// Implemented by hosts that are verified by the conformance suite.
+pub trait Target {
+    /// Sends a request to the reference module, returning the response.
+    fn send(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<TestResponse>;
+}
+
+impl Target for TestHost {
+    fn send(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<TestResponse> {
+        let method = method
+            .parse()
+            .map_err(|e: http_types::Error| e.into_inner())?;
+
+        headers
+            .iter()
+            .fold(self.request(method, path), |req, (name, value)| {
+                req.header(name, value)
+            })
+            .body(body)
+            .send()
+    }
+}
+
+/// Represents a case of the conformance suite that failed.
+pub struct Failure {
+    /// The name of the case.
+    pub case: &'static str,
+    /// The reason the case failed.
+    pub reason: String,
+}
+
+/// Represents the results of running the conformance suite.
+pub struct Report {
+    /// The number of cases that passed.
+    pub passed: usize,
+    /// The cases that failed.
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    /// Determines if every case of the suite passed.
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} passed, {} failed", self.passed, self.failures.len())?;
+
+        for failure in &self.failures {
+            write!(f, "\n  {}: {}", failure.case, failure.reason)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn check(case: &Case, res: &TestResponse) -> Result<(), String> {
+    if res.status() != case.status {
+        return Err(format!(
+            "expected status {} but found {}",
+            case.status,
+            res.status()
+        ));
+    }
+
+    for (name, expected) in case.expected_headers {
+        let found = res
+            .headers()
+            .iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+            .any(|(_, v)| v.contains(expected));
+
+        if !found {
+            return Err(format!(
+                "expected header '{}' containing '{}' but found {:?}",
+                name,
+                expected,
+                res.header(name)
+            ));
+        }
+    }
+
+    if let Some(expected) = case.expected_body {
+        if res.body() != expected {
+            return Err(format!(
+                "expected body {:?} but found {:?}",
+                String::from_utf8_lossy(expected),
+                String::from_utf8_lossy(res.body())
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the conformance suite against the given target.
+pub fn run<T: Target>(target: &T) -> Report {
+    let mut report = Report {
+        passed: 0,
+        failures: Vec::new(),
+    };
+
+    for case in CASES {
+        let result = target
+            .send(case.method, case.path, case.headers, case.body)
+            .map_err(|e| format!("request failed: {:#}", e))
+            .and_then(|res| check(case, &res));
+
+        match result {
+            Ok(()) => report.passed += 1,
+            Err(reason) => report.failures.push(Failure {
+                case: case.name,
+                reason,
+            }),
+        }
+    }
+
+    report
+}
@@ -0,0 +1,137 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use wasmtime_functions_runtime::{FetchProvider, FetchResponse};
+
+/// Represents an outbound HTTP request sent by a function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedFetch {
+    /// The method of the request.
+    pub method: String,
+    /// The URI of the request.
+    pub uri: String,
+    /// The headers of the request.
+    pub headers: Vec<(String, String)>,
+    /// The body of the request.
+    pub body: Vec<u8>,
+}
+
+enum MockResult {
+    Response {
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    },
+    Error(String),
+}
+
+/// An outbound HTTP provider that returns configured responses and records the requests sent by
+/// functions.
+///
+/// Responses are configured per URI. Requests to a URI without a configured response fail, as do
+/// requests to hosts that were not allowed with [`TestHostBuilder::allow_host`](crate::TestHostBuilder::allow_host).
+#[derive(Default)]
+pub struct MockFetchProvider {
+    results: Mutex<HashMap<String, MockResult>>,
+    delays: Mutex<HashMap<String, Duration>>,
+    requests: Mutex<Vec<RecordedFetch>>,
+}
+
+impl MockFetchProvider {
+    /// Creates a new mock outbound HTTP provider.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the response returned for requests to the given URI.
+    pub fn on_request<B: Into<Vec<u8>>>(&self, uri: &str, status: u16, body: B) -> &Self {
+        self.on_request_with_headers(uri, status, Vec::new(), body)
+    }
+
+    /// Sets the response, including headers, returned for requests to the given URI.
+    pub fn on_request_with_headers<B: Into<Vec<u8>>>(
+        &self,
+        uri: &str,
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: B,
+    ) -> &Self {
+        self.results.lock().unwrap().insert(
+            uri.to_string(),
+            MockResult::Response {
+                status,
+                headers,
+                body: body.into(),
+            },
+        );
+        self
+    }
+
+    /// Makes requests to the given URI fail with the given message, as if the connection failed.
+    pub fn on_error(&self, uri: &str, message: &str) -> &Self {
+        self.results
+            .lock()
+            .unwrap()
+            .insert(uri.to_string(), MockResult::Error(message.to_string()));
+        self
+    }
+
+    /// Delays the result of requests to the given URI, simulating a slow remote host.
+    ///
+    /// The delay is real time, so keep it short or pair it with a short function timeout.
+    pub fn delay(&self, uri: &str, delay: Duration) -> &Self {
+        self.delays.lock().unwrap().insert(uri.to_string(), delay);
+        self
+    }
+
+    /// Gets the requests sent so far, in order.
+    pub fn requests(&self) -> Vec<RecordedFetch> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Clears the recorded requests.
+    pub fn clear(&self) {
+        self.requests.lock().unwrap().clear();
+    }
+}
+
+#[async_trait::async_trait]
+impl FetchProvider for MockFetchProvider {
+    async fn send(
+        &self,
+        method: &str,
+        uri: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<FetchResponse> {
+        self.requests.lock().unwrap().push(RecordedFetch {
+            method: method.to_string(),
+            uri: uri.to_string(),
+            headers: headers
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+            body: body.to_vec(),
+        });
+
+        let delay = self.delays.lock().unwrap().get(uri).copied();
+        if let Some(delay) = delay {
+            async_std::task::sleep(delay).await;
+        }
+
+        match self.results.lock().unwrap().get(uri) {
+            Some(MockResult::Response {
+                status,
+                headers,
+                body,
+            }) => Ok(FetchResponse {
+                status: *status,
+                headers: headers.clone(),
+                body: body.clone(),
+            }),
+            Some(MockResult::Error(message)) => bail!("{}", message),
+            None => bail!("no mock response was configured for `{}`", uri),
+        }
+    }
+}
@@ -0,0 +1,477 @@
+//! The Wasmtime Functions test crate.
+//!
+//! This crate is responsible for hosting a Wasmtime Functions application in-process so that
+//! integration tests can send requests to its functions from `cargo test`.
+//!
+//! Requests are processed by the same runtime used by the host, but without binding a socket.
+//!
+//! ```ignore
+//! use wasmtime_functions_test::TestHost;
+//!
+//! #[test]
+//! fn hello() -> anyhow::Result<()> {
+//!     let host = TestHost::from_module(&std::fs::read("target/wasm32-wasi/debug/hello.wasm")?)?;
+//!
+//!     let res = host.get("/hello/world").send()?;
+//!
+//!     assert_eq!(res.status(), 200);
+//!     assert_eq!(res.text()?, "Hello, world!");
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Failures at the host boundary can be injected to exercise a function's error handling: use
+//! [`MockSqlProvider`] and [`MockFetchProvider`] to fail or delay SQL statements and outbound
+//! requests, [`RequestBuilder::truncated_body`] to send a body that ends early, and
+//! [`TestHostBuilder::timeout`] to force functions to time out.
+
+#![deny(missing_docs)]
+
+pub mod conformance;
+mod fetch;
+mod sql;
+
+pub use fetch::{MockFetchProvider, RecordedFetch};
+pub use sql::{MockSqlProvider, RecordedStatement, StatementKind};
+pub use wasmtime_functions_runtime::{
+    AllowedHost, Clock, HostCalls, Interruption, InvocationReport, InvocationStats, ManualClock,
+};
+
+use anyhow::{anyhow, bail, Result};
+use async_std::io::{BufReader, Read};
+use http_types::{Body, Method};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use wasmtime_functions_runtime::{
+    FetchProvider, InvocationRequest, LocalServer, Server, SqlProvider,
+};
+
+struct Environment(HashMap<String, String>);
+
+impl wasmtime_functions_runtime::EnvironmentProvider for Environment {
+    fn var(&self, name: &str) -> Result<String> {
+        self.0
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("environment variable '{}' was not set by the test", name))
+    }
+
+    // Variables set by a test are visible to functions through their configuration
+    fn is_secret(&self, _name: &str) -> bool {
+        false
+    }
+}
+
+/// Builds a test host for a module.
+pub struct TestHostBuilder<'a> {
+    module: &'a [u8],
+    vars: HashMap<String, String>,
+    sql_provider: Option<Arc<dyn SqlProvider>>,
+    sql_statement_timeout: Option<Duration>,
+    fetch_provider: Option<Arc<dyn FetchProvider>>,
+    allowed_hosts: Vec<AllowedHost>,
+    clock: Option<Arc<dyn Clock>>,
+    random_seed: Option<u64>,
+    timeout: Option<Duration>,
+    interruption: Option<Interruption>,
+    injected_fuel: HashMap<String, u64>,
+}
+
+impl<'a> TestHostBuilder<'a> {
+    /// Sets an application environment variable.
+    pub fn env<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.vars.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sets the SQL provider used by functions, such as a [`MockSqlProvider`].
+    ///
+    /// Keep a clone of the provider to inspect the statements executed by functions.
+    pub fn sql_provider(mut self, provider: Arc<dyn SqlProvider>) -> Self {
+        self.sql_provider = Some(provider);
+        self
+    }
+
+    /// Sets the timeout for SQL statements executed by functions.
+    pub fn sql_statement_timeout(mut self, timeout: Duration) -> Self {
+        self.sql_statement_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the provider that sends the outbound HTTP requests of functions, such as a
+    /// [`MockFetchProvider`].
+    ///
+    /// Requests are only passed to the provider for hosts allowed with [`allow_host`](Self::allow_host).
+    pub fn fetch_provider(mut self, provider: Arc<dyn FetchProvider>) -> Self {
+        self.fetch_provider = Some(provider);
+        self
+    }
+
+    /// Allows functions to send outbound HTTP requests to the given host.
+    pub fn allow_host(mut self, host: AllowedHost) -> Self {
+        self.allowed_hosts.push(host);
+        self
+    }
+
+    /// Sets the timeout for function invocations.
+    ///
+    /// A function that exceeds the timeout is interrupted and the request fails with a timeout
+    /// error, as it would in the host.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the mechanism used to interrupt functions that exceed their timeout.
+    ///
+    /// Use [`Interruption::Fuel`] to have the fuel consumed by functions included in their
+    /// invocation reports.
+    pub fn interruption(mut self, interruption: Interruption) -> Self {
+        self.interruption = Some(interruption);
+        self
+    }
+
+    /// Injects an artificially low fuel limit into every invocation of the given function.
+    ///
+    /// An invocation that exhausts the limit fails with a `500 Internal Server Error` response
+    /// whose report is marked as [`fuel_exhausted`](InvocationReport::fuel_exhausted), which tests
+    /// use to verify that the application degrades gracefully when a function is metered out.
+    /// This requires [`Interruption::Fuel`], which is the default.
+    pub fn inject_fuel_limit<T: Into<String>>(mut self, function: T, fuel: u64) -> Self {
+        self.injected_fuel.insert(function.into(), fuel);
+        self
+    }
+
+    /// Sets the clock that provides the current time to functions, such as a [`ManualClock`].
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Seeds the random number generator available to functions so that it is deterministic.
+    pub fn random_seed(mut self, seed: u64) -> Self {
+        self.random_seed = Some(seed);
+        self
+    }
+
+    /// Builds the test host.
+    ///
+    /// The module is compiled and its environment variables resolved before this returns.
+    pub fn build(self) -> Result<TestHost> {
+        let mut builder = Server::builder(self.module, Arc::new(Environment(self.vars)));
+
+        if let Some(provider) = self.sql_provider {
+            builder = builder.sql_provider(provider);
+        }
+
+        if let Some(timeout) = self.sql_statement_timeout {
+            builder = builder.sql_statement_timeout(timeout);
+        }
+
+        if let Some(provider) = self.fetch_provider {
+            builder = builder.fetch_provider(provider);
+        }
+
+        for host in self.allowed_hosts {
+            builder = builder.allow_host(host);
+        }
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        if let Some(interruption) = self.interruption {
+            builder = builder.interruption(interruption);
+        }
+
+        for (function, fuel) in self.injected_fuel {
+            builder = builder.inject_fuel_limit(function, fuel);
+        }
+
+        if let Some(clock) = self.clock {
+            builder = builder.clock(clock);
+        }
+
+        if let Some(seed) = self.random_seed {
+            builder = builder.random_seed(seed);
+        }
+
+        Ok(TestHost {
+            server: async_std::task::block_on(builder.local())?,
+        })
+    }
+}
+
+/// Hosts a Wasmtime Functions application for testing.
+pub struct TestHost {
+    server: LocalServer,
+}
+
+impl TestHost {
+    /// Creates a test host for the given module with no environment variables.
+    pub fn from_module(module: &[u8]) -> Result<Self> {
+        Self::builder(module).build()
+    }
+
+    /// Creates a builder for a test host for the given module.
+    pub fn builder(module: &[u8]) -> TestHostBuilder {
+        TestHostBuilder {
+            module,
+            vars: HashMap::new(),
+            sql_provider: None,
+            sql_statement_timeout: None,
+            fetch_provider: None,
+            allowed_hosts: Vec::new(),
+            clock: None,
+            random_seed: None,
+            timeout: None,
+            interruption: None,
+            injected_fuel: HashMap::new(),
+        }
+    }
+
+    /// Creates a request with the given method and path.
+    ///
+    /// The path may include a query string.
+    pub fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        RequestBuilder {
+            host: self,
+            req: Ok(InvocationRequest::new(method.to_string(), path)),
+            truncated: None,
+        }
+    }
+
+    /// Creates a `GET` request for the given path.
+    pub fn get(&self, path: &str) -> RequestBuilder {
+        self.request(Method::Get, path)
+    }
+
+    /// Creates a `HEAD` request for the given path.
+    pub fn head(&self, path: &str) -> RequestBuilder {
+        self.request(Method::Head, path)
+    }
+
+    /// Creates a `POST` request for the given path.
+    pub fn post(&self, path: &str) -> RequestBuilder {
+        self.request(Method::Post, path)
+    }
+
+    /// Creates a `PUT` request for the given path.
+    pub fn put(&self, path: &str) -> RequestBuilder {
+        self.request(Method::Put, path)
+    }
+
+    /// Creates a `PATCH` request for the given path.
+    pub fn patch(&self, path: &str) -> RequestBuilder {
+        self.request(Method::Patch, path)
+    }
+
+    /// Creates a `DELETE` request for the given path.
+    pub fn delete(&self, path: &str) -> RequestBuilder {
+        self.request(Method::Delete, path)
+    }
+}
+
+/// Builds a request to send to a test host.
+pub struct RequestBuilder<'a> {
+    host: &'a TestHost,
+    req: Result<InvocationRequest>,
+    truncated: Option<(Vec<u8>, usize)>,
+}
+
+impl RequestBuilder<'_> {
+    /// Adds a header to the request.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.req = self.req.map(|req| req.header(name, value));
+        self
+    }
+
+    /// Appends a query parameter to the request's path.
+    pub fn param(mut self, name: &str, value: &str) -> Self {
+        self.req = self.req.map(|req| req.param(name, value));
+        self
+    }
+
+    /// Sets the address of the client that sent the request.
+    pub fn remote_addr(mut self, addr: SocketAddr) -> Self {
+        self.req = self.req.map(|req| req.remote_addr(addr));
+        self
+    }
+
+    /// Attaches an extension to the request, as a host request hook would.
+    pub fn extension(mut self, name: &str, value: &str) -> Self {
+        self.req = self.req.map(|req| req.extension(name, value));
+        self
+    }
+
+    /// Sets the body of the request.
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
+        self.req = self.req.map(|req| req.body(body));
+        self
+    }
+
+    /// Sets a body that ends after the given number of bytes, as if the client disconnected.
+    ///
+    /// The request declares the length of the entire body, but reading past `len` bytes fails.
+    pub fn truncated_body<B: Into<Vec<u8>>>(mut self, body: B, len: usize) -> Self {
+        self.truncated = Some((body.into(), len));
+        self
+    }
+
+    /// Sets the body of the request to the given value serialized as JSON.
+    ///
+    /// The `Content-Type` header is set to `application/json`.
+    pub fn json<T: Serialize>(mut self, value: &T) -> Self {
+        self.req = self.req.and_then(|req| {
+            Ok(req
+                .header("content-type", "application/json")
+                .body(serde_json::to_vec(value)?))
+        });
+        self
+    }
+
+    /// Sends the request, waiting for the function's response.
+    pub fn send(self) -> Result<TestResponse> {
+        async_std::task::block_on(self.send_async())
+    }
+
+    /// Sends the request asynchronously.
+    ///
+    /// Use this from tests that are already running on an async executor.
+    pub async fn send_async(self) -> Result<TestResponse> {
+        let mut req = self.req?.build()?;
+
+        if let Some((body, len)) = self.truncated {
+            let declared = body.len();
+            req.set_body(Body::from_reader(
+                BufReader::new(TruncatedReader { body, len, pos: 0 }),
+                Some(declared),
+            ));
+        }
+
+        let mut res = self.host.server.respond(req).await?;
+
+        let body = res.body_bytes().await.map_err(|e| e.into_inner())?;
+
+        Ok(TestResponse {
+            status: res.status().into(),
+            report: res.ext::<InvocationReport>().cloned(),
+            headers: res
+                .iter()
+                .flat_map(|(name, values)| {
+                    values
+                        .iter()
+                        .map(move |v| (name.as_str().to_string(), v.as_str().to_string()))
+                })
+                .collect(),
+            body,
+        })
+    }
+}
+
+// Reads a body up to a length and then fails as if the connection was reset
+struct TruncatedReader {
+    body: Vec<u8>,
+    len: usize,
+    pos: usize,
+}
+
+impl Read for TruncatedReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let end = self.len.min(self.body.len());
+
+        if self.pos >= end {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::ConnectionReset,
+                "the request body was truncated",
+            )));
+        }
+
+        let n = buf.len().min(end - self.pos);
+        buf[..n].copy_from_slice(&self.body[self.pos..self.pos + n]);
+        self.pos += n;
+
+        Poll::Ready(Ok(n))
+    }
+}
+
+/// Represents a response from a test host.
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    status: u16,
+    report: Option<InvocationReport>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl TestResponse {
+    /// Creates a response from its parts.
+    ///
+    /// This is used by [`conformance::Target`] implementations for other hosts.
+    pub fn new(status: u16, headers: Vec<(String, String)>, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            report: None,
+            headers,
+            body,
+        }
+    }
+
+    /// Gets the report of the function invocation that produced the response.
+    ///
+    /// Use this to assert on the resources used by a function, such as the fuel it consumed or the
+    /// number of SQL statements it executed. This is `None` if no function was invoked, such as
+    /// when the request did not match a route or the response was cached.
+    pub fn report(&self) -> Option<&InvocationReport> {
+        self.report.as_ref()
+    }
+
+    /// Gets the status code of the response.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Gets the first value of the header with the given name.
+    ///
+    /// Header names are compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Gets the headers of the response.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Gets the body of the response.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Gets the body of the response as a string.
+    pub fn text(&self) -> Result<&str> {
+        match std::str::from_utf8(&self.body) {
+            Ok(s) => Ok(s),
+            Err(e) => bail!("response body is not valid UTF-8: {}", e),
+        }
+    }
+
+    /// Deserializes the body of the response from JSON.
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use wasmtime_functions_runtime::{SqlProvider, SqlValue};
+
+/// The kind of a recorded SQL statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementKind {
+    /// The statement was executed with `sql::execute`.
+    Execute,
+    /// The statement was executed with `sql::query`.
+    Query,
+}
+
+/// Represents a SQL statement executed by a function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedStatement {
+    /// How the statement was executed.
+    pub kind: StatementKind,
+    /// The text of the statement.
+    pub statement: String,
+    /// The parameters bound to the statement.
+    pub params: Vec<SqlValue>,
+}
+
+enum MockResult {
+    Affected(u64),
+    Rows(Vec<Vec<SqlValue>>),
+    Error(String),
+}
+
+/// An in-memory SQL provider that records the statements executed by functions.
+///
+/// Results are configured per statement text (ignoring surrounding whitespace). Statements without
+/// a configured result affect no rows and return no rows.
+#[derive(Default)]
+pub struct MockSqlProvider {
+    results: Mutex<HashMap<String, MockResult>>,
+    delays: Mutex<HashMap<String, Duration>>,
+    statements: Mutex<Vec<RecordedStatement>>,
+}
+
+impl MockSqlProvider {
+    /// Creates a new mock SQL provider.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn set(&self, statement: &str, result: MockResult) {
+        self.results
+            .lock()
+            .unwrap()
+            .insert(statement.trim().to_string(), result);
+    }
+
+    /// Sets the number of rows affected when the given statement is executed.
+    pub fn on_execute(&self, statement: &str, affected: u64) -> &Self {
+        self.set(statement, MockResult::Affected(affected));
+        self
+    }
+
+    /// Sets the rows returned when the given statement is queried.
+    pub fn on_query(&self, statement: &str, rows: Vec<Vec<SqlValue>>) -> &Self {
+        self.set(statement, MockResult::Rows(rows));
+        self
+    }
+
+    /// Makes the given statement fail with the given message.
+    pub fn on_error(&self, statement: &str, message: &str) -> &Self {
+        self.set(statement, MockResult::Error(message.to_string()));
+        self
+    }
+
+    /// Delays the result of the given statement, simulating a slow database.
+    ///
+    /// The delay is real time; combine it with a short statement timeout to exercise timeouts.
+    pub fn delay(&self, statement: &str, delay: Duration) -> &Self {
+        self.delays
+            .lock()
+            .unwrap()
+            .insert(statement.trim().to_string(), delay);
+        self
+    }
+
+    /// Gets the statements executed so far, in order.
+    pub fn statements(&self) -> Vec<RecordedStatement> {
+        self.statements.lock().unwrap().clone()
+    }
+
+    /// Clears the recorded statements.
+    pub fn clear(&self) {
+        self.statements.lock().unwrap().clear();
+    }
+
+    async fn record(&self, kind: StatementKind, statement: &str, params: &[SqlValue]) {
+        self.statements.lock().unwrap().push(RecordedStatement {
+            kind,
+            statement: statement.to_string(),
+            params: params.to_vec(),
+        });
+
+        let delay = self.delays.lock().unwrap().get(statement.trim()).copied();
+        if let Some(delay) = delay {
+            async_std::task::sleep(delay).await;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SqlProvider for MockSqlProvider {
+    async fn execute(&self, statement: &str, params: &[SqlValue]) -> Result<u64> {
+        self.record(StatementKind::Execute, statement, params).await;
+
+        match self.results.lock().unwrap().get(statement.trim()) {
+            Some(MockResult::Affected(affected)) => Ok(*affected),
+            Some(MockResult::Rows(rows)) => Ok(rows.len() as u64),
+            Some(MockResult::Error(message)) => bail!("{}", message),
+            None => Ok(0),
+        }
+    }
+
+    async fn query(&self, statement: &str, params: &[SqlValue]) -> Result<Vec<Vec<SqlValue>>> {
+        self.record(StatementKind::Query, statement, params).await;
+
+        match self.results.lock().unwrap().get(statement.trim()) {
+            Some(MockResult::Rows(rows)) => Ok(rows.clone()),
+            Some(MockResult::Affected(_)) | None => Ok(Vec::new()),
+            Some(MockResult::Error(message)) => bail!("{}", message),
+        }
+    }
+}
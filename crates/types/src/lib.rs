@@ -0,0 +1,377 @@
+//! The Wasmtime Functions types crate.
+//!
+//! This crate defines the function descriptors shared by the `wasmtime-functions-codegen` crate,
+//! which serializes them into a WebAssembly module, and the `wasmtime-functions-metadata` crate,
+//! which reads them back.
+//!
+//! Defining the descriptors once keeps the two crates from drifting apart; any change to the
+//! serialized form of these types is a change to the format of module metadata.
+
+#![deny(missing_docs)]
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Represents a HTTP method.
+#[derive(Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Method {
+    /// The `GET` HTTP method.
+    Get,
+    /// The `HEAD` HTTP method.
+    Head,
+    /// The `POST` HTTP method.
+    Post,
+    /// The `PUT` HTTP method.
+    Put,
+    /// The `DELETE` HTTP method.
+    Delete,
+    /// The `CONNECT` HTTP method.
+    Connect,
+    /// The `OPTIONS` HTTP method.
+    Options,
+    /// The `TRACE` HTTP method.
+    Trace,
+    /// The `PATCH` HTTP method.
+    Patch,
+}
+
+impl std::fmt::Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+impl AsRef<str> for Method {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Get => "GET",
+            Self::Head => "HEAD",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Delete => "DELETE",
+            Self::Connect => "CONNECT",
+            Self::Options => "OPTIONS",
+            Self::Trace => "TRACE",
+            Self::Patch => "PATCH",
+        }
+    }
+}
+
+impl std::borrow::Borrow<str> for Method {
+    fn borrow(&self) -> &str {
+        self.as_ref()
+    }
+}
+
+/// Represents the type of a HTTP route parameter.
+#[derive(Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ParameterType {
+    /// The parameter may be any string.
+    String,
+    /// The parameter must be a signed integer.
+    Integer,
+    /// The parameter must be an unsigned integer.
+    Unsigned,
+    /// The parameter must be a number.
+    Number,
+    /// The parameter must be `true` or `false`.
+    Boolean,
+}
+
+impl ParameterType {
+    /// Determines if the given value is valid for the parameter type.
+    pub fn is_valid(self, value: &str) -> bool {
+        match self {
+            Self::String => true,
+            Self::Integer => value.parse::<i64>().is_ok(),
+            Self::Unsigned => value.parse::<u64>().is_ok(),
+            Self::Number => value.parse::<f64>().map(f64::is_finite).unwrap_or(false),
+            Self::Boolean => value == "true" || value == "false",
+        }
+    }
+}
+
+impl std::fmt::Display for ParameterType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::String => "string",
+                Self::Integer => "integer",
+                Self::Unsigned => "unsigned",
+                Self::Number => "number",
+                Self::Boolean => "boolean",
+            }
+        )
+    }
+}
+
+/// Represents a typed HTTP route parameter.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Parameter {
+    /// The name of the parameter.
+    pub name: String,
+    /// The type of the parameter.
+    #[serde(rename = "type")]
+    pub ty: ParameterType,
+}
+
+/// Represents the host-side caching of a HTTP function's responses.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cache {
+    /// The time-to-live of cached responses, in seconds.
+    pub ttl: u32,
+    /// The names of the request headers that cached responses vary on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vary: Vec<String>,
+}
+
+/// Represents the ways a Wasmtime Function can be triggered.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum FunctionTrigger {
+    /// The function is triggered by a HTTP request.
+    Http {
+        /// The request path that triggers the function.
+        path: String,
+        /// The request methods that trigger the function.
+        methods: Vec<Method>,
+        /// The request content types accepted by the function.
+        ///
+        /// If empty, any content type is accepted.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        consumes: Vec<String>,
+        /// The typed parameters of the request path.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        params: Vec<Parameter>,
+        /// The caching of the function's responses.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache: Option<Cache>,
+        /// The static headers added to the function's responses, keyed by lowercase header name.
+        ///
+        /// A header the function sets itself is not replaced.
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        headers: BTreeMap<String, String>,
+        /// Whether requests must use a URL signed by the host and not yet expired.
+        #[serde(default, skip_serializing_if = "is_false")]
+        signed: bool,
+        /// Whether the host generates entity tags for the function's successful `GET` and `HEAD` responses.
+        #[serde(default, skip_serializing_if = "is_false")]
+        etag: bool,
+    },
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Represents an input to a Wasmtime Function.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum FunctionInput {}
+
+/// Represents an output of a Wasmtime Function.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum FunctionOutput {
+    /// The Wasmtime Function returns a HTTP response.
+    Http,
+}
+
+/// Represents the metadata of a Wasmtime Function.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Function {
+    /// The name of the function.
+    pub name: String,
+    /// The trigger of the function.
+    pub trigger: FunctionTrigger,
+    /// The inputs of the function.
+    pub inputs: Vec<FunctionInput>,
+    /// The outputs of the function.
+    pub outputs: Vec<FunctionOutput>,
+    /// The maximum number of concurrent invocations of the function.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<u32>,
+    /// The environment variables available to the function.
+    ///
+    /// When `None`, the function has every environment variable declared by the module.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vars: Option<Vec<String>>,
+    /// The ratio of the function's requests whose host calls are traced when the host samples traces.
+    ///
+    /// When `None`, the host's ratio applies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_sample: Option<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    const METHODS: [Method; 9] = [
+        Method::Get,
+        Method::Head,
+        Method::Post,
+        Method::Put,
+        Method::Delete,
+        Method::Connect,
+        Method::Options,
+        Method::Trace,
+        Method::Patch,
+    ];
+
+    fn http_function(trigger: FunctionTrigger) -> Function {
+        Function {
+            name: "f".to_string(),
+            trigger,
+            inputs: Vec::new(),
+            outputs: vec![FunctionOutput::Http],
+            concurrency: None,
+            vars: None,
+            trace_sample: None,
+        }
+    }
+
+    fn round_trip<T: Serialize + for<'de> Deserialize<'de>>(value: &T) -> Value {
+        let serialized = serde_json::to_value(value).unwrap();
+        let parsed: T = serde_json::from_value(serialized.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), serialized);
+        serialized
+    }
+
+    #[test]
+    fn methods_round_trip() {
+        for method in METHODS.iter() {
+            let serialized = round_trip(method);
+            assert_eq!(serialized, Value::String(method.to_string()));
+        }
+    }
+
+    #[test]
+    fn minimal_function_omits_empty_fields() {
+        let function = http_function(FunctionTrigger::Http {
+            path: "/".to_string(),
+            methods: vec![Method::Get],
+            consumes: Vec::new(),
+            params: Vec::new(),
+            cache: None,
+            headers: BTreeMap::new(),
+            signed: false,
+            etag: false,
+        });
+
+        assert_eq!(
+            round_trip(&function),
+            json!({
+                "name": "f",
+                "trigger": { "type": "http", "path": "/", "methods": ["GET"] },
+                "inputs": [],
+                "outputs": [{ "type": "http" }],
+            })
+        );
+    }
+
+    #[test]
+    fn full_function_round_trips() {
+        let mut headers = BTreeMap::new();
+        headers.insert("cache-control".to_string(), "no-store".to_string());
+
+        let mut function = http_function(FunctionTrigger::Http {
+            path: "/users/:id".to_string(),
+            methods: METHODS.to_vec(),
+            consumes: vec!["application/json".to_string()],
+            params: vec![Parameter {
+                name: "id".to_string(),
+                ty: ParameterType::Unsigned,
+            }],
+            cache: Some(Cache {
+                ttl: 60,
+                vary: vec!["accept".to_string()],
+            }),
+            headers,
+            signed: true,
+            etag: true,
+        });
+        function.concurrency = Some(4);
+        function.vars = Some(vec!["DATABASE_URL".to_string()]);
+        function.trace_sample = Some(0.5);
+
+        assert_eq!(
+            round_trip(&function),
+            json!({
+                "name": "f",
+                "trigger": {
+                    "type": "http",
+                    "path": "/users/:id",
+                    "methods": ["GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH"],
+                    "consumes": ["application/json"],
+                    "params": [{ "name": "id", "type": "unsigned" }],
+                    "cache": { "ttl": 60, "vary": ["accept"] },
+                    "headers": { "cache-control": "no-store" },
+                    "signed": true,
+                    "etag": true,
+                },
+                "inputs": [],
+                "outputs": [{ "type": "http" }],
+                "concurrency": 4,
+                "vars": ["DATABASE_URL"],
+                "traceSample": 0.5,
+            })
+        );
+    }
+
+    #[test]
+    fn omitted_fields_parse_as_defaults() {
+        let function: Function = serde_json::from_value(json!({
+            "name": "f",
+            "trigger": { "type": "http", "path": "/", "methods": ["PATCH"] },
+            "inputs": [],
+            "outputs": [{ "type": "http" }],
+        }))
+        .unwrap();
+
+        assert!(function.concurrency.is_none());
+        assert!(function.vars.is_none());
+        assert!(function.trace_sample.is_none());
+
+        let FunctionTrigger::Http {
+            methods,
+            consumes,
+            params,
+            cache,
+            headers,
+            signed,
+            etag,
+            ..
+        } = function.trigger;
+        assert!(methods == [Method::Patch]);
+        assert!(consumes.is_empty());
+        assert!(params.is_empty());
+        assert!(cache.is_none());
+        assert!(headers.is_empty());
+        assert!(!signed);
+        assert!(!etag);
+    }
+
+    #[test]
+    fn parameter_types_round_trip() {
+        for ty in [
+            ParameterType::String,
+            ParameterType::Integer,
+            ParameterType::Unsigned,
+            ParameterType::Number,
+            ParameterType::Boolean,
+        ]
+        .iter()
+        {
+            assert_eq!(round_trip(ty), Value::String(ty.to_string()));
+        }
+    }
+}
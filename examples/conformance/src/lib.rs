@@ -0,0 +1,81 @@
+//! The reference module for the conformance suite in the `wasmtime-functions-test` crate.
+//!
+//! Each function exercises a part of the host interface; changing a function's behavior
+//! requires changing the expectations of the suite.
+
+use wasmtime_functions::{get, post, put, Cookie, Request, Response, SameSite, StatusCode};
+
+#[put("/method")]
+fn method(req: Request) -> String {
+    req.method()
+}
+
+#[get("/uri")]
+fn uri(req: Request) -> String {
+    req.uri()
+        .path_and_query()
+        .map(ToString::to_string)
+        .unwrap_or_default()
+}
+
+#[get("/params/:name")]
+fn params(req: Request) -> String {
+    req.param("name").unwrap_or_default()
+}
+
+#[get("/header")]
+fn header(req: Request) -> String {
+    req.header("x-conformance").unwrap_or_default()
+}
+
+#[get("/cookie")]
+fn cookie(req: Request) -> String {
+    req.cookie("session").unwrap_or_default()
+}
+
+#[post("/echo")]
+fn echo(req: Request) -> Result<Response, String> {
+    let content_type = req
+        .header("content-type")
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+
+    Ok(Response::build(StatusCode::OK)
+        .header("Content-Type", content_type)
+        .body(req.body()?))
+}
+
+#[get("/status/:code")]
+fn status(req: Request) -> Result<Response, String> {
+    let code: u16 = req
+        .param("code")
+        .unwrap_or_default()
+        .parse()
+        .map_err(|_| "invalid status code".to_string())?;
+
+    Ok(
+        Response::build(StatusCode::from_u16(code).map_err(|e| e.to_string())?)
+            .header("X-Status", code.to_string())
+            .body(""),
+    )
+}
+
+#[get("/set-cookie")]
+fn set_cookie(_req: Request) -> Response {
+    Response::build(StatusCode::OK)
+        .add_cookie(
+            &Cookie::build("session", "abc")
+                .http_only()
+                .same_site(SameSite::Strict)
+                .path("/")
+                .finish(),
+        )
+        .body("")
+}
+
+#[get("/error")]
+fn error(_req: Request) -> Result<Response, String> {
+    Err("function failed".to_string())
+}
+
+#[get("/empty")]
+fn empty(_req: Request) {}
@@ -0,0 +1,224 @@
+use crate::invoke::parse_header;
+use crate::{parse_env_var, EnvironmentProvider};
+use anyhow::{anyhow, bail, Context, Result};
+use http_types::{Method, Request, Url};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+use wasmtime_functions_runtime::{InvocationStats, Server};
+
+fn parse_duration(s: &str) -> Result<Duration> {
+    let (value, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+
+    let value: u64 = value
+        .parse()
+        .map_err(|_| anyhow!("must be a number followed by `ms`, `s`, or `m`"))?;
+
+    match unit {
+        "ms" => Ok(Duration::from_millis(value)),
+        "s" => Ok(Duration::from_secs(value)),
+        "m" => Ok(Duration::from_secs(value * 60)),
+        _ => bail!("must be a number followed by `ms`, `s`, or `m`"),
+    }
+}
+
+#[derive(StructOpt)]
+pub struct BenchOptions {
+    /// The path to the WebAssembly module to benchmark.
+    pub module: PathBuf,
+
+    /// The path (and query) of the requests.
+    #[structopt(long)]
+    pub path: String,
+
+    /// The method of the requests.
+    #[structopt(long, short = "X", default_value = "GET")]
+    pub method: String,
+
+    /// Add a header to the requests.
+    #[structopt(long = "header", short = "H", number_of_values = 1, value_name = "NAME: VALUE", parse(try_from_str = parse_header))]
+    pub headers: Vec<(String, String)>,
+
+    /// The body of the requests.
+    #[structopt(long)]
+    pub body: Option<String>,
+
+    /// The number of requests to process concurrently.
+    #[structopt(long, default_value = "1")]
+    pub concurrency: usize,
+
+    /// How long to run the benchmark, such as `500ms`, `30s`, or `5m`.
+    #[structopt(long, default_value = "10s", parse(try_from_str = parse_duration))]
+    pub duration: Duration,
+
+    /// Override an application environment variable value.
+    #[structopt(long = "env", short, number_of_values = 1, value_name = "NAME=VAL", parse(try_from_str = parse_env_var))]
+    pub environment: Vec<(String, String)>,
+}
+
+struct Sample {
+    latency: Duration,
+    status: u16,
+    stats: Option<InvocationStats>,
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::default();
+    }
+
+    sorted[(((sorted.len() - 1) as f64) * p).round() as usize]
+}
+
+fn mean<I: Iterator<Item = f64>>(values: I) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    if count == 0 {
+        None
+    } else {
+        Some(sum / count as f64)
+    }
+}
+
+pub async fn run(options: &BenchOptions) -> Result<()> {
+    if !options.module.is_file() {
+        bail!("module '{}' does not exist.", options.module.display());
+    }
+
+    if options.concurrency == 0 {
+        bail!("concurrency must be at least 1");
+    }
+
+    let module = std::fs::read(&options.module)?;
+
+    let method = Method::from_str(&options.method).map_err(|e| anyhow!("invalid method: {}", e))?;
+
+    let url = Url::parse("http://localhost")?
+        .join(&options.path)
+        .with_context(|| format!("invalid request path '{}'", options.path))?;
+
+    let environment = Arc::new(EnvironmentProvider::new(
+        options.environment.clone(),
+        atty::is(atty::Stream::Stdin),
+    ));
+
+    let server = Server::builder(&module, environment).local().await?;
+
+    log::info!(
+        "Benchmarking {} {} with {} concurrent requests for {:?}...",
+        method,
+        options.path,
+        options.concurrency,
+        options.duration
+    );
+
+    let start = Instant::now();
+    let deadline = start + options.duration;
+
+    let workers: Vec<_> = (0..options.concurrency)
+        .map(|_| {
+            let server = server.clone();
+            let url = url.clone();
+            let headers = options.headers.clone();
+            let body = options.body.clone();
+
+            async_std::task::spawn(async move {
+                let mut samples = Vec::new();
+
+                while Instant::now() < deadline {
+                    let mut req = Request::new(method, url.clone());
+
+                    for (name, value) in &headers {
+                        req.append_header(name.as_str(), value.as_str());
+                    }
+
+                    if let Some(body) = &body {
+                        req.set_body(body.as_str());
+                    }
+
+                    let start = Instant::now();
+                    let mut res = server.respond(req).await?;
+                    res.body_bytes().await.map_err(|e| e.into_inner())?;
+
+                    samples.push(Sample {
+                        latency: start.elapsed(),
+                        status: res.status() as u16,
+                        stats: res.ext::<InvocationStats>().copied(),
+                    });
+                }
+
+                Ok::<_, anyhow::Error>(samples)
+            })
+        })
+        .collect();
+
+    let mut samples = Vec::new();
+    for worker in workers {
+        samples.extend(worker.await?);
+    }
+
+    let elapsed = start.elapsed();
+
+    let mut latencies: Vec<_> = samples.iter().map(|s| s.latency).collect();
+    latencies.sort();
+
+    let mut statuses = BTreeMap::new();
+    for sample in &samples {
+        *statuses.entry(sample.status).or_insert(0) += 1;
+    }
+
+    println!(
+        "Requests:      {} ({:.1}/s)",
+        samples.len(),
+        samples.len() as f64 / elapsed.as_secs_f64()
+    );
+
+    println!(
+        "Responses:     {}",
+        statuses
+            .iter()
+            .map(|(status, count)| format!("{} x{}", status, count))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    println!(
+        "Latency:       p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+        percentile(&latencies, 0.5),
+        percentile(&latencies, 0.9),
+        percentile(&latencies, 0.99),
+        latencies.last().copied().unwrap_or_default()
+    );
+
+    let stats: Vec<_> = samples.iter().filter_map(|s| s.stats).collect();
+
+    if let Some(instantiation) = mean(
+        stats
+            .iter()
+            .filter_map(|s| s.instantiation)
+            .map(|d| d.as_secs_f64()),
+    ) {
+        println!(
+            "Instantiation: mean {:?}",
+            Duration::from_secs_f64(instantiation)
+        );
+    }
+
+    if let Some(execution) = mean(stats.iter().map(|s| s.execution.as_secs_f64())) {
+        println!(
+            "Execution:     mean {:?}",
+            Duration::from_secs_f64(execution)
+        );
+    }
+
+    if let Some(fuel) = mean(stats.iter().filter_map(|s| s.fuel).map(|f| f as f64)) {
+        println!("Fuel:          mean {:.0} per request", fuel);
+    }
+
+    Ok(())
+}
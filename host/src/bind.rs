@@ -0,0 +1,50 @@
+use anyhow::{Context, Result};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+
+/// Determines if the host is running in a container.
+fn in_container() -> bool {
+    if Path::new("/.dockerenv").exists()
+        || Path::new("/run/.containerenv").exists()
+        || std::env::var_os("KUBERNETES_SERVICE_HOST").is_some()
+    {
+        return true;
+    }
+
+    std::fs::read_to_string("/proc/1/cgroup")
+        .map(|cgroup| {
+            ["docker", "kubepods", "containerd", "libpod"]
+                .iter()
+                .any(|runtime| cgroup.contains(runtime))
+        })
+        .unwrap_or(false)
+}
+
+/// Determines the listen address when one isn't explicitly given.
+///
+/// In a container, the application listens on all interfaces as it would otherwise be unreachable.
+/// If `bind_env` is set, the `HOST` and `PORT` environment variables override the defaults.
+pub fn default_addr(bind_env: bool) -> Result<SocketAddr> {
+    let mut ip = if in_container() {
+        IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+    } else {
+        IpAddr::V4(Ipv4Addr::LOCALHOST)
+    };
+    let mut port = 0;
+
+    if bind_env {
+        if let Ok(host) = std::env::var("HOST") {
+            ip = host
+                .parse()
+                .with_context(|| format!("invalid `HOST` environment variable '{}'", host))?;
+        }
+
+        if let Ok(value) = std::env::var("PORT") {
+            port = value
+                .parse()
+                .with_context(|| format!("invalid `PORT` environment variable '{}'", value))?;
+        }
+    }
+
+    Ok(SocketAddr::new(ip, port))
+}
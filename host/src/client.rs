@@ -0,0 +1,67 @@
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+use structopt::StructOpt;
+use wasmtime_functions_metadata::Metadata;
+
+#[derive(StructOpt)]
+pub struct GenerateClientOptions {
+    /// The path to the WebAssembly module.
+    pub module: PathBuf,
+
+    /// The path of the client crate directory to create.
+    #[structopt(long, short, value_name = "DIR")]
+    pub output: PathBuf,
+
+    /// The name of the client crate; defaults to the name of the module followed by `-client`.
+    #[structopt(long)]
+    pub name: Option<String>,
+
+    /// Overwrite the files of an existing client crate.
+    #[structopt(long)]
+    pub force: bool,
+}
+
+pub fn run(options: &GenerateClientOptions) -> Result<()> {
+    if !options.module.is_file() {
+        bail!("module '{}' does not exist.", options.module.display());
+    }
+
+    let path = &options.output;
+
+    if !options.force && path.exists() && path.read_dir()?.next().is_some() {
+        bail!(
+            "directory '{}' already exists and is not empty; use `--force` to overwrite it.",
+            path.display()
+        );
+    }
+
+    let name = match &options.name {
+        Some(name) => name.clone(),
+        None => match options.module.file_stem().and_then(|n| n.to_str()) {
+            Some(stem) => format!("{}-client", stem.replace('_', "-")),
+            None => bail!(
+                "cannot determine the client name from '{}'; use `--name`.",
+                options.module.display()
+            ),
+        },
+    };
+
+    let metadata = Metadata::from_module_bytes(&std::fs::read(&options.module)?)?;
+
+    for file in metadata.generate_client(&name) {
+        let file_path = path.join(&file.path);
+        if let Some(parent) = file_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(file_path, file.contents)?;
+    }
+
+    log::info!(
+        "Generated client crate '{}' for {} function(s) in '{}'.",
+        name,
+        metadata.functions.len(),
+        path.display()
+    );
+
+    Ok(())
+}
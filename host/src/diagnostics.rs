@@ -0,0 +1,120 @@
+use anyhow::Error;
+use std::io::{self, Write};
+use wasmtime_functions_runtime::ServerError;
+
+const RED: &str = "\x1b[1;31m";
+const CYAN: &str = "\x1b[1;36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Styles text with ANSI escape codes when color is enabled.
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn detect() -> Self {
+        Self {
+            color: atty::is(atty::Stream::Stderr) && std::env::var_os("NO_COLOR").is_none(),
+        }
+    }
+
+    fn paint(&self, code: &str, s: &str) -> String {
+        if self.color {
+            format!("{}{}{}", code, s, RESET)
+        } else {
+            s.to_string()
+        }
+    }
+}
+
+/// Determines hints for resolving an error based on the errors in its chain.
+fn hints(e: &Error) -> Vec<String> {
+    let mut hints = Vec::new();
+    let mut add = |hint: String| {
+        if !hints.contains(&hint) {
+            hints.push(hint);
+        }
+    };
+
+    let binding = e
+        .chain()
+        .any(|cause| cause.to_string().contains("failed to bind"));
+
+    for cause in e.chain() {
+        // Metadata errors are reported both directly and wrapped in `ServerError::InvalidModule`
+        let message = cause.to_string();
+        if message.contains("no Wasmtime functions") {
+            add("the module has no `__functions` section; did you build it with the `wasmtime_functions` macros for the `wasm32-wasi` target (`cargo build --target wasm32-wasi`)?".to_string());
+        } else if message.contains("not a valid WebAssembly module") {
+            add("make sure the path refers to the `.wasm` file under `target/wasm32-wasi` and not to a native executable".to_string());
+        } else if message.contains("'__functions' section") || message.contains("'__vars' section")
+        {
+            add("the module was built with an incompatible version of the `wasmtime-functions` crate; rebuild it against a version that matches the host".to_string());
+        } else if message.contains("does not match its function metadata") {
+            add("the module's `__functions` section is out of sync with its exports; rebuild the module with `cargo build --target wasm32-wasi` rather than post-processing it".to_string());
+        } else if message.contains("environment variables are not set") {
+            add("set the variables with `--env NAME=VALUE`, `--env-file`, or `--secrets`, or run the host from a terminal to be prompted for them".to_string());
+        }
+
+        if let Some(e) = cause.downcast_ref::<ServerError>() {
+            match e {
+                ServerError::MissingVar { name, .. } => add(format!(
+                    "set the variable with `--env {}=VALUE`, `--env-file`, or `--secrets`, or run the host from a terminal to be prompted for it",
+                    name
+                )),
+                ServerError::Signature(_) => add("check that `--signature` was produced for this exact module and that the signing key was passed with `--trusted-key`".to_string()),
+                ServerError::InterfaceVersion { module, max, .. } => match module {
+                    Some(version) if version > max => add("upgrade the host to a version that supports the module's host interface, or rebuild the module with an older `wasmtime-functions` crate".to_string()),
+                    _ => add("rebuild the module with a newer `wasmtime-functions` crate, or pass `--allow-older-sdk` to run it anyway".to_string()),
+                },
+                ServerError::Compile(e) => {
+                    let message = format!("{:#}", e);
+                    if message.contains("incompatible") || message.contains("deserialize") {
+                        add("the precompiled module does not match the host's engine settings; run `precompile` again with the same `--profile`, `--interruption`, and `-g` options used to serve it".to_string());
+                    } else if message.contains("unknown import") {
+                        add("the module imports functions the host does not provide; rebuild it against a version of the `wasmtime-functions` crate that matches the host".to_string());
+                    }
+                }
+                ServerError::Preopen { path, .. } => add(format!(
+                    "check that '{}' exists and is a directory readable by the host",
+                    path.display()
+                )),
+                ServerError::Warmup(_) => add("the module failed to instantiate; use `invoke` to reproduce the failure outside of the server".to_string()),
+                _ => {}
+            }
+        } else if let Some(e) = cause.downcast_ref::<io::Error>() {
+            match e.kind() {
+                io::ErrorKind::AddrInUse => add("another process is already listening on the address; stop it or choose a different address with `--addr`".to_string()),
+                io::ErrorKind::AddrNotAvailable => add("the address does not belong to this machine; use `127.0.0.1` or `0.0.0.0` with `--addr`".to_string()),
+                io::ErrorKind::PermissionDenied if binding => add("ports below 1024 require elevated privileges; choose a higher port with `--addr`".to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    hints
+}
+
+/// Reports an error that caused the host to exit to stderr.
+///
+/// The error's causes are listed after the error, followed by hints for common misconfigurations.
+pub fn report(e: &Error) {
+    let style = Style::detect();
+    let mut stderr = io::stderr();
+
+    let _ = writeln!(
+        stderr,
+        "{} {}",
+        style.paint(RED, "error:"),
+        style.paint(BOLD, &e.to_string())
+    );
+
+    for cause in e.chain().skip(1) {
+        let _ = writeln!(stderr, "  {} {}", style.paint(BOLD, "caused by:"), cause);
+    }
+
+    for hint in hints(e) {
+        let _ = writeln!(stderr, "{} {}", style.paint(CYAN, "hint:"), hint);
+    }
+}
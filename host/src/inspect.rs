@@ -0,0 +1,148 @@
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+use structopt::StructOpt;
+use wasmtime_functions_metadata::{FunctionTrigger, Metadata};
+
+#[derive(StructOpt)]
+pub struct InspectOptions {
+    /// The path to the WebAssembly module to inspect.
+    pub module: PathBuf,
+
+    /// Print the metadata as JSON.
+    #[structopt(long)]
+    pub json: bool,
+
+    /// Print the metadata as normalized JSON that is stable between builds, for snapshots and diffs.
+    #[structopt(long, conflicts_with = "json")]
+    pub canonical: bool,
+}
+
+pub fn run(options: &InspectOptions) -> Result<()> {
+    if !options.module.is_file() {
+        bail!("module '{}' does not exist.", options.module.display());
+    }
+
+    let metadata = Metadata::from_module_bytes(&std::fs::read(&options.module)?)?;
+
+    if options.canonical {
+        print!("{}", metadata.to_canonical_json());
+        return Ok(());
+    }
+
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&metadata)?);
+        return Ok(());
+    }
+
+    let mut rows = vec![[
+        "FUNCTION".to_string(),
+        "METHODS".to_string(),
+        "PATH".to_string(),
+        "OPTIONS".to_string(),
+    ]];
+
+    for function in &metadata.functions {
+        match &function.trigger {
+            FunctionTrigger::Http {
+                path,
+                methods,
+                consumes,
+                params,
+                cache,
+                headers,
+                signed,
+                etag,
+            } => {
+                let mut opts = Vec::new();
+
+                if !consumes.is_empty() {
+                    opts.push(format!("consumes={}", consumes.join(",")));
+                }
+
+                for param in params {
+                    opts.push(format!("{}:{}", param.name, param.ty));
+                }
+
+                if let Some(concurrency) = function.concurrency {
+                    opts.push(format!("concurrency={}", concurrency));
+                }
+
+                if let Some(cache) = cache {
+                    opts.push(format!("cache={}s", cache.ttl));
+
+                    if !cache.vary.is_empty() {
+                        opts.push(format!("vary={}", cache.vary.join(",")));
+                    }
+                }
+
+                if !headers.is_empty() {
+                    opts.push(format!(
+                        "headers={}",
+                        headers.keys().cloned().collect::<Vec<_>>().join(",")
+                    ));
+                }
+
+                if *signed {
+                    opts.push("signed".to_string());
+                }
+
+                if *etag {
+                    opts.push("etag".to_string());
+                }
+
+                if let Some(ratio) = function.trace_sample {
+                    opts.push(format!("trace_sample={}", ratio));
+                }
+
+                rows.push([
+                    function.name.clone(),
+                    if methods.is_empty() {
+                        "*".to_string()
+                    } else {
+                        methods
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    },
+                    path.clone(),
+                    opts.join(" "),
+                ]);
+            }
+        }
+    }
+
+    let mut widths = [0; 4];
+    for row in &rows {
+        for (width, column) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(column.len());
+        }
+    }
+
+    for row in &rows {
+        println!(
+            "{:<w0$}  {:<w1$}  {:<w2$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+        );
+    }
+
+    println!();
+
+    if metadata.vars.is_empty() {
+        println!("The module declares no environment variables.");
+    } else {
+        println!("Environment variables:");
+
+        for var in &metadata.vars {
+            println!("  {}", var);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use crate::{parse_env_var, EnvironmentProvider};
+use anyhow::{anyhow, bail, Context, Result};
+use http_types::Response;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use structopt::StructOpt;
+use wasmtime_functions_metadata::{FunctionTrigger, Metadata};
+use wasmtime_functions_runtime::{InvocationRequest, Server};
+
+pub fn parse_header(s: &str) -> Result<(String, String)> {
+    let parts: Vec<_> = s.splitn(2, ':').collect();
+    if parts.len() != 2 {
+        bail!("must be of the form `name: value`");
+    }
+    Ok((parts[0].trim().to_owned(), parts[1].trim().to_owned()))
+}
+
+#[derive(StructOpt)]
+pub struct InvokeOptions {
+    /// The path to the WebAssembly module containing the function.
+    pub module: PathBuf,
+
+    /// The function to invoke; its route determines the default method and path.
+    #[structopt(long)]
+    pub function: Option<String>,
+
+    /// The method of the request.
+    #[structopt(long, short = "X")]
+    pub method: Option<String>,
+
+    /// The path (and query) of the request.
+    #[structopt(long)]
+    pub path: Option<String>,
+
+    /// Add a header to the request.
+    #[structopt(long = "header", short = "H", number_of_values = 1, value_name = "NAME: VALUE", parse(try_from_str = parse_header))]
+    pub headers: Vec<(String, String)>,
+
+    /// The body of the request; use `@PATH` to read the body from a file.
+    #[structopt(long)]
+    pub body: Option<String>,
+
+    /// Override an application environment variable value.
+    #[structopt(long = "env", short, number_of_values = 1, value_name = "NAME=VAL", parse(try_from_str = parse_env_var))]
+    pub environment: Vec<(String, String)>,
+}
+
+pub async fn run(options: &InvokeOptions) -> Result<()> {
+    if !options.module.is_file() {
+        bail!("module '{}' does not exist.", options.module.display());
+    }
+
+    let module = std::fs::read(&options.module)?;
+
+    let (mut method, mut path) = (options.method.clone(), options.path.clone());
+
+    if let Some(name) = &options.function {
+        let metadata = Metadata::from_module_bytes(&module)?;
+        let function = metadata
+            .functions
+            .iter()
+            .find(|f| &f.name == name)
+            .ok_or_else(|| anyhow!("module does not contain a function named '{}'.", name))?;
+
+        match &function.trigger {
+            FunctionTrigger::Http {
+                path: route,
+                methods,
+                ..
+            } => {
+                if method.is_none() {
+                    method = methods.first().map(ToString::to_string);
+                }
+
+                if path.is_none() {
+                    if route.contains(&[':', '*'][..]) {
+                        bail!(
+                            "function '{}' has route '{}' with parameters; use `--path` to specify the request path.",
+                            name,
+                            route
+                        );
+                    }
+
+                    path = Some(route.clone());
+                }
+            }
+        }
+    }
+
+    let path = path.ok_or_else(|| anyhow!("either `--function` or `--path` must be specified."))?;
+
+    let mut req = options.headers.iter().fold(
+        InvocationRequest::new(method.unwrap_or_else(|| "GET".to_string()), path),
+        |req, (name, value)| req.header(name.as_str(), value.as_str()),
+    );
+
+    if let Some(body) = &options.body {
+        req = match body.strip_prefix('@') {
+            Some(path) => req.body(
+                std::fs::read(path)
+                    .with_context(|| format!("failed to read request body from '{}'", path))?,
+            ),
+            None => req.body(body.as_str()),
+        };
+    }
+
+    let environment = Arc::new(EnvironmentProvider::new(
+        options.environment.clone(),
+        atty::is(atty::Stream::Stdin),
+    ));
+
+    let server = Server::builder(&module, environment).local().await?;
+
+    print_response(server.respond(req.build()?).await?).await
+}
+
+/// Prints the status, headers, and body of a response to stdout.
+pub async fn print_response(mut res: Response) -> Result<()> {
+    let mut stdout = std::io::stdout();
+
+    writeln!(
+        stdout,
+        "{} {}",
+        res.status(),
+        res.status().canonical_reason()
+    )?;
+
+    for (name, values) in res.iter() {
+        for value in values {
+            writeln!(stdout, "{}: {}", name, value)?;
+        }
+    }
+
+    writeln!(stdout)?;
+    stdout.write_all(&res.body_bytes().await.map_err(|e| e.into_inner())?)?;
+    stdout.flush()?;
+
+    Ok(())
+}
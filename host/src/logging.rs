@@ -0,0 +1,53 @@
+use anyhow::{bail, Error, Result};
+use env_logger::fmt::Formatter;
+use log::{LevelFilter, Record};
+use std::io::Write;
+use std::str::FromStr;
+
+/// The format of log output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => bail!("must be either `text` or `json`"),
+        }
+    }
+}
+
+fn format_json(buf: &mut Formatter, record: &Record) -> std::io::Result<()> {
+    writeln!(
+        buf,
+        "{}",
+        serde_json::json!({
+            "timestamp": buf.timestamp().to_string(),
+            "level": record.level().as_str(),
+            "target": record.target(),
+            "message": record.args().to_string(),
+        })
+    )
+}
+
+/// Initializes logging for the host, runtime, and functions.
+pub fn init(format: LogFormat, level: LevelFilter) {
+    let mut builder = env_logger::builder();
+
+    builder
+        .format_module_path(false)
+        .filter_module("wasmtime_functions_runtime", level)
+        .filter_module("wasmtime_functions_host", level);
+
+    if format == LogFormat::Json {
+        builder.format(format_json);
+    }
+
+    builder.init();
+}
@@ -1,12 +1,67 @@
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use async_ctrlc::CtrlC;
 use async_std::prelude::FutureExt;
-use env_logger::builder;
+use futures::future::{try_join_all, LocalBoxFuture};
+use logging::LogFormat;
+use profile::{EngineSettings, Profile};
 use rpassword::read_password_from_tty;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use structopt::StructOpt;
-use wasmtime_functions_runtime::Server;
+use tempfile::TempDir;
+use wasmtime_functions_metadata::Metadata;
+#[cfg(unix)]
+use wasmtime_functions_runtime::SyslogAuditSink;
+use wasmtime_functions_runtime::{
+    AdminServer, AllowedHost, AuditSink, CanaryPolicy, CircuitBreakerPolicy, DirectoryBlobProvider,
+    DiscoveryProvider, DnsDiscovery, DuplicateHeaders, FileAuditSink, HeaderCase, Interruption,
+    MirrorPolicy, MirrorTarget, ProblemErrorRenderer, QuotaLimits, QuotaPolicy, RetryPolicy,
+    Server, ServerBuilder, ServerError, ServiceEndpoint, StaticDiscovery, TraceSampling,
+};
+use watch::Watcher;
+
+// The number of seconds a circuit stays open by default.
+const DEFAULT_CIRCUIT_OPEN_SECS: u64 = 30;
+
+// The name of the Kubernetes service port resolved by default.
+const DEFAULT_KUBERNETES_PORT_NAME: &str = "http";
+
+// The percentage of requests routed to the canary by default.
+const DEFAULT_CANARY_PERCENT: u8 = 10;
+
+// The number of requests the canary serves by default before its error rate is checked.
+const DEFAULT_CANARY_MIN_REQUESTS: u64 = 100;
+
+// The percentage of requests mirrored by default.
+const DEFAULT_MIRROR_PERCENT: u8 = 100;
+
+// The number of seconds in a quota window by default.
+const DEFAULT_QUOTA_WINDOW_SECS: u64 = 60;
+
+// The interval at which the application is checked against the recycling policy.
+const RECYCLE_POLL_INTERVAL_MS: u64 = 1000;
+
+mod bench;
+mod bind;
+mod client;
+mod diagnostics;
+mod inspect;
+mod invoke;
+mod logging;
+mod package;
+mod precompile;
+mod profile;
+mod replay;
+mod routes;
+mod scaffold;
+mod secrets;
+#[cfg(unix)]
+mod systemd;
+mod watch;
 
 fn parse_env_var(s: &str) -> Result<(String, String)> {
     let parts: Vec<_> = s.splitn(2, '=').collect();
@@ -16,33 +71,430 @@ fn parse_env_var(s: &str) -> Result<(String, String)> {
     Ok((parts[0].to_owned(), parts[1].to_owned()))
 }
 
-struct EnvironmentProvider(Vec<(String, String)>);
+fn parse_mount(s: &str) -> Result<(String, PathBuf)> {
+    let (prefix, path) =
+        parse_env_var(s).map_err(|_| anyhow!("must be of the form `prefix=path`"))?;
+    if !prefix.starts_with('/') {
+        bail!("mount prefix '{}' must start with '/'", prefix);
+    }
+    Ok((prefix, PathBuf::from(path)))
+}
+
+fn parse_quota_tenant(s: &str) -> Result<(String, QuotaLimits)> {
+    // Keys are split at the last `=` as base64 keys may end with padding
+    let (key, limits) = s
+        .rsplit_once('=')
+        .ok_or_else(|| anyhow!("must be of the form `key=requests[,fuel]`"))?;
+
+    let (requests, fuel) = match limits.split_once(',') {
+        Some((requests, fuel)) => (requests, Some(fuel)),
+        None => (limits, None),
+    };
+
+    let parse = |limit: &str| -> Result<u64> {
+        limit
+            .parse()
+            .map_err(|_| anyhow!("invalid quota limit `{}`", limit))
+    };
+
+    Ok((
+        key.to_string(),
+        QuotaLimits::new(Some(parse(requests)?), fuel.map(parse).transpose()?),
+    ))
+}
+
+fn parse_injected_fuel(s: &str) -> Result<(String, u64)> {
+    let (function, fuel) =
+        parse_env_var(s).map_err(|_| anyhow!("must be of the form `function=fuel`"))?;
+    let fuel = fuel
+        .parse()
+        .map_err(|_| anyhow!("invalid fuel limit `{}`", fuel))?;
+    Ok((function, fuel))
+}
+
+fn parse_sample_ratio(s: &str) -> Result<f64> {
+    match s.parse::<f64>() {
+        Ok(ratio) if (0.0..=1.0).contains(&ratio) => Ok(ratio),
+        _ => bail!("invalid sample ratio `{}`: must be between 0.0 and 1.0", s),
+    }
+}
+
+fn parse_trace_sample(s: &str) -> Result<(String, f64)> {
+    let (function, ratio) =
+        parse_env_var(s).map_err(|_| anyhow!("must be of the form `function=ratio`"))?;
+    Ok((function, parse_sample_ratio(&ratio)?))
+}
+
+fn parse_blob_bucket(s: &str) -> Result<(String, PathBuf)> {
+    let (name, dir) = parse_env_var(s).map_err(|_| anyhow!("must be of the form `name=dir`"))?;
+    Ok((name, PathBuf::from(dir)))
+}
+
+fn parse_dir(s: &str) -> Result<(PathBuf, PathBuf, bool)> {
+    let (guest_path, host_path) =
+        parse_env_var(s).map_err(|_| anyhow!("must be of the form `guest_path=host_path[:ro]`"))?;
+
+    let (host_path, read_only) = match host_path.strip_suffix(":ro") {
+        Some(host_path) => (host_path.to_string(), true),
+        None => (host_path, false),
+    };
+
+    Ok((
+        PathBuf::from(guest_path),
+        PathBuf::from(host_path),
+        read_only,
+    ))
+}
+
+fn parse_canary_header(s: &str) -> Result<(String, String)> {
+    parse_env_var(s).map_err(|_| anyhow!("must be of the form `name=value`"))
+}
+
+fn parse_interruption(s: &str) -> Result<Interruption> {
+    match s {
+        "fuel" => Ok(Interruption::Fuel),
+        "epoch" => Ok(Interruption::Epoch),
+        _ => bail!("must be `fuel` or `epoch`"),
+    }
+}
+
+fn parse_header_case(s: &str) -> Result<HeaderCase> {
+    match s {
+        "lower" => Ok(HeaderCase::Lower),
+        "title" => Ok(HeaderCase::Title),
+        _ => bail!("must be `lower` or `title`"),
+    }
+}
+
+fn parse_duplicate_headers(s: &str) -> Result<DuplicateHeaders> {
+    match s {
+        "separate" => Ok(DuplicateHeaders::Separate),
+        "combine" => Ok(DuplicateHeaders::Combine),
+        _ => bail!("must be `separate` or `combine`"),
+    }
+}
+
+fn parse_allow_host(s: &str) -> Result<(Option<String>, AllowedHost)> {
+    match s.split_once('=') {
+        Some((prefix, host)) => {
+            if !prefix.starts_with('/') {
+                bail!("mount prefix '{}' must start with '/'", prefix);
+            }
+            Ok((Some(prefix.to_string()), host.parse()?))
+        }
+        None => Ok((None, s.parse()?)),
+    }
+}
+
+fn parse_grpc_service(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((service, target)) if !service.is_empty() => {
+            if !target.starts_with("http://") && !target.starts_with("https://") {
+                bail!("target '{}' must be an `http://` or `https://` URI", target);
+            }
+            Ok((service.to_string(), target.to_string()))
+        }
+        _ => bail!("must be of the form `SERVICE=URI`"),
+    }
+}
+
+fn parse_service_endpoint(s: &str) -> Result<(String, ServiceEndpoint)> {
+    let (service, endpoint) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("must be of the form `SERVICE=HOST:PORT`"))?;
+    let (host, port) = endpoint
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow!("endpoint '{}' must be of the form `HOST:PORT`", endpoint))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    if service.is_empty() || host.is_empty() {
+        bail!("must be of the form `SERVICE=HOST:PORT`");
+    }
+
+    let port = port
+        .parse()
+        .map_err(|_| anyhow!("invalid port in endpoint '{}'", endpoint))?;
+
+    Ok((service.to_string(), ServiceEndpoint::new(host, port)))
+}
+
+fn parse_kubernetes_discovery(s: &str) -> Result<(String, String)> {
+    let (namespace, port) = s
+        .split_once(':')
+        .unwrap_or((s, DEFAULT_KUBERNETES_PORT_NAME));
+
+    if namespace.is_empty() || port.is_empty() {
+        bail!("must be of the form `NAMESPACE[:PORT_NAME]`");
+    }
+
+    Ok((namespace.to_string(), port.to_string()))
+}
+
+fn read_env_file(path: &Path) -> Result<Vec<(String, String)>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read environment file '{}'", path.display()))?;
+
+    let mut vars = Vec::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+
+        let (name, value) = match parse_env_var(line) {
+            Ok((name, value)) => (name.trim().to_string(), value.trim().to_string()),
+            Err(e) => bail!("{}:{}: {}", path.display(), i + 1, e),
+        };
+
+        let value = if value.len() >= 2
+            && ((value.starts_with('"') && value.ends_with('"'))
+                || (value.starts_with('\'') && value.ends_with('\'')))
+        {
+            let unquoted = &value[1..value.len() - 1];
+            if value.starts_with('"') {
+                unquoted.replace("\\n", "\n").replace("\\\"", "\"")
+            } else {
+                unquoted.to_string()
+            }
+        } else {
+            value
+        };
+
+        vars.push((name, value));
+    }
+
+    Ok(vars)
+}
+
+fn parse_hex(s: &str) -> Result<Vec<u8>> {
+    if !s.is_ascii() || s.len() % 2 != 0 {
+        bail!("must be an even number of hexadecimal digits");
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&s[i..i + 2], 16)?))
+        .collect()
+}
+
+struct EnvironmentProvider {
+    overrides: Vec<(String, String)>,
+    // The names of the variables read from secrets sources
+    secrets: HashSet<String>,
+    // The names of the variables functions may read through their configuration
+    exposed: HashSet<String>,
+    interactive: bool,
+    // Values entered at the terminal are remembered so they aren't requested again on reload
+    entered: Mutex<HashMap<String, String>>,
+}
+
+impl EnvironmentProvider {
+    fn new(overrides: Vec<(String, String)>, interactive: bool) -> Self {
+        Self {
+            overrides,
+            secrets: HashSet::new(),
+            exposed: HashSet::new(),
+            interactive,
+            entered: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn with_secrets(mut self, secrets: HashSet<String>) -> Self {
+        self.secrets = secrets;
+        self
+    }
+
+    fn with_exposed(mut self, exposed: HashSet<String>) -> Self {
+        self.exposed = exposed;
+        self
+    }
+
+    fn lookup(&self, name: &str) -> Option<String> {
+        if let Some((_, v)) = self.overrides.iter().find(|(n, _)| n == name) {
+            return Some(v.clone());
+        }
+
+        if let Ok(value) = std::env::var(&name) {
+            return Some(value);
+        }
+
+        self.entered.lock().unwrap().get(name).cloned()
+    }
+
+    /// Fails if any of the variables declared by the module can't be resolved without prompting.
+    fn check(&self, module: &[u8]) -> Result<()> {
+        if self.interactive {
+            return Ok(());
+        }
+
+        let missing: Vec<_> = Metadata::from_module_bytes(&module)?
+            .vars
+            .into_iter()
+            .filter(|name| self.lookup(name).is_none())
+            .collect();
+
+        if !missing.is_empty() {
+            bail!(
+                "the following environment variables are not set: {}",
+                missing.join(", ")
+            );
+        }
+
+        Ok(())
+    }
+}
 
 impl wasmtime_functions_runtime::EnvironmentProvider for EnvironmentProvider {
     fn var(&self, name: &str) -> Result<String> {
-        Ok(
-            if let Some((_, v)) = self.0.iter().find(|(n, _)| n == name) {
-                v.clone()
-            } else if let Ok(value) = std::env::var(&name) {
-                value
-            } else {
-                read_password_from_tty(Some(&format!(
-                    "enter the value for environment variable '{}': ",
-                    name
-                )))?
-            },
-        )
+        if let Some(value) = self.lookup(name) {
+            return Ok(value);
+        }
+
+        if !self.interactive {
+            bail!("environment variable '{}' is not set", name);
+        }
+
+        let value = read_password_from_tty(Some(&format!(
+            "enter the value for environment variable '{}': ",
+            name
+        )))?;
+
+        self.entered
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), value.clone());
+
+        Ok(value)
+    }
+
+    // Only variables named with `--config-var` are visible in the guest's configuration; values from
+    // secrets sources and those entered at the terminal are hidden even then
+    fn is_secret(&self, name: &str) -> bool {
+        !self.exposed.contains(name)
+            || self.secrets.contains(name)
+            || self.entered.lock().unwrap().contains_key(name)
     }
 }
 
 #[derive(StructOpt)]
+pub enum Command {
+    /// Print the functions and environment variables exposed by a module.
+    Inspect(inspect::InspectOptions),
+    /// Print the engine settings resolved from a profile.
+    InspectConfig(profile::InspectConfigOptions),
+    /// Compile a module ahead of time for use with `--precompiled`.
+    Precompile(precompile::PrecompileOptions),
+    /// Create a new application.
+    New(scaffold::NewOptions),
+    /// Invoke a function once without listening for connections.
+    Invoke(invoke::InvokeOptions),
+    /// Print the routes served by a module.
+    Routes(routes::RoutesOptions),
+    /// Bundle a module and its configuration and assets into a deployable package.
+    Package(package::PackageOptions),
+    /// Measure the performance of a function by invoking it repeatedly.
+    Bench(bench::BenchOptions),
+    /// Replay a request captured with `--capture-failures` against a module.
+    Replay(replay::ReplayOptions),
+    /// Generate a Rust client crate for the functions of a module.
+    GenerateClient(client::GenerateClientOptions),
+}
+
+#[derive(StructOpt)]
+#[structopt(setting = structopt::clap::AppSettings::SubcommandsNegateReqs)]
 pub struct Options {
     /// The path to the WebAssembly module to run.
-    pub module: String,
+    #[structopt(required_unless_one = &["mounts", "package"])]
+    pub module: Option<String>,
+
+    /// Serve a WebAssembly module at the given path prefix.
+    ///
+    /// Repeat to serve multiple modules from one process.
+    #[structopt(long = "mount", number_of_values = 1, value_name = "PREFIX=PATH", parse(try_from_str = parse_mount), conflicts_with_all = &["module", "signature", "precompiled"])]
+    pub mounts: Vec<(String, PathBuf)>,
+
+    /// Serve another version of the module as a canary, routing a share of the requests to it.
+    ///
+    /// The canary is reloaded along with the module. Its routes are not listed, and its metrics are not exported.
+    #[structopt(long, value_name = "PATH", conflicts_with_all = &["mounts", "package", "precompiled", "trusted-key"])]
+    pub canary: Option<PathBuf>,
+
+    /// The percentage of requests routed to the canary [default: 10].
+    #[structopt(long, value_name = "PERCENT", requires = "canary")]
+    pub canary_percent: Option<u8>,
+
+    /// Route requests with the given header to the canary regardless of `--canary-percent`.
+    #[structopt(long, value_name = "NAME=VALUE", parse(try_from_str = parse_canary_header), requires = "canary")]
+    pub canary_header: Option<(String, String)>,
+
+    /// Roll back to the module once this fraction of the canary's responses are server errors, e.g. `0.05`.
+    ///
+    /// After a rollback, every request is routed to the module until the application is reloaded.
+    #[structopt(long, value_name = "RATE", requires = "canary")]
+    pub canary_max_error_rate: Option<f64>,
+
+    /// The number of requests the canary must serve before its error rate is checked [default: 100].
+    #[structopt(long, value_name = "COUNT", requires = "canary-max-error-rate")]
+    pub canary_min_requests: Option<u64>,
+
+    /// Mirror requests to another version of the module, discarding its responses.
+    ///
+    /// The status code of each mirrored response is compared with the module's, and differences are logged.
+    /// The functions of the mirrored module run with the same bindings, so their side effects happen twice.
+    #[structopt(long, value_name = "PATH", conflicts_with_all = &["mounts", "package", "precompiled", "trusted-key", "canary", "mirror-url"])]
+    pub mirror: Option<PathBuf>,
+
+    /// Mirror requests to an HTTP server, discarding its responses.
+    ///
+    /// The path and query of each request are appended to the URL.
+    /// Credential headers (`Authorization`, `Proxy-Authorization`, `Cookie`, `X-Api-Key`, and `X-Auth-Token`)
+    /// are removed unless `--mirror-forward-credentials` is given.
+    #[structopt(long, value_name = "URL", conflicts_with_all = &["mounts", "canary"])]
+    pub mirror_url: Option<http_types::Url>,
+
+    /// Forward credential headers to the `--mirror-url` server.
+    ///
+    /// Only use this when the server is trusted with the credentials of the application's users.
+    #[structopt(long, requires = "mirror-url")]
+    pub mirror_forward_credentials: bool,
+
+    /// The percentage of requests mirrored [default: 100].
+    #[structopt(long, value_name = "PERCENT")]
+    pub mirror_percent: Option<u8>,
 
     /// The listen address for the application.
-    #[structopt(long, default_value = "127.0.0.1:0")]
-    pub addr: SocketAddr,
+    ///
+    /// Defaults to a random port on localhost, or on all interfaces when running in a container.
+    #[structopt(long)]
+    pub addr: Option<SocketAddr>,
+
+    /// Listen on the address given by the `HOST` and `PORT` environment variables, if set.
+    #[structopt(long, conflicts_with = "addr")]
+    pub bind_env: bool,
+
+    /// The time in seconds to wait for requests in progress to complete when shutting down.
+    #[structopt(long, default_value = "30", value_name = "SECS")]
+    pub shutdown_timeout: u64,
+
+    /// Reload the application after it has received the given number of requests.
+    ///
+    /// The listening socket is kept open while reloading, so connections received in the meantime are queued.
+    /// On Unix, the application is also reloaded on `SIGHUP`, which reads environment files and secrets again.
+    #[structopt(long, value_name = "COUNT")]
+    pub max_requests_per_instance: Option<u64>,
+
+    /// Reload the application after it has been serving for the given time in seconds.
+    #[structopt(long, value_name = "SECS")]
+    pub max_uptime: Option<u64>,
+
+    /// The preset of engine settings to use: `dev`, `balanced`, or `release`.
+    ///
+    /// Use the `inspect-config` command to print the resolved engine settings.
+    #[structopt(long, possible_values = &["dev", "balanced", "release"])]
+    pub profile: Option<Profile>,
 
     /// Enable debug information for the application.
     #[structopt(short = "g", long)]
@@ -51,47 +503,1301 @@ pub struct Options {
     /// Override an application environment variable value.
     #[structopt(long = "env", short, number_of_values = 1, value_name = "NAME=VAL", parse(try_from_str = parse_env_var))]
     pub environment: Vec<(String, String)>,
+
+    /// Load application environment variables from a dotenv file.
+    ///
+    /// Variables in later files override those in earlier files; `--env` overrides both.
+    #[structopt(long, number_of_values = 1, value_name = "PATH")]
+    pub env_file: Vec<PathBuf>,
+
+    #[structopt(flatten)]
+    pub secrets: secrets::SecretsOptions,
+
+    /// Let functions read an environment variable through `config::all`.
+    ///
+    /// Variables are hidden from the configuration unless named here; variables from secrets sources are always hidden.
+    #[structopt(long = "config-var", number_of_values = 1, value_name = "NAME")]
+    pub config_vars: Vec<String>,
+
+    /// Fail rather than prompt for environment variables that are not set.
+    ///
+    /// This is the default when standard input is not a terminal.
+    #[structopt(long)]
+    pub non_interactive: bool,
+
+    /// Resolve environment variables when a function first needs them rather than at startup.
+    ///
+    /// Only the variables available to the invoked function are resolved.
+    #[structopt(long)]
+    pub lazy_env: bool,
+
+    /// The number of seconds a lazily resolved environment variable is cached before it is resolved again.
+    ///
+    /// By default, resolved values are cached until the application is reloaded.
+    #[structopt(long, value_name = "SECONDS", requires = "lazy-env")]
+    pub env_ttl: Option<u64>,
+
+    /// The maximum time in seconds a function may execute before a `504 Gateway Timeout` response is returned.
+    #[structopt(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+
+    /// Log every call functions make to the host, with a summary of its arguments, result, and duration.
+    ///
+    /// With an admin token, individual requests can instead be traced by sending the token in an `X-Debug` header.
+    #[structopt(long)]
+    pub trace_host_calls: bool,
+
+    /// Trace the host calls of the given ratio of requests, from 0.0 (none) to 1.0 (all).
+    ///
+    /// Routes declaring a `trace_sample` ratio use their own ratio instead.
+    #[structopt(long, value_name = "RATIO", parse(try_from_str = parse_sample_ratio))]
+    pub trace_sample_ratio: Option<f64>,
+
+    /// Trace the given ratio of requests to a function, overriding the ratio declared by its route.
+    #[structopt(long = "trace-sample", number_of_values = 1, value_name = "FUNCTION=RATIO", parse(try_from_str = parse_trace_sample))]
+    pub trace_samples: Vec<(String, f64)>,
+
+    /// Log the traced host calls of requests that fail even if they were not sampled.
+    #[structopt(long)]
+    pub trace_errors: bool,
+
+    /// Render the host's error responses (e.g. 404, 405, 413, and 504) as RFC 7807 `application/problem+json`.
+    #[structopt(long)]
+    pub problem_errors: bool,
+
+    /// The casing of the request header names listed to functions.
+    ///
+    /// Header names are received case-insensitively, so `title` (e.g. `Content-Type`) is a normalized form rather than the client's original casing.
+    #[structopt(long, default_value = "lower", possible_values = &["lower", "title"], parse(try_from_str = parse_header_case))]
+    pub header_case: HeaderCase,
+
+    /// How the values of request headers that are repeated are presented to functions.
+    ///
+    /// `separate` lists each value and looks up the first; `combine` joins them with commas (semicolons for `Cookie`).
+    #[structopt(long, default_value = "separate", possible_values = &["separate", "combine"], parse(try_from_str = parse_duplicate_headers))]
+    pub duplicate_headers: DuplicateHeaders,
+
+    /// The maximum time in seconds the tasks a function spawns to run after its response may execute.
+    #[structopt(long, value_name = "SECS")]
+    pub task_timeout: Option<u64>,
+
+    /// The mechanism used to interrupt functions that exceed their timeout.
+    ///
+    /// `fuel` meters execution, which allows limiting the fuel consumed by each instance, and suits multi-tenant hosts.
+    /// `epoch` checks deadlines on a periodic tick and interrupts functions through Wasmtime's interrupt handle, as Wasmtime 0.30 has no epochs;
+    /// execution is not metered, so fuel limits are unavailable.
+    /// A precompiled module must be precompiled with the same mechanism.
+    #[structopt(long, default_value = "fuel", possible_values = &["fuel", "epoch"], parse(try_from_str = parse_interruption))]
+    pub interruption: Interruption,
+
+    /// The maximum fuel an instance may consume before its function traps.
+    #[structopt(long, value_name = "FUEL")]
+    pub fuel_limit: Option<u64>,
+
+    /// Cap the fuel of every invocation of the given function artificially low, to test how the application degrades when it is metered out.
+    ///
+    /// Invocations that exhaust the limit respond with `500 Internal Server Error` and are logged and counted as fuel exhaustion.
+    /// Requires `--interruption fuel`.
+    #[structopt(long = "inject-fuel-limit", number_of_values = 1, value_name = "FUNCTION=FUEL", parse(try_from_str = parse_injected_fuel))]
+    pub injected_fuel: Vec<(String, u64)>,
+
+    /// The interval in milliseconds at which function deadlines are checked when using epoch interruption.
+    #[structopt(long, value_name = "MS")]
+    pub epoch_tick: Option<u64>,
+
+    /// The maximum size in bytes of a request body.
+    #[structopt(long, value_name = "BYTES")]
+    pub max_body_size: Option<u64>,
+
+    /// The maximum number of concurrent function invocations of each module.
+    #[structopt(long, value_name = "COUNT")]
+    pub max_concurrency: Option<usize>,
+
+    /// Make a host directory available to functions at the given guest path.
+    ///
+    /// Append `:ro` to the host path to make the directory read-only.
+    #[structopt(long = "dir", number_of_values = 1, value_name = "GUEST_PATH=HOST_PATH[:ro]", parse(try_from_str = parse_dir))]
+    pub dirs: Vec<(PathBuf, PathBuf, bool)>,
+
+    /// Allow functions to send outbound HTTP requests to the given host.
+    ///
+    /// A host of the form `*.DOMAIN` allows every subdomain; without a port, only the scheme's default port is allowed.
+    /// Prefix the host with `PREFIX=` to allow it only for the module mounted at the prefix.
+    /// By default, functions may not send outbound requests.
+    #[structopt(long = "allow-host", number_of_values = 1, value_name = "[PREFIX=]HOST[:PORT]", parse(try_from_str = parse_allow_host))]
+    pub allowed_hosts: Vec<(Option<String>, AllowedHost)>,
+
+    /// Allow functions to make gRPC calls to the given service, sending them to the server at the given URI.
+    ///
+    /// The service is the fully-qualified name of a protobuf service, such as `orders.v1.Orders`.
+    /// By default, functions may not make gRPC calls.
+    #[structopt(long = "grpc-service", number_of_values = 1, value_name = "SERVICE=URI", parse(try_from_str = parse_grpc_service))]
+    pub grpc_services: Vec<(String, String)>,
+
+    /// Resolve the given service to an endpoint when functions look it up with service discovery.
+    ///
+    /// Repeat the option to give a service several endpoints. Static endpoints take precedence over
+    /// `--discovery-dns` and `--discovery-kubernetes`.
+    #[structopt(long = "service-endpoint", number_of_values = 1, value_name = "SERVICE=HOST:PORT", parse(try_from_str = parse_service_endpoint))]
+    pub service_endpoints: Vec<(String, ServiceEndpoint)>,
+
+    /// Resolve services looked up by functions with the DNS SRV record `_SERVICE._tcp.DOMAIN`.
+    #[structopt(long, value_name = "DOMAIN", conflicts_with = "discovery-kubernetes")]
+    pub discovery_dns: Option<String>,
+
+    /// Resolve services looked up by functions to the Kubernetes services of the same name in the given namespace.
+    ///
+    /// Endpoints are read from the SRV records of the service's named port, which defaults to `http`.
+    #[structopt(long, value_name = "NAMESPACE[:PORT_NAME]", parse(try_from_str = parse_kubernetes_discovery))]
+    pub discovery_kubernetes: Option<(String, String)>,
+
+    /// Load the Handlebars templates functions render responses with from the given directory.
+    ///
+    /// Every `.hbs` file in the directory is loaded at startup; with `--watch`, changes to the templates reload the application.
+    #[structopt(long, value_name = "DIR")]
+    pub templates: Option<PathBuf>,
+
+    /// Load the translation bundles functions translate messages with from the given directory.
+    ///
+    /// Each `.json` file in the directory is the bundle of the locale it is named for, such as `fr-CA.json`; with `--watch`, changes to the bundles reload the application.
+    #[structopt(long, value_name = "DIR")]
+    pub translations: Option<PathBuf>,
+
+    /// The locale used when a request's languages match no translation bundle [default: en].
+    #[structopt(long, value_name = "LOCALE", requires = "translations")]
+    pub default_locale: Option<String>,
+
+    /// The key functions sign temporary URLs with, which is required by routes declared with the `signed` option.
+    ///
+    /// Changing the key invalidates every URL signed with the previous key.
+    #[structopt(
+        long,
+        value_name = "KEY",
+        env = "WASMTIME_FUNCTIONS_URL_SIGNING_KEY",
+        hide_env_values = true
+    )]
+    pub url_signing_key: Option<String>,
+
+    /// The Content-Security-Policy header of responses from functions.
+    ///
+    /// The nonce of a request is appended to the policy's `script-src` and `style-src` directives when its function uses it; responses for which the function set its own policy are left unchanged.
+    #[structopt(long = "csp", value_name = "POLICY")]
+    pub content_security_policy: Option<String>,
+
+    /// Store the request parts functions upload to the given bucket as files in the given directory.
+    ///
+    /// By default, functions may not upload request parts.
+    #[structopt(long = "blob-bucket", number_of_values = 1, value_name = "NAME=DIR", parse(try_from_str = parse_blob_bucket))]
+    pub blob_buckets: Vec<(String, PathBuf)>,
+
+    /// Replay the responses of requests retried with the same `Idempotency-Key` header for the given number of seconds.
+    ///
+    /// Responses are stored in the app cache, so they are lost when the application is reloaded.
+    /// Keys are scoped to the client identified by `--idempotency-scope-header`, or else by the
+    /// client's IP address.
+    #[structopt(long, value_name = "SECONDS")]
+    pub idempotency_ttl: Option<u64>,
+
+    /// The request header that identifies the client an idempotency key belongs to.
+    ///
+    /// Defaults to `--audit-principal-header` if set, or else `Authorization`.
+    #[structopt(long, value_name = "HEADER", requires = "idempotency-ttl")]
+    pub idempotency_scope_header: Option<String>,
+
+    /// The number of requests that may be made per quota window by clients that are not a `--quota-tenant`, combined.
+    ///
+    /// Tenants are identified by the `--quota-header` of each request; requests over the quota are rejected with `429 Too Many Requests`.
+    #[structopt(long, value_name = "COUNT")]
+    pub quota_requests: Option<u64>,
+
+    /// The fuel that may be consumed per quota window by the invocations of clients that are not a `--quota-tenant`, combined.
+    ///
+    /// Requires `--interruption fuel`.
+    #[structopt(long, value_name = "FUEL")]
+    pub quota_fuel: Option<u64>,
+
+    /// Give the tenant with the given key its own quota limits.
+    ///
+    /// Tenants are listed by the admin API by a hash of their key.
+    #[structopt(long = "quota-tenant", number_of_values = 1, value_name = "KEY=REQUESTS[,FUEL]", parse(try_from_str = parse_quota_tenant))]
+    pub quota_tenants: Vec<(String, QuotaLimits)>,
+
+    /// The number of seconds in a quota window [default: 60].
+    #[structopt(long, value_name = "SECONDS")]
+    pub quota_window: Option<u64>,
+
+    /// The request header identifying the tenant of a request [default: X-Api-Key].
+    #[structopt(long, value_name = "NAME")]
+    pub quota_header: Option<String>,
+
+    /// The maximum number of attempts for outbound calls that fail and are safe to repeat.
+    ///
+    /// Outbound HTTP requests with an idempotent method, SQL queries marked idempotent by the function, and gRPC calls to an unavailable server are retried with an exponential backoff.
+    #[structopt(long, value_name = "ATTEMPTS")]
+    pub retry_attempts: Option<u32>,
+
+    /// Reject outbound calls to a destination after the given number of consecutive failures.
+    ///
+    /// Calls are rejected until `--circuit-open-secs` elapses, after which a single call is attempted to check if the destination has recovered.
+    #[structopt(long, value_name = "FAILURES")]
+    pub circuit_breaker: Option<u32>,
+
+    /// The number of seconds calls to a failing destination are rejected for [default: 30].
+    #[structopt(long, value_name = "SECONDS", requires = "circuit-breaker")]
+    pub circuit_open_secs: Option<u64>,
+
+    /// Resolve environment variables and instantiate every module before accepting connections.
+    ///
+    /// Startup fails if a module can't be instantiated rather than failing the first requests.
+    #[structopt(long)]
+    pub warmup: bool,
+
+    /// Capture the requests of invocations that trap, time out, or return no response to the given directory.
+    ///
+    /// Use the `replay` command to reproduce a captured request. Requests to a mounted module are captured
+    /// to a subdirectory named after its mount prefix.
+    #[structopt(long, value_name = "DIR")]
+    pub capture_failures: Option<PathBuf>,
+
+    /// Collect coverage data written by instrumented modules to the given directory.
+    ///
+    /// After each invocation, a module's `__wasmtime_functions_dump_coverage` export is called so that it
+    /// can write LLVM profile data to `/coverage`. Mounted modules use a subdirectory named after their mount prefix.
+    #[structopt(long, value_name = "DIR")]
+    pub coverage_dir: Option<PathBuf>,
+
+    /// Append an audit record for every function invocation to the given file.
+    #[structopt(long, value_name = "PATH")]
+    pub audit_log: Option<PathBuf>,
+
+    /// Integrate with systemd.
+    ///
+    /// Connections are accepted on a socket passed by socket activation, if any; readiness is
+    /// notified once the application is serving, and watchdog notifications are sent when enabled.
+    #[cfg(unix)]
+    #[structopt(long)]
+    pub systemd: bool,
+
+    /// Send an audit record for every function invocation to the local syslog daemon.
+    #[cfg(unix)]
+    #[structopt(long, conflicts_with = "audit-log")]
+    pub audit_syslog: bool,
+
+    /// The request header that identifies the authenticated principal in audit records.
+    #[structopt(long, value_name = "HEADER")]
+    pub audit_principal_header: Option<String>,
+
+    /// Require the module to be signed by the given hex-encoded Ed25519 public key.
+    #[structopt(long, number_of_values = 1, value_name = "KEY", parse(try_from_str = parse_hex))]
+    pub trusted_key: Vec<Vec<u8>>,
+
+    /// The path to a detached signature of the module.
+    #[structopt(long, value_name = "PATH", requires = "trusted-key")]
+    pub signature: Option<PathBuf>,
+
+    /// Allow modules built with an SDK older than the host interface supported by this host.
+    ///
+    /// Such modules may still fail to link if they import functions the host no longer provides.
+    #[structopt(long)]
+    pub allow_older_sdk: bool,
+
+    /// Reload the application when the module changes.
+    #[structopt(long)]
+    pub watch: bool,
+
+    /// Build the application with `cargo build --target wasm32-wasi` before running it.
+    ///
+    /// When watching, the application is rebuilt when its sources change.
+    #[structopt(long)]
+    pub build: bool,
+
+    /// The path to the precompiled code of the module.
+    ///
+    /// Only use precompiled code produced by the `precompile` command from a trusted module.
+    /// Precompiled code cannot be verified, so it cannot be used with `--trusted-key`.
+    #[structopt(long, value_name = "PATH", conflicts_with_all = &["watch", "trusted-key"])]
+    pub precompiled: Option<PathBuf>,
+
+    /// Serve a package created by the `package` command.
+    ///
+    /// Static assets in the package are available to functions read-only at `/assets`.
+    /// Only serve packages from trusted sources, as precompiled code in a package is loaded as-is.
+    #[structopt(long, value_name = "PATH", conflicts_with_all = &["module", "mounts", "precompiled", "watch"])]
+    pub package: Option<PathBuf>,
+
+    /// Validate the application without listening for requests.
+    ///
+    /// The module is compiled, its routes and imports are checked, and its environment variables are resolved.
+    #[structopt(long, conflicts_with = "watch")]
+    pub dry_run: bool,
+
+    /// The listen address for Prometheus metrics, served at `/metrics`.
+    #[structopt(long, value_name = "ADDR")]
+    pub metrics_addr: Option<SocketAddr>,
+
+    /// The listen address for the admin API.
+    #[structopt(long, value_name = "ADDR")]
+    pub admin_addr: Option<SocketAddr>,
+
+    /// The bearer token required by requests to the admin API.
+    #[structopt(
+        long,
+        value_name = "TOKEN",
+        env = "WASMTIME_FUNCTIONS_ADMIN_TOKEN",
+        hide_env_values = true
+    )]
+    pub admin_token: Option<String>,
+
+    /// The format of log output: `text` or `json`.
+    #[structopt(long, default_value = "text", value_name = "FORMAT")]
+    pub log_format: LogFormat,
+
+    /// The maximum level of log output: `off`, `error`, `warn`, `info`, `debug`, or `trace`.
+    #[structopt(long, default_value = "info", value_name = "LEVEL")]
+    pub log_level: log::LevelFilter,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
 }
 
-async fn run(options: Options) -> Result<()> {
-    let addr = options.addr;
-    let module_path = PathBuf::from(options.module);
+impl Options {
+    /// Gets the modules to serve and the path prefixes they are mounted at.
+    ///
+    /// A module given without `--mount` is served at the root.
+    fn modules(&self) -> Vec<(String, PathBuf)> {
+        match (&self.module, &self.package) {
+            (Some(module), _) => vec![(String::new(), PathBuf::from(module))],
+            (None, Some(package)) => vec![(String::new(), package.clone())],
+            (None, None) => self.mounts.clone(),
+        }
+    }
+}
+
+struct Modules {
+    modules: Vec<(String, Vec<u8>)>,
+    canary: Option<Vec<u8>>,
+    mirror: Option<Vec<u8>>,
+    signature: Option<Vec<u8>>,
+    precompiled: Option<Vec<u8>>,
+    assets: Option<TempDir>,
+}
+
+fn read_modules(options: &Options) -> Result<Modules> {
+    let signature = options.signature.as_ref().map(std::fs::read).transpose()?;
+
+    if let Some(path) = &options.package {
+        let package = package::read(path)?;
+
+        if package.precompiled.is_some() && !options.trusted_key.is_empty() {
+            bail!(
+                "package '{}' contains precompiled code, which cannot be verified with `--trusted-key`; package the module without `--precompile`.",
+                path.display()
+            );
+        }
 
-    if !module_path.is_file() {
-        bail!("module '{}' does not exist.", module_path.display());
+        return Ok(Modules {
+            modules: vec![(String::new(), package.module)],
+            canary: None,
+            mirror: None,
+            signature,
+            precompiled: package.precompiled,
+            assets: package.assets,
+        });
     }
 
-    let module = std::fs::read(&module_path)?;
+    let mut modules = Vec::new();
 
-    let environment = EnvironmentProvider(options.environment);
+    for (prefix, path) in options.modules() {
+        if !path.is_file() {
+            bail!("module '{}' does not exist.", path.display());
+        }
 
-    let mut server = Server::new(addr, &module, &environment, options.debug_info, true).await?;
+        modules.push((prefix, std::fs::read(&path)?));
+    }
+
+    let canary = match &options.canary {
+        Some(path) if !path.is_file() => {
+            bail!("canary module '{}' does not exist.", path.display())
+        }
+        Some(path) => Some(std::fs::read(path)?),
+        None => None,
+    };
+
+    let mirror = match &options.mirror {
+        Some(path) if !path.is_file() => {
+            bail!("mirrored module '{}' does not exist.", path.display())
+        }
+        Some(path) => Some(std::fs::read(path)?),
+        None => None,
+    };
+
+    let precompiled = options
+        .precompiled
+        .as_ref()
+        .map(std::fs::read)
+        .transpose()?;
+
+    Ok(Modules {
+        modules,
+        canary,
+        mirror,
+        signature,
+        precompiled,
+        assets: None,
+    })
+}
+
+struct Builders<'a> {
+    mounts: Vec<(String, ServerBuilder<'a>)>,
+    canary: Option<ServerBuilder<'a>>,
+    mirror: Option<ServerBuilder<'a>>,
+}
+
+fn configure<'a>(
+    options: &Options,
+    modules: &'a Modules,
+    environment: Arc<EnvironmentProvider>,
+) -> Result<Builders<'a>> {
+    let mut audit_sink: Option<Arc<dyn AuditSink>> = None;
+
+    if let Some(path) = &options.audit_log {
+        audit_sink = Some(Arc::new(FileAuditSink::new(path)?));
+    }
+
+    #[cfg(unix)]
+    if options.audit_syslog {
+        audit_sink = Some(Arc::new(SyslogAuditSink::new()?));
+    }
+
+    for prefix in options.allowed_hosts.iter().filter_map(|(p, _)| p.as_ref()) {
+        if !modules.modules.iter().any(|(p, _)| p == prefix) {
+            bail!("no module is mounted at '{}'", prefix);
+        }
+    }
+
+    let mut builders = Vec::new();
+
+    for (prefix, module) in &modules.modules {
+        environment.check(module)?;
+
+        let mut builder = configure_module(
+            options,
+            module,
+            modules.signature.as_deref(),
+            modules.precompiled.as_deref(),
+            audit_sink.clone(),
+            environment.clone(),
+        );
+
+        if let Some(assets) = &modules.assets {
+            builder = builder.preopened_dir(assets.path(), "/assets", true);
+        }
+
+        if let Some(dir) = &options.capture_failures {
+            builder = builder.capture_failures(dir.join(prefix.trim_start_matches('/')));
+        }
+
+        if let Some(dir) = &options.coverage_dir {
+            builder = builder.coverage_dir(dir.join(prefix.trim_start_matches('/')));
+        }
+
+        for (_, host) in options
+            .allowed_hosts
+            .iter()
+            .filter(|(p, _)| p.is_none() || p.as_ref() == Some(prefix))
+        {
+            builder = builder.allow_host(host.clone());
+        }
+
+        builders.push((prefix.clone(), builder));
+    }
+
+    let canary = modules
+        .canary
+        .as_deref()
+        .map(|module| {
+            configure_version(
+                options,
+                module,
+                "canary",
+                audit_sink.clone(),
+                environment.clone(),
+            )
+        })
+        .transpose()?;
+
+    let mirror = modules
+        .mirror
+        .as_deref()
+        .map(|module| configure_version(options, module, "mirror", audit_sink, environment))
+        .transpose()?;
+
+    Ok(Builders {
+        mounts: builders,
+        canary,
+        mirror,
+    })
+}
+
+/// Configures another version of the module, served as a canary or mirrored to.
+fn configure_version<'a>(
+    options: &Options,
+    module: &'a [u8],
+    name: &str,
+    audit_sink: Option<Arc<dyn AuditSink>>,
+    environment: Arc<EnvironmentProvider>,
+) -> Result<ServerBuilder<'a>> {
+    environment.check(module)?;
+
+    let mut builder = configure_module(options, module, None, None, audit_sink, environment);
+
+    if let Some(dir) = &options.capture_failures {
+        builder = builder.capture_failures(dir.join(name));
+    }
+
+    if let Some(dir) = &options.coverage_dir {
+        builder = builder.coverage_dir(dir.join(name));
+    }
+
+    for (_, host) in options.allowed_hosts.iter().filter(|(p, _)| p.is_none()) {
+        builder = builder.allow_host(host.clone());
+    }
+
+    Ok(builder)
+}
+
+/// Gets the provider that resolves the services looked up by functions.
+fn discovery_provider(options: &Options) -> Option<Arc<dyn DiscoveryProvider>> {
+    let dns: Option<Arc<dyn DiscoveryProvider>> =
+        match (&options.discovery_dns, &options.discovery_kubernetes) {
+            (Some(domain), _) => Some(Arc::new(DnsDiscovery::new(domain.clone()))),
+            (_, Some((namespace, port))) => Some(Arc::new(DnsDiscovery::kubernetes(
+                namespace.clone(),
+                port.clone(),
+            ))),
+            _ => None,
+        };
+
+    if options.service_endpoints.is_empty() {
+        return dns;
+    }
+
+    let mut provider = StaticDiscovery::new();
+    for (service, endpoint) in &options.service_endpoints {
+        provider = provider.endpoint(service.clone(), endpoint.clone());
+    }
+
+    if let Some(dns) = dns {
+        provider = provider.fallback(dns);
+    }
+
+    Some(Arc::new(provider))
+}
+
+/// Gets the policy for the quotas of tenants, if any quota is set.
+fn quota_policy(options: &Options) -> Option<QuotaPolicy> {
+    if options.quota_requests.is_none()
+        && options.quota_fuel.is_none()
+        && options.quota_tenants.is_empty()
+    {
+        return None;
+    }
+
+    let mut policy = QuotaPolicy::new(
+        Duration::from_secs(options.quota_window.unwrap_or(DEFAULT_QUOTA_WINDOW_SECS)),
+        QuotaLimits::new(options.quota_requests, options.quota_fuel),
+    );
+
+    if let Some(header) = &options.quota_header {
+        policy = policy.header(header.clone());
+    }
+
+    for (key, limits) in &options.quota_tenants {
+        policy = policy.tenant(key.clone(), *limits);
+    }
+
+    Some(policy)
+}
+
+/// Gets the policy for routing requests to the canary.
+fn canary_policy(options: &Options) -> CanaryPolicy {
+    let mut policy = CanaryPolicy::new(options.canary_percent.unwrap_or(DEFAULT_CANARY_PERCENT));
+
+    if let Some((name, value)) = &options.canary_header {
+        policy = policy.header(name.clone(), value.clone());
+    }
+
+    if let Some(rate) = options.canary_max_error_rate {
+        policy = policy.rollback(
+            rate,
+            options
+                .canary_min_requests
+                .unwrap_or(DEFAULT_CANARY_MIN_REQUESTS),
+        );
+    }
+
+    policy
+}
+
+fn configure_module<'a>(
+    options: &Options,
+    module: &'a [u8],
+    signature: Option<&'a [u8]>,
+    precompiled: Option<&'a [u8]>,
+    audit_sink: Option<Arc<dyn AuditSink>>,
+    environment: Arc<EnvironmentProvider>,
+) -> ServerBuilder<'a> {
+    let mut builder =
+        EngineSettings::resolve(options.profile, options.debug_info, options.interruption)
+            .apply(Server::builder(module, environment))
+            .log_stdout(true);
+
+    if options.lazy_env {
+        builder = builder.lazy_environment(options.env_ttl.map(Duration::from_secs));
+    }
+
+    builder = builder.warmup(options.warmup);
+
+    if let Some(timeout) = options.timeout {
+        builder = builder.timeout(Duration::from_secs(timeout));
+    }
+
+    builder = builder.trace_host_calls(options.trace_host_calls);
+
+    if options.trace_sample_ratio.is_some()
+        || !options.trace_samples.is_empty()
+        || options.trace_errors
+    {
+        let sampling = options.trace_samples.iter().fold(
+            TraceSampling::new(options.trace_sample_ratio.unwrap_or(0.0))
+                .errors(options.trace_errors),
+            |sampling, (function, ratio)| sampling.function(function.as_str(), *ratio),
+        );
+        builder = builder.trace_sampling(sampling);
+    }
+
+    if let Some(token) = &options.admin_token {
+        builder = builder.debug_token(token.clone());
+    }
+
+    if options.problem_errors {
+        builder = builder.error_renderer(Arc::new(ProblemErrorRenderer));
+    }
+
+    builder = builder
+        .header_case(options.header_case)
+        .duplicate_headers(options.duplicate_headers);
+
+    if let Some(timeout) = options.task_timeout {
+        builder = builder.task_timeout(Duration::from_secs(timeout));
+    }
+
+    if let Some(fuel) = options.fuel_limit {
+        builder = builder.fuel_limit(fuel);
+    }
+
+    for (function, fuel) in &options.injected_fuel {
+        builder = builder.inject_fuel_limit(function.as_str(), *fuel);
+    }
+
+    if let Some(tick) = options.epoch_tick {
+        builder = builder.epoch_tick(Duration::from_millis(tick));
+    }
+
+    if let Some(size) = options.max_body_size {
+        builder = builder.max_body_size(size);
+    }
+
+    if let Some(limit) = options.max_concurrency {
+        builder = builder.max_concurrency(limit);
+    }
+
+    for (guest_path, host_path, read_only) in &options.dirs {
+        builder = builder.preopened_dir(host_path, guest_path, *read_only);
+    }
+
+    for (service, target) in &options.grpc_services {
+        builder = builder.grpc_service(service.clone(), target.clone());
+    }
+
+    if let Some(provider) = discovery_provider(options) {
+        builder = builder.discovery_provider(provider);
+    }
+
+    if let Some(dir) = &options.templates {
+        builder = builder.templates_dir(dir);
+    }
+
+    if let Some(dir) = &options.translations {
+        builder = builder.translations_dir(dir);
+    }
+
+    if let Some(locale) = &options.default_locale {
+        builder = builder.default_locale(locale);
+    }
+
+    if let Some(key) = &options.url_signing_key {
+        builder = builder.url_signing_key(key.as_bytes());
+    }
+
+    if let Some(policy) = &options.content_security_policy {
+        builder = builder.content_security_policy(policy);
+    }
+
+    if !options.blob_buckets.is_empty() {
+        let mut provider = DirectoryBlobProvider::new();
+        for (name, dir) in &options.blob_buckets {
+            provider = provider.bucket(name.clone(), dir.clone());
+        }
+
+        builder = builder.blob_provider(Arc::new(provider));
+    }
+
+    if let Some(ttl) = options.idempotency_ttl {
+        builder = builder.idempotency(Duration::from_secs(ttl));
+    }
+
+    if let Some(header) = &options.idempotency_scope_header {
+        builder = builder.idempotency_scope_header(header.clone());
+    }
+
+    if let Some(policy) = quota_policy(options) {
+        builder = builder.quota(policy);
+    }
+
+    if let Some(attempts) = options.retry_attempts {
+        builder = builder.retry_policy(RetryPolicy::new(attempts));
+    }
+
+    if let Some(failures) = options.circuit_breaker {
+        builder = builder.circuit_breaker(CircuitBreakerPolicy::new(
+            failures,
+            Duration::from_secs(
+                options
+                    .circuit_open_secs
+                    .unwrap_or(DEFAULT_CIRCUIT_OPEN_SECS),
+            ),
+        ));
+    }
+
+    if let Some(sink) = audit_sink {
+        builder = builder.audit_sink(sink);
+    }
+
+    if let Some(header) = &options.audit_principal_header {
+        builder = builder.audit_principal_header(header.clone());
+    }
+
+    builder = builder.allow_older_sdk(options.allow_older_sdk);
+
+    for key in &options.trusted_key {
+        builder = builder.trusted_key(key.clone());
+    }
+
+    if let Some(signature) = signature {
+        builder = builder.module_signature(signature);
+    }
+
+    if let Some(code) = precompiled {
+        // Safety: the user is trusted to provide code precompiled by the `precompile` command
+        builder = unsafe { builder.precompiled(code) };
+    }
+
+    builder
+}
+
+/// The addresses of an application's operational listeners.
+#[derive(Clone, Copy)]
+struct Addresses {
+    metrics: Option<SocketAddr>,
+    admin: Option<SocketAddr>,
+}
+
+/// A running application and its operational listeners.
+struct Application {
+    server: Server,
+    metrics: Option<AdminServer>,
+    admin: Option<AdminServer>,
+    // Keeps the package's extracted assets alive while they are being served
+    _assets: Option<TempDir>,
+}
+
+impl Application {
+    /// Gets the addresses the application is bound to.
+    fn addresses(&self) -> Addresses {
+        Addresses {
+            metrics: self.metrics.as_ref().map(AdminServer::local_addr),
+            admin: self.admin.as_ref().map(AdminServer::local_addr),
+        }
+    }
+
+    async fn accept(&mut self) -> Result<()> {
+        let mut listeners: Vec<LocalBoxFuture<'_, Result<(), ServerError>>> =
+            vec![Box::pin(self.server.accept())];
+
+        if let Some(metrics) = &mut self.metrics {
+            listeners.push(Box::pin(metrics.accept()));
+        }
+
+        if let Some(admin) = &mut self.admin {
+            listeners.push(Box::pin(admin.accept()));
+        }
+
+        try_join_all(listeners).await?;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn notify_systemd(options: &Options, state: &str) {
+    if options.systemd {
+        if let Err(e) = systemd::notify(state) {
+            log::warn!("{:?}", e);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn notify_systemd(_: &Options, _: &str) {}
+
+async fn start(
+    options: &Options,
+    environment: Arc<EnvironmentProvider>,
+    addrs: Addresses,
+    listener: &std::net::TcpListener,
+) -> Result<Application> {
+    let modules = read_modules(options)?;
+    let mut builders = configure(options, &modules, environment)?;
+
+    let mirror = match (builders.mirror.take(), &options.mirror_url) {
+        (Some(builder), _) => Some(MirrorTarget::Module(builder)),
+        (None, Some(url)) => Some(MirrorTarget::Url(url.clone())),
+        (None, None) => None,
+    };
+
+    // The socket is cloned so that it remains open, and connections queue, while the application reloads
+    let server = if let Some(canary) = builders.canary.take() {
+        let (_, builder) = builders.mounts.remove(0);
+        Server::canary_listener(
+            listener.try_clone()?,
+            builder,
+            canary,
+            canary_policy(options),
+        )
+        .await?
+    } else if let Some(target) = mirror {
+        let (_, builder) = builders.mounts.remove(0);
+        Server::mirror_listener(
+            listener.try_clone()?,
+            builder,
+            target,
+            MirrorPolicy::new(options.mirror_percent.unwrap_or(DEFAULT_MIRROR_PERCENT))
+                .forward_credentials(options.mirror_forward_credentials),
+        )
+        .await?
+    } else if options.mounts.is_empty() {
+        let (_, builder) = builders.mounts.remove(0);
+        builder.listen(listener.try_clone()?).await?
+    } else {
+        Server::mount_listener(listener.try_clone()?, builders.mounts).await?
+    };
+
+    if options.warmup {
+        log::info!("Application warmed up.");
+    }
 
     log::info!("Application listening at {}", server);
 
+    let metrics = match addrs.metrics {
+        Some(addr) => {
+            let metrics = server.bind_metrics(addr).await?;
+            log::info!("Metrics available at {}/metrics", metrics);
+            Some(metrics)
+        }
+        None => None,
+    };
+
+    let admin = match addrs.admin {
+        Some(addr) => {
+            let admin = server.bind_admin(addr, options.admin_token.clone()).await?;
+            log::info!("Admin API listening at {}", admin);
+            Some(admin)
+        }
+        None => None,
+    };
+
+    Ok(Application {
+        server,
+        metrics,
+        admin,
+        _assets: modules.assets,
+    })
+}
+
+/// Waits until the application should be recycled according to `--max-requests-per-instance` and `--max-uptime`.
+async fn recycle(options: &Options, received: Arc<AtomicU64>) {
+    if options.max_requests_per_instance.is_none() && options.max_uptime.is_none() {
+        return futures::future::pending().await;
+    }
+
+    let started = Instant::now();
+
+    loop {
+        async_std::task::sleep(Duration::from_millis(RECYCLE_POLL_INTERVAL_MS)).await;
+
+        if options
+            .max_requests_per_instance
+            .map(|max| received.load(Ordering::Relaxed) >= max)
+            .unwrap_or(false)
+            || options
+                .max_uptime
+                .map(|secs| started.elapsed() >= Duration::from_secs(secs))
+                .unwrap_or(false)
+        {
+            return;
+        }
+    }
+}
+
+/// The reasons the application is reloaded.
+enum Reload {
+    Changed,
+    #[cfg(unix)]
+    Hangup,
+    Recycle,
+}
+
+async fn load_environment(options: &Options) -> Result<Arc<EnvironmentProvider>> {
+    // Overrides are searched in order, so `--env` takes precedence over files and later files over earlier ones
+    let mut overrides = options.environment.clone();
+    for path in options.env_file.iter().rev() {
+        overrides.extend(read_env_file(path)?.into_iter().rev());
+    }
+    let secrets = options.secrets.load().await?;
+    let names = secrets.iter().map(|(name, _)| name.clone()).collect();
+    overrides.extend(secrets);
+
+    let interactive = !options.non_interactive && atty::is(atty::Stream::Stdin);
+    Ok(Arc::new(
+        EnvironmentProvider::new(overrides, interactive)
+            .with_secrets(names)
+            .with_exposed(options.config_vars.iter().cloned().collect()),
+    ))
+}
+
+async fn run(options: Options) -> Result<()> {
+    match options.interruption {
+        Interruption::Fuel if options.epoch_tick.is_some() => {
+            bail!("`--epoch-tick` requires `--interruption epoch`")
+        }
+        Interruption::Epoch if options.fuel_limit.is_some() => {
+            bail!("`--fuel-limit` requires `--interruption fuel`")
+        }
+        Interruption::Epoch if !options.injected_fuel.is_empty() => {
+            bail!("`--inject-fuel-limit` requires `--interruption fuel`")
+        }
+        Interruption::Epoch
+            if options.quota_fuel.is_some()
+                || options.quota_tenants.iter().any(|(_, l)| l.fuel.is_some()) =>
+        {
+            bail!("fuel quotas require `--interruption fuel`")
+        }
+        _ => {}
+    }
+
+    if matches!(options.canary_percent, Some(percent) if percent > 100) {
+        bail!("`--canary-percent` must be at most 100");
+    }
+
+    if matches!(options.canary_max_error_rate, Some(rate) if !(0.0..=1.0).contains(&rate)) {
+        bail!("`--canary-max-error-rate` must be between 0 and 1");
+    }
+
+    if options.mirror_percent.is_some() && options.mirror.is_none() && options.mirror_url.is_none()
+    {
+        bail!("`--mirror-percent` requires `--mirror` or `--mirror-url`");
+    }
+
+    if options.max_concurrency == Some(0) {
+        bail!("`--max-concurrency` must be greater than zero");
+    }
+
+    if matches!(options.mirror_percent, Some(percent) if percent > 100) {
+        bail!("`--mirror-percent` must be at most 100");
+    }
+
+    if matches!(&options.mirror_url, Some(url) if !matches!(url.scheme(), "http" | "https")) {
+        bail!("`--mirror-url` must be an HTTP or HTTPS URL");
+    }
+
+    let mut environment = load_environment(&options).await?;
+
+    if options.build {
+        watch::build().await?;
+    }
+
+    if options.dry_run {
+        let modules = read_modules(&options)?;
+
+        let builders = configure(&options, &modules, environment)?;
+
+        for ((_, builder), (_, path)) in builders.mounts.into_iter().zip(options.modules()) {
+            builder.validate().await?;
+            log::info!("Module '{}' is valid.", path.display());
+        }
+
+        if let (Some(builder), Some(path)) = (builders.canary, &options.canary) {
+            builder.validate().await?;
+            log::info!("Canary module '{}' is valid.", path.display());
+        }
+
+        if let (Some(builder), Some(path)) = (builders.mirror, &options.mirror) {
+            builder.validate().await?;
+            log::info!("Mirrored module '{}' is valid.", path.display());
+        }
+
+        return Ok(());
+    }
+
+    let addrs = Addresses {
+        metrics: options.metrics_addr,
+        admin: options.admin_addr,
+    };
+
+    #[cfg(unix)]
+    let listener = if options.systemd {
+        systemd::listener()?
+    } else {
+        None
+    };
+
+    #[cfg(not(unix))]
+    let listener: Option<std::net::TcpListener> = None;
+
+    // The application's socket is bound once so that it can be reused across reloads
+    let listener = match listener {
+        Some(listener) => listener,
+        None => {
+            let addr = match options.addr {
+                Some(addr) => addr,
+                None => bind::default_addr(options.bind_env)?,
+            };
+
+            std::net::TcpListener::bind(addr)
+                .with_context(|| format!("failed to bind to address '{}'", addr))?
+        }
+    };
+
+    let app = start(&options, environment.clone(), addrs, &listener).await?;
+
+    notify_systemd(&options, "READY=1");
+
+    #[cfg(unix)]
+    if options.systemd {
+        if let Some(interval) = systemd::watchdog_interval() {
+            async_std::task::spawn(async move {
+                loop {
+                    if let Err(e) = systemd::notify("WATCHDOG=1") {
+                        log::warn!("{:?}", e);
+                    }
+
+                    async_std::task::sleep(interval).await;
+                }
+            });
+        }
+    }
+
+    // Rebind to the same addresses on reload, even if the ports were assigned by the OS
+    let addrs = app.addresses();
+    let mut app = Some(app);
+
+    // Stop on either an interrupt or a termination signal
     let ctrlc = CtrlC::new()?;
 
+    let mut watcher = if options.watch {
+        let mut paths: Vec<_> = options.modules().into_iter().map(|(_, p)| p).collect();
+        paths.extend(options.canary.clone());
+        paths.extend(options.mirror.clone());
+        paths.extend(options.templates.clone());
+        paths.extend(options.translations.clone());
+        if options.build {
+            paths.push(PathBuf::from("src"));
+            paths.push(PathBuf::from("Cargo.toml"));
+        }
+
+        Some(Watcher::new(paths))
+    } else {
+        None
+    };
+
+    #[cfg(unix)]
+    let mut hangups = signal_hook_async_std::Signals::new(&[signal_hook::consts::SIGHUP])?;
+
     ctrlc
-        .race(async move {
-            server.accept().await.unwrap();
+        .race(async {
+            loop {
+                let changed = async {
+                    match &mut watcher {
+                        Some(watcher) => watcher.changed().await,
+                        None => futures::future::pending().await,
+                    }
+
+                    Reload::Changed
+                };
+
+                #[cfg(unix)]
+                let hangup = async {
+                    use futures::StreamExt;
+                    hangups.next().await;
+                    Reload::Hangup
+                };
+
+                #[cfg(not(unix))]
+                let hangup = futures::future::pending();
+
+                let reload = match &mut app {
+                    Some(app) => {
+                        let recycled = recycle(&options, app.server.received_requests());
+
+                        async {
+                            app.accept().await.unwrap();
+                            None
+                        }
+                        .race(async {
+                            Some(
+                                changed
+                                    .race(hangup)
+                                    .race(async {
+                                        recycled.await;
+                                        Reload::Recycle
+                                    })
+                                    .await,
+                            )
+                        })
+                        .await
+                    }
+                    None => Some(changed.race(hangup).await),
+                };
+
+                match reload {
+                    Some(Reload::Changed) => {
+                        log::info!("Change detected; reloading the application...")
+                    }
+                    #[cfg(unix)]
+                    Some(Reload::Hangup) => {
+                        log::info!("Hangup received; reloading the application...");
+
+                        // Environment files and secrets are read again so that updated values are used
+                        match load_environment(&options).await {
+                            Ok(e) => environment = e,
+                            Err(e) => {
+                                log::error!("{:?}", e);
+                                continue;
+                            }
+                        }
+                    }
+                    Some(Reload::Recycle) => log::info!("Recycling the application..."),
+                    None => break,
+                }
+
+                notify_systemd(&options, "RELOADING=1");
+
+                // Drop the current application so that its operational listeners can be rebound
+                app = None;
+
+                if options.build {
+                    if let Err(e) = watch::build().await {
+                        log::error!("{:?}", e);
+                        continue;
+                    }
+
+                    // Ignore the changes made by the build itself
+                    if let Some(watcher) = &mut watcher {
+                        watcher.reset();
+                    }
+                }
+
+                match start(&options, environment.clone(), addrs, &listener).await {
+                    Ok(a) => {
+                        app = Some(a);
+                        notify_systemd(&options, "READY=1");
+                    }
+                    Err(e) => log::error!("{:?}", e),
+                }
+            }
         })
         .await;
 
     log::info!("Shutting down...");
 
+    notify_systemd(&options, "STOPPING=1");
+
+    // The listeners were dropped with the accept futures, so only requests in progress remain
+    if let Some(app) = &app {
+        let timeout = Duration::from_secs(options.shutdown_timeout);
+        if !app.server.drain(timeout).await {
+            log::warn!("Requests were still in progress after {:?}.", timeout);
+        }
+    }
+
     Ok(())
 }
 
 #[async_std::main]
 async fn main() {
-    builder()
-        .format_module_path(false)
-        .filter_module("wasmtime_functions_runtime", log::LevelFilter::Info)
-        .filter_module("wasmtime_functions_host", log::LevelFilter::Info)
-        .init();
-
-    if let Err(e) = run(Options::from_args()).await {
-        log::error!("{:?}", e);
+    let options = Options::from_args();
+
+    logging::init(options.log_format, options.log_level);
+
+    let res = match &options.command {
+        Some(Command::Inspect(inspect)) => inspect::run(inspect),
+        Some(Command::InspectConfig(config)) => profile::run(config),
+        Some(Command::Precompile(precompile)) => precompile::run(precompile),
+        Some(Command::New(new)) => scaffold::run(new),
+        Some(Command::Invoke(invoke)) => invoke::run(invoke).await,
+        Some(Command::Routes(routes)) => routes::run(routes),
+        Some(Command::Bench(bench)) => bench::run(bench).await,
+        Some(Command::Replay(replay)) => replay::run(replay).await,
+        Some(Command::Package(package)) => package::run(package),
+        Some(Command::GenerateClient(client)) => client::run(client),
+        None => run(options).await,
+    };
+
+    if let Err(e) = res {
+        diagnostics::report(&e);
         std::process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmtime_functions_runtime::EnvironmentProvider as _;
+
+    #[test]
+    fn env_file_vars_are_hidden_from_the_configuration() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join(".env");
+        std::fs::write(
+            &path,
+            "DATABASE_URL=postgres://user:pass@db\nLOG_LEVEL=debug\n",
+        )?;
+
+        let provider = EnvironmentProvider::new(read_env_file(&path)?, false)
+            .with_exposed(["LOG_LEVEL".to_string()].iter().cloned().collect());
+
+        assert_eq!(provider.var("DATABASE_URL")?, "postgres://user:pass@db");
+        assert!(provider.is_secret("DATABASE_URL"));
+        assert!(!provider.is_secret("LOG_LEVEL"));
+        Ok(())
+    }
+
+    #[test]
+    fn secrets_stay_hidden_when_exposed() {
+        let provider = EnvironmentProvider::new(vec![("API_KEY".into(), "key".into())], false)
+            .with_secrets(["API_KEY".to_string()].iter().cloned().collect())
+            .with_exposed(["API_KEY".to_string()].iter().cloned().collect());
+
+        assert!(provider.is_secret("API_KEY"));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::profile::{EngineSettings, Profile};
+use crate::EnvironmentProvider;
+use anyhow::{anyhow, bail, Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use wasmtime_functions_metadata::Metadata;
+use wasmtime_functions_runtime::{Interruption, Server};
+
+const MODULE: &str = "module.wasm";
+const PRECOMPILED: &str = "module.cwasm";
+const METADATA: &str = "metadata.json";
+const CONFIG: &str = "functions.toml";
+const ASSETS: &str = "assets/";
+const DIGESTS: &str = "SHA256SUMS";
+
+#[derive(StructOpt)]
+pub struct PackageOptions {
+    /// The path to the WebAssembly module to package.
+    pub module: PathBuf,
+
+    /// The path to write the package to.
+    #[structopt(short, long, value_name = "PATH")]
+    pub output: PathBuf,
+
+    /// Include the precompiled code of the module in the package.
+    #[structopt(long)]
+    pub precompile: bool,
+
+    /// The preset of engine settings for the precompiled code: `dev`, `balanced`, or `release`.
+    ///
+    /// This must match the setting used when serving the package.
+    #[structopt(long, possible_values = &["dev", "balanced", "release"])]
+    pub profile: Option<Profile>,
+
+    /// Enable debug information for the precompiled code.
+    ///
+    /// This must match the setting used when serving the package.
+    #[structopt(short = "g", long, requires = "precompile")]
+    pub debug_info: bool,
+
+    /// The mechanism used to interrupt functions for the precompiled code.
+    ///
+    /// This must match the setting used when serving the package.
+    #[structopt(long, default_value = "fuel", possible_values = &["fuel", "epoch"], parse(try_from_str = crate::parse_interruption))]
+    pub interruption: Interruption,
+
+    /// The configuration file to include in the package.
+    ///
+    /// Defaults to `functions.toml` in the current directory, if present.
+    #[structopt(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// A directory of static assets to include in the package.
+    #[structopt(long, value_name = "DIR")]
+    pub assets: Option<PathBuf>,
+}
+
+fn digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn collect_assets(dir: &Path, prefix: &str, files: &mut BTreeMap<String, Vec<u8>>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read assets directory '{}'", dir.display()))?
+    {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+
+        if entry.file_type()?.is_dir() {
+            collect_assets(&entry.path(), &format!("{}/", name), files)?;
+        } else {
+            files.insert(name, std::fs::read(entry.path())?);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn run(options: &PackageOptions) -> Result<()> {
+    if !options.module.is_file() {
+        bail!("module '{}' does not exist.", options.module.display());
+    }
+
+    let module = std::fs::read(&options.module)?;
+    let metadata = Metadata::from_module_bytes(&module)?;
+
+    let mut files = BTreeMap::new();
+
+    if options.precompile {
+        let code =
+            EngineSettings::resolve(options.profile, options.debug_info, options.interruption)
+                .apply(Server::builder(
+                    &module,
+                    Arc::new(EnvironmentProvider::new(Vec::new(), false)),
+                ))
+                .precompile()?;
+
+        files.insert(PRECOMPILED.to_string(), code);
+    }
+
+    files.insert(METADATA.to_string(), serde_json::to_vec_pretty(&metadata)?);
+
+    let config = match &options.config {
+        Some(path) => Some(path.clone()),
+        None => Some(PathBuf::from(CONFIG)).filter(|p| p.is_file()),
+    };
+
+    if let Some(path) = config {
+        files.insert(
+            CONFIG.to_string(),
+            std::fs::read(&path)
+                .with_context(|| format!("failed to read configuration '{}'", path.display()))?,
+        );
+    }
+
+    if let Some(dir) = &options.assets {
+        collect_assets(dir, ASSETS, &mut files)?;
+    }
+
+    files.insert(MODULE.to_string(), module);
+
+    let digests: String = files
+        .iter()
+        .map(|(name, bytes)| format!("{}  {}\n", digest(bytes), name))
+        .collect();
+
+    let file = std::fs::File::create(&options.output)
+        .with_context(|| format!("failed to create package '{}'", options.output.display()))?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    for (name, bytes) in files
+        .iter()
+        .map(|(name, bytes)| (name.as_str(), bytes.as_slice()))
+        .chain(std::iter::once((DIGESTS, digests.as_bytes())))
+    {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(bytes.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        archive.append_data(&mut header, name, bytes)?;
+    }
+
+    archive.into_inner()?.finish()?;
+
+    log::info!(
+        "Packaged module '{}' to '{}'.",
+        options.module.display(),
+        options.output.display()
+    );
+
+    Ok(())
+}
+
+/// Represents the contents of a package being served.
+pub struct Package {
+    pub module: Vec<u8>,
+    pub precompiled: Option<Vec<u8>>,
+    /// The directory the package's static assets were extracted to, if it has any.
+    pub assets: Option<TempDir>,
+}
+
+/// Reads a package, verifying the digests of its contents.
+pub fn read(path: &Path) -> Result<Package> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open package '{}'", path.display()))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+
+    let mut files = BTreeMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
+        files.insert(name, bytes);
+    }
+
+    let digests = files
+        .remove(DIGESTS)
+        .ok_or_else(|| anyhow!("package '{}' has no digests", path.display()))?;
+
+    let digests = String::from_utf8(digests)?;
+
+    let mut verified = BTreeSet::new();
+    for line in digests.lines() {
+        let (expected, name) = line
+            .split_once("  ")
+            .ok_or_else(|| anyhow!("package '{}' has malformed digests", path.display()))?;
+
+        match files.get(name) {
+            Some(bytes) if digest(bytes) == expected => {
+                verified.insert(name);
+            }
+            Some(_) => bail!(
+                "digest mismatch for '{}' in package '{}'",
+                name,
+                path.display()
+            ),
+            None => bail!("package '{}' is missing '{}'", path.display(), name),
+        }
+    }
+
+    if verified.len() != files.len() {
+        bail!(
+            "package '{}' contains files without digests",
+            path.display()
+        );
+    }
+
+    let module = files
+        .remove(MODULE)
+        .ok_or_else(|| anyhow!("package '{}' has no module", path.display()))?;
+    let precompiled = files.remove(PRECOMPILED);
+
+    let mut assets = None;
+    for (name, bytes) in &files {
+        let relative = match name.strip_prefix(ASSETS) {
+            Some(relative) => Path::new(relative),
+            None => continue,
+        };
+
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            bail!(
+                "package '{}' contains invalid asset path '{}'",
+                path.display(),
+                name
+            );
+        }
+
+        if assets.is_none() {
+            assets = Some(tempfile::tempdir()?);
+        }
+
+        let dest = assets.as_ref().unwrap().path().join(relative);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(dest, bytes)?;
+    }
+
+    Ok(Package {
+        module,
+        precompiled,
+        assets,
+    })
+}
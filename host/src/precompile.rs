@@ -0,0 +1,60 @@
+use crate::profile::{EngineSettings, Profile};
+use crate::EnvironmentProvider;
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+use std::sync::Arc;
+use structopt::StructOpt;
+use wasmtime_functions_runtime::{Interruption, Server};
+
+#[derive(StructOpt)]
+pub struct PrecompileOptions {
+    /// The path to the WebAssembly module to precompile.
+    pub module: PathBuf,
+
+    /// The path to write the precompiled module to.
+    #[structopt(short, long, value_name = "PATH")]
+    pub output: PathBuf,
+
+    /// The preset of engine settings to use: `dev`, `balanced`, or `release`.
+    ///
+    /// This must match the setting used when serving the precompiled module.
+    #[structopt(long, possible_values = &["dev", "balanced", "release"])]
+    pub profile: Option<Profile>,
+
+    /// Enable debug information for the application.
+    ///
+    /// This must match the setting used when serving the precompiled module.
+    #[structopt(short = "g", long)]
+    pub debug_info: bool,
+
+    /// The mechanism used to interrupt functions that exceed their timeout.
+    ///
+    /// This must match the setting used when serving the precompiled module.
+    #[structopt(long, default_value = "fuel", possible_values = &["fuel", "epoch"], parse(try_from_str = crate::parse_interruption))]
+    pub interruption: Interruption,
+}
+
+pub fn run(options: &PrecompileOptions) -> Result<()> {
+    if !options.module.is_file() {
+        bail!("module '{}' does not exist.", options.module.display());
+    }
+
+    let module = std::fs::read(&options.module)?;
+
+    let code = EngineSettings::resolve(options.profile, options.debug_info, options.interruption)
+        .apply(Server::builder(
+            &module,
+            Arc::new(EnvironmentProvider::new(Vec::new(), false)),
+        ))
+        .precompile()?;
+
+    std::fs::write(&options.output, code)?;
+
+    log::info!(
+        "Precompiled module '{}' to '{}'.",
+        options.module.display(),
+        options.output.display()
+    );
+
+    Ok(())
+}
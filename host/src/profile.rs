@@ -0,0 +1,161 @@
+use anyhow::{bail, Result};
+use std::fmt;
+use std::str::FromStr;
+use structopt::StructOpt;
+use wasmtime_functions_runtime::{Interruption, OptLevel, ServerBuilder};
+
+/// A preset of engine settings.
+#[derive(Debug, Clone, Copy)]
+pub enum Profile {
+    /// Compiles quickly and includes debug information.
+    Dev,
+    /// Optimizes for speed with a small instance pool.
+    Balanced,
+    /// Optimizes for speed and size with a large instance pool.
+    Release,
+}
+
+impl FromStr for Profile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "dev" => Ok(Self::Dev),
+            "balanced" => Ok(Self::Balanced),
+            "release" => Ok(Self::Release),
+            _ => bail!("must be `dev`, `balanced`, or `release`"),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Dev => write!(f, "dev"),
+            Self::Balanced => write!(f, "balanced"),
+            Self::Release => write!(f, "release"),
+        }
+    }
+}
+
+/// The engine settings resolved from a profile and the individual options.
+pub struct EngineSettings {
+    pub profile: Option<Profile>,
+    pub opt_level: OptLevel,
+    pub compilation_cache: bool,
+    pub debug_info: bool,
+    pub instance_pool: Option<u32>,
+    pub interruption: Interruption,
+}
+
+impl EngineSettings {
+    /// Resolves the engine settings.
+    ///
+    /// Without a profile, the runtime's defaults are used.
+    pub fn resolve(profile: Option<Profile>, debug_info: bool, interruption: Interruption) -> Self {
+        let (opt_level, compilation_cache, profile_debug_info, instance_pool) = match profile {
+            None => (OptLevel::Speed, false, false, Some(1000)),
+            Some(Profile::Dev) => (OptLevel::None, true, true, None),
+            Some(Profile::Balanced) => (OptLevel::Speed, true, false, Some(100)),
+            Some(Profile::Release) => (OptLevel::SpeedAndSize, true, false, Some(1000)),
+        };
+
+        Self {
+            profile,
+            opt_level,
+            compilation_cache,
+            debug_info: debug_info || profile_debug_info,
+            instance_pool,
+            interruption,
+        }
+    }
+
+    /// Applies the engine settings to a server builder.
+    pub fn apply<'a>(&self, builder: ServerBuilder<'a>) -> ServerBuilder<'a> {
+        builder
+            .opt_level(self.opt_level)
+            .compilation_cache(self.compilation_cache)
+            .debug_info(self.debug_info)
+            .instance_pool(self.instance_pool)
+            .interruption(self.interruption)
+    }
+}
+
+impl fmt::Display for EngineSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "profile:            {}",
+            self.profile
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "(none)".to_string())
+        )?;
+        writeln!(
+            f,
+            "optimization level: {}",
+            match self.opt_level {
+                OptLevel::None => "none",
+                OptLevel::Speed => "speed",
+                OptLevel::SpeedAndSize => "speed and size",
+            }
+        )?;
+        writeln!(
+            f,
+            "compilation cache:  {}",
+            if self.compilation_cache {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        )?;
+        writeln!(
+            f,
+            "debug information:  {}",
+            if self.debug_info {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        )?;
+        writeln!(
+            f,
+            "instance pool:      {}",
+            match self.instance_pool {
+                Some(size) => format!("{} instances", size),
+                None => "disabled (on-demand allocation)".to_string(),
+            }
+        )?;
+        write!(
+            f,
+            "interruption:       {}",
+            match self.interruption {
+                Interruption::Fuel => "fuel",
+                Interruption::Epoch => "epoch",
+            }
+        )
+    }
+}
+
+#[derive(StructOpt)]
+pub struct InspectConfigOptions {
+    /// The engine settings preset to resolve.
+    #[structopt(long, possible_values = &["dev", "balanced", "release"])]
+    pub profile: Option<Profile>,
+
+    /// Enable debug information for the application.
+    #[structopt(short = "g", long)]
+    pub debug_info: bool,
+
+    /// The mechanism used to interrupt functions that exceed their timeout.
+    #[structopt(long, default_value = "fuel", possible_values = &["fuel", "epoch"], parse(try_from_str = crate::parse_interruption))]
+    pub interruption: Interruption,
+}
+
+pub fn run(options: &InspectConfigOptions) -> Result<()> {
+    println!(
+        "{}",
+        EngineSettings::resolve(options.profile, options.debug_info, options.interruption)
+    );
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use crate::{parse_env_var, EnvironmentProvider};
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+use std::sync::Arc;
+use structopt::StructOpt;
+use wasmtime_functions_runtime::{CapturedRequest, Server};
+
+#[derive(StructOpt)]
+pub struct ReplayOptions {
+    /// The path to the WebAssembly module to replay the request against.
+    pub module: PathBuf,
+
+    /// The path to the captured request to replay.
+    pub request: PathBuf,
+
+    /// Enable debug information for the application.
+    #[structopt(short = "g", long)]
+    pub debug_info: bool,
+
+    /// Override an application environment variable value.
+    #[structopt(long = "env", short, number_of_values = 1, value_name = "NAME=VAL", parse(try_from_str = parse_env_var))]
+    pub environment: Vec<(String, String)>,
+}
+
+pub async fn run(options: &ReplayOptions) -> Result<()> {
+    if !options.module.is_file() {
+        bail!("module '{}' does not exist.", options.module.display());
+    }
+
+    let module = std::fs::read(&options.module)?;
+    let captured = CapturedRequest::read(&options.request)?;
+
+    log::info!(
+        "Replaying request to function '{}' captured at {}.",
+        captured.function,
+        captured.timestamp
+    );
+
+    if let Some(error) = &captured.error {
+        log::info!("The captured request failed with: {}", error);
+    }
+
+    let environment = Arc::new(EnvironmentProvider::new(
+        options.environment.clone(),
+        atty::is(atty::Stream::Stdin),
+    ));
+
+    let server = Server::builder(&module, environment)
+        .debug_info(options.debug_info)
+        .local()
+        .await?;
+
+    crate::invoke::print_response(server.respond(captured.to_request()?).await?).await
+}
@@ -0,0 +1,23 @@
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+use structopt::StructOpt;
+use wasmtime_functions_runtime::RouteTable;
+
+#[derive(StructOpt)]
+pub struct RoutesOptions {
+    /// The path to the WebAssembly module.
+    pub module: PathBuf,
+}
+
+pub fn run(options: &RoutesOptions) -> Result<()> {
+    if !options.module.is_file() {
+        bail!("module '{}' does not exist.", options.module.display());
+    }
+
+    println!(
+        "{}",
+        RouteTable::from_module_bytes(&std::fs::read(&options.module)?)?
+    );
+
+    Ok(())
+}
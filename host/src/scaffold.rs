@@ -0,0 +1,107 @@
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+const FUNCTIONS_GIT_URL: &str = "https://github.com/peterhuene/wasmtime-functions";
+
+#[derive(StructOpt)]
+pub struct NewOptions {
+    /// The path of the application directory to create.
+    pub path: PathBuf,
+
+    /// The name of the application crate; defaults to the name of the directory.
+    #[structopt(long)]
+    pub name: Option<String>,
+
+    /// Use the `wasmtime-functions` crate at the given local path rather than from git.
+    #[structopt(long, value_name = "PATH")]
+    pub functions_path: Option<PathBuf>,
+}
+
+pub fn run(options: &NewOptions) -> Result<()> {
+    let path = &options.path;
+
+    if path.exists() && path.read_dir()?.next().is_some() {
+        bail!(
+            "directory '{}' already exists and is not empty.",
+            path.display()
+        );
+    }
+
+    let name = match &options.name {
+        Some(name) => name.clone(),
+        None => match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name.to_string(),
+            None => bail!(
+                "cannot determine the application name from '{}'; use `--name`.",
+                path.display()
+            ),
+        },
+    };
+
+    if name.is_empty()
+        || name.starts_with(|c: char| c.is_ascii_digit())
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!("'{}' is not a valid crate name.", name);
+    }
+
+    let dependency = match &options.functions_path {
+        Some(p) => format!("{{ path = {:?} }}", p.canonicalize()?.display().to_string()),
+        None => format!("{{ git = \"{}\" }}", FUNCTIONS_GIT_URL),
+    };
+
+    std::fs::create_dir_all(path.join("src"))?;
+    std::fs::create_dir_all(path.join(".cargo"))?;
+
+    std::fs::write(
+        path.join("Cargo.toml"),
+        format!(
+            r#"[package]
+name = "{name}"
+version = "0.1.0"
+edition = "2018"
+
+[lib]
+crate-type = ["cdylib"]
+
+[dependencies]
+wasmtime-functions = {dependency}
+
+[workspace]
+"#,
+            name = name,
+            dependency = dependency
+        ),
+    )?;
+
+    std::fs::write(
+        path.join(".cargo").join("config.toml"),
+        r#"[build]
+target = "wasm32-wasi"
+"#,
+    )?;
+
+    std::fs::write(
+        path.join("src").join("lib.rs"),
+        r#"use wasmtime_functions::{get, Request};
+
+#[get("/hello/:name")]
+fn hello(req: Request) -> String {
+    format!("Hello, {}!", req.param("name").unwrap())
+}
+"#,
+    )?;
+
+    std::fs::write(path.join(".gitignore"), "/target\n")?;
+
+    log::info!(
+        "Created application '{}'; build it with `cargo build` and run `target/wasm32-wasi/debug/{}.wasm`.",
+        name,
+        name.replace('-', "_")
+    );
+
+    Ok(())
+}
@@ -0,0 +1,316 @@
+use crate::read_env_file;
+use anyhow::{anyhow, bail, Context, Result};
+use hmac::{Hmac, Mac, NewMac};
+use http_types::Url;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// A source of secrets to expose to the application as environment variables.
+#[derive(Debug, Clone)]
+pub enum SecretsSource {
+    /// A dotenv file, or a directory where each file name is a variable name and its contents the value.
+    File(PathBuf),
+    /// A Vault KV version 2 secret.
+    Vault {
+        base: Url,
+        mount: String,
+        path: String,
+    },
+    /// An AWS Secrets Manager secret containing a JSON object.
+    AwsSecretsManager { region: String, secret_id: String },
+}
+
+impl FromStr for SecretsSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // File paths are taken verbatim as URL parsing would treat the first component as a host
+        if let Some(path) = s.strip_prefix("file://") {
+            return Ok(Self::File(PathBuf::from(path)));
+        }
+
+        let url = Url::parse(s)?;
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow!("secrets URL must include a host"))?;
+        let path = url.path().trim_matches('/');
+
+        match url.scheme() {
+            "vault" => {
+                let (mount, path) = path.split_once('/').ok_or_else(|| {
+                    anyhow!("must be of the form `vault://HOST[:PORT]/MOUNT/PATH`")
+                })?;
+
+                let mut base = Url::parse("https://localhost")?;
+                base.set_host(Some(host))?;
+                base.set_port(url.port())
+                    .map_err(|_| anyhow!("invalid Vault port"))?;
+
+                Ok(Self::Vault {
+                    base,
+                    mount: mount.to_string(),
+                    path: path.to_string(),
+                })
+            }
+            "aws-sm" => {
+                if path.is_empty() {
+                    bail!("must be of the form `aws-sm://REGION/SECRET_ID`");
+                }
+
+                Ok(Self::AwsSecretsManager {
+                    region: host.to_string(),
+                    secret_id: path.to_string(),
+                })
+            }
+            scheme => bail!(
+                "unsupported secrets provider `{}`; expected `file`, `vault`, or `aws-sm`",
+                scheme
+            ),
+        }
+    }
+}
+
+#[derive(StructOpt)]
+pub struct SecretsOptions {
+    /// Load application environment variables from a secrets provider.
+    ///
+    /// Supported providers are `file://PATH`, `vault://HOST[:PORT]/MOUNT/PATH`, and `aws-sm://REGION/SECRET_ID`.
+    /// Secrets in later sources override those in earlier sources; `--env` and `--env-file` override both.
+    #[structopt(long = "secrets", number_of_values = 1, value_name = "URL")]
+    pub sources: Vec<SecretsSource>,
+
+    /// The token used to authenticate with Vault.
+    #[structopt(long, env = "VAULT_TOKEN", hide_env_values = true)]
+    pub vault_token: Option<String>,
+
+    /// The access key ID used to authenticate with AWS.
+    #[structopt(long, env = "AWS_ACCESS_KEY_ID", hide_env_values = true)]
+    pub aws_access_key_id: Option<String>,
+
+    /// The secret access key used to authenticate with AWS.
+    #[structopt(long, env = "AWS_SECRET_ACCESS_KEY", hide_env_values = true)]
+    pub aws_secret_access_key: Option<String>,
+
+    /// The session token used to authenticate with AWS when using temporary credentials.
+    #[structopt(long, env = "AWS_SESSION_TOKEN", hide_env_values = true)]
+    pub aws_session_token: Option<String>,
+}
+
+impl SecretsOptions {
+    /// Loads the secrets from every source.
+    ///
+    /// The secrets are returned in order of precedence, with secrets from later sources first.
+    pub async fn load(&self) -> Result<Vec<(String, String)>> {
+        let mut secrets = Vec::new();
+
+        for source in self.sources.iter().rev() {
+            let mut vars = match source {
+                SecretsSource::File(path) => load_file(path)?,
+                SecretsSource::Vault { base, mount, path } => {
+                    self.load_vault(base, mount, path).await?
+                }
+                SecretsSource::AwsSecretsManager { region, secret_id } => {
+                    self.load_aws(region, secret_id).await?
+                }
+            };
+
+            // Within a source, later values take precedence over earlier ones
+            vars.reverse();
+            secrets.extend(vars);
+        }
+
+        Ok(secrets)
+    }
+
+    async fn load_vault(
+        &self,
+        base: &Url,
+        mount: &str,
+        path: &str,
+    ) -> Result<Vec<(String, String)>> {
+        let token = self.vault_token.as_deref().ok_or_else(|| {
+            anyhow!("a Vault token is required; use `--vault-token` or set `VAULT_TOKEN`")
+        })?;
+
+        let url = base.join(&format!("v1/{}/data/{}", mount, path))?;
+
+        let mut res = surf::get(url.as_str())
+            .header("X-Vault-Token", token)
+            .await
+            .map_err(|e| e.into_inner())
+            .with_context(|| format!("failed to request Vault secret '{}/{}'", mount, path))?;
+
+        if !res.status().is_success() {
+            bail!(
+                "failed to read Vault secret '{}/{}' ({})",
+                mount,
+                path,
+                res.status()
+            );
+        }
+
+        let body: serde_json::Value = res.body_json().await.map_err(|e| e.into_inner())?;
+
+        json_vars(&body["data"]["data"])
+            .with_context(|| format!("invalid Vault secret '{}/{}'", mount, path))
+    }
+
+    async fn load_aws(&self, region: &str, secret_id: &str) -> Result<Vec<(String, String)>> {
+        let (access_key_id, secret_access_key) = match (
+            &self.aws_access_key_id,
+            &self.aws_secret_access_key,
+        ) {
+            (Some(id), Some(key)) => (id, key),
+            _ => bail!("AWS credentials are required; use `--aws-access-key-id` and `--aws-secret-access-key` or set `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`"),
+        };
+
+        let host = format!("secretsmanager.{}.amazonaws.com", region);
+        let body = serde_json::json!({ "SecretId": secret_id }).to_string();
+        let now = time::OffsetDateTime::now_utc();
+        let date = now.format("%Y%m%d");
+        let timestamp = now.format("%Y%m%dT%H%M%SZ");
+
+        // Headers must be sorted by name for signing
+        let mut headers = vec![
+            ("content-type", "application/x-amz-json-1.1".to_string()),
+            ("host", host.clone()),
+            ("x-amz-date", timestamp.clone()),
+        ];
+
+        if let Some(token) = &self.aws_session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+
+        headers.push(("x-amz-target", "secretsmanager.GetSecretValue".to_string()));
+
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let canonical_request = format!(
+            "POST\n/\n\n{}\n{}\n{}",
+            headers
+                .iter()
+                .map(|(name, value)| format!("{}:{}\n", name, value))
+                .collect::<String>(),
+            signed_headers,
+            hex(&Sha256::digest(body.as_bytes()))
+        );
+
+        let scope = format!("{}/{}/secretsmanager/aws4_request", date, region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            timestamp,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = [date.as_str(), region, "secretsmanager", "aws4_request"]
+            .iter()
+            .fold(
+                format!("AWS4{}", secret_access_key).into_bytes(),
+                |key, part| hmac_sha256(&key, part.as_bytes()),
+            );
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            access_key_id,
+            scope,
+            signed_headers,
+            hex(&hmac_sha256(&key, string_to_sign.as_bytes()))
+        );
+
+        let mut req = surf::post(format!("https://{}/", host)).body(body);
+
+        for (name, value) in &headers {
+            if *name != "host" {
+                req = req.header(*name, value.as_str());
+            }
+        }
+
+        let mut res = req
+            .header("authorization", authorization)
+            .await
+            .map_err(|e| e.into_inner())
+            .with_context(|| format!("failed to request AWS secret '{}'", secret_id))?;
+
+        if !res.status().is_success() {
+            bail!(
+                "failed to read AWS secret '{}' ({}): {}",
+                secret_id,
+                res.status(),
+                res.body_string().await.unwrap_or_default()
+            );
+        }
+
+        let body: serde_json::Value = res.body_json().await.map_err(|e| e.into_inner())?;
+
+        let secret = body["SecretString"]
+            .as_str()
+            .ok_or_else(|| anyhow!("AWS secret '{}' has no secret string", secret_id))?;
+
+        json_vars(&serde_json::from_str(secret)?)
+            .with_context(|| format!("invalid AWS secret '{}'", secret_id))
+    }
+}
+
+fn load_file(path: &Path) -> Result<Vec<(String, String)>> {
+    if !path.is_dir() {
+        return read_env_file(path);
+    }
+
+    let mut vars = Vec::new();
+
+    for entry in std::fs::read_dir(path)
+        .with_context(|| format!("failed to read secrets directory '{}'", path.display()))?
+    {
+        let entry = entry?;
+
+        // Skip hidden entries such as the `..data` links of Kubernetes secret volumes
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') || !entry.path().is_file() {
+            continue;
+        }
+
+        let value = std::fs::read_to_string(entry.path())
+            .with_context(|| format!("failed to read secret '{}'", entry.path().display()))?;
+
+        vars.push((name, value.trim_end_matches(&['\r', '\n'][..]).to_string()));
+    }
+
+    vars.sort();
+    Ok(vars)
+}
+
+fn json_vars(value: &serde_json::Value) -> Result<Vec<(String, String)>> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| anyhow!("secret must be a JSON object"))?;
+
+    Ok(object
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.clone(),
+                match value {
+                    serde_json::Value::String(s) => s.clone(),
+                    value => value.to_string(),
+                },
+            )
+        })
+        .collect())
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
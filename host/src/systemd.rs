@@ -0,0 +1,83 @@
+//! Integration with systemd service units.
+
+use anyhow::{Context, Result};
+use std::os::unix::io::FromRawFd;
+use std::os::unix::net::UnixDatagram;
+use std::time::Duration;
+
+// The first file descriptor passed by socket activation.
+const LISTEN_FDS_START: i32 = 3;
+
+/// Takes the listening socket passed to the process by socket activation, if any.
+pub fn listener() -> Result<Option<std::net::TcpListener>> {
+    let pid = match std::env::var("LISTEN_PID") {
+        Ok(pid) => pid,
+        Err(_) => return Ok(None),
+    };
+
+    // The variables may have been inherited from a parent process that was passed the sockets
+    if pid.parse::<u32>().ok() != Some(std::process::id()) {
+        return Ok(None);
+    }
+
+    let fds: i32 = std::env::var("LISTEN_FDS")
+        .context("`LISTEN_FDS` is not set")?
+        .parse()
+        .context("invalid `LISTEN_FDS` environment variable")?;
+
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+
+    if fds < 1 {
+        return Ok(None);
+    }
+
+    if fds > 1 {
+        log::warn!(
+            "{} sockets were passed by systemd; only the first is used.",
+            fds
+        );
+    }
+
+    // Safety: systemd passes ownership of the sockets starting at the well-known descriptor
+    Ok(Some(unsafe {
+        std::net::TcpListener::from_raw_fd(LISTEN_FDS_START)
+    }))
+}
+
+/// Sends a state notification to systemd, such as `READY=1`.
+///
+/// Does nothing if the service manager isn't expecting notifications.
+pub fn notify(state: &str) -> Result<()> {
+    let path = match std::env::var_os("NOTIFY_SOCKET") {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+
+    if path.to_string_lossy().starts_with('@') {
+        log::warn!("Abstract notification sockets are not supported.");
+        return Ok(());
+    }
+
+    let socket = UnixDatagram::unbound()?;
+    socket
+        .send_to(state.as_bytes(), &path)
+        .context("failed to notify systemd")?;
+
+    Ok(())
+}
+
+/// Gets the interval at which watchdog notifications should be sent, if the watchdog is enabled.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+
+    // Notify at half the timeout so a late notification doesn't trip the watchdog
+    Some(Duration::from_micros(usec / 2))
+}
@@ -0,0 +1,88 @@
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL_MS: u64 = 500;
+
+/// Watches a set of files and directories for changes.
+///
+/// Changes are detected by polling the modification times of the watched paths.
+pub struct Watcher {
+    paths: Vec<PathBuf>,
+    last: Option<SystemTime>,
+}
+
+impl Watcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let last = Self::last_modified(&paths);
+        Self { paths, last }
+    }
+
+    /// Resets the watcher so that existing changes are ignored.
+    pub fn reset(&mut self) {
+        self.last = Self::last_modified(&self.paths);
+    }
+
+    /// Waits for a watched path to change.
+    pub async fn changed(&mut self) {
+        loop {
+            async_std::task::sleep(Duration::from_millis(POLL_INTERVAL_MS)).await;
+
+            let mut modified = Self::last_modified(&self.paths);
+            if modified > self.last {
+                // Wait for the changes to settle as files may still be in the process of being written
+                loop {
+                    async_std::task::sleep(Duration::from_millis(POLL_INTERVAL_MS)).await;
+
+                    let settled = Self::last_modified(&self.paths);
+                    if settled == modified {
+                        break;
+                    }
+
+                    modified = settled;
+                }
+
+                self.last = modified;
+                return;
+            }
+        }
+    }
+
+    fn last_modified(paths: &[PathBuf]) -> Option<SystemTime> {
+        paths.iter().filter_map(|p| Self::modified(p)).max()
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        let metadata = std::fs::metadata(path).ok()?;
+
+        if metadata.is_dir() {
+            std::fs::read_dir(path)
+                .ok()?
+                .filter_map(|e| e.ok())
+                .filter_map(|e| Self::modified(&e.path()))
+                .chain(metadata.modified().ok())
+                .max()
+        } else {
+            metadata.modified().ok()
+        }
+    }
+}
+
+/// Builds the application in the current directory for the `wasm32-wasi` target.
+pub async fn build() -> Result<()> {
+    log::info!("Building application...");
+
+    let status = async_std::task::spawn_blocking(|| {
+        Command::new("cargo")
+            .args(&["build", "--target", "wasm32-wasi"])
+            .status()
+    })
+    .await?;
+
+    if !status.success() {
+        bail!("failed to build the application ({})", status);
+    }
+
+    Ok(())
+}